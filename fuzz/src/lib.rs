@@ -0,0 +1,13 @@
+// font-kit/fuzz/src/lib.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The bodies of the fuzz targets, which are shared with the tests in the main crate.
+
+pub use font_kit::fuzzing::{layout_text, load_font, rasterize_glyphs};
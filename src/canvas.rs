@@ -104,9 +104,15 @@ impl Canvas {
             (Format::Rgb24, Format::Rgba32) => {
                 self.blit_from_with::<BlitRgba32ToRgb24>(src_bytes, &size, src_stride, src_format)
             }
-            (Format::Rgba32, Format::Rgb24)
-            | (Format::Rgba32, Format::A8)
-            | (Format::A8, Format::Rgba32) => unimplemented!(),
+            (Format::Rgba32, Format::Rgb24) => {
+                self.blit_from_with::<BlitRgb24ToRgba32>(src_bytes, &size, src_stride, src_format)
+            }
+            (Format::Rgba32, Format::A8) => {
+                self.blit_from_with::<BlitA8ToRgba32>(src_bytes, &size, src_stride, src_format)
+            }
+            (Format::A8, Format::Rgba32) => {
+                self.blit_from_with::<BlitRgba32ToA8>(src_bytes, &size, src_stride, src_format)
+            }
         }
     }
 
@@ -117,14 +123,18 @@ impl Canvas {
         src_size: &Size2D<u32>,
         src_stride: usize,
     ) {
-        if self.format != Format::A8 {
-            unimplemented!()
-        }
-
         let width = cmp::min(src_size.width as usize, self.size.width as usize);
         let height = cmp::min(src_size.height as usize, self.size.height as usize);
         let size = Size2D::new(width, height);
 
+        // Expand to 8 bits per pixel first, and then convert that to the canvas format.
+        if self.format != Format::A8 {
+            let mut a8_canvas = Canvas::new(&size.to_u32(), Format::A8);
+            a8_canvas.blit_from_bitmap_1bpp(src_bytes, src_size, src_stride);
+            self.blit_from_canvas(&a8_canvas);
+            return;
+        }
+
         let dest_bytes_per_pixel = self.format.bytes_per_pixel() as usize;
         let dest_row_stride = size.width * dest_bytes_per_pixel;
         let src_row_stride = utils::div_round_up(size.width, 8);
@@ -233,6 +243,20 @@ pub enum RasterizationOptions {
     SubpixelAa,
 }
 
+/// The order of the color subpixels on an LCD screen, which determines how the channels of a
+/// subpixel-antialiased glyph map to the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SubpixelLayout {
+    /// Subpixels side by side, red on the left. This is the most common layout.
+    Rgb,
+    /// Subpixels side by side, blue on the left.
+    Bgr,
+    /// Subpixels stacked vertically, red on top.
+    VerticalRgb,
+    /// Subpixels stacked vertically, blue on top.
+    VerticalBgr,
+}
+
 trait Blit {
     fn blit(dest: &mut [u8], src: &[u8]);
 }
@@ -271,6 +295,40 @@ impl Blit for BlitA8ToRgb24 {
     }
 }
 
+struct BlitA8ToRgba32;
+
+impl Blit for BlitA8ToRgba32 {
+    #[inline]
+    fn blit(dest: &mut [u8], src: &[u8]) {
+        for (dest, src) in dest.chunks_mut(4).zip(src.iter()) {
+            dest.copy_from_slice(&[*src, *src, *src, *src])
+        }
+    }
+}
+
+struct BlitRgb24ToRgba32;
+
+impl Blit for BlitRgb24ToRgba32 {
+    #[inline]
+    fn blit(dest: &mut [u8], src: &[u8]) {
+        for (dest, src) in dest.chunks_mut(4).zip(src.chunks(3)) {
+            dest[0..3].copy_from_slice(src);
+            dest[3] = 0xff;
+        }
+    }
+}
+
+struct BlitRgba32ToA8;
+
+impl Blit for BlitRgba32ToA8 {
+    #[inline]
+    fn blit(dest: &mut [u8], src: &[u8]) {
+        for (dest, src) in dest.iter_mut().zip(src.chunks(4)) {
+            *dest = src[3]
+        }
+    }
+}
+
 struct BlitRgba32ToRgb24;
 
 impl Blit for BlitRgba32ToRgb24 {
@@ -0,0 +1,708 @@
+// font-kit/src/css.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Parsing of CSS font values into selection queries.
+//!
+//! The results can be passed straight to `Source::select_best_match()`:
+//!
+//!     # use font_kit::css;
+//!     let font = css::parse_font("italic bold 12px/30px Georgia, serif").unwrap();
+//!     println!("{:?} {:?}", font.families, font.properties);
+//!
+//! Only the parts of these values that font-kit can act on are kept: font variants and line
+//! heights are parsed and validated, but then discarded.
+
+use error::CssParseError;
+use family_name::FamilyName;
+use properties::{Properties, Stretch, Style, Weight};
+
+// The size that the `medium` keyword refers to, and that relative sizes are resolved against.
+const MEDIUM_FONT_SIZE: f32 = 16.0;
+
+/// The result of parsing a CSS `font` shorthand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FontShorthand {
+    /// The font families, in order of preference.
+    pub families: Vec<FamilyName>,
+    /// The style, weight, and stretch.
+    pub properties: Properties,
+    /// The font size, in CSS pixels.
+    ///
+    /// Relative sizes (`em`, `%`, `larger`, etc.) are resolved against the `medium` size of 16
+    /// pixels, since there is no parent element to inherit from.
+    pub size: f32,
+}
+
+/// Parses a CSS `font-family` value: a comma-separated list of quoted or unquoted family names and
+/// generic family keywords.
+///
+/// Generic keywords are matched case-insensitively and only when unquoted; `"serif"` in quotes
+/// names a family that is literally called "serif".
+///
+///     # use font_kit::css;
+///     # use font_kit::family_name::FamilyName;
+///     assert_eq!(
+///         css::parse_font_family("\"Helvetica Neue\", Arial, sans-serif").unwrap(),
+///         vec![
+///             FamilyName::Title("Helvetica Neue".to_owned()),
+///             FamilyName::Title("Arial".to_owned()),
+///             FamilyName::SansSerif,
+///         ]
+///     );
+pub fn parse_font_family(value: &str) -> Result<Vec<FamilyName>, CssParseError> {
+    let mut parser = Parser::new(value);
+    let families = parser.parse_family_list()?;
+    parser.expect_end()?;
+    Ok(families)
+}
+
+/// Parses a CSS `font-style` value: `normal`, `italic`, or `oblique` with an optional angle.
+///
+/// Oblique angles are validated but not kept.
+pub fn parse_font_style(value: &str) -> Result<Style, CssParseError> {
+    let mut parser = Parser::new(value);
+    let token = parser.next_token()?;
+    let style = match parse_style_keyword(&token) {
+        Some(style) => style,
+        None => return Err(CssParseError::UnexpectedToken(token)),
+    };
+    if style == Style::Oblique {
+        parser.skip_oblique_angle()?;
+    }
+    parser.expect_end()?;
+    Ok(style)
+}
+
+/// Parses a CSS `font-weight` value: `normal`, `bold`, `bolder`, `lighter`, or a number from 1 to
+/// 1000.
+///
+/// `bolder` and `lighter` are resolved relative to the normal weight, since there is no parent
+/// element to inherit from.
+pub fn parse_font_weight(value: &str) -> Result<Weight, CssParseError> {
+    let mut parser = Parser::new(value);
+    let token = parser.next_token()?;
+    let weight = match parse_weight(&token)? {
+        Some(weight) => weight,
+        None => return Err(CssParseError::UnexpectedToken(token)),
+    };
+    parser.expect_end()?;
+    Ok(weight)
+}
+
+/// Parses a CSS `font-stretch` value: a keyword such as `condensed`, or a non-negative
+/// percentage.
+pub fn parse_font_stretch(value: &str) -> Result<Stretch, CssParseError> {
+    let mut parser = Parser::new(value);
+    let token = parser.next_token()?;
+    let stretch = match parse_stretch_keyword(&token) {
+        Some(stretch) => stretch,
+        None => match parse_percentage(&token)? {
+            Some(percentage) => Stretch(percentage / 100.0),
+            None => return Err(CssParseError::UnexpectedToken(token)),
+        },
+    };
+    parser.expect_end()?;
+    Ok(stretch)
+}
+
+/// Parses a CSS `font` shorthand: e.g. `italic bold 12px/30px Georgia, serif`.
+///
+/// System font keywords such as `menu` and `caption` select the `system-ui` family at the medium
+/// size.
+pub fn parse_font(value: &str) -> Result<FontShorthand, CssParseError> {
+    let mut properties = Properties::new();
+    if is_system_font_keyword(&value.trim().to_ascii_lowercase()) {
+        return Ok(FontShorthand {
+            families: vec![FamilyName::SystemUi],
+            properties,
+            size: MEDIUM_FONT_SIZE,
+        });
+    }
+
+    let mut parser = Parser::new(value);
+    let (mut style, mut variant, mut weight, mut stretch) = (None, None, None, None);
+    let mut normal_count = 0;
+
+    // `font-style`, `font-variant`, `font-weight`, and `font-stretch` may appear in any order
+    // before the size, each at most once. `normal` can stand for any of them.
+    let size = loop {
+        let token = parser.next_token()?;
+        if token.eq_ignore_ascii_case("normal") {
+            normal_count += 1;
+            if normal_count > 4 {
+                return Err(CssParseError::UnexpectedToken(token));
+            }
+            continue;
+        }
+        if let Some(new_style) = parse_style_keyword(&token) {
+            set_once(&mut style, new_style, &token)?;
+            properties.style(new_style);
+            if new_style == Style::Oblique {
+                parser.skip_oblique_angle()?;
+            }
+            continue;
+        }
+        if token.eq_ignore_ascii_case("small-caps") {
+            set_once(&mut variant, (), &token)?;
+            continue;
+        }
+        // Numbers outside the weight range may still be valid sizes (e.g. `0`).
+        if let Ok(Some(new_weight)) = parse_weight(&token) {
+            set_once(&mut weight, new_weight, &token)?;
+            properties.weight(new_weight);
+            continue;
+        }
+        if let Some(new_stretch) = parse_stretch_keyword(&token) {
+            set_once(&mut stretch, new_stretch, &token)?;
+            properties.stretch(new_stretch);
+            continue;
+        }
+
+        // Anything else must be the size.
+        match parse_font_size(&token)? {
+            Some(size) => break size,
+            None => return Err(CssParseError::UnexpectedToken(token)),
+        }
+    };
+
+    if parser.skip_char('/') {
+        let token = parser.next_token()?;
+        if !is_line_height(&token)? {
+            return Err(CssParseError::UnexpectedToken(token));
+        }
+    }
+
+    let families = parser.parse_family_list()?;
+    parser.expect_end()?;
+    Ok(FontShorthand {
+        families,
+        properties,
+        size,
+    })
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Parser<'a> {
+        Parser { input, position: 0 }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.position..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn is_at_end(&mut self) -> bool {
+        self.skip_whitespace();
+        self.rest().is_empty()
+    }
+
+    fn expect_end(&mut self) -> Result<(), CssParseError> {
+        if self.is_at_end() {
+            Ok(())
+        } else {
+            Err(CssParseError::UnexpectedToken(self.rest().to_owned()))
+        }
+    }
+
+    fn skip_char(&mut self, ch: char) -> bool {
+        self.skip_whitespace();
+        if self.rest().starts_with(ch) {
+            self.position += ch.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.rest().chars().next()
+    }
+
+    // Returns the next run of characters up to whitespace, a comma, a slash, or a quote.
+    fn next_token(&mut self) -> Result<String, CssParseError> {
+        self.skip_whitespace();
+        let rest = self.rest();
+        let length = rest
+            .find(|ch: char| ch.is_whitespace() || ch == ',' || ch == '/' || is_quote(ch))
+            .unwrap_or(rest.len());
+        if length == 0 {
+            return match rest.chars().next() {
+                None => Err(CssParseError::UnexpectedEnd),
+                Some(ch) => Err(CssParseError::UnexpectedToken(ch.to_string())),
+            };
+        }
+        self.position += length;
+        Ok(rest[..length].to_owned())
+    }
+
+    fn skip_oblique_angle(&mut self) -> Result<(), CssParseError> {
+        let mut lookahead = Parser {
+            input: self.input,
+            position: self.position,
+        };
+        if let Ok(token) = lookahead.next_token() {
+            if let Some(angle) = parse_angle(&token)? {
+                if !(-90.0..=90.0).contains(&angle) {
+                    return Err(CssParseError::OutOfRange(token));
+                }
+                *self = lookahead;
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_family_list(&mut self) -> Result<Vec<FamilyName>, CssParseError> {
+        let mut families = vec![self.parse_family()?];
+        while self.skip_char(',') {
+            families.push(self.parse_family()?);
+        }
+        Ok(families)
+    }
+
+    fn parse_family(&mut self) -> Result<FamilyName, CssParseError> {
+        match self.peek_char() {
+            None => return Err(CssParseError::UnexpectedEnd),
+            Some(quote) if is_quote(quote) => {
+                self.position += quote.len_utf8();
+                return self.parse_quoted_family(quote);
+            }
+            Some(_) => {}
+        }
+
+        // An unquoted family name is a sequence of identifiers, joined with single spaces.
+        let mut identifiers = vec![];
+        while let Some(ch) = self.peek_char() {
+            if ch == ',' {
+                break;
+            }
+            let token = self.next_token()?;
+            if !is_identifier(&token) {
+                return Err(CssParseError::UnexpectedToken(token));
+            }
+            identifiers.push(unescape(&token));
+        }
+        if identifiers.is_empty() {
+            return Err(CssParseError::UnexpectedEnd);
+        }
+        if identifiers.len() > 1 {
+            return Ok(FamilyName::Title(identifiers.join(" ")));
+        }
+
+        let identifier = identifiers.pop().unwrap();
+        match &*identifier.to_ascii_lowercase() {
+            "serif" => Ok(FamilyName::Serif),
+            "sans-serif" => Ok(FamilyName::SansSerif),
+            "monospace" => Ok(FamilyName::Monospace),
+            "cursive" => Ok(FamilyName::Cursive),
+            "fantasy" => Ok(FamilyName::Fantasy),
+            "system-ui" => Ok(FamilyName::SystemUi),
+            "ui-serif" => Ok(FamilyName::UiSerif),
+            "ui-sans-serif" => Ok(FamilyName::UiSansSerif),
+            "ui-monospace" => Ok(FamilyName::UiMonospace),
+            "ui-rounded" => Ok(FamilyName::UiRounded),
+            "emoji" => Ok(FamilyName::Emoji),
+            "math" => Ok(FamilyName::Math),
+            "fangsong" => Ok(FamilyName::Fangsong),
+            // CSS-wide keywords can't be used as family names.
+            "inherit" | "initial" | "unset" | "revert" | "default" => {
+                Err(CssParseError::UnexpectedToken(identifier))
+            }
+            _ => Ok(FamilyName::Title(identifier)),
+        }
+    }
+
+    fn parse_quoted_family(&mut self, quote: char) -> Result<FamilyName, CssParseError> {
+        let mut family_name = String::new();
+        let mut chars = self.rest().char_indices();
+        while let Some((index, ch)) = chars.next() {
+            match ch {
+                '\\' => match chars.next() {
+                    Some((_, escaped)) => family_name.push(escaped),
+                    None => break,
+                },
+                _ if ch == quote => {
+                    self.position += index + ch.len_utf8();
+                    return Ok(FamilyName::Title(family_name));
+                }
+                _ => family_name.push(ch),
+            }
+        }
+        Err(CssParseError::UnexpectedEnd)
+    }
+}
+
+fn set_once<T>(slot: &mut Option<T>, value: T, token: &str) -> Result<(), CssParseError> {
+    if slot.is_some() {
+        return Err(CssParseError::UnexpectedToken(token.to_owned()));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn is_quote(ch: char) -> bool {
+    ch == '"' || ch == '\''
+}
+
+fn is_identifier(token: &str) -> bool {
+    let mut chars = token.chars();
+    let first_char = match chars.next() {
+        None => return false,
+        Some('-') => match chars.clone().next() {
+            Some(ch) => ch,
+            None => return false,
+        },
+        Some(ch) => ch,
+    };
+    if first_char.is_ascii_digit() {
+        return false;
+    }
+    token
+        .chars()
+        .all(|ch| ch.is_alphanumeric() || ch == '-' || ch == '_' || ch == '\\' || !ch.is_ascii())
+}
+
+fn unescape(identifier: &str) -> String {
+    let mut result = String::with_capacity(identifier.len());
+    let mut chars = identifier.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' => result.extend(chars.next()),
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+fn is_system_font_keyword(keyword: &str) -> bool {
+    matches!(
+        keyword,
+        "caption" | "icon" | "menu" | "message-box" | "small-caption" | "status-bar"
+    )
+}
+
+fn parse_style_keyword(token: &str) -> Option<Style> {
+    match &*token.to_ascii_lowercase() {
+        "normal" => Some(Style::Normal),
+        "italic" => Some(Style::Italic),
+        "oblique" => Some(Style::Oblique),
+        _ => None,
+    }
+}
+
+fn parse_weight(token: &str) -> Result<Option<Weight>, CssParseError> {
+    match &*token.to_ascii_lowercase() {
+        "normal" => return Ok(Some(Weight::NORMAL)),
+        "bold" | "bolder" => return Ok(Some(Weight::BOLD)),
+        "lighter" => return Ok(Some(Weight::THIN)),
+        _ => {}
+    }
+    match parse_number(token) {
+        Some(weight) if (1.0..=1000.0).contains(&weight) => Ok(Some(Weight(weight))),
+        Some(_) => Err(CssParseError::OutOfRange(token.to_owned())),
+        None => Ok(None),
+    }
+}
+
+fn parse_stretch_keyword(token: &str) -> Option<Stretch> {
+    match &*token.to_ascii_lowercase() {
+        "ultra-condensed" => Some(Stretch::ULTRA_CONDENSED),
+        "extra-condensed" => Some(Stretch::EXTRA_CONDENSED),
+        "condensed" => Some(Stretch::CONDENSED),
+        "semi-condensed" => Some(Stretch::SEMI_CONDENSED),
+        "normal" => Some(Stretch::NORMAL),
+        "semi-expanded" => Some(Stretch::SEMI_EXPANDED),
+        "expanded" => Some(Stretch::EXPANDED),
+        "extra-expanded" => Some(Stretch::EXTRA_EXPANDED),
+        "ultra-expanded" => Some(Stretch::ULTRA_EXPANDED),
+        _ => None,
+    }
+}
+
+fn parse_font_size(token: &str) -> Result<Option<f32>, CssParseError> {
+    let size = match &*token.to_ascii_lowercase() {
+        "xx-small" => return Ok(Some(9.0)),
+        "x-small" => return Ok(Some(10.0)),
+        "small" => return Ok(Some(13.0)),
+        "medium" => return Ok(Some(MEDIUM_FONT_SIZE)),
+        "large" => return Ok(Some(18.0)),
+        "x-large" => return Ok(Some(24.0)),
+        "xx-large" => return Ok(Some(32.0)),
+        "xxx-large" => return Ok(Some(48.0)),
+        "larger" => return Ok(Some(MEDIUM_FONT_SIZE * 1.2)),
+        "smaller" => return Ok(Some(MEDIUM_FONT_SIZE / 1.2)),
+        _ => match parse_percentage(token)? {
+            Some(percentage) => MEDIUM_FONT_SIZE * percentage / 100.0,
+            None => match parse_length(token) {
+                Some(size) => size,
+                None => return Ok(None),
+            },
+        },
+    };
+    if size < 0.0 {
+        return Err(CssParseError::OutOfRange(token.to_owned()));
+    }
+    Ok(Some(size))
+}
+
+fn is_line_height(token: &str) -> Result<bool, CssParseError> {
+    if token.eq_ignore_ascii_case("normal") {
+        return Ok(true);
+    }
+    let line_height = match parse_number(token) {
+        Some(line_height) => line_height,
+        None => match parse_percentage(token)? {
+            Some(line_height) => line_height,
+            None => match parse_length(token) {
+                Some(line_height) => line_height,
+                None => return Ok(false),
+            },
+        },
+    };
+    if line_height < 0.0 {
+        return Err(CssParseError::OutOfRange(token.to_owned()));
+    }
+    Ok(true)
+}
+
+// Returns the length in CSS pixels.
+fn parse_length(token: &str) -> Option<f32> {
+    let unit_start = token
+        .rfind(|ch: char| ch.is_ascii_digit() || ch == '.')
+        .map_or(0, |index| index + 1);
+    let pixels_per_unit = match &*token[unit_start..].to_ascii_lowercase() {
+        "px" => 1.0,
+        "pt" => 96.0 / 72.0,
+        "pc" => 16.0,
+        "in" => 96.0,
+        "cm" => 96.0 / 2.54,
+        "mm" => 96.0 / 25.4,
+        "q" => 96.0 / 101.6,
+        "em" | "rem" => MEDIUM_FONT_SIZE,
+        // A bare zero is the only length that doesn't need a unit.
+        "" if parse_number(token) == Some(0.0) => return Some(0.0),
+        _ => return None,
+    };
+    parse_number(&token[..unit_start]).map(|length| length * pixels_per_unit)
+}
+
+fn parse_percentage(token: &str) -> Result<Option<f32>, CssParseError> {
+    if !token.ends_with('%') {
+        return Ok(None);
+    }
+    match parse_number(&token[..token.len() - 1]) {
+        Some(percentage) if percentage >= 0.0 => Ok(Some(percentage)),
+        Some(_) => Err(CssParseError::OutOfRange(token.to_owned())),
+        None => Err(CssParseError::UnexpectedToken(token.to_owned())),
+    }
+}
+
+// Returns the angle in degrees.
+fn parse_angle(token: &str) -> Result<Option<f32>, CssParseError> {
+    let lowercase_token = token.to_ascii_lowercase();
+    let units = [
+        ("deg", 1.0),
+        ("grad", 0.9),
+        ("rad", 180.0 / ::std::f32::consts::PI),
+        ("turn", 360.0),
+    ];
+    for &(unit, degrees_per_unit) in &units {
+        if lowercase_token.ends_with(unit) {
+            return match parse_number(&token[..token.len() - unit.len()]) {
+                Some(angle) => Ok(Some(angle * degrees_per_unit)),
+                None => Err(CssParseError::UnexpectedToken(token.to_owned())),
+            };
+        }
+    }
+    Ok(None)
+}
+
+// CSS numbers: an optional sign, digits with an optional fraction, and an optional exponent.
+// Unlike `f32::from_str`, this rejects `inf`, `NaN`, and a bare `.`.
+fn parse_number(token: &str) -> Option<f32> {
+    let digits = token.trim_start_matches(&['+', '-'][..]);
+    if token.len() - digits.len() > 1
+        || !digits.starts_with(|ch: char| ch.is_ascii_digit() || ch == '.')
+    {
+        return None;
+    }
+    if !digits.chars().any(|ch| ch.is_ascii_digit()) {
+        return None;
+    }
+    if !digits.chars().all(|ch| {
+        ch.is_ascii_digit() || ch == '.' || ch == 'e' || ch == 'E' || ch == '+' || ch == '-'
+    }) {
+        return None;
+    }
+    token.parse().ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_font, parse_font_family, parse_font_stretch, parse_font_style};
+    use super::{parse_font_weight, FontShorthand};
+    use error::CssParseError;
+    use family_name::FamilyName;
+    use properties::{Properties, Stretch, Style, Weight};
+
+    fn title(family_name: &str) -> FamilyName {
+        FamilyName::Title(family_name.to_owned())
+    }
+
+    #[test]
+    fn parse_family_lists() {
+        assert_eq!(
+            parse_font_family("Times New Roman,  'Noto Serif' , \"A \\\"B\\\"\""),
+            Ok(vec![
+                title("Times New Roman"),
+                title("Noto Serif"),
+                title("A \"B\""),
+            ])
+        );
+        assert_eq!(
+            parse_font_family("SYSTEM-UI, ui-monospace, emoji, math, fangsong, ui-rounded"),
+            Ok(vec![
+                FamilyName::SystemUi,
+                FamilyName::UiMonospace,
+                FamilyName::Emoji,
+                FamilyName::Math,
+                FamilyName::Fangsong,
+                FamilyName::UiRounded,
+            ])
+        );
+        // Quoted keywords are family names, not generic families.
+        assert_eq!(parse_font_family("'serif'"), Ok(vec![title("serif")]));
+        assert_eq!(
+            parse_font_family("Sans Serif"),
+            Ok(vec![title("Sans Serif")])
+        );
+        assert_eq!(
+            parse_font_family("Font\\ Awesome"),
+            Ok(vec![title("Font Awesome")])
+        );
+    }
+
+    #[test]
+    fn parse_invalid_family_lists() {
+        assert_eq!(parse_font_family(""), Err(CssParseError::UnexpectedEnd));
+        assert_eq!(
+            parse_font_family("Arial,"),
+            Err(CssParseError::UnexpectedEnd)
+        );
+        assert_eq!(
+            parse_font_family("'Arial"),
+            Err(CssParseError::UnexpectedEnd)
+        );
+        assert_eq!(
+            parse_font_family("inherit"),
+            Err(CssParseError::UnexpectedToken("inherit".to_owned()))
+        );
+        assert_eq!(
+            parse_font_family("Arial 3D"),
+            Err(CssParseError::UnexpectedToken("3D".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_longhands() {
+        assert_eq!(parse_font_style("italic"), Ok(Style::Italic));
+        assert_eq!(parse_font_style("oblique 10deg"), Ok(Style::Oblique));
+        assert_eq!(
+            parse_font_style("oblique 100deg"),
+            Err(CssParseError::OutOfRange("100deg".to_owned()))
+        );
+        assert_eq!(parse_font_weight("bold"), Ok(Weight::BOLD));
+        assert_eq!(parse_font_weight("lighter"), Ok(Weight::THIN));
+        assert_eq!(parse_font_weight(" 350 "), Ok(Weight(350.0)));
+        assert_eq!(
+            parse_font_weight("1001"),
+            Err(CssParseError::OutOfRange("1001".to_owned()))
+        );
+        assert_eq!(
+            parse_font_weight("inf"),
+            Err(CssParseError::UnexpectedToken("inf".to_owned()))
+        );
+        assert_eq!(
+            parse_font_stretch("semi-condensed"),
+            Ok(Stretch::SEMI_CONDENSED)
+        );
+        assert_eq!(parse_font_stretch("150%"), Ok(Stretch::EXTRA_EXPANDED));
+        assert_eq!(
+            parse_font_stretch("-10%"),
+            Err(CssParseError::OutOfRange("-10%".to_owned()))
+        );
+    }
+
+    #[test]
+    fn parse_font_shorthands() {
+        assert_eq!(
+            parse_font("italic bold 12px/30px Georgia, serif"),
+            Ok(FontShorthand {
+                families: vec![title("Georgia"), FamilyName::Serif],
+                properties: *Properties::new().style(Style::Italic).weight(Weight::BOLD),
+                size: 12.0,
+            })
+        );
+        assert_eq!(
+            parse_font("condensed oblique 25deg small-caps 600 12pt / normal ui-sans-serif"),
+            Ok(FontShorthand {
+                families: vec![FamilyName::UiSansSerif],
+                properties: *Properties::new()
+                    .style(Style::Oblique)
+                    .weight(Weight::SEMIBOLD)
+                    .stretch(Stretch::CONDENSED),
+                size: 16.0,
+            })
+        );
+        assert_eq!(
+            parse_font("normal normal 150% \"Fira Sans\""),
+            Ok(FontShorthand {
+                families: vec![title("Fira Sans")],
+                properties: Properties::new(),
+                size: 24.0,
+            })
+        );
+        assert_eq!(
+            parse_font(" menu "),
+            Ok(FontShorthand {
+                families: vec![FamilyName::SystemUi],
+                properties: Properties::new(),
+                size: 16.0,
+            })
+        );
+    }
+
+    #[test]
+    fn parse_invalid_font_shorthands() {
+        // The size and the family list are both required.
+        assert_eq!(
+            parse_font("bold Arial"),
+            Err(CssParseError::UnexpectedToken("Arial".to_owned()))
+        );
+        assert_eq!(parse_font("bold 12px"), Err(CssParseError::UnexpectedEnd));
+        assert_eq!(
+            parse_font("bold bold 12px Arial"),
+            Err(CssParseError::UnexpectedToken("bold".to_owned()))
+        );
+        assert_eq!(
+            parse_font("12px/ Arial"),
+            Err(CssParseError::UnexpectedToken("Arial".to_owned()))
+        );
+    }
+}
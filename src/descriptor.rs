@@ -0,0 +1,338 @@
+// font-kit/src/descriptor.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Lightweight descriptions of fonts, which sources return from their indexes or read from font
+//! headers without loading the fonts.
+
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+
+use error::FontLoadingError;
+use file_type::FontFormat;
+use font::Font;
+use handle::Handle;
+use language::LanguageSupport;
+use loader::Loader;
+use properties::{Properties, Stretch, Style, Weight};
+use sfnt;
+
+/// The names and properties of a font, along with the handle needed to load it.
+#[derive(Clone, Debug)]
+pub struct FontDescriptor {
+    /// The handle that loads this font.
+    pub handle: Handle,
+    /// The name of the font family.
+    pub family_name: String,
+    /// The full name of the font, as reported to the user.
+    pub full_name: String,
+    /// The PostScript name of the font, if it has one.
+    pub postscript_name: Option<String>,
+    /// The style, weight and stretch of the font.
+    pub properties: Properties,
+    /// Whether all glyphs in the font have the same advance width.
+    pub is_monospace: bool,
+    /// The format that the glyphs are stored in.
+    pub format: FontFormat,
+    /// Whether the font has variation axes, as OpenType variable fonts do.
+    pub is_variable: bool,
+}
+
+impl FontDescriptor {
+    /// Describes the font referenced by the given handle.
+    ///
+    /// TrueType and OpenType fonts are described from their table directories and their `name`,
+    /// `OS/2`, `head` and `post` tables, which are read without loading the font; for fonts on
+    /// disk, only those parts of the file are read. Names and properties are derived the way the
+    /// FreeType loader derives them, so native loaders may report slightly different values: the
+    /// family name, for example, is the first of the WWS (name ID 21), typographic (16) and legacy
+    /// (1) family names that the font has, skipping the WWS name if `OS/2` marks the font as
+    /// WWS-only, whereas Core Text and DirectWrite may report the legacy name.
+    /// Fonts in other formats, and fonts whose headers can't be parsed, are loaded as usual.
+    pub fn from_handle(handle: &Handle) -> Result<FontDescriptor, FontLoadingError> {
+        match read_header_from_handle(handle)? {
+            Some(header) => Ok(header.into_descriptor(handle.clone())),
+            None => Ok(FontDescriptor::from_font(
+                handle.clone(),
+                &Font::from_handle(handle)?,
+            )),
+        }
+    }
+
+    /// Describes a font that has already been loaded from the given handle.
+    pub fn from_font<L>(handle: Handle, font: &L) -> FontDescriptor
+    where
+        L: Loader,
+    {
+        FontDescriptor {
+            handle,
+            family_name: font.family_name(),
+            full_name: font.full_name(),
+            postscript_name: font.postscript_name(),
+            properties: font.properties(),
+            is_monospace: font.is_monospace(),
+            format: font_format(font),
+            is_variable: font.has_font_table(sfnt::FVAR),
+        }
+    }
+
+    /// Returns the index of the font within its file or data, which is 0 unless it's part of a
+    /// collection.
+    #[inline]
+    pub fn font_index(&self) -> u32 {
+        match self.handle {
+            Handle::Path { font_index, .. } | Handle::Memory { font_index, .. } => font_index,
+        }
+    }
+}
+
+// Reads the languages that the font referenced by the given handle declares support for from its
+// `OS/2` and `meta` tables, as `Loader::language_support()` does, loading the font only if its
+// header can't be read.
+pub(crate) fn language_support_from_handle(
+    handle: &Handle,
+) -> Result<LanguageSupport, FontLoadingError> {
+    match read_header_from_handle(handle)? {
+        Some(header) => Ok(LanguageSupport::from_tables(
+            header.os2.as_deref(),
+            header.meta.as_deref(),
+        )),
+        None => Ok(Font::from_handle(handle)?.language_support()),
+    }
+}
+
+// The tables of a TrueType or OpenType font that a description is made from.
+struct Header {
+    table_tags: Vec<u32>,
+    name: Vec<u8>,
+    os2: Option<Vec<u8>>,
+    head: Option<Vec<u8>>,
+    post: Option<Vec<u8>>,
+    meta: Option<Vec<u8>>,
+}
+
+// Reads the header of the font referenced by the given handle; for fonts on disk, only the parts
+// of the file that hold it are read.
+fn read_header_from_handle(handle: &Handle) -> Result<Option<Header>, FontLoadingError> {
+    match *handle {
+        Handle::Path {
+            ref path,
+            font_index,
+        } => {
+            let mut file = File::open(path)?;
+            Ok(read_header(
+                |offset, length| {
+                    // Lengths come from the file, so don't trust them to allocate.
+                    let mut data = vec![];
+                    file.seek(SeekFrom::Start(offset as u64)).ok()?;
+                    (&mut file)
+                        .take(length as u64)
+                        .read_to_end(&mut data)
+                        .ok()?;
+                    if data.len() == length {
+                        Some(data)
+                    } else {
+                        None
+                    }
+                },
+                font_index,
+            ))
+        }
+        Handle::Memory {
+            ref bytes,
+            font_index,
+        } => Ok(read_header(
+            |offset, length| Some(bytes.get(offset..(offset.checked_add(length)?))?.to_vec()),
+            font_index,
+        )),
+    }
+}
+
+// Reads the tables that describe the font at `font_index` through `read`, which returns `length`
+// bytes at `offset`. Returns `None` if the data isn't a TrueType or OpenType font, or if it's
+// malformed, so that the caller can fall back to a loader, which reports the error.
+fn read_header<R>(mut read: R, font_index: u32) -> Option<Header>
+where
+    R: FnMut(usize, usize) -> Option<Vec<u8>>,
+{
+    let file_header = read(0, 12)?;
+    let directory_offset = if sfnt::is_collection(&file_header) {
+        if font_index >= sfnt::read_u32(&file_header, 8)? {
+            return None;
+        }
+        let offset = read(12 + font_index as usize * 4, 4)?;
+        sfnt::read_u32(&offset, 0)? as usize
+    } else if font_index == 0 {
+        0
+    } else {
+        return None;
+    };
+
+    let directory_header = read(directory_offset, 12)?;
+    match sfnt::read_u32(&directory_header, 0)? {
+        sfnt::TRUETYPE_VERSION | sfnt::OPENTYPE_CFF_VERSION | TRUETYPE_APPLE_VERSION => {}
+        _ => return None,
+    }
+    let table_count = sfnt::read_u16(&directory_header, 4)? as usize;
+    let table_records = read(directory_offset + 12, table_count * 16)?;
+
+    let mut table_tags = Vec::with_capacity(table_count);
+    let (mut name, mut os2, mut head, mut post, mut meta) = (None, None, None, None, None);
+    for table_index in 0..table_count {
+        let record_offset = table_index * 16;
+        let tag = sfnt::read_u32(&table_records, record_offset)?;
+        table_tags.push(tag);
+        let table = match tag {
+            sfnt::NAME => &mut name,
+            sfnt::OS_2 => &mut os2,
+            sfnt::HEAD => &mut head,
+            sfnt::POST => &mut post,
+            sfnt::META => &mut meta,
+            _ => continue,
+        };
+        let offset = sfnt::read_u32(&table_records, record_offset + 8)? as usize;
+        let length = sfnt::read_u32(&table_records, record_offset + 12)? as usize;
+        *table = Some(read(offset, length)?);
+    }
+
+    Some(Header {
+        table_tags,
+        name: name?,
+        os2,
+        head,
+        post,
+        meta,
+    })
+}
+
+// The version tag of TrueType fonts made for old versions of macOS.
+const TRUETYPE_APPLE_VERSION: u32 = 0x7472_7565; // 'true'
+
+impl Header {
+    fn into_descriptor(self, handle: Handle) -> FontDescriptor {
+        // Like FreeType, prefer the WWS family name, unless the font is WWS-only, then the
+        // typographic family name.
+        let wws_only = match self.os2.as_ref().and_then(|os2| sfnt::read_u16(os2, 62)) {
+            Some(fs_selection) => fs_selection & FS_SELECTION_WWS != 0,
+            None => false,
+        };
+        let family_name_ids: &[u16] = if wws_only {
+            &[sfnt::NAME_ID_TYPOGRAPHIC_FAMILY, sfnt::NAME_ID_FAMILY]
+        } else {
+            &[
+                sfnt::NAME_ID_WWS_FAMILY,
+                sfnt::NAME_ID_TYPOGRAPHIC_FAMILY,
+                sfnt::NAME_ID_FAMILY,
+            ]
+        };
+        let family_name = family_name_ids
+            .iter()
+            .filter_map(|&name_id| sfnt::read_name(&self.name, name_id))
+            .next()
+            .unwrap_or_default();
+        let full_name = sfnt::read_name(&self.name, sfnt::NAME_ID_FULL_NAME)
+            .unwrap_or_else(|| family_name.clone());
+
+        let has_table = |table_tag| self.table_tags.contains(&table_tag);
+        let format = if has_table(sfnt::GLYF) {
+            FontFormat::TrueType
+        } else if has_table(sfnt::CFF) || has_table(sfnt::CFF2) {
+            FontFormat::Cff
+        } else if has_table(sfnt::CBDT) || has_table(sfnt::EBDT) || has_table(sfnt::SBIX) {
+            FontFormat::Bitmap
+        } else {
+            FontFormat::Other
+        };
+
+        FontDescriptor {
+            handle,
+            postscript_name: sfnt::read_name(&self.name, sfnt::NAME_ID_POSTSCRIPT_NAME),
+            properties: self.properties(),
+            is_monospace: self
+                .post
+                .as_ref()
+                .and_then(|post| sfnt::read_u32(post, 12))
+                .unwrap_or(0)
+                != 0,
+            format,
+            is_variable: has_table(sfnt::FVAR),
+            family_name,
+            full_name,
+        }
+    }
+
+    // Follows `FreeTypeLoader::properties()`: the style comes from `fsSelection` if there's an
+    // `OS/2` table and from `head`'s `macStyle` otherwise.
+    fn properties(&self) -> Properties {
+        let os2 = self.os2.as_ref();
+        let fs_selection = os2.and_then(|os2| sfnt::read_u16(os2, 62));
+        let style = match fs_selection {
+            Some(fs_selection) if fs_selection & FS_SELECTION_OBLIQUE != 0 => Style::Oblique,
+            Some(fs_selection) if fs_selection & FS_SELECTION_ITALIC != 0 => Style::Italic,
+            Some(_) => Style::Normal,
+            None => match self.head.as_ref().and_then(|head| sfnt::read_u16(head, 44)) {
+                Some(mac_style) if mac_style & MAC_STYLE_ITALIC != 0 => Style::Italic,
+                _ => Style::Normal,
+            },
+        };
+        let weight = match os2.and_then(|os2| sfnt::read_u16(os2, 4)) {
+            Some(weight_class) => Weight(weight_class as f32),
+            None => Weight::NORMAL,
+        };
+        // Width classes run from 1 to 9; anything else is treated as normal.
+        let stretch = match os2.and_then(|os2| sfnt::read_u16(os2, 6)) {
+            Some(width_class) if width_class > 0 => Stretch::MAPPING
+                .get(width_class as usize - 1)
+                .map_or(Stretch::NORMAL, |&stretch| Stretch(stretch)),
+            _ => Stretch::NORMAL,
+        };
+        Properties {
+            style,
+            weight,
+            stretch,
+        }
+    }
+}
+
+const FS_SELECTION_ITALIC: u16 = 1 << 0;
+const FS_SELECTION_WWS: u16 = 1 << 8;
+const FS_SELECTION_OBLIQUE: u16 = 1 << 9;
+const MAC_STYLE_ITALIC: u16 = 1 << 1;
+
+// OpenType fonts are told apart by their tables. Other formats have no tables, so we look at
+// their signatures instead.
+fn font_format<L>(font: &L) -> FontFormat
+where
+    L: Loader,
+{
+    if font.has_font_table(sfnt::GLYF) {
+        return FontFormat::TrueType;
+    }
+    if font.has_font_table(sfnt::CFF) || font.has_font_table(sfnt::CFF2) {
+        return FontFormat::Cff;
+    }
+    if [sfnt::CBDT, sfnt::EBDT, sfnt::SBIX]
+        .iter()
+        .any(|&table_tag| font.has_font_table(table_tag))
+    {
+        return FontFormat::Bitmap;
+    }
+
+    let font_data = match font.copy_font_data() {
+        Some(font_data) => font_data,
+        None => return FontFormat::Other,
+    };
+    if font_data.starts_with(b"%!") || font_data.starts_with(&[0x80, 0x01]) {
+        FontFormat::Type1
+    } else if font_data.starts_with(b"\x01fcp") || font_data.starts_with(b"STARTFONT") {
+        FontFormat::Bitmap
+    } else {
+        FontFormat::Other
+    }
+}
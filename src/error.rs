@@ -12,7 +12,12 @@
 
 use std::convert::From;
 use std::error::Error;
+use std::fmt::{self, Display, Formatter};
 use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use features::Tag;
 
 macro_rules! impl_display {
     ($enum:ident, {$($variant:pat => $fmt_string:expr),+$(,)* }) => {
@@ -30,6 +35,57 @@ macro_rules! impl_display {
     };
 }
 
+/// An error code reported by the platform library that a loader or source is built on.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BackendError {
+    /// A FreeType `FT_Error` code.
+    FreeType(i32),
+    /// A Windows `HRESULT`, as returned by DirectWrite.
+    HResult(i32),
+    /// A Core Text or Core Graphics call failed. These APIs don't report error codes.
+    CoreText,
+    /// A Fontconfig `FcResult` code.
+    Fontconfig(u32),
+}
+
+impl Error for BackendError {}
+
+impl Display for BackendError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match *self {
+            BackendError::FreeType(error) => {
+                write!(f, "FreeType error 0x{:02x}", error)?;
+                match freetype_error_message(error) {
+                    Some(message) => write!(f, " ({})", message),
+                    None => Ok(()),
+                }
+            }
+            BackendError::HResult(error) => write!(f, "HRESULT 0x{:08x}", error as u32),
+            BackendError::CoreText => write!(f, "Core Text error"),
+            BackendError::Fontconfig(result) => write!(f, "Fontconfig result {}", result),
+        }
+    }
+}
+
+fn freetype_error_message(error: i32) -> Option<&'static str> {
+    // From `fterrdef.h`.
+    match error {
+        0x01 => Some("cannot open resource"),
+        0x02 => Some("unknown file format"),
+        0x03 => Some("broken file"),
+        0x06 => Some("invalid argument"),
+        0x07 => Some("unimplemented feature"),
+        0x08 => Some("broken table"),
+        0x10 => Some("invalid glyph index"),
+        0x11 => Some("invalid character code"),
+        0x12 => Some("unsupported glyph image format"),
+        0x40 => Some("out of memory"),
+        0x55 => Some("stack overflow in bytecode interpreter"),
+        0x8e => Some("table missing"),
+        _ => None,
+    }
+}
+
 /// Reasons why a loader might fail to load a font.
 #[derive(Debug)]
 pub enum FontLoadingError {
@@ -39,24 +95,92 @@ pub enum FontLoadingError {
     ///
     /// For example, if a `.ttc` file has 2 fonts in it, and you ask for the 5th one, you'll get
     /// this error.
-    NoSuchFontInCollection,
+    NoSuchFontInCollection {
+        /// The index that was asked for.
+        font_index: u32,
+    },
     /// Attempted to load a malformed or corrupted font.
-    Parse,
+    ///
+    /// This carries the error code that the platform library reported, if any.
+    Parse(Option<BackendError>),
+    /// A table that the font needs was missing or malformed.
+    MalformedTable {
+        /// The tag of the table.
+        table: Tag,
+        /// The error code that the platform library reported, if any.
+        error: Option<BackendError>,
+    },
     /// Attempted to load a font from the filesystem, but there is no filesystem (e.g. in
     /// WebAssembly).
     NoFilesystem,
     /// A disk or similar I/O error occurred while attempting to load the font.
     Io(io::Error),
+    /// Loading the font at a path failed.
+    ///
+    /// Use `without_path()` to get at the underlying error.
+    Path {
+        /// The path to the font.
+        path: PathBuf,
+        /// Why loading the font failed.
+        error: Box<FontLoadingError>,
+    },
 }
 
-impl Error for FontLoadingError {}
+impl FontLoadingError {
+    /// Attaches the path of the font file to this error, unless it already has one.
+    pub fn in_file<P>(self, path: P) -> FontLoadingError
+    where
+        P: AsRef<Path>,
+    {
+        match self {
+            FontLoadingError::Path { .. } => self,
+            error => FontLoadingError::Path {
+                path: path.as_ref().to_owned(),
+                error: Box::new(error),
+            },
+        }
+    }
+
+    /// Returns the underlying error, stripped of any path that was attached to it.
+    ///
+    /// This is convenient for matching on the reason a font failed to load.
+    pub fn without_path(&self) -> &FontLoadingError {
+        match *self {
+            FontLoadingError::Path { ref error, .. } => error.without_path(),
+            ref error => error,
+        }
+    }
+}
+
+impl Error for FontLoadingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            FontLoadingError::Parse(Some(ref error)) => Some(error),
+            FontLoadingError::MalformedTable {
+                error: Some(ref error),
+                ..
+            } => Some(error),
+            FontLoadingError::Io(ref error) => Some(error),
+            FontLoadingError::Path { ref error, .. } => Some(&**error),
+            _ => None,
+        }
+    }
+}
 
 impl_display! { FontLoadingError, {
         UnknownFormat => "unknown format",
-        NoSuchFontInCollection => "no such font in the collection",
-        Parse => "parse error",
+        NoSuchFontInCollection { font_index } => {
+            format!("no font at index {} in the collection", font_index)
+        },
+        Parse(None) => "parse error",
+        Parse(Some(e)) => format!("parse error: {}", e),
+        MalformedTable { table, error: None } => format!("missing or malformed `{}` table", table),
+        MalformedTable { table, error: Some(e) } => {
+            format!("missing or malformed `{}` table: {}", table, e)
+        },
         NoFilesystem => "no filesystem present",
         Io(e) => format!("I/O error: {}", e),
+        Path { path, error } => format!("{}: {}", path.display(), error),
     }
 }
 
@@ -69,40 +193,179 @@ impl From<io::Error> for FontLoadingError {
 /// Reasons why a font might fail to load a glyph.
 #[derive(Clone, Copy, PartialEq, Debug)]
 pub enum GlyphLoadingError {
-    /// The font didn't contain a glyph with that ID.
-    NoSuchGlyph,
-    /// A platform function returned an error.
-    PlatformError,
+    /// The font didn't contain a glyph with the given ID.
+    NoSuchGlyph(u32),
+    /// A platform function returned an error while loading a glyph.
+    PlatformError {
+        /// The ID of the glyph that was being loaded.
+        glyph_id: u32,
+        /// The error code that the platform library reported.
+        error: BackendError,
+    },
+    /// The rasterizer produced a bitmap in a pixel format that can't be drawn to a canvas (for
+    /// example, a color bitmap).
+    UnsupportedPixelFormat,
 }
 
-impl Error for GlyphLoadingError {}
-
-impl_display! { GlyphLoadingError, {
-        NoSuchGlyph => "no such glyph",
-        PlatformError => "platform error",
+impl Error for GlyphLoadingError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            GlyphLoadingError::PlatformError { ref error, .. } => Some(error),
+            _ => None,
+        }
     }
 }
 
-#[cfg(target_family = "windows")]
-impl From<winapi::um::winnt::HRESULT> for GlyphLoadingError {
-    fn from(_err: winapi::um::winnt::HRESULT) -> GlyphLoadingError {
-        GlyphLoadingError::PlatformError
+impl_display! { GlyphLoadingError, {
+        NoSuchGlyph(glyph_id) => format!("no glyph with ID {}", glyph_id),
+        PlatformError { glyph_id, error } => {
+            format!("platform error loading glyph {}: {}", glyph_id, error)
+        },
+        UnsupportedPixelFormat => "unsupported pixel format",
     }
 }
 
 /// Reasons why a source might fail to look up a font or fonts.
-#[derive(Clone, Copy, PartialEq, Debug)]
+#[derive(Clone, Debug)]
 pub enum SelectionError {
     /// No font matching the given query was found.
     NotFound,
     /// The source was inaccessible because of an I/O or similar error.
-    CannotAccessSource,
+    ///
+    /// This carries the error code that the platform library reported, if any.
+    CannotAccessSource(Option<BackendError>),
+    /// A font in the source couldn't be loaded while evaluating the query.
+    CannotLoadFont(Arc<FontLoadingError>),
+    /// The query required a script that there's no way to check fonts for.
+    UnsupportedScript(String),
 }
 
-impl Error for SelectionError {}
+impl Error for SelectionError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            SelectionError::CannotAccessSource(Some(ref error)) => Some(error),
+            SelectionError::CannotLoadFont(ref error) => Some(&**error),
+            _ => None,
+        }
+    }
+}
 
 impl_display! { SelectionError, {
         NotFound => "no font found",
-        CannotAccessSource => "failed to access source",
+        CannotAccessSource(None) => "failed to access source",
+        CannotAccessSource(Some(e)) => format!("failed to access source: {}", e),
+        CannotLoadFont(e) => format!("failed to load font: {}", e),
+        UnsupportedScript(script) => format!("unsupported script `{}`", script),
+    }
+}
+
+impl From<FontLoadingError> for SelectionError {
+    fn from(error: FontLoadingError) -> SelectionError {
+        SelectionError::CannotLoadFont(Arc::new(error))
+    }
+}
+
+impl PartialEq for SelectionError {
+    fn eq(&self, other: &SelectionError) -> bool {
+        use self::SelectionError::*;
+        match (self, other) {
+            (NotFound, NotFound) => true,
+            (CannotAccessSource(a), CannotAccessSource(b)) => a == b,
+            (CannotLoadFont(a), CannotLoadFont(b)) => {
+                Arc::ptr_eq(a, b) || font_loading_errors_match(a, b)
+            }
+            (UnsupportedScript(a), UnsupportedScript(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// `FontLoadingError` can't be compared directly because `io::Error` can't, so I/O errors are
+// compared by kind.
+fn font_loading_errors_match(a: &FontLoadingError, b: &FontLoadingError) -> bool {
+    use self::FontLoadingError::*;
+    match (a, b) {
+        (UnknownFormat, UnknownFormat) | (NoFilesystem, NoFilesystem) => true,
+        (NoSuchFontInCollection { font_index: a }, NoSuchFontInCollection { font_index: b }) => {
+            a == b
+        }
+        (Parse(a), Parse(b)) => a == b,
+        (
+            MalformedTable {
+                table: table_a,
+                error: error_a,
+            },
+            MalformedTable {
+                table: table_b,
+                error: error_b,
+            },
+        ) => table_a == table_b && error_a == error_b,
+        (Io(a), Io(b)) => a.kind() == b.kind(),
+        (
+            Path {
+                path: path_a,
+                error: error_a,
+            },
+            Path {
+                path: path_b,
+                error: error_b,
+            },
+        ) => path_a == path_b && font_loading_errors_match(error_a, error_b),
+        _ => false,
+    }
+}
+
+/// Reasons why a font might fail to be subset or extracted from a collection.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SubsetError {
+    /// The font's glyphs are stored in a format the subsetter doesn't understand; e.g. `CFF2` or
+    /// bitmap-only fonts.
+    UnsupportedFormat,
+    /// A table that every font needs, such as `head` or `maxp`, was missing.
+    MissingTable(Tag),
+    /// The given table was malformed or corrupted.
+    MalformedTable(Tag),
+    /// The table directory or collection header was malformed or corrupted.
+    Parse,
+    /// The font didn't contain a glyph with the given ID.
+    NoSuchGlyph(u32),
+    /// Attempted to extract an invalid index from a TrueType or OpenType font collection.
+    NoSuchFontInCollection {
+        /// The index that was asked for.
+        font_index: u32,
+    },
+}
+
+impl Error for SubsetError {}
+
+impl_display! { SubsetError, {
+        UnsupportedFormat => "unsupported glyph format",
+        MissingTable(tag) => format!("missing required table `{}`", tag),
+        MalformedTable(tag) => format!("malformed `{}` table", tag),
+        Parse => "parse error",
+        NoSuchGlyph(glyph_id) => format!("no glyph with ID {}", glyph_id),
+        NoSuchFontInCollection { font_index } => {
+            format!("no font at index {} in the collection", font_index)
+        },
+    }
+}
+
+/// Reasons why a CSS font value might fail to parse.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CssParseError {
+    /// The value ended where more was expected; e.g. a `font` shorthand without a family list.
+    UnexpectedEnd,
+    /// The value contained a token that isn't allowed at that point.
+    UnexpectedToken(String),
+    /// A number was outside the range that the property allows; e.g. a weight of 1200.
+    OutOfRange(String),
+}
+
+impl Error for CssParseError {}
+
+impl_display! { CssParseError, {
+        UnexpectedEnd => "unexpected end of value",
+        UnexpectedToken(token) => format!("unexpected `{}`", token),
+        OutOfRange(token) => format!("`{}` is out of range", token),
     }
 }
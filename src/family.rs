@@ -10,10 +10,7 @@
 
 //! Defines a set of faces that vary in weight, width or slope.
 
-use error::FontLoadingError;
-use family_handle::FamilyHandle;
 use font::Font;
-use handle::Handle;
 use loader::Loader;
 
 /// Defines a set of faces that vary in weight, width or slope.
@@ -29,22 +26,6 @@ impl<F> Family<F>
 where
     F: Loader,
 {
-    pub(crate) fn from_font_handles<'a, I>(font_handles: I) -> Result<Family<F>, FontLoadingError>
-    where
-        I: Iterator<Item = &'a Handle>,
-    {
-        let mut fonts = vec![];
-        for font_handle in font_handles {
-            fonts.push(F::from_handle(font_handle)?)
-        }
-        Ok(Family { fonts })
-    }
-
-    #[inline]
-    pub(crate) fn from_handle(family_handle: &FamilyHandle) -> Result<Family<F>, FontLoadingError> {
-        Family::from_font_handles(family_handle.fonts.iter())
-    }
-
     /// Returns the individual fonts in this family.
     #[inline]
     pub fn fonts(&self) -> &[F] {
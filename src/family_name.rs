@@ -13,10 +13,16 @@
 /// A possible value for the `font-family` CSS property.
 ///
 /// These descriptions are taken from CSS Fonts Level 3 § 3.1:
-/// https://drafts.csswg.org/css-fonts-3/#font-family-prop.
+/// https://drafts.csswg.org/css-fonts-3/#font-family-prop, and, for the newer generic families,
+/// CSS Fonts Level 4 § 4.2: https://drafts.csswg.org/css-fonts-4/#generic-font-families.
 ///
-/// TODO(pcwalton): `system-ui`, `emoji`, `math`, `fangsong`
+/// To parse a CSS `font-family` value into a list of these, use `css::parse_font_family()`.
+///
+/// With the `serde` feature, generic families are serialized as their CSS keywords (e.g.
+/// `"sans-serif"`) and specific families as `title` variants.
 #[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "kebab-case"))]
 pub enum FamilyName {
     /// A specific font family, specified by name: e.g. "Arial", "times".
     Title(String),
@@ -34,4 +40,23 @@ pub enum FamilyName {
     /// Fantasy fonts are primarily decorative or expressive fonts that contain decorative or
     /// expressive representations of characters.
     Fantasy,
+    /// Glyphs are taken from the default user interface font on the platform.
+    SystemUi,
+    /// The default user interface serif font.
+    UiSerif,
+    /// The default user interface sans-serif font.
+    UiSansSerif,
+    /// The default user interface monospace font.
+    UiMonospace,
+    /// The default user interface font that has rounded features.
+    UiRounded,
+    /// Fonts that are specifically designed to render emoji.
+    Emoji,
+    /// Fonts meant for displaying mathematical expressions, for example superscript and
+    /// subscript, brackets that cross several lines, nesting expressions, and double-struck
+    /// glyphs with distinct meanings.
+    Math,
+    /// A particular style of Chinese characters that are between serif-style Song and
+    /// cursive-style Kai forms.
+    Fangsong,
 }
@@ -0,0 +1,264 @@
+// font-kit/src/features.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The OpenType scripts, language systems, and features that a font declares.
+//!
+//! These come from the `GSUB` and `GPOS` tables. They describe what the font *can* do (e.g. small
+//! capitals via `smcp` or old-style figures via `onum`); font-kit doesn't apply them.
+
+use std::fmt::{self, Debug, Display, Formatter};
+
+use sfnt::{read_u16, read_u32};
+
+/// A four-byte OpenType tag identifying a script, language system, feature, or table.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Tag(pub u32);
+
+impl Tag {
+    /// The tag for the `DFLT` script, which applies to any script not listed explicitly.
+    pub const DEFAULT_SCRIPT: Tag = Tag(0x4446_4c54);
+
+    /// Creates a tag from its four characters; e.g. `Tag::from_bytes(b"smcp")`.
+    ///
+    /// Tags shorter than four characters, such as many language system tags, are padded with
+    /// spaces; e.g. `b"ENG "`.
+    #[inline]
+    pub fn from_bytes(bytes: &[u8; 4]) -> Tag {
+        Tag(((bytes[0] as u32) << 24)
+            | ((bytes[1] as u32) << 16)
+            | ((bytes[2] as u32) << 8)
+            | (bytes[3] as u32))
+    }
+
+    /// Returns the four characters of this tag.
+    #[inline]
+    pub fn to_bytes(self) -> [u8; 4] {
+        [
+            (self.0 >> 24) as u8,
+            (self.0 >> 16) as u8,
+            (self.0 >> 8) as u8,
+            self.0 as u8,
+        ]
+    }
+}
+
+impl Display for Tag {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        for &byte in &self.to_bytes() {
+            let ch = if byte.is_ascii_graphic() || byte == b' ' {
+                byte as char
+            } else {
+                '?'
+            };
+            write!(formatter, "{}", ch)?;
+        }
+        Ok(())
+    }
+}
+
+impl Debug for Tag {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "Tag(\"{}\")", self)
+    }
+}
+
+/// The scripts and features declared in the `GSUB` and `GPOS` tables of a font.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayoutFeatures {
+    /// The glyph substitution table (ligatures, small caps, alternate figures, etc.)
+    pub gsub: LayoutTable,
+    /// The glyph positioning table (kerning, mark placement, etc.)
+    pub gpos: LayoutTable,
+}
+
+impl LayoutFeatures {
+    /// Parses the raw `GSUB` and `GPOS` tables of a font.
+    ///
+    /// Missing or malformed tables are treated as empty.
+    pub fn from_tables(gsub: Option<&[u8]>, gpos: Option<&[u8]>) -> LayoutFeatures {
+        LayoutFeatures {
+            gsub: gsub.and_then(LayoutTable::parse).unwrap_or_default(),
+            gpos: gpos.and_then(LayoutTable::parse).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the tags of all scripts that either table declares, sorted and deduplicated.
+    pub fn scripts(&self) -> Vec<Tag> {
+        let mut scripts: Vec<Tag> = self
+            .gsub
+            .scripts
+            .iter()
+            .chain(self.gpos.scripts.iter())
+            .map(|script| script.tag)
+            .collect();
+        scripts.sort();
+        scripts.dedup();
+        scripts
+    }
+
+    /// Returns the tags of all features that either table declares, sorted and deduplicated.
+    pub fn features(&self) -> Vec<Tag> {
+        let mut features: Vec<Tag> = self
+            .gsub
+            .features
+            .iter()
+            .chain(self.gpos.features.iter())
+            .cloned()
+            .collect();
+        features.sort();
+        features.dedup();
+        features
+    }
+
+    /// Returns true if and only if either table declares the given script.
+    pub fn supports_script(&self, script: Tag) -> bool {
+        self.gsub.script(script).is_some() || self.gpos.script(script).is_some()
+    }
+
+    /// Returns true if and only if either table declares the given feature.
+    pub fn supports_feature(&self, feature: Tag) -> bool {
+        self.gsub.features.contains(&feature) || self.gpos.features.contains(&feature)
+    }
+}
+
+/// The scripts and features declared in one OpenType layout table (`GSUB` or `GPOS`).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LayoutTable {
+    /// The scripts, in the order the font lists them.
+    pub scripts: Vec<Script>,
+    /// The tags of all features in the table, sorted and deduplicated.
+    pub features: Vec<Tag>,
+}
+
+impl LayoutTable {
+    /// Parses the header of a raw `GSUB` or `GPOS` table.
+    ///
+    /// Returns `None` if the table is truncated or an offset points outside it.
+    pub fn parse(data: &[u8]) -> Option<LayoutTable> {
+        let script_list = data.get((read_u16(data, 4)? as usize)..)?;
+        let feature_list = data.get((read_u16(data, 6)? as usize)..)?;
+
+        // Language systems refer to features by index, so keep them in font order for now.
+        let feature_count = read_u16(feature_list, 0)? as usize;
+        let mut feature_tags = Vec::with_capacity(feature_count);
+        for feature_index in 0..feature_count {
+            feature_tags.push(Tag(read_u32(feature_list, 2 + feature_index * 6)?));
+        }
+
+        let script_count = read_u16(script_list, 0)? as usize;
+        let mut scripts = Vec::with_capacity(script_count);
+        for script_index in 0..script_count {
+            let record_offset = 2 + script_index * 6;
+            let tag = Tag(read_u32(script_list, record_offset)?);
+            let script = script_list.get((read_u16(script_list, record_offset + 4)? as usize)..)?;
+            scripts.push(Script::parse(tag, script, &feature_tags)?);
+        }
+
+        feature_tags.sort();
+        feature_tags.dedup();
+        Some(LayoutTable {
+            scripts,
+            features: feature_tags,
+        })
+    }
+
+    /// Returns the script with the given tag, if the table declares it.
+    pub fn script(&self, tag: Tag) -> Option<&Script> {
+        self.scripts.iter().find(|script| script.tag == tag)
+    }
+}
+
+/// A script declared in an OpenType layout table, with its language systems.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Script {
+    /// The script tag; e.g. `latn` or `cyrl`.
+    pub tag: Tag,
+    /// The language systems of this script.
+    ///
+    /// The default language system, if present, comes first and has no tag.
+    pub language_systems: Vec<LanguageSystem>,
+}
+
+impl Script {
+    fn parse(tag: Tag, script: &[u8], feature_tags: &[Tag]) -> Option<Script> {
+        let mut language_systems = vec![];
+
+        let default_language_system_offset = read_u16(script, 0)? as usize;
+        if default_language_system_offset != 0 {
+            let language_system = script.get(default_language_system_offset..)?;
+            language_systems.push(LanguageSystem::parse(None, language_system, feature_tags)?);
+        }
+
+        let language_system_count = read_u16(script, 2)? as usize;
+        for language_system_index in 0..language_system_count {
+            let record_offset = 4 + language_system_index * 6;
+            let language_system_tag = Tag(read_u32(script, record_offset)?);
+            let language_system = script.get((read_u16(script, record_offset + 4)? as usize)..)?;
+            language_systems.push(LanguageSystem::parse(
+                Some(language_system_tag),
+                language_system,
+                feature_tags,
+            )?);
+        }
+
+        Some(Script {
+            tag,
+            language_systems,
+        })
+    }
+
+    /// Returns the language system with the given tag, if the script declares it.
+    ///
+    /// Pass `None` to get the default language system.
+    pub fn language_system(&self, tag: Option<Tag>) -> Option<&LanguageSystem> {
+        self.language_systems
+            .iter()
+            .find(|language_system| language_system.tag == tag)
+    }
+}
+
+/// A language system declared for a script in an OpenType layout table.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LanguageSystem {
+    /// The language system tag; e.g. `TRK `. This is `None` for the default language system.
+    pub tag: Option<Tag>,
+    /// The feature that must always be applied for this language system, if any.
+    pub required_feature: Option<Tag>,
+    /// The tags of the features available in this language system, sorted and deduplicated.
+    pub features: Vec<Tag>,
+}
+
+impl LanguageSystem {
+    fn parse(
+        tag: Option<Tag>,
+        language_system: &[u8],
+        feature_tags: &[Tag],
+    ) -> Option<LanguageSystem> {
+        let required_feature = match read_u16(language_system, 2)? {
+            0xffff => None,
+            feature_index => Some(*feature_tags.get(feature_index as usize)?),
+        };
+
+        let feature_count = read_u16(language_system, 4)? as usize;
+        let mut features = Vec::with_capacity(feature_count);
+        for index in 0..feature_count {
+            let feature_index = read_u16(language_system, 6 + index * 2)? as usize;
+            features.push(*feature_tags.get(feature_index)?);
+        }
+        features.sort();
+        features.dedup();
+
+        Some(LanguageSystem {
+            tag,
+            required_feature,
+            features,
+        })
+    }
+}
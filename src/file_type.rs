@@ -9,12 +9,32 @@
 // except according to those terms.
 
 //! The type of a font file: either a single font or a TrueType/OpenType collection.
+//!
+//! Also contains the formats that the fonts themselves can be in.
 
 /// The type of a font file: either a single font or a TrueType/OpenType collection.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum FileType {
     /// The font file represents a single font (`.ttf`, `.otf`, `.woff`, etc.)
     Single,
     /// The font file represents a collection of fonts (`.ttc`, `.otc`, etc.)
     Collection(u32),
 }
+
+/// The format that a font's glyphs are stored in.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum FontFormat {
+    /// TrueType outlines, in a `glyf` table.
+    TrueType,
+    /// PostScript outlines, in a `CFF ` or `CFF2` table (`.otf`, etc.)
+    Cff,
+    /// A PostScript Type 1 font (`.pfa`, `.pfb`, etc.)
+    Type1,
+    /// Bitmaps only, in an OpenType font (e.g. color emoji) or a bitmap font format (`.pcf`,
+    /// `.bdf`, etc.)
+    Bitmap,
+    /// Any other format.
+    Other,
+}
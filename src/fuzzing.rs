@@ -0,0 +1,189 @@
+// font-kit/src/fuzzing.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The bodies of the fuzz targets.
+//!
+//! These live in the crate so that the `cargo fuzz` targets, the corpus replay in
+//! `tests/fuzz_corpus.rs`, and the corrupted font tests all run exactly the same code. Errors are
+//! expected and ignored; only panics and crashes count as failures.
+//!
+//! This module is not part of the public API.
+
+use euclid::{Point2D, Size2D};
+use std::cmp;
+use std::sync::Arc;
+
+use canvas::{Canvas, Format, RasterizationOptions};
+use file_type::FileType;
+use font::Font;
+use hinting::HintingOptions;
+use layout::{self, LayoutOptions};
+
+// Keeps the time spent on a single input bounded for fonts that claim huge glyph counts.
+const MAX_GLYPHS: u32 = 64;
+const MAX_RASTERIZED_GLYPHS: u32 = 8;
+const MAX_FONTS_IN_COLLECTION: u32 = 4;
+// Damaged metrics can claim enormous raster bounds; don't allocate canvases for them.
+const MAX_CANVAS_SIZE: i32 = 256;
+
+const FORMATS: [Format; 3] = [Format::A8, Format::Rgb24, Format::Rgba32];
+const RASTERIZATION_OPTIONS: [RasterizationOptions; 3] = [
+    RasterizationOptions::Bilevel,
+    RasterizationOptions::GrayscaleAa,
+    RasterizationOptions::SubpixelAa,
+];
+const HINTING_OPTIONS: [HintingOptions; 4] = [
+    HintingOptions::None,
+    HintingOptions::Vertical(12.0),
+    HintingOptions::VerticalSubpixel(12.0),
+    HintingOptions::Full(12.0),
+];
+
+// Exercises ligatures, kerning pairs, fallback for unmapped characters, and line breaking.
+const LAYOUT_TEXTS: [&str; 3] = [
+    "AVA Wo To fi ffl",
+    "g\u{e9}\u{3042}\u{1f600}\0",
+    "a b\nc  d",
+];
+
+// Table tags that the loaders and `sfnt` helpers parse themselves.
+const TABLE_TAGS: [&[u8; 4]; 8] = [
+    b"head", b"hhea", b"OS/2", b"post", b"vhea", b"VORG", b"GSUB", b"GPOS",
+];
+
+/// Analyzes and loads the data, then calls every metadata accessor and extracts glyph outlines.
+pub fn load_font(data: &[u8]) {
+    for font in fonts_in_data(data) {
+        inspect_font(&font);
+    }
+}
+
+/// Loads the first font in the data and rasterizes some of its glyphs into every canvas format
+/// with every rasterization and hinting mode.
+pub fn rasterize_glyphs(data: &[u8]) {
+    if let Ok(font) = Font::from_bytes(Arc::new(data.to_vec()), 0) {
+        rasterize_font(&font);
+    }
+}
+
+/// Analyzes and loads the data, then lays out text with each font, which runs the `GSUB`,
+/// `GPOS`, and `kern` parsers.
+pub fn layout_text(data: &[u8]) {
+    for font in fonts_in_data(data) {
+        layout_font(&font);
+    }
+}
+
+/// Runs every check that the fuzz targets run on an already-loaded font.
+pub fn exercise_font(font: &Font) {
+    inspect_font(font);
+    rasterize_font(font);
+    layout_font(font);
+}
+
+// Every font in the data that loads, up to a limit for collections.
+fn fonts_in_data(data: &[u8]) -> Vec<Font> {
+    let font_data = Arc::new(data.to_vec());
+    let font_count = match Font::analyze_bytes(font_data.clone()) {
+        Ok(FileType::Collection(font_count)) => cmp::min(font_count, MAX_FONTS_IN_COLLECTION),
+        Ok(FileType::Single) | Err(_) => 1,
+    };
+    (0..font_count)
+        .filter_map(|font_index| Font::from_bytes(font_data.clone(), font_index).ok())
+        .collect()
+}
+
+fn inspect_font(font: &Font) {
+    let _ = (font.postscript_name(), font.full_name(), font.family_name());
+    let _ = (font.is_monospace(), font.properties(), font.metrics());
+    let _ = (font.font_index(), font.handle(), font.copy_font_data());
+    let _ = font.copy_standalone_font_data();
+    let _ = font.layout_features();
+    for table_tag in &TABLE_TAGS {
+        let _ = font.load_font_table(u32::from_be_bytes(**table_tag));
+    }
+    for &character in &['\0', 'A', 'g', 'é', '\u{3042}', '\u{1f600}'] {
+        let _ = font.glyph_for_char(character);
+    }
+    let _ = font.glyph_by_name("A");
+    for &hinting_options in &HINTING_OPTIONS {
+        for &for_rasterization in &[false, true] {
+            let _ = font.supports_hinting_options(hinting_options, for_rasterization);
+        }
+    }
+
+    for glyph_id in glyph_ids(font, MAX_GLYPHS) {
+        let _ = (font.typographic_bounds(glyph_id), font.advance(glyph_id));
+        let _ = (font.origin(glyph_id), font.vertical_advance(glyph_id));
+        let _ = font.vertical_origin(glyph_id);
+        for &hinting_options in &HINTING_OPTIONS {
+            let _ = font.glyph_outline(glyph_id, hinting_options);
+        }
+    }
+}
+
+fn rasterize_font(font: &Font) {
+    for glyph_id in glyph_ids(font, MAX_RASTERIZED_GLYPHS) {
+        for &hinting_options in &HINTING_OPTIONS {
+            for &rasterization_options in &RASTERIZATION_OPTIONS {
+                let raster_rect = match font.raster_bounds(
+                    glyph_id,
+                    16.0,
+                    &Point2D::zero(),
+                    hinting_options,
+                    rasterization_options,
+                ) {
+                    Ok(raster_rect) => raster_rect,
+                    Err(_) => continue,
+                };
+                let canvas_size = Size2D::new(
+                    clamp_canvas_length(raster_rect.size.width),
+                    clamp_canvas_length(raster_rect.size.height),
+                );
+                let origin = Point2D::new(-raster_rect.origin.x, -raster_rect.origin.y).to_f32();
+
+                for &format in &FORMATS {
+                    let mut canvas = Canvas::new(&canvas_size, format);
+                    let _ = font.rasterize_glyph(
+                        &mut canvas,
+                        glyph_id,
+                        16.0,
+                        &origin,
+                        hinting_options,
+                        rasterization_options,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn layout_font(font: &Font) {
+    for text in &LAYOUT_TEXTS {
+        for &(ligatures, kerning) in &[(true, true), (false, false)] {
+            let mut options = LayoutOptions::new(16.0);
+            options
+                .ligatures(ligatures)
+                .kerning(kerning)
+                .max_width(32.0);
+            let _ = layout::layout(font, text, &options);
+        }
+    }
+}
+
+// The first few glyphs, plus a couple just past the end to exercise the error paths.
+fn glyph_ids(font: &Font, max_glyph_count: u32) -> impl Iterator<Item = u32> {
+    let glyph_count = font.glyph_count();
+    (0..cmp::min(glyph_count, max_glyph_count)).chain(glyph_count..glyph_count.saturating_add(2))
+}
+
+fn clamp_canvas_length(length: i32) -> u32 {
+    length.clamp(0, MAX_CANVAS_SIZE) as u32
+}
@@ -0,0 +1,415 @@
+// font-kit/src/generic_family_map.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! A configurable mapping from generic family names to concrete font families.
+
+use family_name::FamilyName;
+
+lazy_static! {
+    pub(crate) static ref PLATFORM_GENERIC_FAMILY_MAP: GenericFamilyMap = {
+        let mut generic_family_map = GenericFamilyMap::empty();
+        for &(ref generic_family, locale, families) in PLATFORM_DEFAULTS {
+            match locale {
+                None => generic_family_map.set(generic_family.clone(), families.iter().cloned()),
+                Some(locale) => generic_family_map.set_for_locale(
+                    locale,
+                    generic_family.clone(),
+                    families.iter().cloned(),
+                ),
+            };
+        }
+        generic_family_map
+    };
+}
+
+// The generic family, the language tag the entry applies to (or `None` for all languages), and
+// the concrete families to try, in order.
+type PlatformDefault = (FamilyName, Option<&'static str>, &'static [&'static str]);
+
+#[cfg(target_family = "windows")]
+static PLATFORM_DEFAULTS: &[PlatformDefault] = &[
+    (FamilyName::Serif, None, &["Times New Roman"]),
+    (FamilyName::SansSerif, None, &["Arial"]),
+    (FamilyName::Monospace, None, &["Courier New"]),
+    (FamilyName::Cursive, None, &["Comic Sans MS"]),
+    (FamilyName::Fantasy, None, &["Impact"]),
+    (FamilyName::SystemUi, None, &["Segoe UI"]),
+    (FamilyName::Emoji, None, &["Segoe UI Emoji"]),
+    (FamilyName::Math, None, &["Cambria Math"]),
+    (FamilyName::Fangsong, None, &["FangSong"]),
+    (FamilyName::Serif, Some("ja"), &["Yu Mincho", "MS Mincho"]),
+    (
+        FamilyName::SansSerif,
+        Some("ja"),
+        &["Yu Gothic", "Meiryo", "MS Gothic"],
+    ),
+    (FamilyName::Monospace, Some("ja"), &["MS Gothic"]),
+    (
+        FamilyName::SystemUi,
+        Some("ja"),
+        &["Yu Gothic UI", "Meiryo UI"],
+    ),
+    (FamilyName::Serif, Some("ko"), &["Batang"]),
+    (FamilyName::SansSerif, Some("ko"), &["Malgun Gothic"]),
+    (FamilyName::SystemUi, Some("ko"), &["Malgun Gothic"]),
+    (FamilyName::Serif, Some("zh"), &["SimSun"]),
+    (FamilyName::SansSerif, Some("zh"), &["Microsoft YaHei"]),
+    (FamilyName::SystemUi, Some("zh"), &["Microsoft YaHei UI"]),
+    (FamilyName::Serif, Some("zh-Hant"), &["PMingLiU", "MingLiU"]),
+    (
+        FamilyName::SansSerif,
+        Some("zh-Hant"),
+        &["Microsoft JhengHei"],
+    ),
+    (
+        FamilyName::SystemUi,
+        Some("zh-Hant"),
+        &["Microsoft JhengHei UI"],
+    ),
+    (FamilyName::Serif, Some("zh-HK"), &["PMingLiU", "MingLiU"]),
+    (
+        FamilyName::SansSerif,
+        Some("zh-HK"),
+        &["Microsoft JhengHei"],
+    ),
+    (
+        FamilyName::SystemUi,
+        Some("zh-HK"),
+        &["Microsoft JhengHei UI"],
+    ),
+    (FamilyName::Serif, Some("zh-TW"), &["PMingLiU", "MingLiU"]),
+    (
+        FamilyName::SansSerif,
+        Some("zh-TW"),
+        &["Microsoft JhengHei"],
+    ),
+    (
+        FamilyName::SystemUi,
+        Some("zh-TW"),
+        &["Microsoft JhengHei UI"],
+    ),
+];
+
+#[cfg(target_os = "macos")]
+static PLATFORM_DEFAULTS: &[PlatformDefault] = &[
+    (FamilyName::Serif, None, &["Times New Roman"]),
+    (FamilyName::SansSerif, None, &["Arial"]),
+    (FamilyName::Monospace, None, &["Courier New"]),
+    (FamilyName::Cursive, None, &["Comic Sans MS"]),
+    (FamilyName::Fantasy, None, &["Papyrus"]),
+    (FamilyName::SystemUi, None, &["Helvetica Neue"]),
+    (FamilyName::Emoji, None, &["Apple Color Emoji"]),
+    (FamilyName::Math, None, &["STIX Two Math"]),
+    (FamilyName::Fangsong, None, &["STFangsong"]),
+    (FamilyName::Serif, Some("ja"), &["Hiragino Mincho ProN"]),
+    (
+        FamilyName::SansSerif,
+        Some("ja"),
+        &["Hiragino Sans", "Hiragino Kaku Gothic ProN"],
+    ),
+    (FamilyName::SystemUi, Some("ja"), &["Hiragino Sans"]),
+    (FamilyName::Serif, Some("ko"), &["AppleMyungjo"]),
+    (FamilyName::SansSerif, Some("ko"), &["Apple SD Gothic Neo"]),
+    (FamilyName::SystemUi, Some("ko"), &["Apple SD Gothic Neo"]),
+    (FamilyName::Serif, Some("zh"), &["Songti SC"]),
+    (FamilyName::SansSerif, Some("zh"), &["PingFang SC"]),
+    (FamilyName::SystemUi, Some("zh"), &["PingFang SC"]),
+    (FamilyName::Serif, Some("zh-Hant"), &["Songti TC"]),
+    (FamilyName::SansSerif, Some("zh-Hant"), &["PingFang TC"]),
+    (FamilyName::SystemUi, Some("zh-Hant"), &["PingFang TC"]),
+    (FamilyName::Serif, Some("zh-HK"), &["Songti TC"]),
+    (FamilyName::SansSerif, Some("zh-HK"), &["PingFang HK"]),
+    (FamilyName::SystemUi, Some("zh-HK"), &["PingFang HK"]),
+    (FamilyName::Serif, Some("zh-TW"), &["Songti TC"]),
+    (FamilyName::SansSerif, Some("zh-TW"), &["PingFang TC"]),
+    (FamilyName::SystemUi, Some("zh-TW"), &["PingFang TC"]),
+];
+
+// The generic keywords come first so that Fontconfig can resolve them according to the system
+// configuration; the common families after them serve sources without that ability, such as
+// `FsSource`.
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+static PLATFORM_DEFAULTS: &[PlatformDefault] = &[
+    (
+        FamilyName::Serif,
+        None,
+        &["serif", "DejaVu Serif", "Liberation Serif", "Noto Serif"],
+    ),
+    (
+        FamilyName::SansSerif,
+        None,
+        &[
+            "sans-serif",
+            "DejaVu Sans",
+            "Liberation Sans",
+            "Roboto",
+            "Noto Sans",
+        ],
+    ),
+    (
+        FamilyName::Monospace,
+        None,
+        &[
+            "monospace",
+            "DejaVu Sans Mono",
+            "Liberation Mono",
+            "Droid Sans Mono",
+        ],
+    ),
+    (FamilyName::Cursive, None, &["cursive"]),
+    (FamilyName::Fantasy, None, &["fantasy"]),
+    (FamilyName::SystemUi, None, &["system-ui"]),
+    (FamilyName::Emoji, None, &["emoji", "Noto Color Emoji"]),
+    (
+        FamilyName::Math,
+        None,
+        &["math", "Noto Sans Math", "STIX Two Math"],
+    ),
+    (FamilyName::Fangsong, None, &["fangsong"]),
+    (FamilyName::Serif, Some("ja"), &["Noto Serif CJK JP"]),
+    (FamilyName::SansSerif, Some("ja"), &["Noto Sans CJK JP"]),
+    (FamilyName::Serif, Some("ko"), &["Noto Serif CJK KR"]),
+    (FamilyName::SansSerif, Some("ko"), &["Noto Sans CJK KR"]),
+    (FamilyName::Serif, Some("zh"), &["Noto Serif CJK SC"]),
+    (FamilyName::SansSerif, Some("zh"), &["Noto Sans CJK SC"]),
+    (FamilyName::Serif, Some("zh-Hant"), &["Noto Serif CJK TC"]),
+    (
+        FamilyName::SansSerif,
+        Some("zh-Hant"),
+        &["Noto Sans CJK TC"],
+    ),
+    (FamilyName::Serif, Some("zh-HK"), &["Noto Serif CJK TC"]),
+    (FamilyName::SansSerif, Some("zh-HK"), &["Noto Sans CJK HK"]),
+    (FamilyName::Serif, Some("zh-TW"), &["Noto Serif CJK TC"]),
+    (FamilyName::SansSerif, Some("zh-TW"), &["Noto Sans CJK TC"]),
+];
+
+/// A mapping from generic family names (`serif`, `sans-serif`, etc.) to ordered lists of
+/// concrete font families, with optional per-language overrides.
+///
+/// Sources try the concrete families in order and use the first one that exists. Overrides for a
+/// language are tried before the families for all languages, from the most specific language tag
+/// to the least: for `zh-Hant-TW`, the families for `zh-Hant-TW`, `zh-Hant` and `zh` come first.
+///
+/// The `ui-serif`, `ui-sans-serif`, `ui-monospace` and `ui-rounded` families fall back to the
+/// families of `serif`, `sans-serif`, `monospace` and `sans-serif` respectively.
+#[derive(Clone, Debug)]
+pub struct GenericFamilyMap {
+    entries: Vec<Entry>,
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    generic_family: FamilyName,
+    // Normalized with `normalize_language_tag()`.
+    locale: Option<String>,
+    families: Vec<String>,
+}
+
+impl GenericFamilyMap {
+    /// Creates a mapping that contains the default families for this platform.
+    #[inline]
+    pub fn new() -> GenericFamilyMap {
+        PLATFORM_GENERIC_FAMILY_MAP.clone()
+    }
+
+    /// Creates a mapping with no families at all.
+    #[inline]
+    pub fn empty() -> GenericFamilyMap {
+        GenericFamilyMap { entries: vec![] }
+    }
+
+    /// Replaces the concrete families that the given generic family maps to for all languages.
+    ///
+    /// Mappings for `FamilyName::Title` are ignored, since specific families always map to
+    /// themselves.
+    pub fn set<I>(&mut self, generic_family: FamilyName, families: I) -> &mut GenericFamilyMap
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.set_entry(generic_family, None, families);
+        self
+    }
+
+    /// Replaces the concrete families that the given generic family maps to for the given BCP 47
+    /// language tag (e.g. `ja` or `zh-Hant`).
+    ///
+    /// These families are tried before the ones for all languages.
+    pub fn set_for_locale<I>(
+        &mut self,
+        locale: &str,
+        generic_family: FamilyName,
+        families: I,
+    ) -> &mut GenericFamilyMap
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        self.set_entry(
+            generic_family,
+            Some(normalize_language_tag(locale)),
+            families,
+        );
+        self
+    }
+
+    /// Returns the concrete families to try for the given family name and optional BCP 47
+    /// language tag, in order of preference.
+    ///
+    /// A specific family (`FamilyName::Title`) yields just its own name.
+    pub fn families(&self, family_name: &FamilyName, locale: Option<&str>) -> Vec<String> {
+        let fallback_family = match *family_name {
+            FamilyName::Title(ref title) => return vec![title.clone()],
+            FamilyName::UiSerif => Some(FamilyName::Serif),
+            FamilyName::UiSansSerif | FamilyName::UiRounded => Some(FamilyName::SansSerif),
+            FamilyName::UiMonospace => Some(FamilyName::Monospace),
+            _ => None,
+        };
+
+        let mut locales: Vec<Option<String>> = match locale {
+            None => vec![],
+            Some(locale) => language_tag_fallbacks(locale)
+                .into_iter()
+                .map(Some)
+                .collect(),
+        };
+        locales.push(None);
+
+        let mut families: Vec<String> = vec![];
+        for generic_family in Some(family_name)
+            .into_iter()
+            .chain(fallback_family.as_ref())
+        {
+            for locale in &locales {
+                if let Some(entry) = self.entry(generic_family, locale.as_deref()) {
+                    for family in &entry.families {
+                        if !families.contains(family) {
+                            families.push(family.clone())
+                        }
+                    }
+                }
+            }
+        }
+        families
+    }
+
+    fn entry(&self, generic_family: &FamilyName, locale: Option<&str>) -> Option<&Entry> {
+        self.entries.iter().find(|entry| {
+            entry.generic_family == *generic_family && entry.locale.as_deref() == locale
+        })
+    }
+
+    fn set_entry<I>(&mut self, generic_family: FamilyName, locale: Option<String>, families: I)
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        if let FamilyName::Title(_) = generic_family {
+            return;
+        }
+
+        let families = families
+            .into_iter()
+            .map(|family| family.as_ref().to_owned())
+            .collect();
+        self.entries
+            .retain(|entry| entry.generic_family != generic_family || entry.locale != locale);
+        self.entries.push(Entry {
+            generic_family,
+            locale,
+            families,
+        })
+    }
+}
+
+impl Default for GenericFamilyMap {
+    #[inline]
+    fn default() -> GenericFamilyMap {
+        GenericFamilyMap::new()
+    }
+}
+
+// Lowercases the tag and accepts POSIX-style underscores (`ja_JP`) as separators.
+pub(crate) fn normalize_language_tag(tag: &str) -> String {
+    tag.trim().replace('_', "-").to_ascii_lowercase()
+}
+
+// Returns the normalized tag followed by successively shorter prefixes of it, per the lookup
+// scheme of RFC 4647 § 3.4: single-letter extension subtags are dropped along with what follows.
+pub(crate) fn language_tag_fallbacks(tag: &str) -> Vec<String> {
+    let mut subtags: Vec<String> = normalize_language_tag(tag)
+        .split('-')
+        .map(|subtag| subtag.to_owned())
+        .collect();
+    let mut fallbacks = vec![];
+    while !subtags.is_empty() && !subtags[0].is_empty() {
+        fallbacks.push(subtags.join("-"));
+        subtags.pop();
+        while subtags.len() > 1 && subtags[subtags.len() - 1].len() == 1 {
+            subtags.pop();
+        }
+    }
+    fallbacks
+}
+
+#[cfg(test)]
+mod test {
+    use super::{language_tag_fallbacks, GenericFamilyMap};
+    use family_name::FamilyName;
+
+    #[test]
+    fn language_tag_lookup_order() {
+        assert_eq!(
+            language_tag_fallbacks("zh-Hant-TW"),
+            vec!["zh-hant-tw", "zh-hant", "zh"]
+        );
+        assert_eq!(language_tag_fallbacks("ja_JP"), vec!["ja-jp", "ja"]);
+        assert_eq!(
+            language_tag_fallbacks("de-DE-x-phonebk"),
+            vec!["de-de-x-phonebk", "de-de", "de"]
+        );
+        assert!(language_tag_fallbacks("").is_empty());
+    }
+
+    #[test]
+    fn locale_overrides_come_first() {
+        let mut generic_family_map = GenericFamilyMap::empty();
+        generic_family_map
+            .set(FamilyName::SansSerif, &["Arial", "Noto Sans"])
+            .set_for_locale("ja", FamilyName::SansSerif, &["Noto Sans CJK JP"])
+            .set_for_locale("zh-Hant", FamilyName::SansSerif, &["Noto Sans CJK TC"])
+            .set_for_locale("zh-hant", FamilyName::SansSerif, &["Noto Sans TC"]);
+
+        assert_eq!(
+            generic_family_map.families(&FamilyName::SansSerif, Some("ja-JP")),
+            vec!["Noto Sans CJK JP", "Arial", "Noto Sans"]
+        );
+        assert_eq!(
+            generic_family_map.families(&FamilyName::SansSerif, Some("zh-Hant-HK")),
+            vec!["Noto Sans TC", "Arial", "Noto Sans"]
+        );
+        assert_eq!(
+            generic_family_map.families(&FamilyName::SansSerif, Some("en")),
+            vec!["Arial", "Noto Sans"]
+        );
+        assert_eq!(
+            generic_family_map.families(&FamilyName::UiRounded, Some("ja")),
+            vec!["Noto Sans CJK JP", "Arial", "Noto Sans"]
+        );
+        assert!(generic_family_map
+            .families(&FamilyName::Serif, None)
+            .is_empty());
+        assert_eq!(
+            generic_family_map.families(&FamilyName::Title("Arial".to_owned()), Some("ja")),
+            vec!["Arial"]
+        );
+    }
+}
@@ -25,7 +25,10 @@ use font::Font;
 /// This is either the path to the font or the raw in-memory font data.
 ///
 /// To open the font referenced by a handle, use a loader.
+///
+/// With the `serde` feature, the data of in-memory fonts is serialized as a byte string.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Handle {
     /// A font on disk referenced by a path.
     Path {
@@ -39,6 +42,7 @@ pub enum Handle {
     /// A font in memory.
     Memory {
         /// The raw TrueType/OpenType/etc. data that makes up this font.
+        #[cfg_attr(feature = "serde", serde(with = "memory_bytes"))]
         bytes: Arc<Vec<u8>>,
         /// The index of the font, if the memory consists of a collection.
         ///
@@ -72,3 +76,68 @@ impl Handle {
         Font::from_handle(self)
     }
 }
+
+#[cfg(feature = "serde")]
+pub(crate) mod memory_bytes {
+    use serde::de::{Deserializer, Error, SeqAccess, Visitor};
+    use serde::ser::Serializer;
+    use std::cmp;
+    use std::fmt::{self, Formatter};
+    use std::sync::Arc;
+
+    // Don't trust the length that the input claims when preallocating.
+    const MAX_PREALLOCATED_LENGTH: usize = 1024 * 1024;
+
+    pub fn serialize<S>(bytes: &Arc<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_bytes(bytes)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Arc<Vec<u8>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer
+            .deserialize_byte_buf(BytesVisitor)
+            .map(Arc::new)
+    }
+
+    struct BytesVisitor;
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, formatter: &mut Formatter) -> fmt::Result {
+            formatter.write_str("font data")
+        }
+
+        fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Vec<u8>, E>
+        where
+            E: Error,
+        {
+            Ok(bytes.to_vec())
+        }
+
+        fn visit_byte_buf<E>(self, bytes: Vec<u8>) -> Result<Vec<u8>, E>
+        where
+            E: Error,
+        {
+            Ok(bytes)
+        }
+
+        // Formats without a byte string type, such as JSON, write bytes as a sequence.
+        fn visit_seq<A>(self, mut seq: A) -> Result<Vec<u8>, A::Error>
+        where
+            A: SeqAccess<'de>,
+        {
+            let length = cmp::min(seq.size_hint().unwrap_or(0), MAX_PREALLOCATED_LENGTH);
+            let mut bytes = Vec::with_capacity(length);
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte)
+            }
+            Ok(bytes)
+        }
+    }
+}
@@ -0,0 +1,344 @@
+// font-kit/src/harfbuzz.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Text shaping with HarfBuzz.
+//!
+//! This module is only available with the `harfbuzz` feature. Unlike the simple shaper in the
+//! `layout` module, HarfBuzz handles complex scripts such as Arabic, Devanagari, and Thai.
+
+use euclid::Vector2D;
+use harfbuzz_sys::{hb_blob_create, hb_blob_destroy, hb_blob_get_empty, hb_blob_t};
+use harfbuzz_sys::{hb_buffer_add_utf8, hb_buffer_create, hb_buffer_destroy};
+use harfbuzz_sys::{hb_buffer_get_glyph_infos, hb_buffer_get_glyph_positions};
+use harfbuzz_sys::{hb_buffer_guess_segment_properties, hb_buffer_set_cluster_level};
+use harfbuzz_sys::{hb_buffer_set_direction, hb_buffer_set_language, hb_buffer_set_script};
+use harfbuzz_sys::{hb_direction_t, hb_face_create, hb_face_create_for_tables, hb_face_destroy};
+use harfbuzz_sys::{hb_face_t, hb_feature_t, hb_font_create, hb_font_destroy, hb_font_set_scale};
+use harfbuzz_sys::{hb_font_set_variations, hb_font_t, hb_language_from_string, hb_shape};
+use harfbuzz_sys::{hb_script_from_iso15924_tag, hb_tag_t, hb_variation_t};
+use harfbuzz_sys::{HB_BUFFER_CLUSTER_LEVEL_MONOTONE_CHARACTERS, HB_MEMORY_MODE_READONLY};
+use harfbuzz_sys::{HB_DIRECTION_BTT, HB_DIRECTION_LTR, HB_DIRECTION_RTL, HB_DIRECTION_TTB};
+use std::fmt::{self, Debug, Formatter};
+use std::os::raw::{c_char, c_int, c_uint, c_void};
+use std::slice;
+use std::sync::Arc;
+
+use features::Tag;
+use loader::Loader;
+
+/// A font prepared for shaping with HarfBuzz.
+pub struct HarfBuzzFont {
+    hb_font: *mut hb_font_t,
+    point_size: f32,
+}
+
+impl HarfBuzzFont {
+    /// Creates a HarfBuzz font from any loaded font, for shaping at the given size in pixels per
+    /// em.
+    ///
+    /// If the loader can provide the raw font data, HarfBuzz shares it without copying.
+    /// Otherwise, HarfBuzz reads tables from the font on demand with `Loader::load_font_table()`.
+    /// The font starts at the loader's `variation_coordinates()`; call `set_variations()` to
+    /// change them.
+    pub fn new<L>(font: &L, point_size: f32) -> HarfBuzzFont
+    where
+        L: Loader + 'static,
+    {
+        unsafe {
+            let hb_face = match font.copy_font_data() {
+                Some(font_data) => create_face_for_data(font_data, font.font_index()),
+                None => hb_face_create_for_tables(
+                    Some(reference_table::<L>),
+                    Box::into_raw(Box::new(font.clone())) as *mut c_void,
+                    Some(destroy_boxed::<L>),
+                ),
+            };
+            let hb_font = hb_font_create(hb_face);
+            hb_face_destroy(hb_face);
+
+            // Work in 26.6 fixed point so that positions keep some subpixel precision.
+            let scale = (point_size * 64.0).round() as c_int;
+            hb_font_set_scale(hb_font, scale, scale);
+
+            // Start from the loader's coordinates, so that shaping agrees with its outlines.
+            let mut hb_font = HarfBuzzFont {
+                hb_font,
+                point_size,
+            };
+            hb_font.set_variations(&font.variation_coordinates());
+            hb_font
+        }
+    }
+
+    /// Sets the coordinates of a variable font along its design axes; e.g. `wght` or `wdth`.
+    ///
+    /// Axes that aren't listed use their default values.
+    pub fn set_variations(&mut self, variations: &[(Tag, f32)]) {
+        let hb_variations: Vec<hb_variation_t> = variations
+            .iter()
+            .map(|&(tag, value)| hb_variation_t {
+                tag: tag.0 as hb_tag_t,
+                value,
+            })
+            .collect();
+        unsafe {
+            hb_font_set_variations(
+                self.hb_font,
+                hb_variations.as_ptr(),
+                hb_variations.len() as c_uint,
+            );
+        }
+    }
+
+    /// Returns the size this font shapes at, in pixels per em.
+    #[inline]
+    pub fn point_size(&self) -> f32 {
+        self.point_size
+    }
+
+    /// Shapes a run of text and returns the glyphs in visual order.
+    ///
+    /// Script, language, and direction are guessed from the text unless `options` sets them.
+    pub fn shape(&self, text: &str, options: &ShapingOptions) -> Vec<ShapedGlyph> {
+        unsafe {
+            let hb_buffer = hb_buffer_create();
+            hb_buffer_set_cluster_level(hb_buffer, HB_BUFFER_CLUSTER_LEVEL_MONOTONE_CHARACTERS);
+            hb_buffer_add_utf8(
+                hb_buffer,
+                text.as_ptr() as *const c_char,
+                text.len() as c_int,
+                0,
+                text.len() as c_int,
+            );
+            if let Some(direction) = options.direction {
+                hb_buffer_set_direction(hb_buffer, direction.to_hb_direction());
+            }
+            if let Some(script) = options.script {
+                hb_buffer_set_script(hb_buffer, hb_script_from_iso15924_tag(script.0));
+            }
+            if let Some(ref language) = options.language {
+                hb_buffer_set_language(
+                    hb_buffer,
+                    hb_language_from_string(
+                        language.as_ptr() as *const c_char,
+                        language.len() as c_int,
+                    ),
+                );
+            }
+            hb_buffer_guess_segment_properties(hb_buffer);
+
+            let hb_features: Vec<hb_feature_t> = options
+                .features
+                .iter()
+                .map(|&(tag, value)| hb_feature_t {
+                    tag: tag.0 as hb_tag_t,
+                    value,
+                    start: 0,
+                    end: c_uint::MAX,
+                })
+                .collect();
+            hb_shape(
+                self.hb_font,
+                hb_buffer,
+                hb_features.as_ptr(),
+                hb_features.len() as c_uint,
+            );
+
+            let mut glyph_count = 0;
+            let glyph_infos = hb_buffer_get_glyph_infos(hb_buffer, &mut glyph_count);
+            let glyph_positions = hb_buffer_get_glyph_positions(hb_buffer, &mut glyph_count);
+            let shaped_glyphs = if glyph_count == 0 {
+                vec![]
+            } else {
+                let glyph_infos = slice::from_raw_parts(glyph_infos, glyph_count as usize);
+                let glyph_positions = slice::from_raw_parts(glyph_positions, glyph_count as usize);
+                glyph_infos
+                    .iter()
+                    .zip(glyph_positions.iter())
+                    .map(|(glyph_info, glyph_position)| ShapedGlyph {
+                        glyph_id: glyph_info.codepoint,
+                        cluster: glyph_info.cluster as usize,
+                        advance: Vector2D::new(
+                            fixed_26_6_to_f32(glyph_position.x_advance),
+                            fixed_26_6_to_f32(glyph_position.y_advance),
+                        ),
+                        offset: Vector2D::new(
+                            fixed_26_6_to_f32(glyph_position.x_offset),
+                            fixed_26_6_to_f32(glyph_position.y_offset),
+                        ),
+                    })
+                    .collect()
+            };
+
+            hb_buffer_destroy(hb_buffer);
+            shaped_glyphs
+        }
+    }
+}
+
+impl Drop for HarfBuzzFont {
+    fn drop(&mut self) {
+        unsafe {
+            hb_font_destroy(self.hb_font);
+        }
+    }
+}
+
+impl Debug for HarfBuzzFont {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("HarfBuzzFont")
+            .field("point_size", &self.point_size)
+            .finish()
+    }
+}
+
+/// Options that control shaping.
+///
+/// This object supports a method chaining style for idiomatic initialization; e.g.
+///
+///     # use font_kit::features::Tag;
+///     # use font_kit::harfbuzz::{Direction, ShapingOptions};
+///     let mut options = ShapingOptions::new();
+///     options.direction(Direction::RightToLeft).feature(Tag::from_bytes(b"liga"), 0);
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShapingOptions {
+    /// The text direction. If `None`, HarfBuzz guesses it from the script.
+    pub direction: Option<Direction>,
+    /// The ISO 15924 script tag; e.g. `Arab`. If `None`, HarfBuzz guesses it from the text.
+    pub script: Option<Tag>,
+    /// The BCP 47 language tag; e.g. `"ur"`. This affects language-specific forms.
+    pub language: Option<String>,
+    /// OpenType features to turn on or off for the whole run, with their values.
+    ///
+    /// A value of 0 disables a feature, and 1 enables it. Alternate-selecting features such as
+    /// `salt` take the index of the alternate.
+    pub features: Vec<(Tag, u32)>,
+}
+
+impl ShapingOptions {
+    /// Creates shaping options that let HarfBuzz guess everything and use default features.
+    #[inline]
+    pub fn new() -> ShapingOptions {
+        ShapingOptions::default()
+    }
+
+    /// Sets the text direction and returns these options for method chaining.
+    #[inline]
+    pub fn direction(&mut self, direction: Direction) -> &mut ShapingOptions {
+        self.direction = Some(direction);
+        self
+    }
+
+    /// Sets the script and returns these options for method chaining.
+    #[inline]
+    pub fn script(&mut self, script: Tag) -> &mut ShapingOptions {
+        self.script = Some(script);
+        self
+    }
+
+    /// Sets the language and returns these options for method chaining.
+    #[inline]
+    pub fn language(&mut self, language: &str) -> &mut ShapingOptions {
+        self.language = Some(language.to_owned());
+        self
+    }
+
+    /// Adds a feature setting and returns these options for method chaining.
+    #[inline]
+    pub fn feature(&mut self, feature: Tag, value: u32) -> &mut ShapingOptions {
+        self.features.push((feature, value));
+        self
+    }
+}
+
+/// The direction in which text is set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Direction {
+    /// Horizontal text, set from left to right.
+    LeftToRight,
+    /// Horizontal text, set from right to left.
+    RightToLeft,
+    /// Vertical text, set from top to bottom.
+    TopToBottom,
+    /// Vertical text, set from bottom to top.
+    BottomToTop,
+}
+
+impl Direction {
+    fn to_hb_direction(self) -> hb_direction_t {
+        match self {
+            Direction::LeftToRight => HB_DIRECTION_LTR,
+            Direction::RightToLeft => HB_DIRECTION_RTL,
+            Direction::TopToBottom => HB_DIRECTION_TTB,
+            Direction::BottomToTop => HB_DIRECTION_BTT,
+        }
+    }
+}
+
+/// A glyph produced by HarfBuzz.
+///
+/// All distances are in pixels, with Y pointing up as in font units.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShapedGlyph {
+    /// The ID of the glyph.
+    pub glyph_id: u32,
+    /// The byte offset in the text of the first character that this glyph represents.
+    pub cluster: usize,
+    /// How far to move the pen after drawing this glyph.
+    pub advance: Vector2D<f32>,
+    /// How far to displace this glyph from the pen position, without moving the pen.
+    pub offset: Vector2D<f32>,
+}
+
+#[inline]
+fn fixed_26_6_to_f32(fixed: i32) -> f32 {
+    fixed as f32 / 64.0
+}
+
+unsafe fn create_face_for_data(font_data: Arc<Vec<u8>>, font_index: u32) -> *mut hb_face_t {
+    // The blob points into the vector that the `Arc` owns, which doesn't move when the `Arc` is
+    // boxed. The blob keeps the `Arc` alive until HarfBuzz is done with it.
+    let hb_blob = hb_blob_create(
+        font_data.as_ptr() as *const c_char,
+        font_data.len() as c_uint,
+        HB_MEMORY_MODE_READONLY,
+        Box::into_raw(Box::new(font_data)) as *mut c_void,
+        Some(destroy_boxed::<Arc<Vec<u8>>>),
+    );
+    let hb_face = hb_face_create(hb_blob, font_index);
+    hb_blob_destroy(hb_blob);
+    hb_face
+}
+
+unsafe extern "C" fn reference_table<L>(
+    _: *mut hb_face_t,
+    tag: hb_tag_t,
+    user_data: *mut c_void,
+) -> *mut hb_blob_t
+where
+    L: Loader,
+{
+    let font = &*(user_data as *const L);
+    match font.load_font_table(tag) {
+        None => hb_blob_get_empty(),
+        Some(table) => hb_blob_create(
+            table.as_ptr() as *const c_char,
+            table.len() as c_uint,
+            HB_MEMORY_MODE_READONLY,
+            Box::into_raw(Box::new(table)) as *mut c_void,
+            Some(destroy_boxed::<Box<[u8]>>),
+        ),
+    }
+}
+
+unsafe extern "C" fn destroy_boxed<T>(user_data: *mut c_void) {
+    drop(Box::from_raw(user_data as *mut T));
+}
@@ -0,0 +1,307 @@
+// font-kit/src/language.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! The languages that a font declares support for.
+//!
+//! These come from the `dlng` and `slng` entries of the `meta` table and from the code page and
+//! Unicode range bits of the `OS/2` table. Like the layout features, they describe what the font
+//! claims; font-kit doesn't check the claims against the character map.
+
+use generic_family_map;
+use sfnt::{read_u16, read_u32};
+
+// `dlng` and `slng` in the `meta` table.
+const META_DESIGN_LANGUAGES: u32 = 0x646c_6e67;
+const META_SUPPORTED_LANGUAGES: u32 = 0x736c_6e67;
+
+struct Language {
+    // The language subtag.
+    language: &'static str,
+    // The ISO 15924 script, in lowercase. Chinese has an entry per script.
+    script: &'static str,
+    // Bits of `ulCodePageRange1` and `ulCodePageRange2`, numbered from 0 to 63.
+    code_page_bits: &'static [u32],
+    // Bits of `ulUnicodeRange1` through `ulUnicodeRange4`, numbered from 0 to 127, all of which
+    // must be set.
+    unicode_range_bits: &'static [u32],
+}
+
+// Latin-script languages need Basic Latin and the Latin-1 Supplement.
+static LANGUAGES: [Language; 37] = [
+    language("en", "latn", &[0], &[0, 1]),
+    language("fr", "latn", &[0], &[0, 1]),
+    language("de", "latn", &[0], &[0, 1]),
+    language("es", "latn", &[0], &[0, 1]),
+    language("it", "latn", &[0], &[0, 1]),
+    language("pt", "latn", &[0], &[0, 1]),
+    language("nl", "latn", &[0], &[0, 1]),
+    language("sv", "latn", &[0], &[0, 1]),
+    language("da", "latn", &[0], &[0, 1]),
+    language("nb", "latn", &[0], &[0, 1]),
+    language("fi", "latn", &[0], &[0, 1]),
+    language("pl", "latn", &[1], &[0, 1]),
+    language("cs", "latn", &[1], &[0, 1]),
+    language("sk", "latn", &[1], &[0, 1]),
+    language("hu", "latn", &[1], &[0, 1]),
+    language("ro", "latn", &[1], &[0, 1]),
+    language("hr", "latn", &[1], &[0, 1]),
+    language("tr", "latn", &[4], &[0, 1]),
+    language("lt", "latn", &[7], &[0, 1]),
+    language("lv", "latn", &[7], &[0, 1]),
+    language("vi", "latn", &[8], &[0, 1]),
+    language("ru", "cyrl", &[2], &[9]),
+    language("uk", "cyrl", &[2], &[9]),
+    language("bg", "cyrl", &[2], &[9]),
+    language("sr", "cyrl", &[2], &[9]),
+    language("el", "grek", &[3], &[7]),
+    language("he", "hebr", &[5], &[11]),
+    language("ar", "arab", &[6], &[13]),
+    language("fa", "arab", &[6], &[13]),
+    language("hi", "deva", &[], &[15]),
+    language("bn", "beng", &[], &[16]),
+    language("ta", "taml", &[], &[20]),
+    language("th", "thai", &[16], &[24]),
+    // Hiragana and Katakana.
+    language("ja", "jpan", &[17], &[49, 50]),
+    // Hangul Syllables; the code pages are Wansung and Johab.
+    language("ko", "kore", &[19, 21], &[56]),
+    // CJK Unified Ideographs, which can't tell the two scripts apart.
+    language("zh", "hans", &[18], &[59]),
+    language("zh", "hant", &[20], &[59]),
+];
+
+const fn language(
+    language: &'static str,
+    script: &'static str,
+    code_page_bits: &'static [u32],
+    unicode_range_bits: &'static [u32],
+) -> Language {
+    Language {
+        language,
+        script,
+        code_page_bits,
+        unicode_range_bits,
+    }
+}
+
+/// The languages that a font declares support for.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LanguageSupport {
+    /// The ScriptLangTags (e.g. `ja`, `zh-Hant` or `Latn`) listed in the `dlng` (design
+    /// languages) and `slng` (supported languages) entries of the `meta` table.
+    pub meta_languages: Vec<String>,
+    /// The `ulCodePageRange1` and `ulCodePageRange2` fields of the `OS/2` table, or zero if the
+    /// table predates version 1.
+    pub code_page_ranges: [u32; 2],
+    /// The `ulUnicodeRange1` through `ulUnicodeRange4` fields of the `OS/2` table.
+    pub unicode_ranges: [u32; 4],
+}
+
+impl LanguageSupport {
+    /// Reads the declared languages from the raw `OS/2` and `meta` tables.
+    ///
+    /// Missing or malformed tables declare nothing.
+    pub fn from_tables(os2: Option<&[u8]>, meta: Option<&[u8]>) -> LanguageSupport {
+        let mut language_support = LanguageSupport::default();
+        if let Some(meta) = meta {
+            language_support.meta_languages = parse_meta_languages(meta).unwrap_or_default();
+        }
+        if let Some(os2) = os2 {
+            for (index, unicode_range) in language_support.unicode_ranges.iter_mut().enumerate() {
+                *unicode_range = read_u32(os2, 42 + index * 4).unwrap_or(0);
+            }
+            if read_u16(os2, 0).unwrap_or(0) >= 1 {
+                for (index, code_page_range) in
+                    language_support.code_page_ranges.iter_mut().enumerate()
+                {
+                    *code_page_range = read_u32(os2, 78 + index * 4).unwrap_or(0);
+                }
+            }
+        }
+        language_support
+    }
+
+    /// Returns true if the font declares support for the language identified by the given BCP
+    /// 47 tag (e.g. `ja-JP` or `zh-Hant`).
+    ///
+    /// The `meta` table is authoritative when it lists any languages. Otherwise, the code page
+    /// bits are consulted, and, failing those, the Unicode range bits. Chinese tags without a
+    /// script subtag take their script from the region (`zh-TW` is Traditional Chinese) and
+    /// otherwise match either script.
+    pub fn supports_language(&self, tag: &str) -> bool {
+        let (language, script) = match parse_tag(tag) {
+            Some(language_and_script) => language_and_script,
+            None => return false,
+        };
+
+        if !self.meta_languages.is_empty() {
+            return self.meta_languages.iter().any(|meta_language| {
+                meta_language_matches(meta_language, &language, script.as_deref())
+            });
+        }
+
+        let candidates = LANGUAGES.iter().filter(|candidate| {
+            candidate.language == language
+                && (script.is_none() || script.as_deref() == Some(candidate.script))
+        });
+        let code_page_ranges_declared = self.code_page_ranges != [0, 0];
+        for candidate in candidates {
+            let supported = if code_page_ranges_declared && !candidate.code_page_bits.is_empty() {
+                candidate
+                    .code_page_bits
+                    .iter()
+                    .any(|&bit| self.code_page_ranges[bit as usize / 32] & (1 << (bit % 32)) != 0)
+            } else {
+                candidate
+                    .unicode_range_bits
+                    .iter()
+                    .all(|&bit| self.unicode_ranges[bit as usize / 32] & (1 << (bit % 32)) != 0)
+            };
+            if supported {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+fn parse_meta_languages(meta: &[u8]) -> Option<Vec<String>> {
+    let mut languages = vec![];
+    let data_map_count = read_u32(meta, 12)?;
+    for data_map_index in 0..data_map_count as usize {
+        let data_map_offset = 16 + data_map_index * 12;
+        let tag = read_u32(meta, data_map_offset)?;
+        if tag != META_DESIGN_LANGUAGES && tag != META_SUPPORTED_LANGUAGES {
+            continue;
+        }
+        let data_offset = read_u32(meta, data_map_offset + 4)? as usize;
+        let data_length = read_u32(meta, data_map_offset + 8)? as usize;
+        let data = meta.get(data_offset..data_offset.checked_add(data_length)?)?;
+        for language in String::from_utf8_lossy(data).split(',') {
+            let language = language.trim();
+            if !language.is_empty() {
+                languages.push(language.to_owned());
+            }
+        }
+    }
+    Some(languages)
+}
+
+// Splits a tag into its lowercase language subtag and, for Chinese, its script.
+fn parse_tag(tag: &str) -> Option<(String, Option<String>)> {
+    let tag = generic_family_map::normalize_language_tag(tag);
+    let mut subtags = tag.split('-');
+    let language = subtags.next().filter(|language| !language.is_empty())?;
+    let mut script = None;
+    for subtag in subtags {
+        match subtag.len() {
+            4 if subtag.chars().all(|ch| ch.is_ascii_alphabetic()) => {
+                script = Some(subtag.to_owned())
+            }
+            2 if language == "zh" && script.is_none() => {
+                script = match subtag {
+                    "tw" | "hk" | "mo" => Some("hant".to_owned()),
+                    "cn" | "sg" => Some("hans".to_owned()),
+                    _ => None,
+                }
+            }
+            _ => {}
+        }
+    }
+    if language != "zh" {
+        script = None;
+    }
+    Some((language.to_owned(), script))
+}
+
+// A ScriptLangTag is either a language tag or, for fonts that cover a whole script, just an ISO
+// 15924 script code such as `Latn` or `Jpan`.
+fn meta_language_matches(meta_language: &str, language: &str, script: Option<&str>) -> bool {
+    let meta_tag = generic_family_map::normalize_language_tag(meta_language);
+    if meta_tag.len() == 4 && !meta_tag.contains('-') {
+        return match (&*meta_tag, language) {
+            ("hani", "zh") | ("hani", "ja") | ("hani", "ko") => true,
+            ("hans", "zh") | ("hant", "zh") => script.is_none() || script == Some(&*meta_tag),
+            _ => LANGUAGES
+                .iter()
+                .any(|candidate| candidate.language == language && candidate.script == meta_tag),
+        };
+    }
+
+    match parse_tag(&meta_tag) {
+        Some((meta_language, meta_script)) => {
+            meta_language == language
+                && match (meta_script, script) {
+                    (Some(meta_script), Some(script)) => meta_script == script,
+                    _ => true,
+                }
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::LanguageSupport;
+    use test::meta_table;
+
+    fn os2_table(code_page_ranges: [u32; 2], unicode_ranges: [u32; 4]) -> Vec<u8> {
+        let mut os2 = vec![0; 86];
+        os2[1] = 1;
+        for (index, &range) in unicode_ranges.iter().enumerate() {
+            os2[42 + index * 4..46 + index * 4].copy_from_slice(&range.to_be_bytes());
+        }
+        for (index, &range) in code_page_ranges.iter().enumerate() {
+            os2[78 + index * 4..82 + index * 4].copy_from_slice(&range.to_be_bytes());
+        }
+        os2
+    }
+
+    #[test]
+    fn languages_from_code_pages() {
+        // Latin 1 and JIS.
+        let os2 = os2_table([(1 << 0) | (1 << 17), 0], [0b11, 0, 0, 0]);
+        let language_support = LanguageSupport::from_tables(Some(&os2), None);
+        assert!(language_support.supports_language("en-US"));
+        assert!(language_support.supports_language("ja"));
+        assert!(!language_support.supports_language("ko"));
+        assert!(!language_support.supports_language("zh"));
+        assert!(!language_support.supports_language("ru"));
+        assert!(!language_support.supports_language(""));
+    }
+
+    #[test]
+    fn languages_from_unicode_ranges() {
+        // CJK Unified Ideographs only.
+        let os2 = os2_table([0, 0], [0, 1 << (59 - 32), 0, 0]);
+        let language_support = LanguageSupport::from_tables(Some(&os2), None);
+        assert!(language_support.supports_language("zh-TW"));
+        assert!(language_support.supports_language("zh-Hans"));
+        assert!(!language_support.supports_language("ja"));
+    }
+
+    #[test]
+    fn languages_from_meta() {
+        let os2 = os2_table([(1 << 18) | (1 << 20), 0], [0, 0, 0, 0]);
+        let meta = meta_table("ja, zh-Hant,Latn");
+        let language_support = LanguageSupport::from_tables(Some(&os2), Some(&meta));
+        assert_eq!(
+            language_support.meta_languages,
+            vec!["ja", "zh-Hant", "Latn"]
+        );
+        assert!(language_support.supports_language("ja-JP"));
+        assert!(language_support.supports_language("zh-HK"));
+        assert!(language_support.supports_language("zh"));
+        assert!(language_support.supports_language("fr"));
+        // The `meta` table overrides the code pages.
+        assert!(!language_support.supports_language("zh-CN"));
+        assert!(!language_support.supports_language("ko"));
+    }
+}
@@ -0,0 +1,834 @@
+// font-kit/src/layout.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Basic text layout: turns a string into positioned glyphs.
+//!
+//! This is a deliberately simple shaper, good enough for labels and UI text. It maps characters
+//! to glyphs through the `cmap`, applies standard ligatures (`liga` in `GSUB`) and pair kerning
+//! (`kern` in `GPOS`, or the legacy `kern` table), picks fallback fonts via
+//! `Loader::get_fallbacks()`, and breaks lines greedily at whitespace.
+//!
+//! Text is laid out left to right. Only plain ligature and pair adjustment lookups are applied;
+//! contextual lookups, complex scripts, bidirectional text, and mark positioning are not
+//! supported. Use a full shaper such as HarfBuzz for those.
+
+use euclid::{Point2D, Rect, Size2D};
+use std::iter;
+use std::ops::Range;
+
+use features::Tag;
+use loader::Loader;
+use sfnt::{self, read_i16, read_u16, read_u32};
+
+const GSUB_LIGATURE_LOOKUP_TYPE: u16 = 4;
+const GSUB_EXTENSION_LOOKUP_TYPE: u16 = 7;
+const GPOS_PAIR_ADJUSTMENT_LOOKUP_TYPE: u16 = 2;
+const GPOS_EXTENSION_LOOKUP_TYPE: u16 = 9;
+
+const VALUE_FORMAT_X_PLACEMENT: u16 = 0x0001;
+const VALUE_FORMAT_Y_PLACEMENT: u16 = 0x0002;
+const VALUE_FORMAT_X_ADVANCE: u16 = 0x0004;
+
+const KERN_COVERAGE_HORIZONTAL: u16 = 0x0001;
+const KERN_COVERAGE_CROSS_STREAM: u16 = 0x0004;
+
+/// Options that control text layout.
+///
+/// This object supports a method chaining style for idiomatic initialization; e.g.
+///
+///     # use font_kit::layout::LayoutOptions;
+///     println!("{:?}", LayoutOptions::new(16.0).max_width(200.0).kerning(false));
+#[derive(Clone, Debug, PartialEq)]
+pub struct LayoutOptions {
+    /// The font size, in pixels per em.
+    pub point_size: f32,
+    /// The width at which lines wrap, in pixels. If `None`, lines only break at newlines.
+    pub max_width: Option<f32>,
+    /// The language tag passed to `Loader::get_fallbacks()`; e.g. `"en-US"`.
+    pub locale: String,
+    /// Whether standard ligatures are applied.
+    pub ligatures: bool,
+    /// Whether kerning is applied.
+    pub kerning: bool,
+}
+
+impl LayoutOptions {
+    /// Creates layout options for the given size, with ligatures and kerning on and no wrapping.
+    #[inline]
+    pub fn new(point_size: f32) -> LayoutOptions {
+        LayoutOptions {
+            point_size,
+            max_width: None,
+            locale: "en-US".to_owned(),
+            ligatures: true,
+            kerning: true,
+        }
+    }
+
+    /// Sets the width at which lines wrap and returns these options for method chaining.
+    #[inline]
+    pub fn max_width(&mut self, max_width: f32) -> &mut LayoutOptions {
+        self.max_width = Some(max_width);
+        self
+    }
+
+    /// Sets the locale used for fallback and returns these options for method chaining.
+    #[inline]
+    pub fn locale(&mut self, locale: &str) -> &mut LayoutOptions {
+        self.locale = locale.to_owned();
+        self
+    }
+
+    /// Enables or disables ligatures and returns these options for method chaining.
+    #[inline]
+    pub fn ligatures(&mut self, ligatures: bool) -> &mut LayoutOptions {
+        self.ligatures = ligatures;
+        self
+    }
+
+    /// Enables or disables kerning and returns these options for method chaining.
+    #[inline]
+    pub fn kerning(&mut self, kerning: bool) -> &mut LayoutOptions {
+        self.kerning = kerning;
+        self
+    }
+}
+
+/// The result of laying out a string.
+#[derive(Debug)]
+pub struct Layout<F> {
+    /// The fonts that the glyphs refer to.
+    ///
+    /// The first font is always the one passed to `layout()`; any others are fallbacks.
+    pub fonts: Vec<F>,
+    /// The positioned glyphs, in logical order.
+    pub glyphs: Vec<PositionedGlyph>,
+    /// The lines of text, from top to bottom.
+    pub lines: Vec<Line>,
+    /// The logical bounds of the text, in pixels.
+    ///
+    /// This spans from the top of the first line to the bottom of the last and is as wide as the
+    /// widest line, not counting trailing whitespace.
+    pub bounds: Rect<f32>,
+}
+
+/// A glyph positioned by `layout()`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PositionedGlyph {
+    /// The index into `Layout::fonts` of the font this glyph comes from.
+    pub font_index: usize,
+    /// The ID of the glyph within its font.
+    pub glyph_id: u32,
+    /// The position of the glyph origin, on the baseline, in pixels.
+    ///
+    /// The layout's origin is its top left corner, and Y points down.
+    pub position: Point2D<f32>,
+    /// The horizontal advance of the glyph, including kerning, in pixels.
+    pub advance: f32,
+    /// The size to render this glyph at, in pixels per em.
+    ///
+    /// This only differs from the requested size for fallback fonts that need scaling.
+    pub point_size: f32,
+    /// The byte offset in the text of the first character that this glyph represents.
+    pub cluster: usize,
+}
+
+/// A line of laid-out text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    /// The range of `Layout::glyphs` on this line.
+    pub glyph_range: Range<usize>,
+    /// The byte range of the text on this line, excluding any newline that ends it.
+    pub text_range: Range<usize>,
+    /// The Y position of the baseline, in pixels.
+    pub baseline: f32,
+    /// The width of the line, not counting trailing whitespace, in pixels.
+    pub width: f32,
+}
+
+/// Lays out a string with the given font.
+///
+/// Lines break at `\n` and, if `options.max_width` is set, at whitespace before the width is
+/// exceeded. Words wider than a whole line overflow it. Line spacing comes from the metrics of
+/// `font`.
+pub fn layout<L>(font: &L, text: &str, options: &LayoutOptions) -> Layout<L>
+where
+    L: Loader,
+{
+    let metrics = font.metrics();
+    let scale = options.point_size / metrics.units_per_em as f32;
+    let ascent = metrics.ascent * scale;
+    let line_height = (metrics.ascent - metrics.descent + metrics.line_gap) * scale;
+
+    let mut layout_fonts = vec![LayoutFont::new(font.clone(), 1.0, options)];
+    let mut glyphs = vec![];
+    let mut lines = vec![];
+
+    let mut paragraph_start = 0;
+    for paragraph in text.split('\n') {
+        let paragraph_len = paragraph.trim_end_matches('\r').len();
+        let paragraph_range = paragraph_start..(paragraph_start + paragraph_len);
+        paragraph_start += paragraph.len() + 1;
+
+        let mut shaped_glyphs = vec![];
+        for run in itemize(&mut layout_fonts, text, paragraph_range.clone(), options) {
+            layout_fonts[run.font_index].shape(
+                run.font_index,
+                text,
+                run.text_range,
+                options,
+                &mut shaped_glyphs,
+            );
+        }
+
+        for line_glyphs in break_lines(text, &shaped_glyphs, options.max_width) {
+            let baseline = ascent + line_height * lines.len() as f32;
+            let glyph_start = glyphs.len();
+            let mut x = 0.0;
+            for shaped_glyph in &shaped_glyphs[line_glyphs.clone()] {
+                glyphs.push(PositionedGlyph {
+                    font_index: shaped_glyph.font_index,
+                    glyph_id: shaped_glyph.glyph_id,
+                    position: Point2D::new(x, baseline),
+                    advance: shaped_glyph.advance,
+                    point_size: shaped_glyph.point_size,
+                    cluster: shaped_glyph.cluster,
+                });
+                x += shaped_glyph.advance;
+            }
+
+            let text_start = shaped_glyphs
+                .get(line_glyphs.start)
+                .map_or(paragraph_range.start, |glyph| glyph.cluster);
+            let text_end = shaped_glyphs
+                .get(line_glyphs.end)
+                .map_or(paragraph_range.end, |glyph| glyph.cluster);
+            lines.push(Line {
+                glyph_range: glyph_start..glyphs.len(),
+                text_range: text_start..text_end,
+                baseline,
+                width: visible_width(text, &shaped_glyphs[line_glyphs]),
+            });
+        }
+    }
+
+    let width = lines.iter().map(|line| line.width).fold(0.0, f32::max);
+    let height = line_height * lines.len() as f32;
+    Layout {
+        fonts: layout_fonts
+            .into_iter()
+            .map(|layout_font| layout_font.font)
+            .collect(),
+        glyphs,
+        lines,
+        bounds: Rect::new(Point2D::zero(), Size2D::new(width, height)),
+    }
+}
+
+struct TextRun {
+    font_index: usize,
+    text_range: Range<usize>,
+}
+
+struct ShapedGlyph {
+    font_index: usize,
+    glyph_id: u32,
+    advance: f32,
+    point_size: f32,
+    cluster: usize,
+}
+
+// Splits a paragraph into runs of characters that the same font can display, asking the primary
+// font for fallbacks where it has no glyph.
+fn itemize<L>(
+    layout_fonts: &mut Vec<LayoutFont<L>>,
+    text: &str,
+    range: Range<usize>,
+    options: &LayoutOptions,
+) -> Vec<TextRun>
+where
+    L: Loader,
+{
+    let mut runs: Vec<TextRun> = vec![];
+    let mut position = range.start;
+    while position < range.end {
+        let missing_end = text[position..range.end]
+            .char_indices()
+            .find(|&(_, character)| layout_fonts[0].font.glyph_for_char(character).is_some())
+            .map_or(range.end, |(offset, _)| position + offset);
+
+        let (font_index, run_end) = if missing_end == position {
+            let covered_end = text[position..range.end]
+                .char_indices()
+                .find(|&(_, character)| layout_fonts[0].font.glyph_for_char(character).is_none())
+                .map_or(range.end, |(offset, _)| position + offset);
+            (0, covered_end)
+        } else {
+            let fallback_result = layout_fonts[0]
+                .font
+                .get_fallbacks(&text[position..missing_end], &options.locale);
+
+            // Always make progress, even if the fallback result is empty or bogus.
+            let mut run_end = position + fallback_result.valid_len;
+            if run_end <= position || run_end > missing_end {
+                run_end = missing_end;
+            }
+            while !text.is_char_boundary(run_end) {
+                run_end += 1;
+            }
+
+            let font_index = match fallback_result.fonts.into_iter().next() {
+                None => 0,
+                Some(fallback_font) => {
+                    let postscript_name = fallback_font.font.postscript_name();
+                    match layout_fonts.iter().position(|layout_font| {
+                        postscript_name.is_some()
+                            && layout_font.font.postscript_name() == postscript_name
+                    }) {
+                        Some(font_index) => font_index,
+                        None => {
+                            layout_fonts.push(LayoutFont::new(
+                                fallback_font.font,
+                                fallback_font.scale,
+                                options,
+                            ));
+                            layout_fonts.len() - 1
+                        }
+                    }
+                }
+            };
+            (font_index, run_end)
+        };
+
+        match runs.last_mut() {
+            Some(ref mut last_run) if last_run.font_index == font_index => {
+                last_run.text_range.end = run_end
+            }
+            _ => runs.push(TextRun {
+                font_index,
+                text_range: position..run_end,
+            }),
+        }
+        position = run_end;
+    }
+    runs
+}
+
+// Greedily splits the glyphs of a paragraph into lines, breaking after whitespace.
+fn break_lines(text: &str, glyphs: &[ShapedGlyph], max_width: Option<f32>) -> Vec<Range<usize>> {
+    let max_width = match max_width {
+        None => return iter::once(0..glyphs.len()).collect(),
+        Some(max_width) => max_width,
+    };
+
+    let mut lines = vec![];
+    let (mut line_start, mut line_width) = (0, 0.0);
+    let mut word_start = 0;
+    while word_start < glyphs.len() {
+        // A word is a run of non-whitespace glyphs plus the whitespace that follows it.
+        let mut word_end = word_start;
+        while word_end < glyphs.len() && !is_whitespace(text, &glyphs[word_end]) {
+            word_end += 1;
+        }
+        let visible_end = word_end;
+        while word_end < glyphs.len() && is_whitespace(text, &glyphs[word_end]) {
+            word_end += 1;
+        }
+
+        let word_visible_width = total_advance(&glyphs[word_start..visible_end]);
+        let word_width = total_advance(&glyphs[word_start..word_end]);
+
+        if word_start > line_start && line_width + word_visible_width > max_width {
+            lines.push(line_start..word_start);
+            line_start = word_start;
+            line_width = 0.0;
+        }
+        line_width += word_width;
+        word_start = word_end;
+    }
+    lines.push(line_start..glyphs.len());
+    lines
+}
+
+fn visible_width(text: &str, glyphs: &[ShapedGlyph]) -> f32 {
+    let visible_len = glyphs
+        .iter()
+        .rposition(|glyph| !is_whitespace(text, glyph))
+        .map_or(0, |index| index + 1);
+    total_advance(&glyphs[0..visible_len])
+}
+
+fn total_advance(glyphs: &[ShapedGlyph]) -> f32 {
+    glyphs
+        .iter()
+        .fold(0.0, |width, glyph| width + glyph.advance)
+}
+
+fn is_whitespace(text: &str, glyph: &ShapedGlyph) -> bool {
+    text[glyph.cluster..]
+        .chars()
+        .next()
+        .map_or(false, char::is_whitespace)
+}
+
+// A font used in a layout, along with the tables needed to shape text with it.
+struct LayoutFont<L> {
+    font: L,
+    point_size: f32,
+    units_per_em: f32,
+    gsub: Option<Box<[u8]>>,
+    ligature_lookups: Vec<u16>,
+    gpos: Option<Box<[u8]>>,
+    kerning_lookups: Vec<u16>,
+    kern: Option<Box<[u8]>>,
+}
+
+impl<L> LayoutFont<L>
+where
+    L: Loader,
+{
+    fn new(font: L, scale: f32, options: &LayoutOptions) -> LayoutFont<L> {
+        let units_per_em = font.metrics().units_per_em as f32;
+
+        let gsub = font.load_font_table(sfnt::GSUB);
+        let ligature_lookups = match gsub {
+            Some(ref gsub) if options.ligatures => {
+                feature_lookup_indices(gsub, Tag::from_bytes(b"liga")).unwrap_or_default()
+            }
+            _ => vec![],
+        };
+
+        let (gpos, kerning_lookups, kern) = if options.kerning {
+            let gpos = font.load_font_table(sfnt::GPOS);
+            let kerning_lookups = gpos
+                .as_ref()
+                .and_then(|gpos| feature_lookup_indices(gpos, Tag::from_bytes(b"kern")))
+                .unwrap_or_default();
+            // Only fall back to the legacy table if `GPOS` has no kerning of its own.
+            let kern = if kerning_lookups.is_empty() {
+                font.load_font_table(sfnt::KERN)
+            } else {
+                None
+            };
+            (gpos, kerning_lookups, kern)
+        } else {
+            (None, vec![], None)
+        };
+
+        LayoutFont {
+            font,
+            point_size: options.point_size * scale,
+            units_per_em,
+            gsub,
+            ligature_lookups,
+            gpos,
+            kerning_lookups,
+            kern,
+        }
+    }
+
+    fn shape(
+        &self,
+        font_index: usize,
+        text: &str,
+        range: Range<usize>,
+        options: &LayoutOptions,
+        shaped_glyphs: &mut Vec<ShapedGlyph>,
+    ) {
+        let mut glyphs: Vec<(u32, usize)> = text[range.clone()]
+            .char_indices()
+            .map(|(offset, character)| {
+                let glyph_id = self.font.glyph_for_char(character).unwrap_or(0);
+                (glyph_id, range.start + offset)
+            })
+            .collect();
+
+        if options.ligatures {
+            if let Some(ref gsub) = self.gsub {
+                for &lookup_index in &self.ligature_lookups {
+                    apply_ligature_lookup(gsub, lookup_index, &mut glyphs);
+                }
+            }
+        }
+
+        let scale = self.point_size / self.units_per_em;
+        for (index, &(glyph_id, cluster)) in glyphs.iter().enumerate() {
+            let mut advance = match self.font.advance(glyph_id) {
+                Ok(advance) => advance.x,
+                Err(_) => 0.0,
+            };
+            if options.kerning {
+                if let Some(&(next_glyph_id, _)) = glyphs.get(index + 1) {
+                    advance += self.kerning(glyph_id, next_glyph_id);
+                }
+            }
+            shaped_glyphs.push(ShapedGlyph {
+                font_index,
+                glyph_id,
+                advance: advance * scale,
+                point_size: self.point_size,
+                cluster,
+            });
+        }
+    }
+
+    // Returns the kerning adjustment between two glyphs, in font units.
+    fn kerning(&self, first: u32, second: u32) -> f32 {
+        if !self.kerning_lookups.is_empty() {
+            let mut adjustment = 0.0;
+            if let Some(ref gpos) = self.gpos {
+                for &lookup_index in &self.kerning_lookups {
+                    if let Some(lookup_adjustment) =
+                        pair_adjustment_lookup(gpos, lookup_index, first, second)
+                    {
+                        adjustment += lookup_adjustment as f32;
+                    }
+                }
+            }
+            return adjustment;
+        }
+        if let Some(ref kern) = self.kern {
+            return legacy_kerning(kern, first, second) as f32;
+        }
+        0.0
+    }
+}
+
+// Returns the indices of the lookups for a feature in a `GSUB` or `GPOS` table, using the default
+// language system of the `DFLT` script (or `latn` if there is no `DFLT`).
+fn feature_lookup_indices(table: &[u8], feature: Tag) -> Option<Vec<u16>> {
+    let script_list = table.get((read_u16(table, 4)? as usize)..)?;
+    let feature_list = table.get((read_u16(table, 6)? as usize)..)?;
+
+    let script_count = read_u16(script_list, 0)? as usize;
+    let mut script_offset = None;
+    for &wanted_script in &[Tag::DEFAULT_SCRIPT, Tag::from_bytes(b"latn")] {
+        for script_index in 0..script_count {
+            let record_offset = 2 + script_index * 6;
+            if read_u32(script_list, record_offset)? == wanted_script.0 {
+                script_offset = Some(read_u16(script_list, record_offset + 4)? as usize);
+                break;
+            }
+        }
+        if script_offset.is_some() {
+            break;
+        }
+    }
+    let script = script_list.get(script_offset?..)?;
+    let language_system = match read_u16(script, 0)? {
+        0 => return None,
+        offset => script.get((offset as usize)..)?,
+    };
+
+    let mut lookup_indices = vec![];
+    let feature_count = read_u16(language_system, 4)? as usize;
+    for index in 0..feature_count {
+        let feature_index = read_u16(language_system, 6 + index * 2)? as usize;
+        let record_offset = 2 + feature_index * 6;
+        if read_u32(feature_list, record_offset)? != feature.0 {
+            continue;
+        }
+        let feature_table =
+            feature_list.get((read_u16(feature_list, record_offset + 4)? as usize)..)?;
+        let lookup_count = read_u16(feature_table, 2)? as usize;
+        for lookup in 0..lookup_count {
+            lookup_indices.push(read_u16(feature_table, 4 + lookup * 2)?);
+        }
+    }
+    lookup_indices.sort();
+    lookup_indices.dedup();
+    Some(lookup_indices)
+}
+
+// Returns the subtables of a lookup that have the given type, looking through extension
+// subtables.
+fn lookup_subtables(
+    table: &[u8],
+    lookup_index: u16,
+    lookup_type: u16,
+    extension_lookup_type: u16,
+) -> Option<Vec<&[u8]>> {
+    let lookup_list = table.get((read_u16(table, 8)? as usize)..)?;
+    let lookup_offset = read_u16(lookup_list, 2 + lookup_index as usize * 2)? as usize;
+    let lookup = lookup_list.get(lookup_offset..)?;
+    let this_lookup_type = read_u16(lookup, 0)?;
+    let subtable_count = read_u16(lookup, 4)? as usize;
+
+    let mut subtables = vec![];
+    for subtable_index in 0..subtable_count {
+        let subtable = lookup.get((read_u16(lookup, 6 + subtable_index * 2)? as usize)..)?;
+        if this_lookup_type == lookup_type {
+            subtables.push(subtable);
+        } else if this_lookup_type == extension_lookup_type && read_u16(subtable, 2)? == lookup_type
+        {
+            subtables.push(subtable.get((read_u32(subtable, 4)? as usize)..)?);
+        }
+    }
+    Some(subtables)
+}
+
+// Applies a `GSUB` ligature substitution lookup, merging each ligature into the cluster of its
+// first component.
+fn apply_ligature_lookup(gsub: &[u8], lookup_index: u16, glyphs: &mut Vec<(u32, usize)>) {
+    let subtables = lookup_subtables(
+        gsub,
+        lookup_index,
+        GSUB_LIGATURE_LOOKUP_TYPE,
+        GSUB_EXTENSION_LOOKUP_TYPE,
+    )
+    .unwrap_or_default();
+    if subtables.is_empty() {
+        return;
+    }
+
+    let mut index = 0;
+    while index < glyphs.len() {
+        let ligature = subtables
+            .iter()
+            .filter_map(|subtable| match_ligature(subtable, &glyphs[index..]))
+            .next();
+        if let Some((ligature_glyph_id, component_count)) = ligature {
+            glyphs[index].0 = ligature_glyph_id;
+            glyphs.drain((index + 1)..(index + component_count));
+        }
+        index += 1;
+    }
+}
+
+// Returns the ligature glyph and its number of components if a ligature subtable matches the
+// start of the given glyphs.
+fn match_ligature(subtable: &[u8], glyphs: &[(u32, usize)]) -> Option<(u32, usize)> {
+    if read_u16(subtable, 0)? != 1 {
+        return None;
+    }
+    let coverage = subtable.get((read_u16(subtable, 2)? as usize)..)?;
+    let coverage_index = coverage_index(coverage, glyphs[0].0)?;
+    let ligature_set_offset = read_u16(subtable, 6 + coverage_index as usize * 2)? as usize;
+    let ligature_set = subtable.get(ligature_set_offset..)?;
+
+    let ligature_count = read_u16(ligature_set, 0)? as usize;
+    'ligatures: for ligature_index in 0..ligature_count {
+        let ligature =
+            ligature_set.get((read_u16(ligature_set, 2 + ligature_index * 2)? as usize)..)?;
+        let component_count = read_u16(ligature, 2)? as usize;
+        if component_count < 2 || component_count > glyphs.len() {
+            continue;
+        }
+        for (component_index, &(glyph_id, _)) in glyphs[1..component_count].iter().enumerate() {
+            if read_u16(ligature, 4 + component_index * 2)? as u32 != glyph_id {
+                continue 'ligatures;
+            }
+        }
+        return Some((read_u16(ligature, 0)? as u32, component_count));
+    }
+    None
+}
+
+// Returns the X advance adjustment of the first glyph from a `GPOS` pair adjustment lookup, if
+// the lookup covers the pair.
+fn pair_adjustment_lookup(gpos: &[u8], lookup_index: u16, first: u32, second: u32) -> Option<i16> {
+    lookup_subtables(
+        gpos,
+        lookup_index,
+        GPOS_PAIR_ADJUSTMENT_LOOKUP_TYPE,
+        GPOS_EXTENSION_LOOKUP_TYPE,
+    )?
+    .into_iter()
+    .filter_map(|subtable| pair_adjustment(subtable, first, second))
+    .next()
+}
+
+fn pair_adjustment(subtable: &[u8], first: u32, second: u32) -> Option<i16> {
+    let format = read_u16(subtable, 0)?;
+    let coverage = subtable.get((read_u16(subtable, 2)? as usize)..)?;
+    let coverage_index = coverage_index(coverage, first)? as usize;
+    let value_format_1 = read_u16(subtable, 4)?;
+    let value_format_2 = read_u16(subtable, 6)?;
+    let value_size_1 = value_record_size(value_format_1);
+    let value_size_2 = value_record_size(value_format_2);
+
+    let value_record_1 = match format {
+        1 => {
+            let pair_set_offset = read_u16(subtable, 10 + coverage_index * 2)? as usize;
+            let pair_set = subtable.get(pair_set_offset..)?;
+            let record_size = 2 + value_size_1 + value_size_2;
+
+            // Pair value records are sorted by the second glyph.
+            let (mut low, mut high) = (0, read_u16(pair_set, 0)? as usize);
+            loop {
+                if low >= high {
+                    return None;
+                }
+                let mid = (low + high) / 2;
+                let record_offset = 2 + mid * record_size;
+                let mid_glyph_id = read_u16(pair_set, record_offset)? as u32;
+                if mid_glyph_id == second {
+                    break pair_set.get((record_offset + 2)..)?;
+                }
+                if mid_glyph_id < second {
+                    low = mid + 1
+                } else {
+                    high = mid
+                }
+            }
+        }
+        2 => {
+            let class_def_1 = subtable.get((read_u16(subtable, 8)? as usize)..)?;
+            let class_def_2 = subtable.get((read_u16(subtable, 10)? as usize)..)?;
+            let class_1_count = read_u16(subtable, 12)? as usize;
+            let class_2_count = read_u16(subtable, 14)? as usize;
+            let class_1 = glyph_class(class_def_1, first)? as usize;
+            let class_2 = glyph_class(class_def_2, second)? as usize;
+            if class_1 >= class_1_count || class_2 >= class_2_count {
+                return None;
+            }
+            let record_offset =
+                16 + (class_1 * class_2_count + class_2) * (value_size_1 + value_size_2);
+            subtable.get(record_offset..)?
+        }
+        _ => return None,
+    };
+
+    if value_format_1 & VALUE_FORMAT_X_ADVANCE == 0 {
+        return Some(0);
+    }
+    let placement_fields = value_format_1 & (VALUE_FORMAT_X_PLACEMENT | VALUE_FORMAT_Y_PLACEMENT);
+    read_i16(value_record_1, placement_fields.count_ones() as usize * 2)
+}
+
+fn value_record_size(value_format: u16) -> usize {
+    (value_format & 0xff).count_ones() as usize * 2
+}
+
+// Returns the index of a glyph in a coverage table, if the table covers it.
+fn coverage_index(coverage: &[u8], glyph_id: u32) -> Option<u16> {
+    match read_u16(coverage, 0)? {
+        1 => {
+            let (mut low, mut high) = (0, read_u16(coverage, 2)? as usize);
+            while low < high {
+                let mid = (low + high) / 2;
+                let mid_glyph_id = read_u16(coverage, 4 + mid * 2)? as u32;
+                if mid_glyph_id == glyph_id {
+                    return Some(mid as u16);
+                }
+                if mid_glyph_id < glyph_id {
+                    low = mid + 1
+                } else {
+                    high = mid
+                }
+            }
+            None
+        }
+        2 => {
+            let (start, end, start_index) = find_range(coverage, glyph_id)?;
+            if glyph_id < start || glyph_id > end {
+                return None;
+            }
+            start_index.checked_add((glyph_id - start) as u16)
+        }
+        _ => None,
+    }
+}
+
+// Returns the class of a glyph from a class definition table. Glyphs not listed are class 0.
+fn glyph_class(class_def: &[u8], glyph_id: u32) -> Option<u16> {
+    match read_u16(class_def, 0)? {
+        1 => {
+            let start = read_u16(class_def, 2)? as u32;
+            let count = read_u16(class_def, 4)? as u32;
+            if glyph_id < start || glyph_id >= start + count {
+                return Some(0);
+            }
+            read_u16(class_def, 6 + (glyph_id - start) as usize * 2)
+        }
+        2 => match find_range(class_def, glyph_id) {
+            Some((start, end, class)) if glyph_id >= start && glyph_id <= end => Some(class),
+            _ => Some(0),
+        },
+        _ => None,
+    }
+}
+
+// Binary searches the range records shared by coverage format 2 and class definition format 2.
+// Returns the start, end, and value of the last range starting at or before the glyph.
+fn find_range(table: &[u8], glyph_id: u32) -> Option<(u32, u32, u16)> {
+    let (mut low, mut high) = (0, read_u16(table, 2)? as usize);
+    let mut found = None;
+    while low < high {
+        let mid = (low + high) / 2;
+        let record_offset = 4 + mid * 6;
+        let start = read_u16(table, record_offset)? as u32;
+        if start <= glyph_id {
+            found = Some((
+                start,
+                read_u16(table, record_offset + 2)? as u32,
+                read_u16(table, record_offset + 4)?,
+            ));
+            low = mid + 1
+        } else {
+            high = mid
+        }
+    }
+    found
+}
+
+// Looks up a pair in the horizontal format 0 subtables of a Microsoft-style `kern` table.
+fn legacy_kerning(kern: &[u8], first: u32, second: u32) -> i16 {
+    let mut adjustment: i16 = 0;
+    if read_u16(kern, 0) != Some(0) {
+        return 0;
+    }
+    let subtable_count = read_u16(kern, 2).unwrap_or(0) as usize;
+    let mut subtable_offset = 4;
+    for _ in 0..subtable_count {
+        let (length, coverage) = match (
+            read_u16(kern, subtable_offset + 2),
+            read_u16(kern, subtable_offset + 4),
+        ) {
+            (Some(length), Some(coverage)) => (length as usize, coverage),
+            _ => break,
+        };
+        if coverage >> 8 == 0
+            && coverage & KERN_COVERAGE_HORIZONTAL != 0
+            && coverage & KERN_COVERAGE_CROSS_STREAM == 0
+        {
+            if let Some(value) = kern
+                .get(subtable_offset..)
+                .and_then(|subtable| legacy_kerning_pair(subtable, first, second))
+            {
+                adjustment = adjustment.saturating_add(value);
+            }
+        }
+        if length == 0 {
+            break;
+        }
+        subtable_offset += length;
+    }
+    adjustment
+}
+
+fn legacy_kerning_pair(subtable: &[u8], first: u32, second: u32) -> Option<i16> {
+    let key = (first << 16) | second;
+    let (mut low, mut high) = (0, read_u16(subtable, 6)? as usize);
+    while low < high {
+        let mid = (low + high) / 2;
+        let pair_offset = 14 + mid * 6;
+        let mid_key = read_u32(subtable, pair_offset)?;
+        if mid_key == key {
+            return read_i16(subtable, pair_offset + 4);
+        }
+        if mid_key < key {
+            low = mid + 1
+        } else {
+            high = mid
+        }
+    }
+    None
+}
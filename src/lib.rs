@@ -70,9 +70,11 @@
 //! * Filesystem (cross-platform): A simple source that reads fonts from a path on disk. This is
 //!   the default on Android.
 //!
-//! * Memory (cross-platform): A source that reads from a fixed set of fonts in memory.
+//! * Memory (cross-platform): A source that reads from a set of fonts in memory, to which fonts
+//!   can be added and from which they can be removed at runtime.
 //!
-//! * Multi (cross-platform): A source that allows multiple sources to be queried at once.
+//! * Multi (cross-platform): A source that allows multiple sources to be queried at once, in
+//!   order of priority.
 //!
 //! On Windows and macOS, the FreeType loader and the Fontconfig source are not built by default.
 //! To build them, use the `loader-freetype` and `source-fontconfig` Cargo features respectively.
@@ -106,8 +108,15 @@
 //!
 //! * Looking up all fonts on the system.
 //!
+//! * Indexing fonts on several threads, optionally in the background while the source is already
+//!   in use.
+//!
+//! * Noticing fonts that are installed, removed or modified while the application is running.
+//!
 //! * Searching for specific fonts by family or PostScript name.
 //!
+//! * Listing the fonts that match a query on their properties, format and character coverage.
+//!
 //! * Performing font matching according to the [CSS Fonts Module Level 3] specification.
 //!
 //! ## License
@@ -121,6 +130,7 @@
 #![warn(missing_copy_implementations)]
 
 extern crate byteorder;
+extern crate crossbeam_utils;
 extern crate euclid;
 extern crate float_ord;
 extern crate libc;
@@ -154,31 +164,51 @@ extern crate fontconfig;
     feature = "loader-freetype"
 ))]
 extern crate freetype;
+#[cfg(feature = "harfbuzz")]
+extern crate harfbuzz_sys;
 #[cfg(not(target_arch = "wasm32"))]
 extern crate memmap;
+#[cfg(feature = "serde")]
+#[macro_use]
+extern crate serde;
 #[cfg(not(target_arch = "wasm32"))]
 extern crate walkdir;
 #[cfg(target_family = "windows")]
 extern crate winapi;
 
 pub mod canvas;
+pub mod css;
+pub mod descriptor;
 pub mod error;
 pub mod family;
 pub mod family_handle;
 pub mod family_name;
+pub mod features;
 pub mod file_type;
 pub mod font;
+#[doc(hidden)]
+pub mod fuzzing;
+pub mod generic_family_map;
 pub mod handle;
+#[cfg(feature = "harfbuzz")]
+pub mod harfbuzz;
 pub mod hinting;
+pub mod language;
+pub mod layout;
 pub mod loader;
 pub mod loaders;
 pub mod metrics;
+pub mod outline;
 pub mod properties;
+pub mod query;
 pub mod source;
 pub mod sources;
+pub mod subset;
+pub mod watcher;
 
 #[cfg(test)]
 pub mod test;
 
 mod matching;
+mod sfnt;
 mod utils;
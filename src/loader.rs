@@ -12,16 +12,21 @@
 //! fonts.
 
 use euclid::{Point2D, Rect, Vector2D};
-use lyon_path::builder::PathBuilder;
+use lyon_path::builder::{FlatPathBuilder, PathBuilder};
 use std::sync::Arc;
 
 use canvas::{Canvas, RasterizationOptions};
 use error::{FontLoadingError, GlyphLoadingError};
+use features::{LayoutFeatures, Tag};
 use file_type::FileType;
 use handle::Handle;
 use hinting::HintingOptions;
+use language::LanguageSupport;
 use metrics::Metrics;
+use outline::Outline;
 use properties::Properties;
+use sfnt::{self, VerticalHeader};
+use subset;
 
 #[cfg(not(target_arch = "wasm32"))]
 use std::fs::File;
@@ -51,12 +56,18 @@ pub trait Loader: Clone + Sized {
     ///
     /// If the file is a collection (`.ttc`/`.otc`/etc.), `font_index` specifies the index of the
     /// font to load from it. If the file represents a single font, pass 0 for `font_index`.
+    ///
+    /// Errors are wrapped in `FontLoadingError::Path`, so that they say which file was at fault.
     #[cfg(not(target_arch = "wasm32"))]
     fn from_path<P>(path: P, font_index: u32) -> Result<Self, FontLoadingError>
     where
         P: AsRef<Path>,
     {
-        Loader::from_file(&mut try!(File::open(path)), font_index)
+        let path = path.as_ref();
+        File::open(path)
+            .map_err(FontLoadingError::from)
+            .and_then(|mut file| Loader::from_file(&mut file, font_index))
+            .map_err(|error| error.in_file(path))
     }
 
     /// Creates a font from a native API handle.
@@ -94,7 +105,11 @@ pub trait Loader: Clone + Sized {
     where
         P: AsRef<Path>,
     {
-        <Self as Loader>::analyze_file(&mut try!(File::open(path)))
+        let path = path.as_ref();
+        File::open(path)
+            .map_err(FontLoadingError::from)
+            .and_then(|mut file| <Self as Loader>::analyze_file(&mut file))
+            .map_err(|error| error.in_file(path))
     }
 
     /// Returns the wrapped native font handle.
@@ -149,6 +164,20 @@ pub trait Loader: Clone + Sized {
     where
         B: PathBuilder;
 
+    /// Returns the vector path for a glyph as an owned `Outline`.
+    ///
+    /// This records the path that `outline()` would send to a path builder. If `hinting_mode` is
+    /// not None, grid-fitting is performed as requested.
+    fn glyph_outline(
+        &self,
+        glyph_id: u32,
+        hinting_mode: HintingOptions,
+    ) -> Result<Outline, GlyphLoadingError> {
+        let mut outline_builder = Outline::builder();
+        self.outline(glyph_id, hinting_mode, &mut outline_builder)?;
+        Ok(outline_builder.build())
+    }
+
     /// Returns the boundaries of a glyph in font units.
     fn typographic_bounds(&self, glyph_id: u32) -> Result<Rect<f32>, GlyphLoadingError>;
 
@@ -159,24 +188,135 @@ pub trait Loader: Clone + Sized {
     /// Returns the amount that the given glyph should be displaced from the origin.
     fn origin(&self, glyph_id: u32) -> Result<Point2D<f32>, GlyphLoadingError>;
 
+    /// Returns the distance from the origin of the glyph with the given ID to the next in vertical
+    /// layout, in font units.
+    ///
+    /// This comes from the `vmtx` table if the font has one; otherwise, the distance from the
+    /// ascent to the descent is used. The returned vector points down, toward negative Y.
+    fn vertical_advance(&self, glyph_id: u32) -> Result<Vector2D<f32>, GlyphLoadingError> {
+        if glyph_id >= self.glyph_count() {
+            return Err(GlyphLoadingError::NoSuchGlyph(glyph_id));
+        }
+        let advance_height = match vertical_glyph_metrics(self, glyph_id) {
+            Some((advance_height, _)) => advance_height as f32,
+            None => {
+                let metrics = self.metrics();
+                metrics.ascent - metrics.descent
+            }
+        };
+        Ok(Vector2D::new(0.0, -advance_height))
+    }
+
+    /// Returns the position of the vertical origin of the glyph with the given ID, relative to
+    /// its horizontal origin, in font units.
+    ///
+    /// In vertical layout, glyphs are positioned so that this point lies on the pen position. The
+    /// X coordinate is half the horizontal advance. The Y coordinate comes from the `VORG` table
+    /// if present, then from the top side bearing in the `vmtx` table, and finally falls back to
+    /// the ascent.
+    fn vertical_origin(&self, glyph_id: u32) -> Result<Point2D<f32>, GlyphLoadingError> {
+        let advance = self.advance(glyph_id)?;
+        let vertical_origin_y = self
+            .load_font_table(sfnt::VORG)
+            .and_then(|vorg| sfnt::vertical_origin_y(&vorg, glyph_id));
+        let y = match (vertical_origin_y, vertical_glyph_metrics(self, glyph_id)) {
+            (Some(vertical_origin_y), _) => vertical_origin_y as f32,
+            (None, Some((_, top_side_bearing))) => {
+                self.typographic_bounds(glyph_id)?.max_y() + top_side_bearing as f32
+            }
+            (None, None) => self.metrics().ascent,
+        };
+        Ok(Point2D::new(advance.x * 0.5, y))
+    }
+
     /// Retrieves various metrics that apply to the entire font.
     fn metrics(&self) -> Metrics;
 
+    /// Returns the raw contents of the OpenType table with the given tag, if the font has one.
+    ///
+    /// The tag is a big-endian four-character code; e.g. `0x7668_6561` for `vhea`.
+    fn load_font_table(&self, table_tag: u32) -> Option<Box<[u8]>>;
+
+    /// Returns true if the font has an OpenType table with the given tag.
+    ///
+    /// Loaders that can look a table up without copying it should override the default, which
+    /// loads the whole table.
+    #[inline]
+    fn has_font_table(&self, table_tag: u32) -> bool {
+        self.load_font_table(table_tag).is_some()
+    }
+
+    /// Returns the OpenType scripts, language systems, and features that this font declares in
+    /// its `GSUB` and `GPOS` tables.
+    ///
+    /// Fonts without these tables, such as bitmap fonts, return an empty set.
+    fn layout_features(&self) -> LayoutFeatures {
+        LayoutFeatures::from_tables(
+            self.load_font_table(sfnt::GSUB).as_deref(),
+            self.load_font_table(sfnt::GPOS).as_deref(),
+        )
+    }
+
+    /// Returns the languages that this font declares support for in its `meta` and `OS/2`
+    /// tables.
+    fn language_support(&self) -> LanguageSupport {
+        LanguageSupport::from_tables(
+            self.load_font_table(sfnt::OS_2).as_deref(),
+            self.load_font_table(sfnt::META).as_deref(),
+        )
+    }
+
     /// Returns a handle to this font, if possible.
     ///
-    /// This is useful if you want to open the font with a different loader.
+    /// This is useful if you want to open the font with a different loader. If this font is a
+    /// member of a collection, the handle refers to the whole collection and carries the index of
+    /// this font within it.
     fn handle(&self) -> Option<Handle> {
-        // FIXME(pcwalton): This doesn't handle font collections!
         self.copy_font_data()
-            .map(|font_data| Handle::from_memory(font_data, 0))
+            .map(|font_data| Handle::from_memory(font_data, self.font_index()))
     }
 
     /// Attempts to return the raw font data (contents of the font file).
     ///
     /// If this font is a member of a collection, this function returns the data for the entire
-    /// collection.
+    /// collection; `font_index()` says which member this font is. Use
+    /// `copy_standalone_font_data()` to get a file containing only this font.
     fn copy_font_data(&self) -> Option<Arc<Vec<u8>>>;
 
+    /// Returns the index of this font within the data that `copy_font_data()` returns.
+    ///
+    /// This is 0 unless the font is a member of a collection (`.ttc`/`.otc`/etc.) Loaders that
+    /// can load members of collections must override the default, which always returns 0.
+    #[inline]
+    fn font_index(&self) -> u32 {
+        0
+    }
+
+    /// Returns the coordinates of this font along its variation axes; e.g. `wght` or `wdth`.
+    ///
+    /// An empty list means the default coordinates, which fonts without variation axes always
+    /// use. Loaders that can load named instances of variable fonts must override the default,
+    /// which always returns an empty list.
+    #[inline]
+    fn variation_coordinates(&self) -> Vec<(Tag, f32)> {
+        vec![]
+    }
+
+    /// Attempts to return the data of a font file that contains only this font.
+    ///
+    /// If this font is a member of a collection, its tables are copied into a new, self-contained
+    /// font file, suitable for handing to systems that don't understand collections (a PDF
+    /// writer, a browser, etc.) Otherwise, this returns the same data as `copy_font_data()`.
+    fn copy_standalone_font_data(&self) -> Option<Arc<Vec<u8>>> {
+        let font_data = self.copy_font_data()?;
+        if !sfnt::is_collection(&font_data) {
+            return Some(font_data);
+        }
+        subset::extract_font(&font_data, self.font_index())
+            .ok()
+            .map(Arc::new)
+    }
+
     /// Returns true if and only if the font loader can perform hinting in the requested way.
     ///
     /// Some APIs support only rasterizing glyphs with hinting, not retriving hinted outlines. If
@@ -191,6 +331,9 @@ pub trait Loader: Clone + Sized {
 
     /// Returns the pixel boundaries that the glyph will take up when rendered using this loader's
     /// rasterizer at the given size and origin.
+    ///
+    /// Bounds that can't be represented in pixels (for example, because the font has no
+    /// units-per-em value) are returned as an empty rectangle.
     fn raster_bounds(
         &self,
         glyph_id: u32,
@@ -205,7 +348,8 @@ pub trait Loader: Clone + Sized {
         Ok(typographic_raster_bounds
             .translate(&origin.to_vector())
             .round_out()
-            .to_i32())
+            .try_cast()
+            .unwrap_or_else(Rect::zero))
     }
 
     /// Rasterizes a glyph to a canvas with the given size and origin.
@@ -251,3 +395,12 @@ pub struct FallbackFont<Font> {
     pub scale: f32,
     // TODO: add font simulation data
 }
+
+fn vertical_glyph_metrics<L>(font: &L, glyph_id: u32) -> Option<(u16, i16)>
+where
+    L: Loader,
+{
+    let vertical_header = VerticalHeader::parse(&font.load_font_table(sfnt::VHEA)?)?;
+    let vmtx = font.load_font_table(sfnt::VMTX)?;
+    sfnt::vertical_glyph_metrics(&vmtx, &vertical_header, glyph_id)
+}
@@ -35,13 +35,17 @@ use std::path::Path;
 use std::sync::Arc;
 
 use canvas::{Canvas, Format, RasterizationOptions};
-use error::{FontLoadingError, GlyphLoadingError};
+use error::{BackendError, FontLoadingError, GlyphLoadingError};
+use features::{LayoutFeatures, Tag};
 use file_type::FileType;
 use handle::Handle;
 use hinting::HintingOptions;
+use language::LanguageSupport;
 use loader::{FallbackResult, Loader};
 use metrics::Metrics;
+use outline::Outline;
 use properties::{Properties, Stretch, Style, Weight};
+use sfnt;
 use sources;
 use utils;
 
@@ -77,8 +81,8 @@ impl Font {
         }
 
         let data_provider = CGDataProvider::from_buffer(font_data.clone());
-        let core_graphics_font =
-            CGFont::from_data_provider(data_provider).map_err(|_| FontLoadingError::Parse)?;
+        let core_graphics_font = CGFont::from_data_provider(data_provider)
+            .map_err(|_| FontLoadingError::Parse(Some(BackendError::CoreText)))?;
         let core_text_font = core_text::font::new_from_CGFont(&core_graphics_font, 16.0);
         Ok(Font {
             core_text_font,
@@ -105,8 +109,8 @@ impl Font {
             let mmap = Arc::new(mmap.make_read_only().map_err(FontLoadingError::Io)?);
             let mmap_data = Box::new(Box::new(MmapData::new(mmap.clone())) as Box<CustomData>);
             let provider = CGDataProvider::from_custom_data(mmap_data);
-            let core_graphics_font =
-                CGFont::from_data_provider(provider).map_err(|_| FontLoadingError::Parse)?;
+            let core_graphics_font = CGFont::from_data_provider(provider)
+                .map_err(|_| FontLoadingError::Parse(Some(BackendError::CoreText)))?;
             let core_text_font = core_text::font::new_from_CGFont(&core_graphics_font, 16.0);
 
             Ok(Font {
@@ -175,7 +179,7 @@ impl Font {
         let data_provider = CGDataProvider::from_buffer(font_data);
         match CGFont::from_data_provider(data_provider) {
             Ok(_) => Ok(FileType::Single),
-            Err(_) => Err(FontLoadingError::Parse),
+            Err(_) => Err(FontLoadingError::Parse(Some(BackendError::CoreText))),
         }
     }
 
@@ -195,7 +199,7 @@ impl Font {
             let provider = CGDataProvider::from_custom_data(mmap_data);
             match CGFont::from_data_provider(provider) {
                 Ok(_) => Ok(FileType::Single),
-                Err(_) => Err(FontLoadingError::Parse),
+                Err(_) => Err(FontLoadingError::Parse(Some(BackendError::CoreText))),
             }
         }
     }
@@ -356,6 +360,19 @@ impl Font {
         Ok(())
     }
 
+    /// Returns the vector path for a glyph as an owned `Outline`.
+    ///
+    /// This records the path that `outline()` would send to a path builder. If `hinting_mode` is
+    /// not None, grid-fitting is performed as requested.
+    #[inline]
+    pub fn glyph_outline(
+        &self,
+        glyph_id: u32,
+        hinting_mode: HintingOptions,
+    ) -> Result<Outline, GlyphLoadingError> {
+        <Self as Loader>::glyph_outline(self, glyph_id, hinting_mode)
+    }
+
     /// Returns the boundaries of a glyph in font units.
     pub fn typographic_bounds(&self, glyph_id: u32) -> Result<Rect<f32>, GlyphLoadingError> {
         let rect = self
@@ -415,7 +432,7 @@ impl Font {
     pub fn metrics(&self) -> Metrics {
         let units_per_em = self.core_text_font.units_per_em();
         let units_per_point = (units_per_em as f64) / self.core_text_font.pt_size();
-        Metrics {
+        let mut metrics = Metrics {
             units_per_em,
             ascent: (self.core_text_font.ascent() * units_per_point) as f32,
             descent: (-self.core_text_font.descent() * units_per_point) as f32,
@@ -425,7 +442,45 @@ impl Font {
                 as f32,
             cap_height: (self.core_text_font.cap_height() * units_per_point) as f32,
             x_height: (self.core_text_font.x_height() * units_per_point) as f32,
-        }
+            ..Metrics::default()
+        };
+        sfnt::fill_in_table_metrics(&mut metrics, |table_tag| self.load_font_table(table_tag));
+        metrics
+    }
+
+    /// Returns the distance from the origin of the glyph with the given ID to the next in vertical
+    /// layout, in font units.
+    #[inline]
+    pub fn vertical_advance(&self, glyph_id: u32) -> Result<Vector2D<f32>, GlyphLoadingError> {
+        <Self as Loader>::vertical_advance(self, glyph_id)
+    }
+
+    /// Returns the position of the vertical origin of the glyph with the given ID, relative to
+    /// its horizontal origin, in font units.
+    #[inline]
+    pub fn vertical_origin(&self, glyph_id: u32) -> Result<Point2D<f32>, GlyphLoadingError> {
+        <Self as Loader>::vertical_origin(self, glyph_id)
+    }
+
+    /// Returns the raw contents of the OpenType table with the given tag, if the font has one.
+    pub fn load_font_table(&self, table_tag: u32) -> Option<Box<[u8]>> {
+        self.core_text_font
+            .get_font_table(table_tag)
+            .map(|data| data.bytes().into())
+    }
+
+    /// Returns the OpenType scripts, language systems, and features that this font declares in
+    /// its `GSUB` and `GPOS` tables.
+    #[inline]
+    pub fn layout_features(&self) -> LayoutFeatures {
+        <Self as Loader>::layout_features(self)
+    }
+
+    /// Returns the languages that this font declares support for in its `meta` and `OS/2`
+    /// tables.
+    #[inline]
+    pub fn language_support(&self) -> LanguageSupport {
+        <Self as Loader>::language_support(self)
     }
 
     /// Returns a handle to this font, if possible.
@@ -439,7 +494,7 @@ impl Font {
     /// Attempts to return the raw font data (contents of the font file).
     ///
     /// If this font is a member of a collection, this function returns the data for the entire
-    /// collection.
+    /// collection; `font_index()` says which member this font is.
     pub fn copy_font_data(&self) -> Option<Arc<Vec<u8>>> {
         match self.font_data {
             FontData::Unavailable => None,
@@ -448,6 +503,32 @@ impl Font {
         }
     }
 
+    /// Returns the index of this font within the data that `copy_font_data()` returns.
+    ///
+    /// This is always 0, because collections are unpacked when loaded so that this font's table
+    /// directory comes first.
+    #[inline]
+    pub fn font_index(&self) -> u32 {
+        0
+    }
+
+    /// Returns the coordinates of this font along its variation axes; e.g. `wght` or `wdth`.
+    ///
+    /// This loader always reports the default coordinates, as an empty list.
+    #[inline]
+    pub fn variation_coordinates(&self) -> Vec<(Tag, f32)> {
+        <Self as Loader>::variation_coordinates(self)
+    }
+
+    /// Attempts to return the data of a font file that contains only this font.
+    ///
+    /// If this font is a member of a collection, its tables are copied into a new, self-contained
+    /// font file. Otherwise, this returns the same data as `copy_font_data()`.
+    #[inline]
+    pub fn copy_standalone_font_data(&self) -> Option<Arc<Vec<u8>>> {
+        <Self as Loader>::copy_standalone_font_data(self)
+    }
+
     /// Returns the pixel boundaries that the glyph will take up when rendered using this loader's
     /// rasterizer at the given size and origin.
     #[inline]
@@ -708,11 +789,21 @@ impl Loader for Font {
         self.metrics()
     }
 
+    #[inline]
+    fn load_font_table(&self, table_tag: u32) -> Option<Box<[u8]>> {
+        self.load_font_table(table_tag)
+    }
+
     #[inline]
     fn copy_font_data(&self) -> Option<Arc<Vec<u8>>> {
         self.copy_font_data()
     }
 
+    #[inline]
+    fn font_index(&self) -> u32 {
+        self.font_index()
+    }
+
     #[inline]
     fn supports_hinting_options(
         &self,
@@ -765,7 +856,8 @@ impl Deref for FontData {
     type Target = [u8];
     fn deref(&self) -> &[u8] {
         match *self {
-            FontData::Unavailable => panic!("Font data unavailable!"),
+            // Fonts created from native handles may have no data to expose.
+            FontData::Unavailable => &[],
             FontData::File(ref mmap) => &***mmap,
             FontData::Memory(ref data) => &***data,
         }
@@ -833,7 +925,7 @@ fn read_number_of_fonts_from_otc_header(header: &[u8]) -> Result<u32, FontLoadin
 // Unpacks an OTC font "in-place".
 fn unpack_otc_font(data: &mut [u8], font_index: u32) -> Result<(), FontLoadingError> {
     if font_index >= read_number_of_fonts_from_otc_header(data)? {
-        return Err(FontLoadingError::NoSuchFontInCollection);
+        return Err(FontLoadingError::NoSuchFontInCollection { font_index });
     }
 
     let offset_table_pos_pos = 12 + 4 * font_index as usize;
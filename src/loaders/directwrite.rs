@@ -41,15 +41,20 @@ use winapi::um::dwrite::{
     DWRITE_READING_DIRECTION_LEFT_TO_RIGHT,
 };
 use winapi::um::fileapi;
+use winapi::um::winnt::HRESULT;
 
 use canvas::{Canvas, Format, RasterizationOptions};
-use error::{FontLoadingError, GlyphLoadingError};
+use error::{BackendError, FontLoadingError, GlyphLoadingError};
+use features::{LayoutFeatures, Tag};
 use file_type::FileType;
 use handle::Handle;
 use hinting::HintingOptions;
+use language::LanguageSupport;
 use loader::{FallbackFont, FallbackResult, Loader};
 use metrics::Metrics;
+use outline::Outline;
 use properties::{Properties, Stretch, Style, Weight};
+use sfnt;
 
 const ERROR_BOUND: f32 = 0.0001;
 
@@ -86,16 +91,17 @@ impl dwrote::TextAnalysisSourceMethods for MyTextAnalysisSource {
 impl Font {
     fn from_dwrite_font_file(
         font_file: DWriteFontFile,
-        mut font_index: u32,
+        font_index: u32,
         font_data: Option<Arc<Vec<u8>>>,
     ) -> Result<Font, FontLoadingError> {
+        let mut fonts_to_skip = font_index;
         let collection_loader = CustomFontCollectionLoaderImpl::new(&[font_file.clone()]);
         let collection = DWriteFontCollection::from_loader(collection_loader);
         let families = collection.families_iter();
         for family in families {
             for family_font_index in 0..family.get_font_count() {
-                if font_index > 0 {
-                    font_index -= 1;
+                if fonts_to_skip > 0 {
+                    fonts_to_skip -= 1;
                     continue;
                 }
                 let dwrite_font = family.get_font(family_font_index);
@@ -107,7 +113,7 @@ impl Font {
                 });
             }
         }
-        Err(FontLoadingError::NoSuchFontInCollection)
+        Err(FontLoadingError::NoSuchFontInCollection { font_index })
     }
 
     /// Loads a font from raw font data (the contents of a `.ttf`/`.otf`/etc. file).
@@ -115,8 +121,8 @@ impl Font {
     /// If the data represents a collection (`.ttc`/`.otc`/etc.), `font_index` specifies the index
     /// of the font to load from it. If the data represents a single font, pass 0 for `font_index`.
     pub fn from_bytes(font_data: Arc<Vec<u8>>, font_index: u32) -> Result<Font, FontLoadingError> {
-        let font_file =
-            DWriteFontFile::new_from_data(font_data.clone()).ok_or(FontLoadingError::Parse)?;
+        let font_file = DWriteFontFile::new_from_data(font_data.clone())
+            .ok_or(FontLoadingError::Parse(None))?;
         Font::from_dwrite_font_file(font_file, font_index, Some(font_data))
     }
 
@@ -147,8 +153,11 @@ impl Font {
     /// font to load from it. If the file represents a single font, pass 0 for `font_index`.
     #[inline]
     pub fn from_path<P: AsRef<Path>>(path: P, font_index: u32) -> Result<Font, FontLoadingError> {
-        let font_file = DWriteFontFile::new_from_path(path).ok_or(FontLoadingError::Parse)?;
-        Font::from_dwrite_font_file(font_file, font_index, None)
+        let path = path.as_ref();
+        DWriteFontFile::new_from_path(path)
+            .ok_or(FontLoadingError::Parse(None))
+            .and_then(|font_file| Font::from_dwrite_font_file(font_file, font_index, None))
+            .map_err(|error| error.in_file(path))
     }
 
     /// Creates a font from a native API handle.
@@ -171,7 +180,7 @@ impl Font {
     /// type of font it is.
     pub fn analyze_bytes(font_data: Arc<Vec<u8>>) -> Result<FileType, FontLoadingError> {
         match DWriteFontFile::analyze_data(font_data) {
-            0 => Err(FontLoadingError::Parse),
+            0 => Err(FontLoadingError::Parse(None)),
             1 => Ok(FileType::Single),
             font_count => Ok(FileType::Collection(font_count)),
         }
@@ -235,7 +244,9 @@ impl Font {
         let dwrite_font = &self.dwrite_font;
         Properties {
             style: style_for_dwrite_style(dwrite_font.style()),
-            stretch: Stretch(Stretch::MAPPING[(dwrite_font.stretch() as usize) - 1]),
+            stretch: Stretch::MAPPING
+                .get((dwrite_font.stretch() as usize).wrapping_sub(1))
+                .map_or(Stretch::NORMAL, |&stretch| Stretch(stretch)),
             weight: Weight(dwrite_font.weight().to_u32() as f32),
         }
     }
@@ -291,6 +302,19 @@ impl Font {
         Ok(())
     }
 
+    /// Returns the vector path for a glyph as an owned `Outline`.
+    ///
+    /// This records the path that `outline()` would send to a path builder. If `hinting_mode` is
+    /// not None, grid-fitting is performed as requested.
+    #[inline]
+    pub fn glyph_outline(
+        &self,
+        glyph_id: u32,
+        hinting_mode: HintingOptions,
+    ) -> Result<Outline, GlyphLoadingError> {
+        <Self as Loader>::glyph_outline(self, glyph_id, hinting_mode)
+    }
+
     /// Returns the boundaries of a glyph in font units.
     pub fn typographic_bounds(&self, glyph_id: u32) -> Result<Rect<f32>, GlyphLoadingError> {
         let metrics = self
@@ -341,7 +365,7 @@ impl Font {
     pub fn metrics(&self) -> Metrics {
         let dwrite_font = &self.dwrite_font;
         let dwrite_metrics = dwrite_font.metrics();
-        Metrics {
+        let mut metrics = Metrics {
             units_per_em: dwrite_metrics.designUnitsPerEm as u32,
             ascent: dwrite_metrics.ascent as f32,
             descent: -(dwrite_metrics.descent as f32),
@@ -350,7 +374,46 @@ impl Font {
             x_height: dwrite_metrics.xHeight as f32,
             underline_position: dwrite_metrics.underlinePosition as f32,
             underline_thickness: dwrite_metrics.underlineThickness as f32,
-        }
+            ..Metrics::default()
+        };
+        sfnt::fill_in_table_metrics(&mut metrics, |table_tag| self.load_font_table(table_tag));
+        metrics
+    }
+
+    /// Returns the distance from the origin of the glyph with the given ID to the next in vertical
+    /// layout, in font units.
+    #[inline]
+    pub fn vertical_advance(&self, glyph_id: u32) -> Result<Vector2D<f32>, GlyphLoadingError> {
+        <Self as Loader>::vertical_advance(self, glyph_id)
+    }
+
+    /// Returns the position of the vertical origin of the glyph with the given ID, relative to
+    /// its horizontal origin, in font units.
+    #[inline]
+    pub fn vertical_origin(&self, glyph_id: u32) -> Result<Point2D<f32>, GlyphLoadingError> {
+        <Self as Loader>::vertical_origin(self, glyph_id)
+    }
+
+    /// Returns the raw contents of the OpenType table with the given tag, if the font has one.
+    pub fn load_font_table(&self, table_tag: u32) -> Option<Box<[u8]>> {
+        // DirectWrite wants tags in little-endian order.
+        self.dwrite_font_face
+            .get_font_table(table_tag.swap_bytes())
+            .map(|table| table.into_boxed_slice())
+    }
+
+    /// Returns the OpenType scripts, language systems, and features that this font declares in
+    /// its `GSUB` and `GPOS` tables.
+    #[inline]
+    pub fn layout_features(&self) -> LayoutFeatures {
+        <Self as Loader>::layout_features(self)
+    }
+
+    /// Returns the languages that this font declares support for in its `meta` and `OS/2`
+    /// tables.
+    #[inline]
+    pub fn language_support(&self) -> LanguageSupport {
+        <Self as Loader>::language_support(self)
     }
 
     /// Returns a handle to this font, if possible.
@@ -364,7 +427,7 @@ impl Font {
     /// Attempts to return the raw font data (contents of the font file).
     ///
     /// If this font is a member of a collection, this function returns the data for the entire
-    /// collection.
+    /// collection; `font_index()` says which member this font is.
     pub fn copy_font_data(&self) -> Option<Arc<Vec<u8>>> {
         let mut font_data = self.cached_data.lock().unwrap();
         if font_data.is_none() {
@@ -377,6 +440,31 @@ impl Font {
         (*font_data).clone()
     }
 
+    /// Returns the index of this font within the data that `copy_font_data()` returns.
+    ///
+    /// This is 0 unless the font is a member of a collection (`.ttc`/`.otc`/etc.)
+    #[inline]
+    pub fn font_index(&self) -> u32 {
+        self.dwrite_font_face.get_index()
+    }
+
+    /// Returns the coordinates of this font along its variation axes; e.g. `wght` or `wdth`.
+    ///
+    /// This loader always reports the default coordinates, as an empty list.
+    #[inline]
+    pub fn variation_coordinates(&self) -> Vec<(Tag, f32)> {
+        <Self as Loader>::variation_coordinates(self)
+    }
+
+    /// Attempts to return the data of a font file that contains only this font.
+    ///
+    /// If this font is a member of a collection, its tables are copied into a new, self-contained
+    /// font file. Otherwise, this returns the same data as `copy_font_data()`.
+    #[inline]
+    pub fn copy_standalone_font_data(&self) -> Option<Arc<Vec<u8>>> {
+        <Self as Loader>::copy_standalone_font_data(self)
+    }
+
     /// Returns the pixel boundaries that the glyph will take up when rendered using this loader's
     /// rasterizer at the given size and origin.
     #[inline]
@@ -403,7 +491,9 @@ impl Font {
             }
         };
 
-        let texture_bounds = dwrite_analysis.get_alpha_texture_bounds(texture_type)?;
+        let texture_bounds = dwrite_analysis
+            .get_alpha_texture_bounds(texture_type)
+            .map_err(|error| glyph_loading_error(glyph_id, error))?;
         let texture_width = texture_bounds.right - texture_bounds.left;
         let texture_height = texture_bounds.bottom - texture_bounds.top;
 
@@ -450,7 +540,9 @@ impl Font {
         };
 
         // TODO(pcwalton): Avoid a copy in some cases by writing directly to the canvas.
-        let texture_bounds = dwrite_analysis.get_alpha_texture_bounds(texture_type)?;
+        let texture_bounds = dwrite_analysis
+            .get_alpha_texture_bounds(texture_type)
+            .map_err(|error| glyph_loading_error(glyph_id, error))?;
         let texture_format = if texture_type == DWRITE_TEXTURE_ALIASED_1x1 {
             Format::A8
         } else {
@@ -463,8 +555,9 @@ impl Font {
         let texture_size = Size2D::new(texture_width, texture_height).to_u32();
         let texture_stride = texture_width as usize * texture_bytes_per_pixel;
 
-        let mut texture_bytes =
-            dwrite_analysis.create_alpha_texture(texture_type, texture_bounds)?;
+        let mut texture_bytes = dwrite_analysis
+            .create_alpha_texture(texture_type, texture_bounds)
+            .map_err(|error| glyph_loading_error(glyph_id, error))?;
         canvas.blit_from(
             &mut texture_bytes,
             &texture_size,
@@ -529,7 +622,7 @@ impl Font {
                 }
             };
 
-            Ok(DWriteGlyphRunAnalysis::create(
+            DWriteGlyphRunAnalysis::create(
                 &glyph_run,
                 1.0,
                 None,
@@ -537,7 +630,8 @@ impl Font {
                 DWRITE_MEASURING_MODE_NATURAL,
                 0.0,
                 0.0,
-            )?)
+            )
+            .map_err(|error| glyph_loading_error(glyph_id as u32, error))
         }
     }
 
@@ -547,10 +641,17 @@ impl Font {
     ///
     /// Note: on Windows 10, the result is a single font.
     fn get_fallbacks(&self, text: &str, locale: &str) -> FallbackResult<Font> {
-        let sys_fallback = DWriteFontFallback::get_system_fallback();
-        if sys_fallback.is_none() {
-            unimplemented!("Need Windows 7 method for font fallbacks")
-        }
+        // FIXME: Windows 7 has no system fallback object and needs a different approach; report
+        // no fallbacks there for now.
+        let sys_fallback = match DWriteFontFallback::get_system_fallback() {
+            Some(sys_fallback) => sys_fallback,
+            None => {
+                return FallbackResult {
+                    fonts: vec![],
+                    valid_len: 0,
+                }
+            }
+        };
         let text_utf16: Vec<u16> = text.encode_utf16().collect();
         let text_utf16_len = text_utf16.len() as u32;
         let number_subst =
@@ -564,7 +665,6 @@ impl Font {
             text_utf16,
             number_subst,
         );
-        let sys_fallback = sys_fallback.unwrap();
         // TODO: I think the MapCharacters can take a null pointer, update
         // dwrote to accept an optional collection. This appears to be what
         // blink does.
@@ -735,6 +835,11 @@ impl Loader for Font {
         self.metrics()
     }
 
+    #[inline]
+    fn load_font_table(&self, table_tag: u32) -> Option<Box<[u8]>> {
+        self.load_font_table(table_tag)
+    }
+
     #[inline]
     fn supports_hinting_options(
         &self,
@@ -749,6 +854,11 @@ impl Loader for Font {
         self.copy_font_data()
     }
 
+    #[inline]
+    fn font_index(&self) -> u32 {
+        self.font_index()
+    }
+
     #[inline]
     fn rasterize_glyph(
         &self,
@@ -848,6 +958,13 @@ impl OutlineBuilder for OutlineBuffer {
     }
 }
 
+fn glyph_loading_error(glyph_id: u32, error: HRESULT) -> GlyphLoadingError {
+    GlyphLoadingError::PlatformError {
+        glyph_id,
+        error: BackendError::HResult(error),
+    }
+}
+
 fn style_for_dwrite_style(style: DWriteFontStyle) -> Style {
     match style {
         DWriteFontStyle::Normal => Style::Normal,
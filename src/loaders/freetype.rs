@@ -19,18 +19,18 @@ use euclid::{Point2D, Rect, Size2D, Vector2D};
 use freetype::freetype::{FT_Byte, FT_Done_Face, FT_Error, FT_Face, FT_FACE_FLAG_FIXED_WIDTH};
 use freetype::freetype::{
     FT_Get_Char_Index, FT_Get_Name_Index, FT_Get_Postscript_Name, FT_Get_Sfnt_Table,
+    FT_Load_Sfnt_Table,
 };
 use freetype::freetype::{FT_Init_FreeType, FT_LOAD_DEFAULT, FT_LOAD_MONOCHROME};
 use freetype::freetype::{FT_Library, FT_Load_Glyph, FT_Long, FT_LOAD_NO_HINTING, FT_LOAD_RENDER};
 use freetype::freetype::{FT_New_Memory_Face, FT_Reference_Face, FT_STYLE_FLAG_ITALIC};
-use freetype::freetype::{FT_Set_Char_Size, FT_Set_Transform, FT_Sfnt_Tag, FT_UInt, FT_ULong};
-use freetype::freetype::{FT_UShort, FT_Vector};
+use freetype::freetype::{FT_Outline_Check, FT_ULong, FT_UShort, FT_Vector};
+use freetype::freetype::{FT_Set_Char_Size, FT_Set_Transform, FT_Sfnt_Tag, FT_Stream, FT_UInt};
 use freetype::tt_os2::TT_OS2;
 use lyon_path::builder::PathBuilder;
 use std::f32;
 use std::ffi::{CStr, CString};
 use std::fmt::{self, Debug, Formatter};
-use std::iter;
 use std::mem;
 use std::ops::Deref;
 use std::os::raw::{c_char, c_void};
@@ -38,13 +38,17 @@ use std::ptr;
 use std::slice;
 use std::sync::Arc;
 
-use error::{FontLoadingError, GlyphLoadingError};
+use error::{BackendError, FontLoadingError, GlyphLoadingError};
+use features::{LayoutFeatures, Tag};
 use file_type::FileType;
 use handle::Handle;
 use hinting::HintingOptions;
+use language::LanguageSupport;
 use loader::{FallbackResult, Loader};
 use metrics::Metrics;
+use outline::Outline;
 use properties::{Properties, Stretch, Style, Weight};
+use sfnt;
 
 #[cfg(not(target_arch = "wasm32"))]
 use memmap::Mmap;
@@ -76,6 +80,17 @@ const FT_PIXEL_MODE_GRAY: u8 = 2;
 const FT_PIXEL_MODE_LCD: u8 = 5;
 const FT_PIXEL_MODE_LCD_V: u8 = 6;
 
+const FT_ERR_UNKNOWN_FILE_FORMAT: FT_Error = 0x02;
+const FT_ERR_INVALID_ARGUMENT: FT_Error = 0x06;
+const FT_ERR_INVALID_GLYPH_INDEX: FT_Error = 0x10;
+const FT_ERR_INVALID_OUTLINE: FT_Error = 0x14;
+const FT_ERR_HORIZ_HEADER_MISSING: FT_Error = 0x8f;
+const FT_ERR_LOCATIONS_MISSING: FT_Error = 0x90;
+const FT_ERR_NAME_TABLE_MISSING: FT_Error = 0x91;
+const FT_ERR_CMAP_TABLE_MISSING: FT_Error = 0x92;
+const FT_ERR_HMTX_TABLE_MISSING: FT_Error = 0x93;
+const FT_ERR_POST_TABLE_MISSING: FT_Error = 0x94;
+
 const OS2_FS_SELECTION_OBLIQUE: u16 = 1 << 9;
 
 // Not in our FreeType bindings, so we define these ourselves.
@@ -91,8 +106,11 @@ const BDF_PROPERTY_TYPE_CARDINAL: BDF_PropertyType = 3;
 thread_local! {
     static FREETYPE_LIBRARY: FT_Library = {
         unsafe {
+            // If initialization fails, every subsequent call reports an invalid library handle.
             let mut library = ptr::null_mut();
-            assert_eq!(FT_Init_FreeType(&mut library), 0);
+            if FT_Init_FreeType(&mut library) != 0 {
+                library = ptr::null_mut();
+            }
             library
         }
     };
@@ -130,15 +148,15 @@ impl Font {
     pub fn from_bytes(font_data: Arc<Vec<u8>>, font_index: u32) -> Result<Font, FontLoadingError> {
         FREETYPE_LIBRARY.with(|freetype_library| unsafe {
             let mut freetype_face = ptr::null_mut();
-            if FT_New_Memory_Face(
+            let error = FT_New_Memory_Face(
                 *freetype_library,
                 (*font_data).as_ptr(),
                 font_data.len() as FT_Long,
                 font_index as FT_Long,
                 &mut freetype_face,
-            ) != 0
-            {
-                return Err(FontLoadingError::Parse);
+            );
+            if error != 0 {
+                return Err(font_loading_error(error, font_index));
             }
 
             setup_freetype_face(freetype_face);
@@ -160,15 +178,15 @@ impl Font {
             let mmap = Mmap::map(&file)?;
             FREETYPE_LIBRARY.with(|freetype_library| {
                 let mut freetype_face = ptr::null_mut();
-                if FT_New_Memory_Face(
+                let error = FT_New_Memory_Face(
                     *freetype_library,
                     (*mmap).as_ptr(),
                     mmap.len() as FT_Long,
                     font_index as FT_Long,
                     &mut freetype_face,
-                ) != 0
-                {
-                    return Err(FontLoadingError::Parse);
+                );
+                if error != 0 {
+                    return Err(font_loading_error(error, font_index));
                 }
 
                 setup_freetype_face(freetype_face);
@@ -196,26 +214,26 @@ impl Font {
     }
 
     /// Creates a font from a native API handle.
+    ///
+    /// The font data is copied out of the face and reopened, because the native face does not
+    /// necessarily hold a strong reference to the memory backing it. If the data can't be copied
+    /// or reopened, the font shares the native face instead and has no data to expose; in that
+    /// case the memory backing the face must outlive the font.
     pub unsafe fn from_native_font(freetype_face: NativeFont) -> Font {
-        // We make an in-memory copy of the underlying font data. This is because the native font
-        // does not necessarily hold a strong reference to the memory backing it.
-        const CHUNK_SIZE: usize = 4096;
-        let mut font_data = vec![];
-        loop {
-            font_data.extend(iter::repeat(0).take(CHUNK_SIZE));
-            let freetype_stream = (*freetype_face).stream;
-            let n_read = ((*freetype_stream).read.unwrap())(
-                freetype_stream,
-                font_data.len() as FT_ULong,
-                font_data.as_mut_ptr(),
-                CHUNK_SIZE as FT_ULong,
-            );
-            if n_read < CHUNK_SIZE as FT_ULong {
-                break;
+        // The upper bits hold the index of the named instance in variable fonts.
+        let font_index = ((*freetype_face).face_index & 0xffff) as u32;
+        if let Some(font_data) = copy_stream_data((*freetype_face).stream) {
+            if let Ok(font) = Font::from_bytes(Arc::new(font_data), font_index) {
+                return font;
             }
         }
 
-        Font::from_bytes(Arc::new(font_data), (*freetype_face).face_index as u32).unwrap()
+        warn!("Could not copy the data of a native FreeType face!");
+        FT_Reference_Face(freetype_face);
+        Font {
+            freetype_face,
+            font_data: FontData::Unavailable,
+        }
     }
 
     /// Loads the font pointed to by a handle.
@@ -229,15 +247,15 @@ impl Font {
     pub fn analyze_bytes(font_data: Arc<Vec<u8>>) -> Result<FileType, FontLoadingError> {
         FREETYPE_LIBRARY.with(|freetype_library| unsafe {
             let mut freetype_face = ptr::null_mut();
-            if FT_New_Memory_Face(
+            let error = FT_New_Memory_Face(
                 *freetype_library,
                 (*font_data).as_ptr(),
                 font_data.len() as FT_Long,
                 0,
                 &mut freetype_face,
-            ) != 0
-            {
-                return Err(FontLoadingError::Parse);
+            );
+            if error != 0 {
+                return Err(font_loading_error(error, 0));
             }
 
             let font_type = match (*freetype_face).num_faces {
@@ -255,15 +273,15 @@ impl Font {
         FREETYPE_LIBRARY.with(|freetype_library| unsafe {
             let mmap = Mmap::map(&file)?;
             let mut freetype_face = ptr::null_mut();
-            if FT_New_Memory_Face(
+            let error = FT_New_Memory_Face(
                 *freetype_library,
                 (*mmap).as_ptr(),
                 mmap.len() as FT_Long,
                 0,
                 &mut freetype_face,
-            ) != 0
-            {
-                return Err(FontLoadingError::Parse);
+            );
+            if error != 0 {
+                return Err(font_loading_error(error, 0));
             }
 
             let font_type = match (*freetype_face).num_faces {
@@ -291,7 +309,8 @@ impl Font {
     /// Therefore, it is the caller's responsibility to free it with `FT_Done_Face`.
     pub fn native_font(&self) -> NativeFont {
         unsafe {
-            assert_eq!(FT_Reference_Face(self.freetype_face), 0);
+            // This only fails for a null face, which a `Font` never holds.
+            FT_Reference_Face(self.freetype_face);
             self.freetype_face
         }
     }
@@ -301,13 +320,18 @@ impl Font {
         unsafe {
             let postscript_name = FT_Get_Postscript_Name(self.freetype_face);
             if !postscript_name.is_null() {
-                return Some(CStr::from_ptr(postscript_name).to_str().unwrap().to_owned());
+                return CStr::from_ptr(postscript_name)
+                    .to_str()
+                    .ok()
+                    .map(|postscript_name| postscript_name.to_owned());
             }
 
             let font_format = FT_Get_Font_Format(self.freetype_face);
-            assert!(!font_format.is_null());
-            let font_format = CStr::from_ptr(font_format).to_str().unwrap();
-            if font_format != "BDF" && font_format != "PCF" {
+            if font_format.is_null() {
+                return None;
+            }
+            let font_format = CStr::from_ptr(font_format).to_bytes();
+            if font_format != b"BDF" && font_format != b"PCF" {
                 return None;
             }
 
@@ -323,7 +347,10 @@ impl Font {
             if property.property_type != BDF_PROPERTY_TYPE_ATOM {
                 return None;
             }
-            let dec_device_fontnames = CStr::from_ptr(property.value).to_str().unwrap();
+            let dec_device_fontnames = match CStr::from_ptr(property.value).to_str() {
+                Ok(dec_device_fontnames) => dec_device_fontnames,
+                Err(_) => return None,
+            };
             if !dec_device_fontnames.starts_with("PS=") {
                 return None;
             }
@@ -338,12 +365,16 @@ impl Font {
     }
 
     /// Returns the name of the font family.
+    ///
+    /// Invalid UTF-8 sequences are replaced with U+FFFD, and fonts without a family name yield an
+    /// empty string.
     pub fn family_name(&self) -> String {
         unsafe {
-            CStr::from_ptr((*self.freetype_face).family_name)
-                .to_str()
-                .unwrap()
-                .to_owned()
+            let family_name = (*self.freetype_face).family_name;
+            if family_name.is_null() {
+                return String::new();
+            }
+            CStr::from_ptr(family_name).to_string_lossy().into_owned()
         }
     }
 
@@ -367,10 +398,11 @@ impl Font {
                 }
                 _ => Style::Normal,
             };
+            // Width classes run from 1 to 9; anything else is treated as normal.
             let stretch = match os2_table {
-                Some(os2_table) if (*os2_table).usWidthClass > 0 => {
-                    Stretch(Stretch::MAPPING[((*os2_table).usWidthClass as usize) - 1])
-                }
+                Some(os2_table) if (*os2_table).usWidthClass > 0 => Stretch::MAPPING
+                    .get(((*os2_table).usWidthClass as usize) - 1)
+                    .map_or(Stretch::NORMAL, |&stretch| Stretch(stretch)),
                 _ => Stretch::NORMAL,
             };
             let weight = match os2_table {
@@ -446,17 +478,42 @@ impl Font {
             let units_per_em = (*self.freetype_face).units_per_EM;
             let grid_fitting_size = hinting.grid_fitting_size();
             if let Some(size) = grid_fitting_size {
-                assert_eq!(
-                    FT_Set_Char_Size(self.freetype_face, f32_to_ft_fixed_26_6(size), 0, 0, 0),
-                    0
-                );
+                let error =
+                    FT_Set_Char_Size(self.freetype_face, f32_to_ft_fixed_26_6(size), 0, 0, 0);
+                if error != 0 {
+                    return Err(GlyphLoadingError::PlatformError {
+                        glyph_id,
+                        error: BackendError::FreeType(error),
+                    });
+                }
             }
 
-            if FT_Load_Glyph(self.freetype_face, glyph_id, load_flags as i32) != 0 {
-                return Err(GlyphLoadingError::NoSuchGlyph);
+            let error = FT_Load_Glyph(self.freetype_face, glyph_id, load_flags as i32);
+            if error != 0 {
+                if grid_fitting_size.is_some() {
+                    reset_freetype_face_char_size(self.freetype_face)
+                }
+                return Err(self.glyph_loading_error(glyph_id, error));
             }
 
-            let outline = &(*(*self.freetype_face).glyph).outline;
+            let outline = &mut (*(*self.freetype_face).glyph).outline;
+            if (*outline).n_contours <= 0 || (*outline).n_points <= 0 {
+                // Empty glyphs (e.g. spaces) have null contour and point arrays.
+                if grid_fitting_size.is_some() {
+                    reset_freetype_face_char_size((*self).freetype_face)
+                }
+                return Ok(());
+            }
+            // Make sure contour end points are in order and in range before we walk them.
+            if FT_Outline_Check(outline) != 0 {
+                if grid_fitting_size.is_some() {
+                    reset_freetype_face_char_size(self.freetype_face)
+                }
+                return Err(GlyphLoadingError::PlatformError {
+                    glyph_id,
+                    error: BackendError::FreeType(FT_ERR_INVALID_OUTLINE),
+                });
+            }
             let contours =
                 slice::from_raw_parts((*outline).contours, (*outline).n_contours as usize);
             let point_positions =
@@ -531,14 +588,22 @@ impl Font {
                         if (tag0 & FT_POINT_TAG_CUBIC_CONTROL) != 0 {
                             // FIXME(pcwalton): Can we have implied on-curve points for cubic
                             // control points too?
-                            let (point2, _) = get_point(
-                                &mut current_point_index,
-                                point_positions,
-                                point_tags,
-                                last_point_index_in_contour,
-                                grid_fitting_size,
-                                units_per_em,
-                            );
+                            //
+                            // A cubic curve that ends the contour may omit its endpoint, in which
+                            // case it implicitly ends at the first point (as in CFF fonts).
+                            let point2 = if current_point_index > last_point_index_in_contour {
+                                first_point
+                            } else {
+                                get_point(
+                                    &mut current_point_index,
+                                    point_positions,
+                                    point_tags,
+                                    last_point_index_in_contour,
+                                    grid_fitting_size,
+                                    units_per_em,
+                                )
+                                .0
+                            };
                             path_builder.cubic_bezier_to(point0, point1, point2);
                             break;
                         }
@@ -573,7 +638,7 @@ impl Font {
             grid_fitting_size: Option<f32>,
             units_per_em: u16,
         ) -> (Point2D<f32>, c_char) {
-            assert!(*current_point_index <= last_point_index_in_contour);
+            debug_assert!(*current_point_index <= last_point_index_in_contour);
             let point_position = point_positions[*current_point_index];
             let point_tag = point_tags[*current_point_index];
             *current_point_index += 1;
@@ -590,16 +655,29 @@ impl Font {
         }
     }
 
+    /// Returns the vector path for a glyph as an owned `Outline`.
+    ///
+    /// This records the path that `outline()` would send to a path builder. If `hinting_mode` is
+    /// not None, grid-fitting is performed as requested.
+    #[inline]
+    pub fn glyph_outline(
+        &self,
+        glyph_id: u32,
+        hinting_mode: HintingOptions,
+    ) -> Result<Outline, GlyphLoadingError> {
+        <Self as Loader>::glyph_outline(self, glyph_id, hinting_mode)
+    }
+
     /// Returns the boundaries of a glyph in font units.
     pub fn typographic_bounds(&self, glyph_id: u32) -> Result<Rect<f32>, GlyphLoadingError> {
         unsafe {
-            if FT_Load_Glyph(
+            let error = FT_Load_Glyph(
                 self.freetype_face,
                 glyph_id,
                 (FT_LOAD_DEFAULT | FT_LOAD_NO_HINTING) as i32,
-            ) != 0
-            {
-                return Err(GlyphLoadingError::NoSuchGlyph);
+            );
+            if error != 0 {
+                return Err(self.glyph_loading_error(glyph_id, error));
             }
 
             let metrics = &(*(*self.freetype_face).glyph).metrics;
@@ -620,13 +698,13 @@ impl Font {
     /// units.
     pub fn advance(&self, glyph_id: u32) -> Result<Vector2D<f32>, GlyphLoadingError> {
         unsafe {
-            if FT_Load_Glyph(
+            let error = FT_Load_Glyph(
                 self.freetype_face,
                 glyph_id,
                 (FT_LOAD_DEFAULT | FT_LOAD_NO_HINTING) as i32,
-            ) != 0
-            {
-                return Err(GlyphLoadingError::NoSuchGlyph);
+            );
+            if error != 0 {
+                return Err(self.glyph_loading_error(glyph_id, error));
             }
 
             let advance = (*(*self.freetype_face).glyph).advance;
@@ -639,16 +717,16 @@ impl Font {
 
     /// Returns the amount that the given glyph should be displaced from the origin.
     ///
-    /// FIXME(pcwalton): This always returns zero on FreeType.
+    /// In horizontal layout, FreeType never displaces glyphs, so this always returns zero. For
+    /// the origin used in vertical layout, see `vertical_origin()`.
     pub fn origin(&self, _: u32) -> Result<Point2D<f32>, GlyphLoadingError> {
-        warn!("unimplemented");
         Ok(Point2D::zero())
     }
 
     /// Retrieves various metrics that apply to the entire font.
     pub fn metrics(&self) -> Metrics {
         let os2_table = self.get_os2_table();
-        unsafe {
+        let mut metrics = unsafe {
             let ascender = (*self.freetype_face).ascender;
             let descender = (*self.freetype_face).descender;
             let underline_position = (*self.freetype_face).underline_position;
@@ -657,8 +735,10 @@ impl Font {
                 units_per_em: (*self.freetype_face).units_per_EM as u32,
                 ascent: ascender as f32,
                 descent: descender as f32,
-                line_gap: ((*self.freetype_face).height + descender - ascender) as f32,
-                underline_position: (underline_position + underline_thickness / 2) as f32,
+                line_gap: ((*self.freetype_face).height as i32 + descender as i32 - ascender as i32)
+                    as f32,
+                underline_position: (underline_position as i32 + underline_thickness as i32 / 2)
+                    as f32,
                 underline_thickness: underline_thickness as f32,
                 cap_height: os2_table
                     .map(|table| (*table).sCapHeight as f32)
@@ -666,10 +746,82 @@ impl Font {
                 x_height: os2_table
                     .map(|table| (*table).sxHeight as f32)
                     .unwrap_or(0.0),
+                ..Metrics::default()
+            }
+        };
+        sfnt::fill_in_table_metrics(&mut metrics, |table_tag| self.load_font_table(table_tag));
+        metrics
+    }
+
+    /// Returns the distance from the origin of the glyph with the given ID to the next in vertical
+    /// layout, in font units.
+    #[inline]
+    pub fn vertical_advance(&self, glyph_id: u32) -> Result<Vector2D<f32>, GlyphLoadingError> {
+        <Self as Loader>::vertical_advance(self, glyph_id)
+    }
+
+    /// Returns the position of the vertical origin of the glyph with the given ID, relative to
+    /// its horizontal origin, in font units.
+    #[inline]
+    pub fn vertical_origin(&self, glyph_id: u32) -> Result<Point2D<f32>, GlyphLoadingError> {
+        <Self as Loader>::vertical_origin(self, glyph_id)
+    }
+
+    /// Returns the raw contents of the OpenType table with the given tag, if the font has one.
+    pub fn load_font_table(&self, table_tag: u32) -> Option<Box<[u8]>> {
+        unsafe {
+            let mut len = self.font_table_len(table_tag)?;
+            let mut buf = vec![0; len as usize];
+            if 0 != FT_Load_Sfnt_Table(
+                self.freetype_face,
+                table_tag as FT_ULong,
+                0,
+                buf.as_mut_ptr(),
+                &mut len,
+            ) {
+                return None;
+            }
+            Some(buf.into_boxed_slice())
+        }
+    }
+
+    /// Returns true if the font has an OpenType table with the given tag.
+    #[inline]
+    pub fn has_font_table(&self, table_tag: u32) -> bool {
+        self.font_table_len(table_tag).is_some()
+    }
+
+    // Asks FreeType for the length of a table without loading it, by passing a null buffer.
+    fn font_table_len(&self, table_tag: u32) -> Option<FT_ULong> {
+        unsafe {
+            let mut len = 0;
+            if 0 != FT_Load_Sfnt_Table(
+                self.freetype_face,
+                table_tag as FT_ULong,
+                0,
+                ptr::null_mut(),
+                &mut len,
+            ) {
+                return None;
             }
+            Some(len)
         }
     }
 
+    /// Returns the OpenType scripts, language systems, and features that this font declares in
+    /// its `GSUB` and `GPOS` tables.
+    #[inline]
+    pub fn layout_features(&self) -> LayoutFeatures {
+        <Self as Loader>::layout_features(self)
+    }
+
+    /// Returns the languages that this font declares support for in its `meta` and `OS/2`
+    /// tables.
+    #[inline]
+    pub fn language_support(&self) -> LanguageSupport {
+        <Self as Loader>::language_support(self)
+    }
+
     /// Returns true if and only if the font loader can perform hinting in the requested way.
     ///
     /// Some APIs support only rasterizing glyphs with hinting, not retriving hinted outlines. If
@@ -714,10 +866,9 @@ impl Font {
             let sfnt_name_count = FT_Get_Sfnt_Name_Count(self.freetype_face);
             let mut sfnt_name = mem::zeroed();
             for sfnt_name_index in 0..sfnt_name_count {
-                assert_eq!(
-                    FT_Get_Sfnt_Name(self.freetype_face, sfnt_name_index, &mut sfnt_name),
-                    0
-                );
+                if FT_Get_Sfnt_Name(self.freetype_face, sfnt_name_index, &mut sfnt_name) != 0 {
+                    continue;
+                }
                 if sfnt_name.name_id != sfnt_id {
                     continue;
                 }
@@ -727,8 +878,8 @@ impl Font {
                         let mut sfnt_name_bytes =
                             slice::from_raw_parts(sfnt_name.string, sfnt_name.string_len as usize);
                         let mut sfnt_name_string = Vec::with_capacity(sfnt_name_bytes.len() / 2);
-                        while !sfnt_name_bytes.is_empty() {
-                            sfnt_name_string.push(sfnt_name_bytes.read_u16::<BigEndian>().unwrap())
+                        while let Ok(code_unit) = sfnt_name_bytes.read_u16::<BigEndian>() {
+                            sfnt_name_string.push(code_unit)
                         }
                         if let Ok(result) = String::from_utf16(&sfnt_name_string) {
                             return Some(result);
@@ -807,24 +958,31 @@ impl Font {
             };
             FT_Set_Transform(self.freetype_face, ptr::null_mut(), &mut delta);
 
-            assert_eq!(
-                FT_Set_Char_Size(
-                    self.freetype_face,
-                    f32_to_ft_fixed_26_6(point_size),
-                    0,
-                    0,
-                    0
-                ),
-                0
+            let error = FT_Set_Char_Size(
+                self.freetype_face,
+                f32_to_ft_fixed_26_6(point_size),
+                0,
+                0,
+                0,
             );
+            if error != 0 {
+                FT_Set_Transform(self.freetype_face, ptr::null_mut(), ptr::null_mut());
+                return Err(GlyphLoadingError::PlatformError {
+                    glyph_id,
+                    error: BackendError::FreeType(error),
+                });
+            }
 
             let mut load_flags = FT_LOAD_DEFAULT | FT_LOAD_RENDER;
             load_flags |= self.hinting_and_rasterization_options_to_load_flags(
                 hinting_options,
                 rasterization_options,
             );
-            if FT_Load_Glyph(self.freetype_face, glyph_id, load_flags as i32) != 0 {
-                return Err(GlyphLoadingError::NoSuchGlyph);
+            let error = FT_Load_Glyph(self.freetype_face, glyph_id, load_flags as i32);
+            if error != 0 {
+                FT_Set_Transform(self.freetype_face, ptr::null_mut(), ptr::null_mut());
+                reset_freetype_face_char_size(self.freetype_face);
+                return Err(self.glyph_loading_error(glyph_id, error));
             }
 
             // TODO(pcwalton): Use the FreeType "direct" API to save a copy here. Note that we will
@@ -837,25 +995,44 @@ impl Font {
             let bitmap_size = Size2D::new(bitmap_width, bitmap_height);
             let bitmap_buffer = (*bitmap).buffer as *const i8 as *const u8;
             let bitmap_length = bitmap_stride * bitmap_height as usize;
-            let buffer = slice::from_raw_parts(bitmap_buffer, bitmap_length);
+            // Empty glyphs have no bitmap buffer at all.
+            let buffer = if bitmap_buffer.is_null() {
+                &[]
+            } else {
+                slice::from_raw_parts(bitmap_buffer, bitmap_length)
+            };
 
-            // FIXME(pcwalton): This function should return a Result instead.
-            match (*bitmap).pixel_mode {
+            let result = match (*bitmap).pixel_mode {
                 FT_PIXEL_MODE_GRAY => {
                     canvas.blit_from(buffer, &bitmap_size, bitmap_stride, Format::A8);
+                    Ok(())
                 }
                 FT_PIXEL_MODE_LCD | FT_PIXEL_MODE_LCD_V => {
                     canvas.blit_from(buffer, &bitmap_size, bitmap_stride, Format::Rgb24);
+                    Ok(())
                 }
                 FT_PIXEL_MODE_MONO => {
                     canvas.blit_from_bitmap_1bpp(buffer, &bitmap_size, bitmap_stride);
+                    Ok(())
                 }
-                _ => panic!("Unexpected FreeType pixel mode!"),
-            }
+                _ => Err(GlyphLoadingError::UnsupportedPixelFormat),
+            };
 
             FT_Set_Transform(self.freetype_face, ptr::null_mut(), ptr::null_mut());
             reset_freetype_face_char_size(self.freetype_face);
-            Ok(())
+            result
+        }
+    }
+
+    fn glyph_loading_error(&self, glyph_id: u32, error: FT_Error) -> GlyphLoadingError {
+        // FreeType reports out-of-range glyph IDs as invalid arguments.
+        if error == FT_ERR_INVALID_GLYPH_INDEX || glyph_id >= self.glyph_count() {
+            GlyphLoadingError::NoSuchGlyph(glyph_id)
+        } else {
+            GlyphLoadingError::PlatformError {
+                glyph_id,
+                error: BackendError::FreeType(error),
+            }
         }
     }
 
@@ -891,15 +1068,49 @@ impl Font {
     /// Attempts to return the raw font data (contents of the font file).
     ///
     /// If this font is a member of a collection, this function returns the data for the entire
-    /// collection.
+    /// collection; `font_index()` says which member this font is.
     pub fn copy_font_data(&self) -> Option<Arc<Vec<u8>>> {
         match self.font_data {
+            FontData::Unavailable => None,
             #[cfg(not(target_arch = "wasm32"))]
             FontData::File(ref file) => Some(Arc::new((*file).to_vec())),
             FontData::Memory(ref memory) => Some((*memory).clone()),
         }
     }
 
+    /// Returns the index of this font within the data that `copy_font_data()` returns.
+    ///
+    /// This is 0 unless the font is a member of a collection (`.ttc`/`.otc`/etc.)
+    #[inline]
+    pub fn font_index(&self) -> u32 {
+        // The upper bits hold the index of the named instance in variable fonts.
+        unsafe { ((*self.freetype_face).face_index & 0xffff) as u32 }
+    }
+
+    /// Returns the coordinates of this font along its variation axes; e.g. `wght` or `wdth`.
+    ///
+    /// These are the coordinates of the named instance that the upper 16 bits of the index this
+    /// font was loaded with select, if any. Otherwise, this returns an empty list, meaning the
+    /// default coordinates.
+    pub fn variation_coordinates(&self) -> Vec<(Tag, f32)> {
+        let named_instance = unsafe { ((*self.freetype_face).face_index >> 16) as u16 };
+        if named_instance == 0 {
+            return vec![];
+        }
+        self.load_font_table(sfnt::FVAR)
+            .and_then(|fvar| sfnt::named_instance_coordinates(&fvar, named_instance - 1))
+            .unwrap_or_default()
+    }
+
+    /// Attempts to return the data of a font file that contains only this font.
+    ///
+    /// If this font is a member of a collection, its tables are copied into a new, self-contained
+    /// font file. Otherwise, this returns the same data as `copy_font_data()`.
+    #[inline]
+    pub fn copy_standalone_font_data(&self) -> Option<Arc<Vec<u8>>> {
+        <Self as Loader>::copy_standalone_font_data(self)
+    }
+
     /// Get font fallback results for the given text and locale.
     ///
     /// Note: this is currently just a stub implementation, a proper implementation
@@ -917,7 +1128,7 @@ impl Font {
 impl Clone for Font {
     fn clone(&self) -> Font {
         unsafe {
-            assert_eq!(FT_Reference_Face(self.freetype_face), 0);
+            FT_Reference_Face(self.freetype_face);
             Font {
                 freetype_face: self.freetype_face,
                 font_data: self.font_data.clone(),
@@ -929,8 +1140,8 @@ impl Clone for Font {
 impl Drop for Font {
     fn drop(&mut self) {
         unsafe {
-            if !self.freetype_face.is_null() {
-                assert_eq!(FT_Done_Face(self.freetype_face), 0);
+            if !self.freetype_face.is_null() && FT_Done_Face(self.freetype_face) != 0 {
+                warn!("Could not free a FreeType face!");
             }
         }
     }
@@ -1049,11 +1260,31 @@ impl Loader for Font {
         self.metrics()
     }
 
+    #[inline]
+    fn load_font_table(&self, table_tag: u32) -> Option<Box<[u8]>> {
+        self.load_font_table(table_tag)
+    }
+
+    #[inline]
+    fn has_font_table(&self, table_tag: u32) -> bool {
+        self.has_font_table(table_tag)
+    }
+
     #[inline]
     fn copy_font_data(&self) -> Option<Arc<Vec<u8>>> {
         self.copy_font_data()
     }
 
+    #[inline]
+    fn font_index(&self) -> u32 {
+        self.font_index()
+    }
+
+    #[inline]
+    fn variation_coordinates(&self) -> Vec<(Tag, f32)> {
+        self.variation_coordinates()
+    }
+
     #[inline]
     fn supports_hinting_options(
         &self,
@@ -1091,6 +1322,7 @@ impl Loader for Font {
 
 #[derive(Clone)]
 enum FontData {
+    Unavailable,
     Memory(Arc<Vec<u8>>),
     #[cfg(not(target_arch = "wasm32"))]
     File(Arc<Mmap>),
@@ -1100,6 +1332,8 @@ impl Deref for FontData {
     type Target = [u8];
     fn deref(&self) -> &[u8] {
         match *self {
+            // Fonts created from native handles may have no data to expose.
+            FontData::Unavailable => &[],
             #[cfg(not(target_arch = "wasm32"))]
             FontData::File(ref mmap) => &***mmap,
             FontData::Memory(ref data) => &***data,
@@ -1107,6 +1341,58 @@ impl Deref for FontData {
     }
 }
 
+// Reads the whole contents of a FreeType stream, which is either backed by memory or read through
+// a callback.
+unsafe fn copy_stream_data(stream: FT_Stream) -> Option<Vec<u8>> {
+    if stream.is_null() {
+        return None;
+    }
+    let size = (*stream).size as usize;
+    match (*stream).read {
+        None if (*stream).base.is_null() => None,
+        None => Some(slice::from_raw_parts((*stream).base, size).to_vec()),
+        Some(read) => {
+            let mut font_data = vec![0; size];
+            let bytes_read = read(stream, 0, font_data.as_mut_ptr(), size as FT_ULong) as usize;
+            if bytes_read == size {
+                Some(font_data)
+            } else {
+                None
+            }
+        }
+    }
+}
+
+fn font_loading_error(error: FT_Error, font_index: u32) -> FontLoadingError {
+    match error {
+        FT_ERR_UNKNOWN_FILE_FORMAT => FontLoadingError::UnknownFormat,
+        // FreeType reports out-of-range face indices as invalid arguments.
+        FT_ERR_INVALID_ARGUMENT if font_index > 0 => {
+            FontLoadingError::NoSuchFontInCollection { font_index }
+        }
+        _ => match missing_table(error) {
+            Some(table) => FontLoadingError::MalformedTable {
+                table: Tag(table),
+                error: Some(BackendError::FreeType(error)),
+            },
+            None => FontLoadingError::Parse(Some(BackendError::FreeType(error))),
+        },
+    }
+}
+
+// Returns the table that a FreeType error code says is missing, if it names one.
+fn missing_table(error: FT_Error) -> Option<u32> {
+    match error {
+        FT_ERR_HORIZ_HEADER_MISSING => Some(sfnt::HHEA),
+        FT_ERR_LOCATIONS_MISSING => Some(sfnt::LOCA),
+        FT_ERR_NAME_TABLE_MISSING => Some(sfnt::NAME),
+        FT_ERR_CMAP_TABLE_MISSING => Some(sfnt::CMAP),
+        FT_ERR_HMTX_TABLE_MISSING => Some(sfnt::HMTX),
+        FT_ERR_POST_TABLE_MISSING => Some(sfnt::POST),
+        _ => None,
+    }
+}
+
 unsafe fn setup_freetype_face(face: FT_Face) {
     reset_freetype_face_char_size(face);
 }
@@ -1114,11 +1400,9 @@ unsafe fn setup_freetype_face(face: FT_Face) {
 unsafe fn reset_freetype_face_char_size(face: FT_Face) {
     // Apple Color Emoji has 0 units per em. Whee!
     let units_per_em = (*face).units_per_EM as i64;
+    // Bitmap-only fonts can reject this size; they keep whatever size they had before.
     if units_per_em > 0 {
-        assert_eq!(
-            FT_Set_Char_Size(face, ((*face).units_per_EM as FT_Long) << 6, 0, 0, 0),
-            0
-        );
+        FT_Set_Char_Size(face, ((*face).units_per_EM as FT_Long) << 6, 0, 0, 0);
     }
 }
 
@@ -1160,14 +1444,149 @@ extern "C" {
 
 #[cfg(test)]
 mod test {
+    use descriptor::FontDescriptor;
+    use error::FontLoadingError;
+    use features::Tag;
+    use freetype::freetype::FT_Done_Face;
+    use handle::Handle;
     use loaders::freetype::Font;
+    use sfnt;
+    use std::sync::Arc;
+    use test::{rewrite_font_tables, rewrite_name_table};
 
     static PCF_FONT_PATH: &'static str = "resources/tests/times-roman-pcf/timR12.pcf";
     static PCF_FONT_POSTSCRIPT_NAME: &'static str = "Times-Roman";
+    static COLLECTION_FONT_PATH: &'static str = "resources/tests/eb-garamond/EBGaramond12.otc";
+    static TRUETYPE_FONT_PATH: &'static str = "resources/tests/inconsolata/Inconsolata-Regular.ttf";
 
     #[test]
     fn get_pcf_postscript_name() {
         let font = Font::from_path(PCF_FONT_PATH, 0).unwrap();
         assert_eq!(font.postscript_name().unwrap(), PCF_FONT_POSTSCRIPT_NAME);
     }
+
+    #[test]
+    fn load_font_from_native_font() {
+        let font = Font::from_path(COLLECTION_FONT_PATH, 1).unwrap();
+        unsafe {
+            let native_font = font.native_font();
+            let native_copy = Font::from_native_font(native_font);
+            assert_eq!(FT_Done_Face(native_font), 0);
+            assert_eq!(native_copy.postscript_name(), font.postscript_name());
+            assert_eq!(native_copy.font_index(), 1);
+            assert_eq!(native_copy.copy_font_data(), font.copy_font_data());
+        }
+    }
+
+    #[test]
+    fn load_font_without_required_table() {
+        let data = rewrite_font_tables(TRUETYPE_FONT_PATH, vec![], |tag, table| {
+            if tag == sfnt::HMTX {
+                table.clear()
+            }
+        });
+        match Font::from_bytes(Arc::new(data), 0) {
+            Err(FontLoadingError::MalformedTable { table, error }) => {
+                assert_eq!(table, Tag(sfnt::HMTX));
+                assert!(error.is_some());
+            }
+            result => panic!("Expected a missing `hmtx` table: {:?}", result.err()),
+        }
+    }
+
+    #[test]
+    fn describe_font_with_family_names_like_loader() {
+        let cases: &[(&[(u16, &str)], bool, &str)] = &[
+            (
+                &[(sfnt::NAME_ID_TYPOGRAPHIC_FAMILY, "Typographic")],
+                false,
+                "Typographic",
+            ),
+            (
+                &[
+                    (sfnt::NAME_ID_TYPOGRAPHIC_FAMILY, "Typographic"),
+                    (sfnt::NAME_ID_WWS_FAMILY, "WWS"),
+                ],
+                false,
+                "WWS",
+            ),
+            (
+                &[
+                    (sfnt::NAME_ID_TYPOGRAPHIC_FAMILY, "Typographic"),
+                    (sfnt::NAME_ID_WWS_FAMILY, "WWS"),
+                ],
+                true,
+                "Typographic",
+            ),
+        ];
+        for &(names, wws_only, family_name) in cases {
+            let handle = Handle::from_memory(Arc::new(font_with_names(names, wws_only)), 0);
+            let font = Font::from_handle(&handle).unwrap();
+            let descriptor = FontDescriptor::from_handle(&handle).unwrap();
+            assert_eq!(font.family_name(), family_name);
+            assert_eq!(descriptor.family_name, family_name);
+        }
+    }
+
+    #[test]
+    fn get_named_instance_variation_coordinates() {
+        let fvar = fvar(&[b"wght", b"wdth"], &[&[300.0, 100.0], &[700.0, 87.5]]);
+        let data = rewrite_font_tables(TRUETYPE_FONT_PATH, vec![(sfnt::FVAR, fvar)], |_, _| {});
+        let data = Arc::new(data);
+
+        let font = Font::from_bytes(data.clone(), 0).unwrap();
+        assert_eq!(font.variation_coordinates(), vec![]);
+        let font = Font::from_bytes(data.clone(), 2 << 16).unwrap();
+        assert_eq!(font.font_index(), 0);
+        assert_eq!(
+            font.variation_coordinates(),
+            vec![
+                (Tag::from_bytes(b"wght"), 700.0),
+                (Tag::from_bytes(b"wdth"), 87.5)
+            ]
+        );
+    }
+
+    // Builds an `fvar` table with the given axes, which range from 0 to 1000 and default to 400,
+    // and named instances.
+    fn fvar(axes: &[&[u8; 4]], instances: &[&[f32]]) -> Vec<u8> {
+        let mut fvar = vec![];
+        let instance_size = 4 + axes.len() * 4;
+        for &field in &[1, 0, 16, 2, axes.len(), 20, instances.len(), instance_size] {
+            fvar.extend_from_slice(&(field as u16).to_be_bytes());
+        }
+        for (axis_index, &tag) in axes.iter().enumerate() {
+            fvar.extend_from_slice(tag);
+            for &value in &[0, 400, 1000] {
+                fvar.extend_from_slice(&((value as i32) << 16).to_be_bytes());
+            }
+            fvar.extend_from_slice(&[0, 0]);
+            fvar.extend_from_slice(&(256 + axis_index as u16).to_be_bytes());
+        }
+        for coordinates in instances {
+            fvar.extend_from_slice(&[0x01, 0x00, 0, 0]);
+            for &value in coordinates.iter() {
+                fvar.extend_from_slice(&((value * 65536.0) as i32).to_be_bytes());
+            }
+        }
+        fvar
+    }
+
+    // Adds Windows English names to the `name` table of a TrueType font and sets or clears the
+    // WWS-only bit of the `fsSelection` field of its `OS/2` table.
+    fn font_with_names(names: &[(u16, &str)], wws_only: bool) -> Vec<u8> {
+        rewrite_font_tables(TRUETYPE_FONT_PATH, vec![], |tag, table| match tag {
+            sfnt::NAME => *table = rewrite_name_table(table, |_| true, names),
+            sfnt::OS_2 => {
+                let fs_selection = u16::from_be_bytes([table[62], table[63]]);
+                let fs_selection = if wws_only {
+                    fs_selection | 1 << 8
+                } else {
+                    fs_selection & !(1 << 8)
+                };
+                table[62..64].copy_from_slice(&fs_selection.to_be_bytes());
+            }
+            _ => {}
+        })
+    }
 }
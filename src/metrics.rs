@@ -12,10 +12,13 @@
 //!
 //! For OpenType fonts, these mostly come from the `OS/2` table.
 
+use euclid::{Size2D, Vector2D};
+
 /// Various metrics that apply to the entire font.
 ///
 /// For OpenType fonts, these mostly come from the `OS/2` table.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Metrics {
     /// The number of font units per em.
     ///
@@ -48,4 +51,91 @@ pub struct Metrics {
     /// The approximate amount that non-ascending lowercase letters rise above the baseline, in
     /// font units.
     pub x_height: f32,
+
+    /// The distance from the vertical baseline to the right edge of a column of vertical text, in
+    /// font units.
+    ///
+    /// This comes from the `vhea` table. Fonts without one report half the em.
+    pub vertical_ascent: f32,
+
+    /// The distance from the vertical baseline to the left edge of a column of vertical text, in
+    /// font units.
+    ///
+    /// Like `descent`, this is typically a negative value. Fonts without a `vhea` table report
+    /// negative half the em.
+    pub vertical_descent: f32,
+
+    /// Distance between columns of vertical text, in font units.
+    pub vertical_line_gap: f32,
+
+    /// The ascent from the `hhea` table, in font units.
+    ///
+    /// This is what Mac OS and most FreeType-based layout engines use for line spacing. Fonts
+    /// without an `hhea` table report `ascent`.
+    pub hhea_ascent: f32,
+
+    /// The descent from the `hhea` table, in font units; typically negative.
+    pub hhea_descent: f32,
+
+    /// The line gap from the `hhea` table, in font units.
+    pub hhea_line_gap: f32,
+
+    /// The typographic ascent (`sTypoAscender`) from the `OS/2` table, in font units.
+    ///
+    /// Fonts without an `OS/2` table report `ascent`.
+    pub typo_ascent: f32,
+
+    /// The typographic descent (`sTypoDescender`) from the `OS/2` table, in font units; typically
+    /// negative.
+    pub typo_descent: f32,
+
+    /// The typographic line gap (`sTypoLineGap`) from the `OS/2` table, in font units.
+    pub typo_line_gap: f32,
+
+    /// The Windows clipping ascent (`usWinAscent`) from the `OS/2` table, in font units.
+    ///
+    /// Fonts without an `OS/2` table report `ascent`.
+    pub win_ascent: f32,
+
+    /// The Windows clipping descent from the `OS/2` table, in font units.
+    ///
+    /// NB: `usWinDescent` is stored as a positive number, but this value is negated to match
+    /// `descent`.
+    pub win_descent: f32,
+
+    /// True if the font asks for the typographic metrics to be used for line spacing instead of
+    /// the `hhea` or Windows metrics (the `USE_TYPO_METRICS` bit of `fsSelection`).
+    pub use_typo_metrics: bool,
+
+    /// The suggested distance of the top of the strikeout stroke from the baseline, in font
+    /// units.
+    pub strikeout_position: f32,
+
+    /// A suggested value for the strikeout thickness, in font units.
+    pub strikeout_thickness: f32,
+
+    /// The suggested size of subscript glyphs, in font units.
+    pub subscript_size: Size2D<f32>,
+
+    /// The suggested offset of subscript glyphs from the baseline, in font units.
+    ///
+    /// The Y value is typically negative, as subscripts sit below the baseline.
+    pub subscript_offset: Vector2D<f32>,
+
+    /// The suggested size of superscript glyphs, in font units.
+    pub superscript_size: Size2D<f32>,
+
+    /// The suggested offset of superscript glyphs from the baseline, in font units.
+    pub superscript_offset: Vector2D<f32>,
+
+    /// The slope of the caret, as a run (X) and rise (Y).
+    ///
+    /// Upright fonts have a slope of (0, 1).
+    pub caret_slope: Vector2D<f32>,
+
+    /// The amount the caret should be shifted horizontally for slanted fonts, in font units.
+    pub caret_offset: f32,
+
+    /// The average width of the glyphs in the font (`xAvgCharWidth`), in font units.
+    pub average_char_width: f32,
 }
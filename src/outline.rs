@@ -0,0 +1,458 @@
+// font-kit/src/outline.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! An owned, recorded representation of a glyph outline.
+//!
+//! Loaders stream outlines into a `lyon_path` builder. The types in this module record that stream
+//! so that outlines can be cached, inspected, transformed, and exported without depending on a
+//! particular path library.
+
+use euclid::{Point2D, Rect, Size2D, Transform2D};
+use lyon_path::builder::{FlatPathBuilder, PathBuilder};
+use lyon_path::geom::{Arc, CubicBezierSegment, QuadraticBezierSegment};
+use lyon_path::math::{Angle, Point, Vector};
+use std::fmt::{self, Write};
+use std::mem;
+
+/// A glyph outline, consisting of zero or more contours.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Outline {
+    /// The contours that make up this outline, in the order they were recorded.
+    pub contours: Vec<Contour>,
+}
+
+/// A single contour (subpath) of an outline.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Contour {
+    /// The point at which this contour begins.
+    pub start: Point2D<f32>,
+    /// The segments that make up this contour. Each segment begins at the endpoint of the previous
+    /// one (or at `start` for the first segment).
+    pub segments: Vec<Segment>,
+    /// True if the contour is closed with a straight line back to `start`.
+    pub closed: bool,
+}
+
+/// A single segment of a contour.
+///
+/// Each variant stores its control points (if any) followed by its endpoint.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Segment {
+    /// A straight line to the given point.
+    Line(Point2D<f32>),
+    /// A quadratic Bézier curve with one control point.
+    Quadratic(Point2D<f32>, Point2D<f32>),
+    /// A cubic Bézier curve with two control points.
+    Cubic(Point2D<f32>, Point2D<f32>, Point2D<f32>),
+}
+
+impl Outline {
+    /// Creates a new empty outline.
+    #[inline]
+    pub fn new() -> Outline {
+        Outline::default()
+    }
+
+    /// Returns a path builder that records an outline.
+    ///
+    /// The builder can be passed to `Loader::outline()`.
+    #[inline]
+    pub fn builder() -> OutlineBuilder {
+        OutlineBuilder::new()
+    }
+
+    /// Returns true if and only if this outline has no contours.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.contours.is_empty()
+    }
+
+    /// Returns the tight bounding rectangle of this outline, taking curve extrema into account.
+    ///
+    /// An empty outline has zero bounds.
+    pub fn bounds(&self) -> Rect<f32> {
+        let mut bounds: Option<Rect<f32>> = None;
+        for contour in &self.contours {
+            let contour_bounds = contour.bounds();
+            bounds = Some(match bounds {
+                None => contour_bounds,
+                Some(bounds) => union_rects(&bounds, &contour_bounds),
+            });
+        }
+        bounds.unwrap_or_else(Rect::zero)
+    }
+
+    /// Applies an affine transform to every point in this outline.
+    ///
+    /// Outlines are in font units with the Y axis pointing up; to produce SVG path data in a Y-down
+    /// coordinate system, apply a transform such as `Transform2D::create_scale(1.0, -1.0)` first.
+    pub fn transform(&mut self, transform: &Transform2D<f32>) {
+        for contour in &mut self.contours {
+            contour.transform(transform)
+        }
+    }
+
+    /// Reverses the winding direction of every contour in this outline.
+    pub fn reverse(&mut self) {
+        for contour in &mut self.contours {
+            contour.reverse()
+        }
+    }
+
+    /// Returns a copy of this outline in which every cubic Bézier curve has been approximated by
+    /// quadratic Bézier curves to within `tolerance` font units.
+    ///
+    /// Lines and quadratic curves are preserved as-is.
+    pub fn to_quadratics(&self, tolerance: f32) -> Outline {
+        Outline {
+            contours: self
+                .contours
+                .iter()
+                .map(|contour| contour.to_quadratics(tolerance))
+                .collect(),
+        }
+    }
+
+    /// Returns a copy of this outline in which every quadratic Bézier curve has been converted to
+    /// the equivalent cubic Bézier curve.
+    ///
+    /// This conversion is exact. Lines and cubic curves are preserved as-is.
+    pub fn to_cubics(&self) -> Outline {
+        Outline {
+            contours: self
+                .contours
+                .iter()
+                .map(|contour| contour.to_cubics())
+                .collect(),
+        }
+    }
+
+    /// Returns this outline as SVG path data, suitable for the `d` attribute of a `<path>`.
+    ///
+    /// Coordinates are written as-is, so the result is in font units with the Y axis pointing up.
+    /// See `transform()` for how to flip the outline.
+    pub fn to_svg_path(&self) -> String {
+        let mut string = String::new();
+        for contour in &self.contours {
+            if !string.is_empty() {
+                string.push(' ')
+            }
+            // Writing to a `String` can't fail.
+            let _ = contour.write_svg_path(&mut string);
+        }
+        string
+    }
+
+    /// Sends this outline to a path builder, in the same way `Loader::outline()` does.
+    pub fn send_to<B>(&self, path_builder: &mut B)
+    where
+        B: PathBuilder,
+    {
+        for contour in &self.contours {
+            path_builder.move_to(contour.start);
+            for segment in &contour.segments {
+                match *segment {
+                    Segment::Line(to) => path_builder.line_to(to),
+                    Segment::Quadratic(ctrl, to) => path_builder.quadratic_bezier_to(ctrl, to),
+                    Segment::Cubic(ctrl0, ctrl1, to) => {
+                        path_builder.cubic_bezier_to(ctrl0, ctrl1, to)
+                    }
+                }
+            }
+            if contour.closed {
+                path_builder.close()
+            }
+        }
+    }
+}
+
+impl Contour {
+    /// Creates a new open contour beginning at the given point with no segments.
+    #[inline]
+    pub fn new(start: Point2D<f32>) -> Contour {
+        Contour {
+            start,
+            segments: vec![],
+            closed: false,
+        }
+    }
+
+    /// Returns the point at which this contour ends, not counting the closing line.
+    #[inline]
+    pub fn end(&self) -> Point2D<f32> {
+        self.segments
+            .last()
+            .map(|segment| segment.to())
+            .unwrap_or(self.start)
+    }
+
+    /// Returns the tight bounding rectangle of this contour.
+    pub fn bounds(&self) -> Rect<f32> {
+        let mut bounds = Rect::new(self.start, Size2D::zero());
+        let mut from = self.start;
+        for segment in &self.segments {
+            let segment_bounds = match *segment {
+                Segment::Line(to) => Rect::from_points([from, to]),
+                Segment::Quadratic(ctrl, to) => {
+                    QuadraticBezierSegment { from, ctrl, to }.bounding_rect()
+                }
+                Segment::Cubic(ctrl1, ctrl2, to) => CubicBezierSegment {
+                    from,
+                    ctrl1,
+                    ctrl2,
+                    to,
+                }
+                .bounding_rect(),
+            };
+            bounds = union_rects(&bounds, &segment_bounds);
+            from = segment.to();
+        }
+        bounds
+    }
+
+    /// Applies an affine transform to every point in this contour.
+    pub fn transform(&mut self, transform: &Transform2D<f32>) {
+        self.start = transform.transform_point(&self.start);
+        for segment in &mut self.segments {
+            *segment = segment.transform(transform)
+        }
+    }
+
+    /// Reverses the direction of this contour.
+    ///
+    /// The reversed contour starts at the old endpoint, traces the same segments backwards, and
+    /// remains closed if it was closed before.
+    pub fn reverse(&mut self) {
+        let mut from = self.start;
+        let mut reversed = Vec::with_capacity(self.segments.len());
+        for &segment in &self.segments {
+            reversed.push(match segment {
+                Segment::Line(_) => Segment::Line(from),
+                Segment::Quadratic(ctrl, _) => Segment::Quadratic(ctrl, from),
+                Segment::Cubic(ctrl0, ctrl1, _) => Segment::Cubic(ctrl1, ctrl0, from),
+            });
+            from = segment.to();
+        }
+        reversed.reverse();
+        self.start = from;
+        self.segments = reversed;
+    }
+
+    fn to_quadratics(&self, tolerance: f32) -> Contour {
+        let mut contour = Contour::new(self.start);
+        contour.closed = self.closed;
+        let mut from = self.start;
+        for segment in &self.segments {
+            match *segment {
+                Segment::Line(_) | Segment::Quadratic(..) => contour.segments.push(*segment),
+                Segment::Cubic(ctrl1, ctrl2, to) => {
+                    let cubic = CubicBezierSegment {
+                        from,
+                        ctrl1,
+                        ctrl2,
+                        to,
+                    };
+                    cubic.for_each_quadratic_bezier(tolerance, &mut |quadratic| {
+                        contour
+                            .segments
+                            .push(Segment::Quadratic(quadratic.ctrl, quadratic.to))
+                    });
+                    // Make sure the contour stays connected despite any rounding.
+                    if let Some(&mut Segment::Quadratic(_, ref mut last_to)) =
+                        contour.segments.last_mut()
+                    {
+                        *last_to = to
+                    }
+                }
+            }
+            from = segment.to();
+        }
+        contour
+    }
+
+    fn to_cubics(&self) -> Contour {
+        let mut contour = Contour::new(self.start);
+        contour.closed = self.closed;
+        let mut from = self.start;
+        for segment in &self.segments {
+            match *segment {
+                Segment::Line(_) | Segment::Cubic(..) => contour.segments.push(*segment),
+                Segment::Quadratic(ctrl, to) => {
+                    let cubic = QuadraticBezierSegment { from, ctrl, to }.to_cubic();
+                    contour
+                        .segments
+                        .push(Segment::Cubic(cubic.ctrl1, cubic.ctrl2, cubic.to))
+                }
+            }
+            from = segment.to();
+        }
+        contour
+    }
+
+    fn write_svg_path(&self, string: &mut String) -> fmt::Result {
+        write!(string, "M{} {}", self.start.x, self.start.y)?;
+        for segment in &self.segments {
+            match *segment {
+                Segment::Line(to) => write!(string, " L{} {}", to.x, to.y)?,
+                Segment::Quadratic(ctrl, to) => {
+                    write!(string, " Q{} {} {} {}", ctrl.x, ctrl.y, to.x, to.y)?
+                }
+                Segment::Cubic(ctrl0, ctrl1, to) => write!(
+                    string,
+                    " C{} {} {} {} {} {}",
+                    ctrl0.x, ctrl0.y, ctrl1.x, ctrl1.y, to.x, to.y
+                )?,
+            }
+        }
+        if self.closed {
+            string.push_str(" Z")
+        }
+        Ok(())
+    }
+}
+
+impl Segment {
+    /// Returns the endpoint of this segment.
+    #[inline]
+    pub fn to(&self) -> Point2D<f32> {
+        match *self {
+            Segment::Line(to) | Segment::Quadratic(_, to) | Segment::Cubic(_, _, to) => to,
+        }
+    }
+
+    /// Returns a copy of this segment with an affine transform applied to all of its points.
+    pub fn transform(&self, transform: &Transform2D<f32>) -> Segment {
+        match *self {
+            Segment::Line(to) => Segment::Line(transform.transform_point(&to)),
+            Segment::Quadratic(ctrl, to) => Segment::Quadratic(
+                transform.transform_point(&ctrl),
+                transform.transform_point(&to),
+            ),
+            Segment::Cubic(ctrl0, ctrl1, to) => Segment::Cubic(
+                transform.transform_point(&ctrl0),
+                transform.transform_point(&ctrl1),
+                transform.transform_point(&to),
+            ),
+        }
+    }
+}
+
+/// A path builder that records the commands it receives into an `Outline`.
+///
+/// Arcs are approximated with cubic Bézier curves.
+#[derive(Clone, Debug, Default)]
+pub struct OutlineBuilder {
+    outline: Outline,
+    current_contour: Option<Contour>,
+}
+
+impl OutlineBuilder {
+    /// Creates a new builder with no recorded contours.
+    #[inline]
+    pub fn new() -> OutlineBuilder {
+        OutlineBuilder::default()
+    }
+
+    fn flush_current_contour(&mut self) {
+        if let Some(contour) = self.current_contour.take() {
+            self.outline.contours.push(contour)
+        }
+    }
+
+    fn push_segment(&mut self, segment: Segment) {
+        if self.current_contour.is_none() {
+            // Follow lyon's behavior of implicitly starting at the current position.
+            let start = self.current_position();
+            self.current_contour = Some(Contour::new(start));
+        }
+        if let Some(ref mut contour) = self.current_contour {
+            contour.segments.push(segment)
+        }
+    }
+}
+
+impl FlatPathBuilder for OutlineBuilder {
+    type PathType = Outline;
+
+    fn move_to(&mut self, to: Point) {
+        self.flush_current_contour();
+        self.current_contour = Some(Contour::new(to));
+    }
+
+    fn line_to(&mut self, to: Point) {
+        self.push_segment(Segment::Line(to))
+    }
+
+    fn close(&mut self) {
+        if let Some(ref mut contour) = self.current_contour {
+            contour.closed = true;
+        }
+        self.flush_current_contour();
+    }
+
+    fn build(mut self) -> Outline {
+        self.flush_current_contour();
+        self.outline
+    }
+
+    fn build_and_reset(&mut self) -> Outline {
+        self.flush_current_contour();
+        mem::replace(&mut self.outline, Outline::new())
+    }
+
+    fn current_position(&self) -> Point {
+        match self.current_contour {
+            Some(ref contour) => contour.end(),
+            None => self
+                .outline
+                .contours
+                .last()
+                .map(|contour| {
+                    if contour.closed {
+                        contour.start
+                    } else {
+                        contour.end()
+                    }
+                })
+                .unwrap_or_else(Point2D::zero),
+        }
+    }
+}
+
+impl PathBuilder for OutlineBuilder {
+    fn quadratic_bezier_to(&mut self, ctrl: Point, to: Point) {
+        self.push_segment(Segment::Quadratic(ctrl, to))
+    }
+
+    fn cubic_bezier_to(&mut self, ctrl1: Point, ctrl2: Point, to: Point) {
+        self.push_segment(Segment::Cubic(ctrl1, ctrl2, to))
+    }
+
+    fn arc(&mut self, center: Point, radii: Vector, sweep_angle: Angle, x_rotation: Angle) {
+        let start = self.current_position();
+        let start_angle = (start - center).angle_from_x_axis() - x_rotation;
+        let arc = Arc {
+            center,
+            radii,
+            start_angle,
+            sweep_angle,
+            x_rotation,
+        };
+        arc.for_each_cubic_bezier(&mut |cubic| {
+            self.push_segment(Segment::Cubic(cubic.ctrl1, cubic.ctrl2, cubic.to))
+        });
+    }
+}
+
+// Unlike `Rect::union()`, this doesn't discard degenerate (zero-area) rectangles, which are common
+// for outlines consisting of horizontal or vertical lines.
+fn union_rects(a: &Rect<f32>, b: &Rect<f32>) -> Rect<f32> {
+    Rect::from_points([a.origin, a.bottom_right(), b.origin, b.bottom_right()])
+}
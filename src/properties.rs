@@ -21,7 +21,11 @@ use std::fmt::{self, Debug, Display, Formatter};
 ///
 ///     # use font_kit::properties::{Properties, Style};
 ///     println!("{:?}", Properties::new().style(Style::Italic));
+///
+/// With the `serde` feature, missing properties deserialize to their default values.
 #[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Properties {
     /// The font style, as defined in CSS.
     pub style: Style,
@@ -62,7 +66,11 @@ impl Properties {
 }
 
 /// Allows italic or oblique faces to be selected.
+///
+/// With the `serde` feature, styles are serialized as their CSS keywords: e.g. `"italic"`.
 #[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum Style {
     /// A face that is neither italic not obliqued.
     Normal,
@@ -86,7 +94,11 @@ impl Display for Style {
 
 /// The degree of blackness or stroke thickness of a font. This value ranges from 100.0 to 900.0,
 /// with 400.0 as normal.
+///
+/// With the `serde` feature, weights are serialized as bare numbers.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Weight(pub f32);
 
 impl Default for Weight {
@@ -120,7 +132,11 @@ impl Weight {
 /// The width of a font as an approximate fraction of the normal width.
 ///
 /// Widths range from 0.5 to 2.0 inclusive, with 1.0 as the normal width.
+///
+/// With the `serde` feature, widths are serialized as bare numbers.
 #[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
 pub struct Stretch(pub f32);
 
 impl Default for Stretch {
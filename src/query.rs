@@ -0,0 +1,231 @@
+// font-kit/src/query.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Criteria for enumerating the fonts in a source with `Source::query_fonts()`.
+
+use std::ops::RangeInclusive;
+
+use descriptor::FontDescriptor;
+use error::SelectionError;
+use file_type::FontFormat;
+use loader::Loader;
+use properties::{Stretch, Style, Weight};
+
+/// Criteria that the fonts returned by `Source::query_fonts()` must all meet.
+///
+/// Criteria that aren't set match every font. This object supports a method chaining style for
+/// idiomatic initialization; e.g.
+///
+///     # use font_kit::properties::{Style, Weight};
+///     # use font_kit::query::FontQuery;
+///     println!("{:?}", FontQuery::new().weight(Weight::BOLD..=Weight::BLACK).style(Style::Italic));
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FontQuery {
+    /// Text that the family name must contain, compared case-insensitively.
+    pub family_name: Option<String>,
+    /// The range that the weight must be in.
+    pub weight: Option<RangeInclusive<Weight>>,
+    /// The range that the stretch must be in.
+    pub stretch: Option<RangeInclusive<Stretch>>,
+    /// The styles that are allowed. If empty, every style is.
+    pub styles: Vec<Style>,
+    /// Whether the font must be monospace or must not be.
+    pub monospace: Option<bool>,
+    /// Characters that the font must all have glyphs for.
+    pub characters: Vec<char>,
+    /// ISO 15924 script codes (e.g. `Cyrl` or `Hani`) whose basic letters the font must have
+    /// glyphs for. Queries for an unknown script fail with `SelectionError::UnsupportedScript`.
+    pub scripts: Vec<String>,
+    /// The formats that are allowed. If empty, every format is.
+    pub formats: Vec<FontFormat>,
+    /// Whether the font must be a variable font or must not be.
+    pub variable: Option<bool>,
+}
+
+impl FontQuery {
+    /// Creates a query that matches every font.
+    #[inline]
+    pub fn new() -> FontQuery {
+        FontQuery::default()
+    }
+
+    /// Requires the family name to contain the given text and returns this query for method
+    /// chaining.
+    #[inline]
+    pub fn family_name(&mut self, family_name: &str) -> &mut FontQuery {
+        self.family_name = Some(family_name.to_owned());
+        self
+    }
+
+    /// Requires the weight to be in the given range and returns this query for method chaining.
+    #[inline]
+    pub fn weight(&mut self, weight: RangeInclusive<Weight>) -> &mut FontQuery {
+        self.weight = Some(weight);
+        self
+    }
+
+    /// Requires the stretch to be in the given range and returns this query for method chaining.
+    #[inline]
+    pub fn stretch(&mut self, stretch: RangeInclusive<Stretch>) -> &mut FontQuery {
+        self.stretch = Some(stretch);
+        self
+    }
+
+    /// Allows the given style and returns this query for method chaining.
+    ///
+    /// Once a style has been allowed, fonts in other styles no longer match.
+    #[inline]
+    pub fn style(&mut self, style: Style) -> &mut FontQuery {
+        self.styles.push(style);
+        self
+    }
+
+    /// Requires the font to be monospace, or not to be, and returns this query for method
+    /// chaining.
+    #[inline]
+    pub fn monospace(&mut self, monospace: bool) -> &mut FontQuery {
+        self.monospace = Some(monospace);
+        self
+    }
+
+    /// Requires the font to have glyphs for all characters in the given text and returns this
+    /// query for method chaining.
+    pub fn covering(&mut self, text: &str) -> &mut FontQuery {
+        for character in text.chars() {
+            if !self.characters.contains(&character) {
+                self.characters.push(character)
+            }
+        }
+        self
+    }
+
+    /// Requires the font to support the script with the given ISO 15924 code (e.g. `Cyrl` or
+    /// `Hani`) and returns this query for method chaining.
+    #[inline]
+    pub fn script(&mut self, script: &str) -> &mut FontQuery {
+        self.scripts.push(script.to_owned());
+        self
+    }
+
+    /// Allows the given format and returns this query for method chaining.
+    ///
+    /// Once a format has been allowed, fonts in other formats no longer match.
+    #[inline]
+    pub fn format(&mut self, format: FontFormat) -> &mut FontQuery {
+        self.formats.push(format);
+        self
+    }
+
+    /// Requires the font to be a variable font, or not to be, and returns this query for method
+    /// chaining.
+    #[inline]
+    pub fn variable(&mut self, variable: bool) -> &mut FontQuery {
+        self.variable = Some(variable);
+        self
+    }
+
+    /// Returns true if the described font meets all criteria other than character coverage.
+    pub fn matches(&self, descriptor: &FontDescriptor) -> bool {
+        if let Some(ref family_name) = self.family_name {
+            let family_name = family_name.to_lowercase();
+            if !descriptor.family_name.to_lowercase().contains(&family_name) {
+                return false;
+            }
+        }
+        let properties = &descriptor.properties;
+        if let Some(ref weight) = self.weight {
+            if !weight.contains(&properties.weight) {
+                return false;
+            }
+        }
+        if let Some(ref stretch) = self.stretch {
+            if !stretch.contains(&properties.stretch) {
+                return false;
+            }
+        }
+        if !self.styles.is_empty() && !self.styles.contains(&properties.style) {
+            return false;
+        }
+        if !self.formats.is_empty() && !self.formats.contains(&descriptor.format) {
+            return false;
+        }
+        self.monospace != Some(!descriptor.is_monospace)
+            && self.variable != Some(!descriptor.is_variable)
+    }
+
+    /// Returns every character that a font must have glyphs for, including those that stand for
+    /// the required scripts, or `SelectionError::UnsupportedScript` if a required script is
+    /// unknown.
+    pub fn required_characters(&self) -> Result<Vec<char>, SelectionError> {
+        let mut characters = self.characters.clone();
+        for script in &self.scripts {
+            let &(_, sample) = SCRIPT_SAMPLES
+                .iter()
+                .find(|&&(code, _)| code.eq_ignore_ascii_case(script))
+                .ok_or_else(|| SelectionError::UnsupportedScript(script.clone()))?;
+            for character in sample.chars() {
+                if !characters.contains(&character) {
+                    characters.push(character)
+                }
+            }
+        }
+        Ok(characters)
+    }
+
+    /// Returns true if the given font has glyphs for all required characters.
+    ///
+    /// No font has glyphs for an unknown script.
+    pub fn is_covered_by<L>(&self, font: &L) -> bool
+    where
+        L: Loader,
+    {
+        match self.required_characters() {
+            Ok(characters) => characters
+                .iter()
+                .all(|&character| font.glyph_for_char(character).is_some()),
+            Err(_) => false,
+        }
+    }
+}
+
+// A few basic letters of each script, which fonts that support the script can be expected to
+// have.
+static SCRIPT_SAMPLES: [(&str, &str); 30] = [
+    ("Arab", "\u{627}\u{628}\u{645}"),
+    ("Armn", "\u{531}\u{561}"),
+    ("Beng", "\u{995}\u{9be}"),
+    ("Cyrl", "\u{416}\u{42f}\u{436}\u{44f}"),
+    ("Deva", "\u{915}\u{93e}"),
+    ("Ethi", "\u{1200}\u{12a0}"),
+    ("Geor", "\u{10d0}\u{10d1}"),
+    ("Grek", "\u{391}\u{3a9}\u{3b1}\u{3c9}"),
+    ("Gujr", "\u{a95}\u{abe}"),
+    ("Guru", "\u{a15}\u{a3e}"),
+    ("Hang", "\u{ac00}\u{d55c}"),
+    ("Hani", "\u{4e00}\u{4e2d}"),
+    ("Hebr", "\u{5d0}\u{5d1}"),
+    ("Hira", "\u{3042}\u{3093}"),
+    ("Jpan", "\u{3042}\u{30a2}\u{4e00}"),
+    ("Kana", "\u{30a2}\u{30f3}"),
+    ("Khmr", "\u{1780}\u{1781}"),
+    ("Knda", "\u{c95}\u{cbe}"),
+    ("Kore", "\u{ac00}\u{4e00}"),
+    ("Laoo", "\u{e81}\u{e82}"),
+    ("Latn", "AZaz"),
+    ("Mlym", "\u{d15}\u{d3e}"),
+    ("Mymr", "\u{1000}\u{1001}"),
+    ("Orya", "\u{b15}\u{b3e}"),
+    ("Sinh", "\u{d9a}\u{dcf}"),
+    ("Taml", "\u{b95}\u{bbe}"),
+    ("Telu", "\u{c15}\u{c3e}"),
+    ("Thai", "\u{e01}\u{e02}"),
+    ("Tibt", "\u{f40}\u{f41}"),
+    ("Zyyy", "0123456789"),
+];
@@ -0,0 +1,622 @@
+// font-kit/src/sfnt.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Minimal, bounds-checked parsing of the TrueType/OpenType tables that the platform APIs don't
+//! expose uniformly.
+//!
+//! Loaders hand us raw table data via `Loader::load_font_table()`; everything here works on byte
+//! slices and returns `None` for missing or truncated data instead of panicking.
+
+use byteorder::{BigEndian, ByteOrder};
+use euclid::{Size2D, Vector2D};
+
+use error::SubsetError;
+use features::Tag;
+use metrics::Metrics;
+
+pub(crate) const CBDT: u32 = 0x4342_4454; // 'CBDT'
+pub(crate) const CFF: u32 = 0x4346_4620; // 'CFF '
+pub(crate) const CFF2: u32 = 0x4346_4632; // 'CFF2'
+pub(crate) const CMAP: u32 = 0x636d_6170; // 'cmap'
+pub(crate) const CVT: u32 = 0x6376_7420; // 'cvt '
+pub(crate) const DSIG: u32 = 0x4453_4947; // 'DSIG'
+pub(crate) const EBDT: u32 = 0x4542_4454; // 'EBDT'
+pub(crate) const FPGM: u32 = 0x6670_676d; // 'fpgm'
+pub(crate) const FVAR: u32 = 0x6676_6172; // 'fvar'
+pub(crate) const GASP: u32 = 0x6761_7370; // 'gasp'
+pub(crate) const GLYF: u32 = 0x676c_7966; // 'glyf'
+pub(crate) const GPOS: u32 = 0x4750_4f53; // 'GPOS'
+pub(crate) const GSUB: u32 = 0x4753_5542; // 'GSUB'
+pub(crate) const HEAD: u32 = 0x6865_6164; // 'head'
+pub(crate) const HHEA: u32 = 0x6868_6561; // 'hhea'
+pub(crate) const HMTX: u32 = 0x686d_7478; // 'hmtx'
+pub(crate) const KERN: u32 = 0x6b65_726e; // 'kern'
+pub(crate) const LOCA: u32 = 0x6c6f_6361; // 'loca'
+pub(crate) const MAXP: u32 = 0x6d61_7870; // 'maxp'
+pub(crate) const META: u32 = 0x6d65_7461; // 'meta'
+pub(crate) const NAME: u32 = 0x6e61_6d65; // 'name'
+pub(crate) const OS_2: u32 = 0x4f53_2f32; // 'OS/2'
+pub(crate) const POST: u32 = 0x706f_7374; // 'post'
+pub(crate) const PREP: u32 = 0x7072_6570; // 'prep'
+pub(crate) const SBIX: u32 = 0x7362_6978; // 'sbix'
+pub(crate) const VHEA: u32 = 0x7668_6561; // 'vhea'
+pub(crate) const VMTX: u32 = 0x766d_7478; // 'vmtx'
+pub(crate) const VORG: u32 = 0x564f_5247; // 'VORG'
+
+pub(crate) const TRUETYPE_VERSION: u32 = 0x0001_0000;
+pub(crate) const OPENTYPE_CFF_VERSION: u32 = 0x4f54_544f; // 'OTTO'
+const COLLECTION_TAG: u32 = 0x7474_6366; // 'ttcf'
+
+#[inline]
+pub(crate) fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..(offset + 2)).map(BigEndian::read_u16)
+}
+
+#[inline]
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..(offset + 4)).map(BigEndian::read_u32)
+}
+
+#[inline]
+pub(crate) fn read_i16(data: &[u8], offset: usize) -> Option<i16> {
+    data.get(offset..(offset + 2)).map(BigEndian::read_i16)
+}
+
+const OS2_FS_SELECTION_USE_TYPO_METRICS: u16 = 1 << 7;
+
+/// The interesting fields of the `hhea` table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct HorizontalHeader {
+    pub(crate) ascent: i16,
+    pub(crate) descent: i16,
+    pub(crate) line_gap: i16,
+    pub(crate) caret_slope_rise: i16,
+    pub(crate) caret_slope_run: i16,
+    pub(crate) caret_offset: i16,
+}
+
+impl HorizontalHeader {
+    pub(crate) fn parse(hhea: &[u8]) -> Option<HorizontalHeader> {
+        Some(HorizontalHeader {
+            ascent: read_i16(hhea, 4)?,
+            descent: read_i16(hhea, 6)?,
+            line_gap: read_i16(hhea, 8)?,
+            caret_slope_rise: read_i16(hhea, 18)?,
+            caret_slope_run: read_i16(hhea, 20)?,
+            caret_offset: read_i16(hhea, 22)?,
+        })
+    }
+}
+
+/// The interesting fields of the `OS/2` table.
+///
+/// Only fields present in version 0 of the table are parsed here.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct Os2Table {
+    pub(crate) average_char_width: i16,
+    pub(crate) subscript_size: (i16, i16),
+    pub(crate) subscript_offset: (i16, i16),
+    pub(crate) superscript_size: (i16, i16),
+    pub(crate) superscript_offset: (i16, i16),
+    pub(crate) strikeout_size: i16,
+    pub(crate) strikeout_position: i16,
+    pub(crate) fs_selection: u16,
+    pub(crate) typo_ascender: i16,
+    pub(crate) typo_descender: i16,
+    pub(crate) typo_line_gap: i16,
+    pub(crate) win_ascent: u16,
+    pub(crate) win_descent: u16,
+}
+
+impl Os2Table {
+    pub(crate) fn parse(os2: &[u8]) -> Option<Os2Table> {
+        Some(Os2Table {
+            average_char_width: read_i16(os2, 2)?,
+            subscript_size: (read_i16(os2, 10)?, read_i16(os2, 12)?),
+            subscript_offset: (read_i16(os2, 14)?, read_i16(os2, 16)?),
+            superscript_size: (read_i16(os2, 18)?, read_i16(os2, 20)?),
+            superscript_offset: (read_i16(os2, 22)?, read_i16(os2, 24)?),
+            strikeout_size: read_i16(os2, 26)?,
+            strikeout_position: read_i16(os2, 28)?,
+            fs_selection: read_u16(os2, 62)?,
+            typo_ascender: read_i16(os2, 68)?,
+            typo_descender: read_i16(os2, 70)?,
+            typo_line_gap: read_i16(os2, 72)?,
+            win_ascent: read_u16(os2, 74)?,
+            win_descent: read_u16(os2, 76)?,
+        })
+    }
+}
+
+/// Fills in the metrics that every loader reads straight from the `hhea`, `OS/2` and `vhea`
+/// tables.
+///
+/// The loader must have filled in `units_per_em`, `ascent`, `descent` and `line_gap` already;
+/// those are used as fallbacks when a table is missing.
+pub(crate) fn fill_in_table_metrics<F>(metrics: &mut Metrics, load_font_table: F)
+where
+    F: Fn(u32) -> Option<Box<[u8]>>,
+{
+    match load_font_table(HHEA).and_then(|hhea| HorizontalHeader::parse(&hhea)) {
+        Some(horizontal_header) => {
+            metrics.hhea_ascent = horizontal_header.ascent as f32;
+            metrics.hhea_descent = horizontal_header.descent as f32;
+            metrics.hhea_line_gap = horizontal_header.line_gap as f32;
+            metrics.caret_slope = Vector2D::new(
+                horizontal_header.caret_slope_run as f32,
+                horizontal_header.caret_slope_rise as f32,
+            );
+            metrics.caret_offset = horizontal_header.caret_offset as f32;
+        }
+        None => {
+            metrics.hhea_ascent = metrics.ascent;
+            metrics.hhea_descent = metrics.descent;
+            metrics.hhea_line_gap = metrics.line_gap;
+            metrics.caret_slope = Vector2D::new(0.0, 1.0);
+            metrics.caret_offset = 0.0;
+        }
+    }
+
+    match load_font_table(OS_2).and_then(|os2| Os2Table::parse(&os2)) {
+        Some(os2_table) => {
+            metrics.typo_ascent = os2_table.typo_ascender as f32;
+            metrics.typo_descent = os2_table.typo_descender as f32;
+            metrics.typo_line_gap = os2_table.typo_line_gap as f32;
+            metrics.win_ascent = os2_table.win_ascent as f32;
+            metrics.win_descent = -(os2_table.win_descent as f32);
+            metrics.use_typo_metrics =
+                (os2_table.fs_selection & OS2_FS_SELECTION_USE_TYPO_METRICS) != 0;
+            metrics.strikeout_position = os2_table.strikeout_position as f32;
+            metrics.strikeout_thickness = os2_table.strikeout_size as f32;
+            metrics.subscript_size = size(os2_table.subscript_size);
+            metrics.subscript_offset = Vector2D::new(
+                os2_table.subscript_offset.0 as f32,
+                -(os2_table.subscript_offset.1 as f32),
+            );
+            metrics.superscript_size = size(os2_table.superscript_size);
+            metrics.superscript_offset = Vector2D::new(
+                os2_table.superscript_offset.0 as f32,
+                os2_table.superscript_offset.1 as f32,
+            );
+            metrics.average_char_width = os2_table.average_char_width as f32;
+        }
+        None => {
+            metrics.typo_ascent = metrics.ascent;
+            metrics.typo_descent = metrics.descent;
+            metrics.typo_line_gap = metrics.line_gap;
+            metrics.win_ascent = metrics.ascent;
+            metrics.win_descent = metrics.descent;
+            metrics.use_typo_metrics = false;
+            metrics.strikeout_position = 0.0;
+            metrics.strikeout_thickness = 0.0;
+            metrics.subscript_size = Size2D::zero();
+            metrics.subscript_offset = Vector2D::zero();
+            metrics.superscript_size = Size2D::zero();
+            metrics.superscript_offset = Vector2D::zero();
+            metrics.average_char_width = 0.0;
+        }
+    }
+
+    let (vertical_ascent, vertical_descent, vertical_line_gap) =
+        vertical_line_metrics(load_font_table(VHEA).as_deref(), metrics.units_per_em);
+    metrics.vertical_ascent = vertical_ascent;
+    metrics.vertical_descent = vertical_descent;
+    metrics.vertical_line_gap = vertical_line_gap;
+}
+
+fn size((width, height): (i16, i16)) -> Size2D<f32> {
+    Size2D::new(width as f32, height as f32)
+}
+
+/// The interesting fields of the `vhea` table.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct VerticalHeader {
+    pub(crate) ascent: i16,
+    pub(crate) descent: i16,
+    pub(crate) line_gap: i16,
+    pub(crate) number_of_long_metrics: u16,
+}
+
+impl VerticalHeader {
+    pub(crate) fn parse(vhea: &[u8]) -> Option<VerticalHeader> {
+        Some(VerticalHeader {
+            ascent: read_i16(vhea, 4)?,
+            descent: read_i16(vhea, 6)?,
+            line_gap: read_i16(vhea, 8)?,
+            number_of_long_metrics: read_u16(vhea, 34)?,
+        })
+    }
+}
+
+/// Returns the vertical ascent, descent, and line gap from the `vhea` table.
+///
+/// If the table is missing or malformed, a column one em wide centered on the vertical baseline
+/// is synthesized.
+pub(crate) fn vertical_line_metrics(vhea: Option<&[u8]>, units_per_em: u32) -> (f32, f32, f32) {
+    match vhea.and_then(VerticalHeader::parse) {
+        Some(vertical_header) => (
+            vertical_header.ascent as f32,
+            vertical_header.descent as f32,
+            vertical_header.line_gap as f32,
+        ),
+        None => {
+            let half_em = units_per_em as f32 * 0.5;
+            (half_em, -half_em, 0.0)
+        }
+    }
+}
+
+/// Looks up the advance height and top side bearing of a glyph in the `vmtx` table.
+pub(crate) fn vertical_glyph_metrics(
+    vmtx: &[u8],
+    vertical_header: &VerticalHeader,
+    glyph_id: u32,
+) -> Option<(u16, i16)> {
+    long_glyph_metrics(vmtx, vertical_header.number_of_long_metrics, glyph_id)
+}
+
+/// Looks up the advance and side bearing of a glyph in an `hmtx` or `vmtx` table with the given
+/// number of long metrics.
+pub(crate) fn long_glyph_metrics(
+    table: &[u8],
+    long_metric_count: u16,
+    glyph_id: u32,
+) -> Option<(u16, i16)> {
+    let long_metric_count = long_metric_count as usize;
+    if long_metric_count == 0 {
+        return None;
+    }
+
+    let glyph_id = glyph_id as usize;
+    if glyph_id < long_metric_count {
+        let offset = glyph_id * 4;
+        return Some((read_u16(table, offset)?, read_i16(table, offset + 2)?));
+    }
+
+    // Glyphs past the end of the long metrics share the last advance.
+    let advance = read_u16(table, (long_metric_count - 1) * 4)?;
+    let bearing_offset = long_metric_count * 4 + (glyph_id - long_metric_count) * 2;
+    Some((advance, read_i16(table, bearing_offset)?))
+}
+
+/// Looks up the Y coordinate of the vertical origin of a glyph in the `VORG` table.
+pub(crate) fn vertical_origin_y(vorg: &[u8], glyph_id: u32) -> Option<i16> {
+    let default_origin_y = read_i16(vorg, 4)?;
+    let metric_count = read_u16(vorg, 6)? as usize;
+
+    // The metrics are sorted by glyph ID, so we can binary search.
+    let (mut low, mut high) = (0, metric_count);
+    while low < high {
+        let mid = (low + high) / 2;
+        let offset = 8 + mid * 4;
+        let mid_glyph_id = read_u16(vorg, offset)? as u32;
+        if mid_glyph_id == glyph_id {
+            return read_i16(vorg, offset + 2);
+        }
+        if mid_glyph_id < glyph_id {
+            low = mid + 1
+        } else {
+            high = mid
+        }
+    }
+    Some(default_origin_y)
+}
+
+pub(crate) const NAME_ID_FAMILY: u16 = 1;
+pub(crate) const NAME_ID_FULL_NAME: u16 = 4;
+pub(crate) const NAME_ID_POSTSCRIPT_NAME: u16 = 6;
+pub(crate) const NAME_ID_TYPOGRAPHIC_FAMILY: u16 = 16;
+pub(crate) const NAME_ID_WWS_FAMILY: u16 = 21;
+
+/// Reads the string with the given ID from the `name` table.
+///
+/// Like FreeType, this prefers US English Windows names, then Roman Macintosh names, then any
+/// other Unicode names. Empty names count as missing. Macintosh names are decoded as ASCII, with
+/// other characters replaced by U+FFFD.
+pub(crate) fn read_name(name: &[u8], name_id: u16) -> Option<String> {
+    let record_count = read_u16(name, 2)? as usize;
+    let storage_offset = read_u16(name, 4)? as usize;
+    let mut best_name: Option<(u8, String)> = None;
+    for record_index in 0..record_count {
+        let record_offset = 6 + record_index * 12;
+        if read_u16(name, record_offset + 6)? != name_id {
+            continue;
+        }
+        let platform_id = read_u16(name, record_offset)?;
+        let encoding_id = read_u16(name, record_offset + 2)?;
+        let language_id = read_u16(name, record_offset + 4)?;
+        let length = read_u16(name, record_offset + 8)? as usize;
+        let offset = storage_offset + read_u16(name, record_offset + 10)? as usize;
+        let string = match name.get(offset..(offset + length)) {
+            Some(string) if !string.is_empty() => string,
+            _ => continue,
+        };
+
+        let rank = match (platform_id, encoding_id, language_id) {
+            (3, 1, 0x409) | (3, 10, 0x409) => 0,
+            (1, 0, 0) => 1,
+            (3, 1, _) | (3, 10, _) => 2,
+            (0, _, _) => 3,
+            _ => continue,
+        };
+        if let Some((best_rank, _)) = best_name {
+            if best_rank <= rank {
+                continue;
+            }
+        }
+
+        let string = if platform_id == 1 {
+            string
+                .iter()
+                .map(|&byte| {
+                    if byte < 0x80 {
+                        byte as char
+                    } else {
+                        '\u{fffd}'
+                    }
+                })
+                .collect()
+        } else {
+            let code_units: Vec<_> = string.chunks(2).map(|pair| read_u16(pair, 0)).collect();
+            match code_units.into_iter().collect::<Option<Vec<_>>>() {
+                Some(code_units) => String::from_utf16_lossy(&code_units),
+                None => continue,
+            }
+        };
+        best_name = Some((rank, string));
+    }
+    best_name.map(|(_, string)| string)
+}
+
+/// Reads the coordinates of the named instance with the given zero-based index from the `fvar`
+/// table, paired with the tags of their axes.
+pub(crate) fn named_instance_coordinates(
+    fvar: &[u8],
+    instance_index: u16,
+) -> Option<Vec<(Tag, f32)>> {
+    let axes_offset = read_u16(fvar, 4)? as usize;
+    let axis_count = read_u16(fvar, 8)? as usize;
+    let axis_size = read_u16(fvar, 10)? as usize;
+    let instance_count = read_u16(fvar, 12)?;
+    let instance_size = read_u16(fvar, 14)? as usize;
+    if instance_index >= instance_count {
+        return None;
+    }
+
+    // Instance records follow the axis records, and start with two 16-bit fields.
+    let instance_offset =
+        axes_offset + axis_count * axis_size + instance_index as usize * instance_size;
+    (0..axis_count)
+        .map(|axis_index| {
+            let tag = read_u32(fvar, axes_offset + axis_index * axis_size)?;
+            let value = read_u32(fvar, instance_offset + 4 + axis_index * 4)? as i32;
+            Some((Tag(tag), value as f32 / 65536.0))
+        })
+        .collect()
+}
+
+/// The magic number that the checksum of a whole font file, including `head`'s
+/// `checkSumAdjustment`, must add up to.
+const CHECKSUM_MAGIC: u32 = 0xb1b0_afba;
+
+/// Returns true if the data starts with the header of a TrueType or OpenType collection.
+pub(crate) fn is_collection(data: &[u8]) -> bool {
+    read_u32(data, 0) == Some(COLLECTION_TAG)
+}
+
+/// The version tag of a font and its tables, in the order of its table directory.
+pub(crate) type FontTables<'a> = (u32, Vec<(u32, &'a [u8])>);
+
+/// Returns the raw tables of a font, along with the version tag that starts its table directory.
+///
+/// If `data` is a TrueType or OpenType collection, the font at `font_index` is read; otherwise
+/// `font_index` must be 0.
+pub(crate) fn read_font_tables(
+    data: &[u8],
+    font_index: u32,
+) -> Result<FontTables<'_>, SubsetError> {
+    let directory_offset = match read_u32(data, 0).ok_or(SubsetError::Parse)? {
+        COLLECTION_TAG => {
+            let font_count = read_u32(data, 8).ok_or(SubsetError::Parse)?;
+            if font_index >= font_count {
+                return Err(SubsetError::NoSuchFontInCollection { font_index });
+            }
+            read_u32(data, 12 + font_index as usize * 4).ok_or(SubsetError::Parse)? as usize
+        }
+        _ if font_index != 0 => return Err(SubsetError::NoSuchFontInCollection { font_index }),
+        _ => 0,
+    };
+    read_table_directory(data, directory_offset).ok_or(SubsetError::Parse)
+}
+
+fn read_table_directory(data: &[u8], offset: usize) -> Option<FontTables<'_>> {
+    let version = read_u32(data, offset)?;
+    let table_count = read_u16(data, offset + 4)? as usize;
+    let mut tables = Vec::with_capacity(table_count);
+    for table_index in 0..table_count {
+        let record_offset = offset + 12 + table_index * 16;
+        let tag = read_u32(data, record_offset)?;
+        let table_offset = read_u32(data, record_offset + 8)? as usize;
+        let table_length = read_u32(data, record_offset + 12)? as usize;
+        let table = data.get(table_offset..table_offset.checked_add(table_length)?)?;
+        tables.push((tag, table));
+    }
+    Some((version, tables))
+}
+
+/// Sums a table as a sequence of big-endian 32-bit words, padding the end with zeroes.
+pub(crate) fn table_checksum(table: &[u8]) -> u32 {
+    table.chunks(4).fold(0u32, |checksum, chunk| {
+        let mut word = [0; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        checksum.wrapping_add(BigEndian::read_u32(&word))
+    })
+}
+
+/// Serializes a standalone font file from its version tag (`0x00010000` or `OTTO`) and tables.
+///
+/// Tables are sorted by tag and padded to four bytes, and the checksums, including `head`'s
+/// `checkSumAdjustment`, are computed. Fails with `SubsetError::Parse` if there are no tables, or
+/// too many to fit in a table directory.
+pub(crate) fn write_font(
+    version: u32,
+    mut tables: Vec<(u32, Vec<u8>)>,
+) -> Result<Vec<u8>, SubsetError> {
+    // The table directory stores sizes of up to 16 bytes per table in 16 bits.
+    if tables.is_empty() || tables.len() >= 0x1000 {
+        return Err(SubsetError::Parse);
+    }
+    tables.sort_by_key(|&(tag, _)| tag);
+
+    let table_count = tables.len() as u32;
+    let mut entry_selector = 0;
+    while (2 << entry_selector) <= table_count {
+        entry_selector += 1;
+    }
+    let search_range = (1 << entry_selector) * 16;
+
+    let mut data = vec![0; 12 + tables.len() * 16];
+    BigEndian::write_u32(&mut data[0..], version);
+    BigEndian::write_u16(&mut data[4..], table_count as u16);
+    BigEndian::write_u16(&mut data[6..], search_range as u16);
+    BigEndian::write_u16(&mut data[8..], entry_selector);
+    BigEndian::write_u16(
+        &mut data[10..],
+        (table_count * 16).saturating_sub(search_range) as u16,
+    );
+
+    let mut head_offset = None;
+    for (table_index, &mut (tag, ref mut table)) in tables.iter_mut().enumerate() {
+        if tag == HEAD && table.len() >= 12 {
+            BigEndian::write_u32(&mut table[8..], 0);
+            head_offset = Some(data.len());
+        }
+
+        let record_offset = 12 + table_index * 16;
+        let table_offset = data.len() as u32;
+        BigEndian::write_u32(&mut data[record_offset..], tag);
+        BigEndian::write_u32(&mut data[(record_offset + 4)..], table_checksum(table));
+        BigEndian::write_u32(&mut data[(record_offset + 8)..], table_offset);
+        BigEndian::write_u32(&mut data[(record_offset + 12)..], table.len() as u32);
+
+        data.extend_from_slice(table);
+        let padded_len = (data.len() + 3) & !3;
+        data.resize(padded_len, 0);
+    }
+
+    if let Some(head_offset) = head_offset {
+        let adjustment = CHECKSUM_MAGIC.wrapping_sub(table_checksum(&data));
+        BigEndian::write_u32(&mut data[(head_offset + 8)..], adjustment);
+    }
+    Ok(data)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{read_name, vertical_glyph_metrics, vertical_origin_y};
+    use super::{HorizontalHeader, Os2Table, VerticalHeader};
+
+    fn vhea(number_of_long_metrics: u16) -> Vec<u8> {
+        let mut vhea = vec![0; 36];
+        vhea[0..4].copy_from_slice(&[0x00, 0x01, 0x10, 0x00]);
+        vhea[4..6].copy_from_slice(&500i16.to_be_bytes());
+        vhea[6..8].copy_from_slice(&(-500i16).to_be_bytes());
+        vhea[34..36].copy_from_slice(&number_of_long_metrics.to_be_bytes());
+        vhea
+    }
+
+    #[test]
+    fn parse_vertical_glyph_metrics() {
+        let vertical_header = VerticalHeader::parse(&vhea(2)).unwrap();
+        assert_eq!(vertical_header.ascent, 500);
+        assert_eq!(vertical_header.descent, -500);
+
+        // Two long metrics followed by one short bearing.
+        let vmtx = [0x03, 0xe8, 0x00, 0x10, 0x04, 0x00, 0x00, 0x20, 0x00, 0x30];
+        assert_eq!(
+            vertical_glyph_metrics(&vmtx, &vertical_header, 0),
+            Some((1000, 16))
+        );
+        assert_eq!(
+            vertical_glyph_metrics(&vmtx, &vertical_header, 1),
+            Some((1024, 32))
+        );
+        assert_eq!(
+            vertical_glyph_metrics(&vmtx, &vertical_header, 2),
+            Some((1024, 48))
+        );
+        assert_eq!(vertical_glyph_metrics(&vmtx, &vertical_header, 3), None);
+        assert_eq!(VerticalHeader::parse(&vhea(2)[0..20]), None);
+    }
+
+    #[test]
+    fn parse_vertical_origins() {
+        // Default origin 880; glyph 5 → 900, glyph 9 → 850.
+        let vorg = [
+            0x00, 0x01, 0x00, 0x00, 0x03, 0x70, 0x00, 0x02, 0x00, 0x05, 0x03, 0x84, 0x00, 0x09,
+            0x03, 0x52,
+        ];
+        assert_eq!(vertical_origin_y(&vorg, 5), Some(900));
+        assert_eq!(vertical_origin_y(&vorg, 9), Some(850));
+        assert_eq!(vertical_origin_y(&vorg, 7), Some(880));
+        assert_eq!(vertical_origin_y(&vorg[0..4], 7), None);
+    }
+
+    // A `name` table with the given (platform, encoding, language, name ID, string) records.
+    fn name(records: &[(u16, u16, u16, u16, &[u8])]) -> Vec<u8> {
+        let storage_offset = 6 + records.len() * 12;
+        let mut name = vec![0, 0];
+        name.extend_from_slice(&(records.len() as u16).to_be_bytes());
+        name.extend_from_slice(&(storage_offset as u16).to_be_bytes());
+        let mut storage = vec![];
+        for &(platform_id, encoding_id, language_id, name_id, string) in records {
+            for &field in &[platform_id, encoding_id, language_id, name_id] {
+                name.extend_from_slice(&field.to_be_bytes());
+            }
+            name.extend_from_slice(&(string.len() as u16).to_be_bytes());
+            name.extend_from_slice(&(storage.len() as u16).to_be_bytes());
+            storage.extend_from_slice(string);
+        }
+        name.extend_from_slice(&storage);
+        name
+    }
+
+    #[test]
+    fn read_names_in_preferred_order() {
+        let table = name(&[
+            (0, 3, 0, 1, &[0, b'U']),
+            (3, 1, 0x407, 1, &[0, b'D', 0, 0xe9]),
+            (1, 0, 0, 1, b"Mac\xe9"),
+            (3, 1, 0x409, 4, &[0, b'W', 0, 0xe9]),
+            (3, 1, 0x409, 6, &[]),
+        ]);
+        assert_eq!(read_name(&table, 1).unwrap(), "Mac\u{fffd}");
+        assert_eq!(read_name(&table, 4).unwrap(), "W\u{e9}");
+        assert_eq!(read_name(&table, 6), None);
+        assert_eq!(read_name(&table, 16), None);
+        assert_eq!(
+            read_name(
+                &name(&[(0, 3, 0, 1, &[0, b'U']), (3, 1, 0x407, 1, &[0, b'D'])]),
+                1
+            )
+            .unwrap(),
+            "D"
+        );
+        assert_eq!(read_name(&table[..20], 1), None);
+    }
+
+    #[test]
+    fn reject_truncated_metrics_tables() {
+        assert_eq!(HorizontalHeader::parse(&[0; 23]), None);
+        assert!(HorizontalHeader::parse(&[0; 36]).is_some());
+        assert_eq!(Os2Table::parse(&[0; 77]), None);
+        assert!(Os2Table::parse(&[0; 78]).is_some());
+    }
+}
@@ -10,14 +10,19 @@
 
 //! A database of installed fonts that can be queried.
 
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use descriptor::{self, FontDescriptor};
 use error::SelectionError;
-use family::Family;
 use family_handle::FamilyHandle;
 use family_name::FamilyName;
 use font::Font;
+use generic_family_map::{GenericFamilyMap, PLATFORM_GENERIC_FAMILY_MAP};
 use handle::Handle;
 use matching::{self, Description};
 use properties::Properties;
+use query::FontQuery;
 
 #[cfg(all(target_os = "macos", not(feature = "source-fontconfig-default")))]
 pub use sources::core_text::CoreTextSource as SystemSource;
@@ -36,31 +41,6 @@ pub use sources::fontconfig::FontconfigSource as SystemSource;
 #[cfg(all(target_os = "android", not(feature = "source-fontconfig-default")))]
 pub use sources::fs::FsSource as SystemSource;
 
-// FIXME(pcwalton): These could expand to multiple fonts, and they could be language-specific.
-#[cfg(any(target_family = "windows", target_os = "macos"))]
-const DEFAULT_FONT_FAMILY_SERIF: &'static str = "Times New Roman";
-#[cfg(any(target_family = "windows", target_os = "macos"))]
-const DEFAULT_FONT_FAMILY_SANS_SERIF: &'static str = "Arial";
-#[cfg(any(target_family = "windows", target_os = "macos"))]
-const DEFAULT_FONT_FAMILY_MONOSPACE: &'static str = "Courier New";
-#[cfg(any(target_family = "windows", target_os = "macos"))]
-const DEFAULT_FONT_FAMILY_CURSIVE: &'static str = "Comic Sans MS";
-#[cfg(target_family = "windows")]
-const DEFAULT_FONT_FAMILY_FANTASY: &'static str = "Impact";
-#[cfg(target_os = "macos")]
-const DEFAULT_FONT_FAMILY_FANTASY: &'static str = "Papyrus";
-
-#[cfg(not(any(target_family = "windows", target_os = "macos")))]
-const DEFAULT_FONT_FAMILY_SERIF: &'static str = "serif";
-#[cfg(not(any(target_family = "windows", target_os = "macos")))]
-const DEFAULT_FONT_FAMILY_SANS_SERIF: &'static str = "sans-serif";
-#[cfg(not(any(target_family = "windows", target_os = "macos")))]
-const DEFAULT_FONT_FAMILY_MONOSPACE: &'static str = "monospace";
-#[cfg(not(any(target_family = "windows", target_os = "macos")))]
-const DEFAULT_FONT_FAMILY_CURSIVE: &'static str = "cursive";
-#[cfg(not(any(target_family = "windows", target_os = "macos")))]
-const DEFAULT_FONT_FAMILY_FANTASY: &'static str = "fantasy";
-
 /// A database of installed fonts that can be queried.
 ///
 /// This trait is object-safe.
@@ -76,41 +56,110 @@ pub trait Source {
 
     /// Selects a font by PostScript name, which should be a unique identifier.
     ///
-    /// The default implementation, which is used by the DirectWrite and the filesystem backends,
-    /// does a brute-force search of installed fonts to find the one that matches.
+    /// The default implementation, which is used by the DirectWrite backend, does a brute-force
+    /// search of installed fonts to find the one that matches, describing each font with
+    /// `describe_font()` rather than loading it. Fonts that can't be described are skipped.
     fn select_by_postscript_name(&self, postscript_name: &str) -> Result<Handle, SelectionError> {
-        // TODO(pcwalton): Optimize this by searching for families with similar names first.
-        for family_name in self.all_families()? {
-            if let Ok(family_handle) = self.select_family_by_name(&family_name) {
-                if let Ok(family) = Family::<Font>::from_handle(&family_handle) {
-                    for (handle, font) in family_handle.fonts().iter().zip(family.fonts().iter()) {
-                        if let Some(font_postscript_name) = font.postscript_name() {
-                            if font_postscript_name == postscript_name {
-                                return Ok((*handle).clone());
-                            }
-                        }
-                    }
+        for handle in self.all_fonts()? {
+            if let Ok(descriptor) = self.describe_font(&handle) {
+                if descriptor.postscript_name.as_deref() == Some(postscript_name) {
+                    return Ok(handle);
                 }
             }
         }
         Err(SelectionError::NotFound)
     }
 
-    // FIXME(pcwalton): This only returns one family instead of multiple families for the generic
-    // family names.
+    /// Returns the names, properties and format of the font referenced by the given handle.
+    ///
+    /// The default implementation reads them from the font's header with
+    /// `FontDescriptor::from_handle()`. Sources that index their fonts answer from the index
+    /// instead, as long as the handle refers to one of their fonts.
+    fn describe_font(&self, handle: &Handle) -> Result<FontDescriptor, SelectionError> {
+        Ok(FontDescriptor::from_handle(handle)?)
+    }
+
+    // Describes the font from this source's index, if the source has one and the font is in it.
     #[doc(hidden)]
-    fn select_family_by_generic_name(
+    fn describe_indexed_font(&self, _handle: &Handle) -> Option<FontDescriptor> {
+        None
+    }
+
+    /// Returns the mapping from generic family names to concrete families that this source uses.
+    ///
+    /// The default implementation returns the defaults for this platform.
+    fn generic_family_map(&self) -> &GenericFamilyMap {
+        &PLATFORM_GENERIC_FAMILY_MAP
+    }
+
+    /// Brings this source up to date with the fonts on disk and reports what changed.
+    ///
+    /// Sources that snapshot the installed fonts when they are created (Fontconfig, filesystem,
+    /// and memory sources) pick up fonts that have been installed, removed or modified since then.
+    /// The default implementation, which is used by the Core Text and DirectWrite backends, reports
+    /// no changes, as those backends rely on the system to keep their font databases current.
+    fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        Ok(SourceChanges::default())
+    }
+
+    /// Returns descriptions of all fonts that meet the given criteria, in the order of
+    /// `all_fonts()`.
+    ///
+    /// The default implementation, which is used by the Core Text and DirectWrite backends,
+    /// describes every font with `describe_font()` and only loads the fonts that meet the other
+    /// criteria to check their character coverage. Fonts that can't be described or loaded are
+    /// skipped.
+    fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        query.required_characters()?;
+        let mut descriptors = vec![];
+        for handle in self.all_fonts()? {
+            let descriptor = match self.describe_font(&handle) {
+                Ok(descriptor) => descriptor,
+                Err(_) => continue,
+            };
+            if !query.matches(&descriptor) {
+                continue;
+            }
+            if query.characters.is_empty() && query.scripts.is_empty() {
+                descriptors.push(descriptor);
+                continue;
+            }
+            if let Ok(font) = Font::from_handle(&handle) {
+                if query.is_covered_by(&font) {
+                    descriptors.push(descriptor)
+                }
+            }
+        }
+        Ok(descriptors)
+    }
+
+    /// Looks up a family by a specific or generic name and returns the handles of all the fonts
+    /// in that family.
+    ///
+    /// Generic names are resolved through `generic_family_map()`, using the overrides for the
+    /// given BCP 47 language tag if there are any. The concrete families are tried in order, and
+    /// the first one that exists is returned.
+    fn select_family_by_generic_name_for_locale(
         &self,
         family_name: &FamilyName,
+        locale: Option<&str>,
     ) -> Result<FamilyHandle, SelectionError> {
-        match *family_name {
-            FamilyName::Title(ref title) => self.select_family_by_name(title),
-            FamilyName::Serif => self.select_family_by_name(DEFAULT_FONT_FAMILY_SERIF),
-            FamilyName::SansSerif => self.select_family_by_name(DEFAULT_FONT_FAMILY_SANS_SERIF),
-            FamilyName::Monospace => self.select_family_by_name(DEFAULT_FONT_FAMILY_MONOSPACE),
-            FamilyName::Cursive => self.select_family_by_name(DEFAULT_FONT_FAMILY_CURSIVE),
-            FamilyName::Fantasy => self.select_family_by_name(DEFAULT_FONT_FAMILY_FANTASY),
+        for concrete_family_name in self.generic_family_map().families(family_name, locale) {
+            match self.select_family_by_name(&concrete_family_name) {
+                Ok(family_handle) => return Ok(family_handle),
+                Err(SelectionError::NotFound) => {}
+                Err(err) => return Err(err),
+            }
         }
+        Err(SelectionError::NotFound)
+    }
+
+    #[doc(hidden)]
+    fn select_family_by_generic_name(
+        &self,
+        family_name: &FamilyName,
+    ) -> Result<FamilyHandle, SelectionError> {
+        self.select_family_by_generic_name_for_locale(family_name, None)
     }
 
     /// Performs font matching according to the CSS Fonts Level 3 specification and returns the
@@ -132,6 +181,72 @@ pub trait Source {
         Err(SelectionError::NotFound)
     }
 
+    /// Performs font matching like `select_best_match()`, but prefers fonts that declare support
+    /// for the language identified by the given BCP 47 tag (e.g. `ja` or `zh-Hant`).
+    ///
+    /// Generic families are resolved with the overrides for the language in
+    /// `generic_family_map()`. A family containing fonts that declare support for the language is
+    /// preferred over earlier families in the list that contain none, and, within a family, those
+    /// fonts are preferred over the rest. If no family declares support for the language, this
+    /// picks the same family as `select_best_match()` would. Families whose fonts can't be read
+    /// are skipped.
+    fn select_best_match_for_locale(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: &str,
+    ) -> Result<Handle, SelectionError> {
+        let mut fallback = None;
+        for family_name in family_names {
+            let family_handle =
+                match self.select_family_by_generic_name_for_locale(family_name, Some(locale)) {
+                    Ok(family_handle) => family_handle,
+                    Err(_) => continue,
+                };
+            let candidates = match self.select_descriptions_in_family(&family_handle) {
+                Ok(candidates) => candidates,
+                Err(_) => continue,
+            };
+            let language_support =
+                match self.select_language_support_in_family(&family_handle, locale) {
+                    Ok(language_support) => language_support,
+                    Err(_) => continue,
+                };
+
+            let (supported_indices, supported_candidates): (Vec<_>, Vec<_>) = candidates
+                .iter()
+                .enumerate()
+                .zip(language_support)
+                .filter(|&(_, supported)| supported)
+                .map(|((index, candidate), _)| (index, candidate.clone()))
+                .unzip();
+            if let Ok(index) = matching::find_best_match(&supported_candidates, properties) {
+                return Ok(family_handle.fonts[supported_indices[index]].clone());
+            }
+
+            if fallback.is_none() {
+                if let Ok(index) = matching::find_best_match(&candidates, properties) {
+                    fallback = Some(family_handle.fonts[index].clone());
+                }
+            }
+        }
+        fallback.ok_or(SelectionError::NotFound)
+    }
+
+    #[doc(hidden)]
+    fn select_language_support_in_family(
+        &self,
+        family: &FamilyHandle,
+        locale: &str,
+    ) -> Result<Vec<bool>, SelectionError> {
+        let mut language_support = vec![];
+        for font_handle in family.fonts() {
+            let supported = descriptor::language_support_from_handle(font_handle)?;
+            language_support.push(supported.supports_language(locale));
+        }
+        Ok(language_support)
+    }
+
     #[doc(hidden)]
     fn select_descriptions_in_family(
         &self,
@@ -139,13 +254,86 @@ pub trait Source {
     ) -> Result<Vec<Description>, SelectionError> {
         let mut fields = vec![];
         for font_handle in family.fonts() {
-            let font = Font::from_handle(font_handle).unwrap();
-            let (family_name, properties) = (font.family_name(), font.properties());
+            let descriptor = self.describe_font(font_handle)?;
             fields.push(Description {
-                family_name,
-                properties,
+                family_name: descriptor.family_name,
+                properties: descriptor.properties,
             })
         }
         Ok(fields)
     }
 }
+
+/// The fonts that were added to, removed from, or modified in a source when it was refreshed.
+#[derive(Clone, Debug, Default)]
+pub struct SourceChanges {
+    /// Fonts that the source didn't contain before.
+    pub added: Vec<Handle>,
+    /// Fonts that the source no longer contains.
+    pub removed: Vec<Handle>,
+    /// Fonts that the source still contains, but whose files have been modified.
+    pub changed: Vec<Handle>,
+}
+
+impl SourceChanges {
+    /// Returns true if nothing changed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Appends the changes in `other` to these changes.
+    pub fn append(&mut self, other: SourceChanges) {
+        self.added.extend(other.added);
+        self.removed.extend(other.removed);
+        self.changed.extend(other.changed);
+    }
+
+    // Compares two lists of fonts on disk; fonts in memory never change, so they are skipped.
+    // Fonts in both lists count as changed if `modified` returns true for their path.
+    pub(crate) fn between<F>(old_fonts: Vec<Handle>, new_fonts: Vec<Handle>, modified: F) -> Self
+    where
+        F: Fn(&Path) -> bool,
+    {
+        fn key(handle: &Handle) -> Option<(PathBuf, u32)> {
+            match *handle {
+                Handle::Path {
+                    ref path,
+                    font_index,
+                } => Some((path.clone(), font_index)),
+                Handle::Memory { .. } => None,
+            }
+        }
+
+        let mut old_keys: HashSet<_> = old_fonts.iter().filter_map(key).collect();
+        let mut changes = SourceChanges::default();
+        for handle in new_fonts {
+            if let Some(key) = key(&handle) {
+                if !old_keys.remove(&key) {
+                    changes.added.push(handle)
+                } else if modified(&key.0) {
+                    changes.changed.push(handle)
+                }
+            }
+        }
+        changes.removed = old_fonts
+            .into_iter()
+            .filter(|handle| match key(handle) {
+                Some(key) => old_keys.contains(&key),
+                None => false,
+            })
+            .collect();
+        changes
+    }
+}
+
+/// How far a source has got with indexing its fonts, as reported to progress callbacks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct IndexingProgress {
+    /// The number of files (for filesystem sources) or fonts (for memory sources) that have been
+    /// indexed so far.
+    pub indexed: usize,
+    /// The number of files or fonts to index in all. Zero until filesystem sources have finished
+    /// scanning their directories.
+    pub total: usize,
+}
@@ -26,6 +26,7 @@ use family_handle::FamilyHandle;
 use family_name::FamilyName;
 use file_type::FileType;
 use font::Font;
+use generic_family_map::GenericFamilyMap;
 use handle::Handle;
 use properties::{Properties, Stretch, Weight};
 use source::Source;
@@ -35,7 +36,9 @@ pub(crate) static FONT_WEIGHT_MAPPING: [f32; 9] = [-0.7, -0.5, -0.23, 0.0, 0.2,
 
 /// A source that contains the installed fonts on macOS.
 #[allow(missing_debug_implementations)]
-pub struct CoreTextSource;
+pub struct CoreTextSource {
+    generic_family_map: GenericFamilyMap,
+}
 
 impl CoreTextSource {
     /// Opens a new connection to the system font source.
@@ -44,7 +47,9 @@ impl CoreTextSource {
     /// lazily on demand by the Core Text/Core Graphics API.)
     #[inline]
     pub fn new() -> CoreTextSource {
-        CoreTextSource
+        CoreTextSource {
+            generic_family_map: GenericFamilyMap::new(),
+        }
     }
 
     /// Returns paths of all fonts installed on the system.
@@ -92,10 +97,26 @@ impl CoreTextSource {
         let collection = font_collection::new_from_descriptors(&descriptors);
         match collection.get_descriptors() {
             None => Err(SelectionError::NotFound),
-            Some(descriptors) => Ok(create_handle_from_descriptor(&*descriptors.get(0).unwrap())),
+            Some(descriptors) => descriptors
+                .get(0)
+                .and_then(|descriptor| create_handle_from_descriptor(&*descriptor))
+                .ok_or(SelectionError::NotFound),
         }
     }
 
+    /// Returns the mapping from generic family names to concrete families that this source uses.
+    #[inline]
+    pub fn generic_family_map(&self) -> &GenericFamilyMap {
+        &self.generic_family_map
+    }
+
+    /// Replaces the mapping from generic family names to concrete families that this source
+    /// uses.
+    #[inline]
+    pub fn set_generic_family_map(&mut self, generic_family_map: GenericFamilyMap) {
+        self.generic_family_map = generic_family_map
+    }
+
     /// Performs font matching according to the CSS Fonts Level 3 specification and returns the
     /// handle.
     #[inline]
@@ -106,6 +127,18 @@ impl CoreTextSource {
     ) -> Result<Handle, SelectionError> {
         <Self as Source>::select_best_match(self, family_names, properties)
     }
+
+    /// Performs font matching like `select_best_match()`, but prefers fonts that declare support
+    /// for the language identified by the given BCP 47 tag (e.g. `ja` or `zh-Hant`).
+    #[inline]
+    pub fn select_best_match_for_locale(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: &str,
+    ) -> Result<Handle, SelectionError> {
+        <Self as Source>::select_best_match_for_locale(self, family_names, properties, locale)
+    }
 }
 
 impl Source for CoreTextSource {
@@ -124,6 +157,11 @@ impl Source for CoreTextSource {
     fn select_by_postscript_name(&self, postscript_name: &str) -> Result<Handle, SelectionError> {
         self.select_by_postscript_name(postscript_name)
     }
+
+    #[inline]
+    fn generic_family_map(&self) -> &GenericFamilyMap {
+        self.generic_family_map()
+    }
 }
 
 pub(crate) fn piecewise_linear_lookup(index: f32, mapping: &[f32]) -> f32 {
@@ -167,9 +205,8 @@ fn create_handles_from_core_text_collection(
 ) -> Result<Vec<Handle>, SelectionError> {
     let mut fonts = vec![];
     if let Some(descriptors) = collection.get_descriptors() {
-        for index in 0..descriptors.len() {
-            let descriptor = descriptors.get(index).unwrap();
-            fonts.push(create_handle_from_descriptor(&*descriptor));
+        for descriptor in descriptors.iter() {
+            fonts.extend(create_handle_from_descriptor(&*descriptor));
         }
     }
     if fonts.is_empty() {
@@ -179,8 +216,9 @@ fn create_handles_from_core_text_collection(
     }
 }
 
-fn create_handle_from_descriptor(descriptor: &CTFontDescriptor) -> Handle {
-    let font_path = Path::new(&descriptor.font_path().unwrap()).to_owned();
+// Descriptors without a font file (e.g. fonts registered from memory) are skipped.
+fn create_handle_from_descriptor(descriptor: &CTFontDescriptor) -> Option<Handle> {
+    let font_path = Path::new(&descriptor.font_path()?).to_owned();
     if let Ok(FileType::Collection(font_count)) = Font::analyze_path(font_path.clone()) {
         let postscript_name = descriptor.font_name();
         for font_index in 0..font_count {
@@ -188,13 +226,13 @@ fn create_handle_from_descriptor(descriptor: &CTFontDescriptor) -> Handle {
             if let Ok(font) = Font::from_handle(&font_handle) {
                 if let Some(font_postscript_name) = font.postscript_name() {
                     if postscript_name == font_postscript_name {
-                        return font_handle;
+                        return Some(font_handle);
                     }
                 }
             }
         }
     }
-    Handle::from_path(font_path, 0)
+    Some(Handle::from_path(font_path, 0))
 }
 
 #[cfg(test)]
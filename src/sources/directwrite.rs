@@ -16,6 +16,7 @@ use dwrote::FontCollection as DWriteFontCollection;
 use error::SelectionError;
 use family_handle::FamilyHandle;
 use family_name::FamilyName;
+use generic_family_map::GenericFamilyMap;
 use handle::Handle;
 use properties::Properties;
 use source::Source;
@@ -24,6 +25,7 @@ use source::Source;
 #[allow(missing_debug_implementations)]
 pub struct DirectWriteSource {
     system_font_collection: DWriteFontCollection,
+    generic_family_map: GenericFamilyMap,
 }
 
 impl DirectWriteSource {
@@ -31,6 +33,7 @@ impl DirectWriteSource {
     pub fn new() -> DirectWriteSource {
         DirectWriteSource {
             system_font_collection: DWriteFontCollection::system(),
+            generic_family_map: GenericFamilyMap::new(),
         }
     }
 
@@ -41,7 +44,7 @@ impl DirectWriteSource {
         for dwrite_family in self.system_font_collection.families_iter() {
             for font_index in 0..dwrite_family.get_font_count() {
                 let dwrite_font = dwrite_family.get_font(font_index);
-                handles.push(self.create_handle_from_dwrite_font(dwrite_font))
+                handles.extend(self.create_handle_from_dwrite_font(dwrite_font))
             }
         }
 
@@ -71,7 +74,9 @@ impl DirectWriteSource {
         };
         for font_index in 0..dwrite_family.get_font_count() {
             let dwrite_font = dwrite_family.get_font(font_index);
-            family.push(self.create_handle_from_dwrite_font(dwrite_font))
+            if let Some(handle) = self.create_handle_from_dwrite_font(dwrite_font) {
+                family.push(handle)
+            }
         }
         Ok(family)
     }
@@ -87,6 +92,19 @@ impl DirectWriteSource {
         <Self as Source>::select_by_postscript_name(self, postscript_name)
     }
 
+    /// Returns the mapping from generic family names to concrete families that this source uses.
+    #[inline]
+    pub fn generic_family_map(&self) -> &GenericFamilyMap {
+        &self.generic_family_map
+    }
+
+    /// Replaces the mapping from generic family names to concrete families that this source
+    /// uses.
+    #[inline]
+    pub fn set_generic_family_map(&mut self, generic_family_map: GenericFamilyMap) {
+        self.generic_family_map = generic_family_map
+    }
+
     /// Performs font matching according to the CSS Fonts Level 3 specification and returns the
     /// handle.
     #[inline]
@@ -98,13 +116,26 @@ impl DirectWriteSource {
         <Self as Source>::select_best_match(self, family_names, properties)
     }
 
-    fn create_handle_from_dwrite_font(&self, dwrite_font: DWriteFont) -> Handle {
+    /// Performs font matching like `select_best_match()`, but prefers fonts that declare support
+    /// for the language identified by the given BCP 47 tag (e.g. `ja` or `zh-Hant`).
+    #[inline]
+    pub fn select_best_match_for_locale(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: &str,
+    ) -> Result<Handle, SelectionError> {
+        <Self as Source>::select_best_match_for_locale(self, family_names, properties, locale)
+    }
+
+    // Fonts that aren't backed by a file on disk are skipped.
+    fn create_handle_from_dwrite_font(&self, dwrite_font: DWriteFont) -> Option<Handle> {
         let dwrite_font_face = dwrite_font.create_font_face();
         let dwrite_font_files = dwrite_font_face.get_files();
-        Handle::Path {
-            path: dwrite_font_files[0].get_font_file_path().unwrap(),
+        Some(Handle::Path {
+            path: dwrite_font_files.get(0)?.get_font_file_path()?,
             font_index: dwrite_font_face.get_index(),
-        }
+        })
     }
 }
 
@@ -123,4 +154,9 @@ impl Source for DirectWriteSource {
     fn select_family_by_name(&self, family_name: &str) -> Result<FamilyHandle, SelectionError> {
         self.select_family_by_name(family_name)
     }
+
+    #[inline]
+    fn generic_family_map(&self) -> &GenericFamilyMap {
+        self.generic_family_map()
+    }
 }
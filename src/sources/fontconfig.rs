@@ -15,12 +15,23 @@
 //! support. To prefer it over the native font source (only if you know what you're doing), use the
 //! `source-fontconfig-default` feature.
 
+use fontconfig::fontconfig as ffi;
+
+use canvas::{RasterizationOptions, SubpixelLayout};
+use descriptor::FontDescriptor;
 use error::SelectionError;
 use family_handle::FamilyHandle;
 use family_name::FamilyName;
+use file_type::FontFormat;
+use generic_family_map::GenericFamilyMap;
 use handle::Handle;
-use properties::Properties;
-use source::Source;
+use hinting::HintingOptions;
+use properties::{Properties, Stretch, Style, Weight};
+use query::FontQuery;
+use source::{Source, SourceChanges};
+use std::ops::Range;
+use std::time::SystemTime;
+use utils;
 
 /// A source that contains the fonts installed on the system, as reported by the Fontconfig
 /// library.
@@ -31,6 +42,8 @@ use source::Source;
 #[allow(missing_debug_implementations)]
 pub struct FontconfigSource {
     config: fc::Config,
+    loaded_at: SystemTime,
+    generic_family_map: GenericFamilyMap,
 }
 
 impl FontconfigSource {
@@ -38,6 +51,8 @@ impl FontconfigSource {
     pub fn new() -> FontconfigSource {
         FontconfigSource {
             config: fc::Config::new(),
+            loaded_at: SystemTime::now(),
+            generic_family_map: GenericFamilyMap::new(),
         }
     }
 
@@ -47,15 +62,13 @@ impl FontconfigSource {
 
         // We want the family name.
         let mut object_set = fc::ObjectSet::new();
-        object_set.push_string(fc::Object::File);
-        object_set.push_string(fc::Object::Index);
+        object_set.push_string(fc::Object::File)?;
+        object_set.push_string(fc::Object::Index)?;
 
-        let patterns = pattern
-            .list(&self.config, object_set)
-            .map_err(|_| SelectionError::NotFound)?;
+        let patterns = pattern.list(&self.config, object_set)?;
 
         let mut handles = vec![];
-        for patt in patterns {
+        for patt in patterns.iter() {
             let path = match patt.get_string(fc::Object::File) {
                 Some(v) => v,
                 None => continue,
@@ -85,14 +98,12 @@ impl FontconfigSource {
 
         // We want the family name.
         let mut object_set = fc::ObjectSet::new();
-        object_set.push_string(fc::Object::Family);
+        object_set.push_string(fc::Object::Family)?;
 
-        let patterns = pattern
-            .list(&self.config, object_set)
-            .map_err(|_| SelectionError::NotFound)?;
+        let patterns = pattern.list(&self.config, object_set)?;
 
         let mut result_families = vec![];
-        for patt in patterns {
+        for patt in patterns.iter() {
             if let Some(family) = patt.get_string(fc::Object::Family) {
                 result_families.push(family);
             }
@@ -113,29 +124,23 @@ impl FontconfigSource {
         use std::borrow::Cow;
 
         let family_name = match family_name {
-            "serif" | "sans-serif" | "monospace" | "cursive" | "fantasy" => {
-                Cow::from(self.select_generic_font(family_name)?)
-            }
+            "serif" | "sans-serif" | "monospace" | "cursive" | "fantasy" | "system-ui"
+            | "emoji" | "math" | "fangsong" => Cow::from(self.select_generic_font(family_name)?),
             _ => Cow::from(family_name),
         };
 
-        let pattern = fc::Pattern::from_name(family_name.as_ref());
+        let pattern = fc::Pattern::from_name(family_name.as_ref())?;
 
         let mut object_set = fc::ObjectSet::new();
-        object_set.push_string(fc::Object::File);
-        object_set.push_string(fc::Object::Index);
+        object_set.push_string(fc::Object::File)?;
+        object_set.push_string(fc::Object::Index)?;
 
-        let patterns = pattern
-            .list(&self.config, object_set)
-            .map_err(|_| SelectionError::NotFound)?;
+        let patterns = pattern.list(&self.config, object_set)?;
 
-        let mut handles = vec![];
-        for patt in patterns {
-            let font_path = patt.get_string(fc::Object::File).unwrap();
-            let font_index = patt.get_integer(fc::Object::Index).unwrap() as u32;
-            let handle = Handle::from_path(std::path::PathBuf::from(font_path), font_index);
-            handles.push(handle);
-        }
+        let handles: Vec<_> = patterns
+            .iter()
+            .filter_map(|patt| handle_from_pattern(&patt))
+            .collect();
 
         if !handles.is_empty() {
             Ok(FamilyHandle::from_font_handles(handles.into_iter()))
@@ -146,17 +151,16 @@ impl FontconfigSource {
 
     /// Selects a font by a generic name.
     ///
-    /// Accepts: serif, sans-serif, monospace, cursive and fantasy.
+    /// Accepts: serif, sans-serif, monospace, cursive, fantasy, system-ui, emoji, math and
+    /// fangsong.
     fn select_generic_font(&self, name: &str) -> Result<String, SelectionError> {
-        let mut pattern = fc::Pattern::from_name(name);
-        pattern.config_substitute(fc::MatchKind::Pattern);
+        let mut pattern = fc::Pattern::from_name(name)?;
+        pattern.config_substitute(&self.config, fc::MatchKind::Pattern);
         pattern.default_substitute();
 
-        let patterns = pattern
-            .sorted(&self.config)
-            .map_err(|_| SelectionError::NotFound)?;
+        let patterns = pattern.sorted(&self.config)?;
 
-        if let Some(patt) = patterns.into_iter().next() {
+        if let Some(patt) = patterns.iter().next() {
             if let Some(family) = patt.get_string(fc::Object::Family) {
                 return Ok(family);
             }
@@ -174,36 +178,315 @@ impl FontconfigSource {
         postscript_name: &str,
     ) -> Result<Handle, SelectionError> {
         let mut pattern = fc::Pattern::new();
-        pattern.push_string(fc::Object::PostScriptName, postscript_name.to_owned());
+        pattern.push_string(fc::Object::PostScriptName, postscript_name.to_owned())?;
 
         // We want the file path and the font index.
         let mut object_set = fc::ObjectSet::new();
-        object_set.push_string(fc::Object::File);
-        object_set.push_string(fc::Object::Index);
-
-        let patterns = pattern
-            .list(&self.config, object_set)
-            .map_err(|_| SelectionError::NotFound)?;
-
-        if let Some(patt) = patterns.into_iter().next() {
-            let font_path = patt.get_string(fc::Object::File).unwrap();
-            let font_index = patt.get_integer(fc::Object::Index).unwrap() as u32;
-            let handle = Handle::from_path(std::path::PathBuf::from(font_path), font_index);
-            Ok(handle)
-        } else {
-            Err(SelectionError::NotFound)
+        object_set.push_string(fc::Object::File)?;
+        object_set.push_string(fc::Object::Index)?;
+
+        let patterns = pattern.list(&self.config, object_set)?;
+
+        patterns
+            .iter()
+            .filter_map(|patt| handle_from_pattern(&patt))
+            .next()
+            .ok_or(SelectionError::NotFound)
+    }
+
+    /// Returns descriptions of all fonts that meet the given criteria.
+    ///
+    /// Everything is read from Fontconfig's cache, including character coverage, so no fonts are
+    /// loaded.
+    pub fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        let characters = query.required_characters()?;
+
+        // Fontconfig lists only the fonts whose character sets contain this one.
+        let mut pattern = fc::Pattern::new();
+        if !characters.is_empty() {
+            let mut charset = fc::CharSet::new();
+            for &character in &characters {
+                charset.push_char(character);
+            }
+            pattern.push_charset(&charset);
         }
+
+        let mut object_set = fc::ObjectSet::new();
+        for &object in &[
+            fc::Object::File,
+            fc::Object::Index,
+            fc::Object::Family,
+            fc::Object::FullName,
+            fc::Object::PostScriptName,
+            fc::Object::Weight,
+            fc::Object::Slant,
+            fc::Object::Width,
+            fc::Object::Spacing,
+            fc::Object::FontFormat,
+            fc::Object::Variable,
+        ] {
+            object_set.push_string(object)?;
+        }
+
+        let patterns = pattern.list(&self.config, object_set)?;
+        Ok(patterns
+            .iter()
+            .filter_map(|patt| descriptor_from_pattern(&patt))
+            .filter(|descriptor| query.matches(descriptor))
+            .collect())
     }
 
-    /// Performs font matching according to the CSS Fonts Level 3 specification and returns the
-    /// handle.
+    /// Reloads the Fontconfig configuration and font list if any configuration file or font
+    /// directory has changed since they were loaded (as `FcConfigUptoDate` reports), and reports
+    /// the fonts that were added, removed or modified.
+    pub fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        if self.config.is_up_to_date() {
+            return Ok(SourceChanges::default());
+        }
+
+        let old_fonts = self.all_fonts().unwrap_or_default();
+        let loaded_at = self.loaded_at;
+        self.loaded_at = SystemTime::now();
+        self.config = fc::Config::new();
+        let new_fonts = self.all_fonts().unwrap_or_default();
+
+        Ok(SourceChanges::between(
+            old_fonts,
+            new_fonts,
+            |path| match utils::file_stamp(path) {
+                Some((modified, _)) => modified >= loaded_at,
+                None => true,
+            },
+        ))
+    }
+
+    /// Returns the mapping from generic family names to concrete families that this source uses.
+    ///
+    /// By default, this is the platform's mapping, in which generic families map to any
+    /// per-language families first and then to the Fontconfig aliases of the same names (`serif`,
+    /// `sans-serif`, etc.), which the user's configuration resolves.
     #[inline]
+    pub fn generic_family_map(&self) -> &GenericFamilyMap {
+        &self.generic_family_map
+    }
+
+    /// Replaces the mapping from generic family names to concrete families that this source
+    /// uses.
+    #[inline]
+    pub fn set_generic_family_map(&mut self, generic_family_map: GenericFamilyMap) {
+        self.generic_family_map = generic_family_map
+    }
+
+    /// Performs font matching with Fontconfig and returns the handle.
+    ///
+    /// The family names and properties become a Fontconfig pattern, which goes through the
+    /// user's configuration and the default substitutions. `FcFontSetSort` then orders the fonts
+    /// of the requested families against it, so no font files are loaded, and the first family
+    /// that exists wins. Generic families resolve through the generic family map; when they reach
+    /// a Fontconfig alias (`serif`, `sans-serif`, etc.), `FcFontSort` picks the user's preferred
+    /// font for it. Unlike `fc-match`, this doesn't fall back to the user's default fonts for
+    /// specific families, but returns `SelectionError::NotFound`, as other sources do.
     pub fn select_best_match(
         &self,
         family_names: &[FamilyName],
         properties: &Properties,
     ) -> Result<Handle, SelectionError> {
-        <Self as Source>::select_best_match(self, family_names, properties)
+        let pattern = self.query_pattern(family_names, properties, None)?;
+        self.select_best_match_with_pattern(pattern, family_names, None)
+    }
+
+    /// Performs font matching like `select_best_match()`, but prefers fonts that declare support
+    /// for the language identified by the given BCP 47 tag (e.g. `ja` or `zh-Hant`).
+    ///
+    /// The language goes into the pattern, where Fontconfig weighs it against its `lang`
+    /// property, which it derives from the font's tables and character coverage, and the user's
+    /// per-language rules apply.
+    pub fn select_best_match_for_locale(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: &str,
+    ) -> Result<Handle, SelectionError> {
+        let pattern = self.query_pattern(family_names, properties, Some(locale))?;
+        self.select_best_match_with_pattern(pattern, family_names, Some(locale))
+    }
+
+    // Builds the pattern for a query, before any substitutions. Generic families are expanded
+    // through the generic family map, which by default yields any per-language families followed
+    // by the Fontconfig aliases (`serif`, `sans-serif`, etc.) that the user's configuration
+    // customizes.
+    fn query_pattern(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: Option<&str>,
+    ) -> Result<fc::Pattern, SelectionError> {
+        let mut pattern = fc::Pattern::new();
+        for family_name in family_names {
+            for concrete_family_name in self.generic_family_map.families(family_name, locale) {
+                pattern.push_string(fc::Object::Family, concrete_family_name)?;
+            }
+        }
+        pattern.push_integer(
+            fc::Object::Weight,
+            css_to_fontconfig_weight(properties.weight),
+        );
+        pattern.push_integer(fc::Object::Slant, css_to_fontconfig_slant(properties.style));
+        pattern.push_integer(
+            fc::Object::Width,
+            css_to_fontconfig_width(properties.stretch),
+        );
+        if let Some(locale) = locale {
+            pattern.push_string(fc::Object::Lang, fontconfig_language(locale))?;
+        }
+        Ok(pattern)
+    }
+
+    /// Returns the fonts to render the given text with, in the order of the user's Fontconfig
+    /// fallback chain for the given families, properties and language.
+    ///
+    /// This is the chain that `FcFontSort` produces, with fonts that add no coverage trimmed
+    /// away, further narrowed to the fonts that cover characters of `text` that no earlier font
+    /// in the chain covers. Each font comes with the byte ranges of `text` whose characters it
+    /// supports, so the result can be used to implement `Loader::get_fallbacks()`: a character
+    /// should be rendered with the first font that covers it. Characters that no font covers
+    /// appear in no ranges.
+    ///
+    /// `locale` is a BCP 47 language tag such as `"en-US"`, or an empty string if the language is
+    /// unknown.
+    pub fn select_fallbacks(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: &str,
+        text: &str,
+    ) -> Result<Vec<FallbackHandle>, SelectionError> {
+        let locale = if locale.trim().is_empty() {
+            None
+        } else {
+            Some(locale)
+        };
+        let mut pattern = self.query_pattern(family_names, properties, locale)?;
+        pattern.config_substitute(&self.config, fc::MatchKind::Pattern);
+        pattern.default_substitute();
+
+        let chars: Vec<(usize, char)> = text.char_indices().collect();
+        let mut covered = vec![false; chars.len()];
+        let mut uncovered_count = chars.len();
+
+        let mut fallbacks = vec![];
+        let patterns = pattern.sorted(&self.config)?;
+        for patt in patterns.iter() {
+            if uncovered_count == 0 {
+                break;
+            }
+            let (handle, charset) = match (handle_from_pattern(&patt), patt.get_charset()) {
+                (Some(handle), Some(charset)) => (handle, charset),
+                _ => continue,
+            };
+
+            let mut coverage: Vec<Range<usize>> = vec![];
+            let mut adds_coverage = false;
+            for (char_index, &(offset, character)) in chars.iter().enumerate() {
+                if !charset.has_char(character) {
+                    continue;
+                }
+                if !covered[char_index] {
+                    covered[char_index] = true;
+                    uncovered_count -= 1;
+                    adds_coverage = true;
+                }
+                let end = offset + character.len_utf8();
+                match coverage.last_mut() {
+                    Some(range) if range.end == offset => range.end = end,
+                    _ => coverage.push(offset..end),
+                }
+            }
+
+            if adds_coverage {
+                fallbacks.push(FallbackHandle { handle, coverage });
+            }
+        }
+        Ok(fallbacks)
+    }
+
+    /// Returns the hinting, antialiasing and related settings that the user's Fontconfig
+    /// configuration requests for the given font at the given size, in pixels per em.
+    ///
+    /// These are the settings that `fc-match` reports for the font: the font goes through the
+    /// configuration's substitutions and its per-font rules, so preferences like disabling
+    /// hinting for small sizes or for particular families are honored. Fonts that Fontconfig
+    /// doesn't know about, including those in memory, get the settings that apply to the user's
+    /// default font.
+    pub fn rendering_preferences(
+        &self,
+        font_handle: &Handle,
+        size: f32,
+    ) -> Result<RenderingPreferences, SelectionError> {
+        let mut pattern = fc::Pattern::new();
+        if let Handle::Path {
+            ref path,
+            font_index,
+        } = *font_handle
+        {
+            if let Some(path) = path.to_str() {
+                pattern.push_string(fc::Object::File, path.to_owned())?;
+                pattern.push_integer(fc::Object::Index, font_index as i32);
+            }
+        }
+
+        // Our sizes are in pixels, which are points at 72 DPI.
+        pattern.push_double(fc::Object::Size, size as f64);
+        pattern.push_double(fc::Object::PixelSize, size as f64);
+
+        pattern.config_substitute(&self.config, fc::MatchKind::Pattern);
+        pattern.default_substitute();
+
+        let font_pattern = pattern.font_match(&self.config)?;
+        Ok(rendering_preferences_from_pattern(
+            &font_pattern.as_pattern_ref(),
+            size,
+        ))
+    }
+
+    // Returns the first of the fonts of the requested families in Fontconfig's order for the
+    // pattern. If none of the families before the first Fontconfig generic family exists, the
+    // generic family resolves to the first font in `FcFontSort` order.
+    fn select_best_match_with_pattern(
+        &self,
+        mut pattern: fc::Pattern,
+        family_names: &[FamilyName],
+        locale: Option<&str>,
+    ) -> Result<Handle, SelectionError> {
+        let mut family_pattern = fc::Pattern::new();
+        let mut has_concrete_families = false;
+        let mut has_generic_family = false;
+        'families: for family_name in family_names {
+            for concrete_family_name in self.generic_family_map.families(family_name, locale) {
+                if FONTCONFIG_GENERIC_FAMILIES.contains(&&*concrete_family_name) {
+                    has_generic_family = true;
+                    break 'families;
+                }
+                family_pattern.push_string(fc::Object::Family, concrete_family_name)?;
+                has_concrete_families = true;
+            }
+        }
+
+        pattern.config_substitute(&self.config, fc::MatchKind::Pattern);
+        pattern.default_substitute();
+
+        if has_concrete_families {
+            let fonts = family_pattern.list_all(&self.config)?;
+            if !fonts.is_empty() {
+                if let Some(handle) = first_handle(&pattern.sort_font_set(&self.config, &fonts)?) {
+                    return Ok(handle);
+                }
+            }
+        }
+        if !has_generic_family {
+            return Err(SelectionError::NotFound);
+        }
+        first_handle(&pattern.sorted(&self.config)?).ok_or(SelectionError::NotFound)
     }
 }
 
@@ -227,19 +510,340 @@ impl Source for FontconfigSource {
     fn select_by_postscript_name(&self, postscript_name: &str) -> Result<Handle, SelectionError> {
         self.select_by_postscript_name(postscript_name)
     }
+
+    #[inline]
+    fn generic_family_map(&self) -> &GenericFamilyMap {
+        self.generic_family_map()
+    }
+
+    #[inline]
+    fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        self.refresh()
+    }
+
+    #[inline]
+    fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        self.query_fonts(query)
+    }
+
+    #[inline]
+    fn select_best_match(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+    ) -> Result<Handle, SelectionError> {
+        self.select_best_match(family_names, properties)
+    }
+
+    #[inline]
+    fn select_best_match_for_locale(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: &str,
+    ) -> Result<Handle, SelectionError> {
+        self.select_best_match_for_locale(family_names, properties, locale)
+    }
+}
+
+/// A font in a fallback chain, as returned by `FontconfigSource::select_fallbacks()`.
+#[derive(Clone, Debug)]
+pub struct FallbackHandle {
+    /// The font.
+    pub handle: Handle,
+    /// The byte ranges of the text whose characters this font supports, in order. Adjacent
+    /// characters are merged into a single range.
+    pub coverage: Vec<Range<usize>>,
+}
+
+/// The rendering settings that the user's Fontconfig configuration requests for a font.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderingPreferences {
+    /// The hinting to apply, from the `hinting` and `hintstyle` properties.
+    pub hinting_options: HintingOptions,
+    /// The antialiasing to apply, from the `antialias` and `rgba` properties.
+    pub rasterization_options: RasterizationOptions,
+    /// The subpixel order of the screen, from the `rgba` property, or `None` if it is unknown or
+    /// the screen has no subpixels.
+    pub subpixel_layout: Option<SubpixelLayout>,
+    /// The filter to apply to subpixel-antialiased glyphs, from the `lcdfilter` property.
+    pub lcd_filter: LcdFilter,
+    /// Whether embedded bitmaps should be used in place of outlines when the font has them, from
+    /// the `embeddedbitmap` property.
+    pub embedded_bitmaps: bool,
+}
+
+/// The filter that reduces color fringes when rendering with subpixel antialiasing.
+///
+/// These correspond to FreeType's LCD filters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LcdFilter {
+    /// No filtering.
+    None,
+    /// The default FIR filter, which suits most screens.
+    Default,
+    /// A lighter FIR filter, which is sharper but shows more color fringes.
+    Light,
+    /// The filter used by older versions of FreeType.
+    Legacy,
+}
+
+// Values of `FC_HINT_STYLE`, `FC_RGBA` and `FC_LCD_FILTER`, which the bindings lack.
+const FC_HINT_NONE: i32 = 0;
+const FC_HINT_SLIGHT: i32 = 1;
+const FC_RGBA_RGB: i32 = 1;
+const FC_RGBA_BGR: i32 = 2;
+const FC_RGBA_VRGB: i32 = 3;
+const FC_RGBA_VBGR: i32 = 4;
+const FC_LCD_DEFAULT: i32 = 1;
+const FC_LCD_LIGHT: i32 = 2;
+const FC_LCD_LEGACY: i32 = 3;
+
+// Missing properties get the defaults that `FcDefaultSubstitute()` and FreeType's Fontconfig
+// clients use. Fontconfig's medium hint style has no FreeType counterpart, so it means full
+// hinting.
+fn rendering_preferences_from_pattern(pattern: &fc::PatternRef, size: f32) -> RenderingPreferences {
+    let subpixel_layout = match pattern.get_integer(fc::Object::Rgba) {
+        Some(FC_RGBA_RGB) => Some(SubpixelLayout::Rgb),
+        Some(FC_RGBA_BGR) => Some(SubpixelLayout::Bgr),
+        Some(FC_RGBA_VRGB) => Some(SubpixelLayout::VerticalRgb),
+        Some(FC_RGBA_VBGR) => Some(SubpixelLayout::VerticalBgr),
+        _ => None,
+    };
+
+    let rasterization_options = if !pattern.get_bool(fc::Object::Antialias).unwrap_or(true) {
+        RasterizationOptions::Bilevel
+    } else if subpixel_layout.is_some() {
+        RasterizationOptions::SubpixelAa
+    } else {
+        RasterizationOptions::GrayscaleAa
+    };
+
+    let hinting = pattern.get_bool(fc::Object::Hinting).unwrap_or(true);
+    let hint_style = pattern.get_integer(fc::Object::HintStyle);
+    let hinting_options = match hint_style {
+        _ if !hinting => HintingOptions::None,
+        Some(FC_HINT_NONE) => HintingOptions::None,
+        Some(FC_HINT_SLIGHT) if rasterization_options == RasterizationOptions::SubpixelAa => {
+            HintingOptions::VerticalSubpixel(size)
+        }
+        Some(FC_HINT_SLIGHT) => HintingOptions::Vertical(size),
+        _ => HintingOptions::Full(size),
+    };
+
+    let lcd_filter = match pattern.get_integer(fc::Object::LcdFilter) {
+        Some(FC_LCD_DEFAULT) => LcdFilter::Default,
+        Some(FC_LCD_LIGHT) => LcdFilter::Light,
+        Some(FC_LCD_LEGACY) => LcdFilter::Legacy,
+        _ => LcdFilter::None,
+    };
+
+    RenderingPreferences {
+        hinting_options,
+        rasterization_options,
+        subpixel_layout,
+        lcd_filter,
+        embedded_bitmaps: pattern.get_bool(fc::Object::EmbeddedBitmap).unwrap_or(true),
+    }
+}
+
+// The `FcWeightFromOpenType()` mapping, which this version of Fontconfig predates.
+static FONT_WEIGHT_MAPPING: [(f32, f32); 12] = [
+    (100.0, ffi::FC_WEIGHT_THIN as f32),
+    (200.0, ffi::FC_WEIGHT_EXTRALIGHT as f32),
+    (300.0, ffi::FC_WEIGHT_LIGHT as f32),
+    (350.0, 55.0), // FC_WEIGHT_DEMILIGHT
+    (380.0, ffi::FC_WEIGHT_BOOK as f32),
+    (400.0, ffi::FC_WEIGHT_REGULAR as f32),
+    (500.0, ffi::FC_WEIGHT_MEDIUM as f32),
+    (600.0, ffi::FC_WEIGHT_DEMIBOLD as f32),
+    (700.0, ffi::FC_WEIGHT_BOLD as f32),
+    (800.0, ffi::FC_WEIGHT_EXTRABOLD as f32),
+    (900.0, ffi::FC_WEIGHT_BLACK as f32),
+    (1000.0, ffi::FC_WEIGHT_EXTRABLACK as f32),
+];
+
+fn css_to_fontconfig_weight(css_weight: Weight) -> i32 {
+    let css_weight = css_weight.0;
+    let mut previous = FONT_WEIGHT_MAPPING[0];
+    if css_weight <= previous.0 {
+        return previous.1 as i32;
+    }
+    for &next in &FONT_WEIGHT_MAPPING[1..] {
+        if css_weight <= next.0 {
+            let t = (css_weight - previous.0) / (next.0 - previous.0);
+            return (previous.1 + t * (next.1 - previous.1)).round() as i32;
+        }
+        previous = next;
+    }
+    previous.1 as i32
+}
+
+fn css_to_fontconfig_slant(css_style: Style) -> i32 {
+    match css_style {
+        Style::Normal => ffi::FC_SLANT_ROMAN,
+        Style::Italic => ffi::FC_SLANT_ITALIC,
+        Style::Oblique => ffi::FC_SLANT_OBLIQUE,
+    }
+}
+
+// `FC_WIDTH` is the width as a percentage of normal, like the CSS `font-stretch` percentages.
+fn css_to_fontconfig_width(css_stretch: Stretch) -> i32 {
+    (css_stretch.0 * 100.0).round() as i32
+}
+
+fn fontconfig_to_css_weight(fontconfig_weight: i32) -> Weight {
+    let fontconfig_weight = fontconfig_weight as f32;
+    let mut previous = FONT_WEIGHT_MAPPING[0];
+    if fontconfig_weight <= previous.1 {
+        return Weight(previous.0);
+    }
+    for &next in &FONT_WEIGHT_MAPPING[1..] {
+        if fontconfig_weight <= next.1 {
+            let t = (fontconfig_weight - previous.1) / (next.1 - previous.1);
+            return Weight((previous.0 + t * (next.0 - previous.0)).round());
+        }
+        previous = next;
+    }
+    Weight(previous.0)
+}
+
+fn fontconfig_to_css_slant(fontconfig_slant: i32) -> Style {
+    match fontconfig_slant {
+        ffi::FC_SLANT_ITALIC => Style::Italic,
+        ffi::FC_SLANT_OBLIQUE => Style::Oblique,
+        _ => Style::Normal,
+    }
+}
+
+// `FC_SPACING` values of 100 (`FC_MONO`) and above mean that all glyphs have the same advance.
+const FC_MONO: i32 = 100;
+
+// The `FC_FONTFORMAT` strings are FreeType's format names.
+fn font_format_from_fontconfig(fontconfig_format: &str) -> FontFormat {
+    match fontconfig_format {
+        "TrueType" => FontFormat::TrueType,
+        "CFF" => FontFormat::Cff,
+        "Type 1" | "CID Type 1" => FontFormat::Type1,
+        "PCF" | "BDF" | "Windows FNT" => FontFormat::Bitmap,
+        _ => FontFormat::Other,
+    }
+}
+
+// Properties that Fontconfig doesn't know take their defaults. Named instances of variable
+// fonts, whose indices carry the instance number in their upper bits, are skipped, since the
+// loaders can only load the default instance.
+fn descriptor_from_pattern(pattern: &fc::PatternRef) -> Option<FontDescriptor> {
+    let handle = handle_from_pattern(pattern)?;
+    match handle {
+        Handle::Path { font_index, .. } if font_index > 0xffff => return None,
+        _ => {}
+    }
+
+    let mut properties = Properties::new();
+    if let Some(weight) = pattern.get_integer(fc::Object::Weight) {
+        properties.weight = fontconfig_to_css_weight(weight);
+    }
+    if let Some(slant) = pattern.get_integer(fc::Object::Slant) {
+        properties.style = fontconfig_to_css_slant(slant);
+    }
+    if let Some(width) = pattern.get_integer(fc::Object::Width) {
+        properties.stretch = Stretch(width as f32 / 100.0);
+    }
+
+    let family_name = pattern.get_string(fc::Object::Family)?;
+    Some(FontDescriptor {
+        handle,
+        full_name: pattern
+            .get_string(fc::Object::FullName)
+            .unwrap_or_else(|| family_name.clone()),
+        family_name,
+        postscript_name: pattern.get_string(fc::Object::PostScriptName),
+        properties,
+        is_monospace: match pattern.get_integer(fc::Object::Spacing) {
+            Some(spacing) => spacing >= FC_MONO,
+            None => false,
+        },
+        format: pattern
+            .get_string(fc::Object::FontFormat)
+            .map_or(FontFormat::Other, |format| {
+                font_format_from_fontconfig(&format)
+            }),
+        is_variable: pattern.get_bool(fc::Object::Variable).unwrap_or(false),
+    })
+}
+
+// Converts a BCP 47 tag to Fontconfig's RFC 3066-style language names, which have no script
+// subtags: Chinese scripts become their usual regions (`zh-Hant` is `zh-tw`), and other scripts
+// are dropped.
+fn fontconfig_language(tag: &str) -> String {
+    let tag = tag.trim().replace('_', "-").to_ascii_lowercase();
+    let mut subtags = tag.split('-');
+    let language = subtags.next().unwrap_or("").to_owned();
+    let (mut script, mut region) = (None, None);
+    for subtag in subtags {
+        match subtag.len() {
+            4 if script.is_none() => script = Some(subtag),
+            2 if region.is_none() => region = Some(subtag),
+            _ => {}
+        }
+    }
+    let region = match (&*language, script, region) {
+        (_, _, Some(region)) => Some(region),
+        ("zh", Some("hant"), None) => Some("tw"),
+        ("zh", Some("hans"), None) => Some("cn"),
+        _ => None,
+    };
+    match region {
+        Some(region) => format!("{}-{}", language, region),
+        None => language,
+    }
+}
+
+// The Fontconfig aliases for generic families, which the user's configuration resolves to
+// fonts. The generic family map puts them before the common concrete families.
+static FONTCONFIG_GENERIC_FAMILIES: [&str; 9] = [
+    "serif",
+    "sans-serif",
+    "monospace",
+    "cursive",
+    "fantasy",
+    "system-ui",
+    "emoji",
+    "math",
+    "fangsong",
+];
+
+// Fonts whose path isn't valid UTF-8 or that lack an index are skipped.
+fn handle_from_pattern(pattern: &fc::PatternRef) -> Option<Handle> {
+    let font_path = pattern.get_string(fc::Object::File)?;
+    let font_index = pattern.get_integer(fc::Object::Index)?;
+    Some(Handle::from_path(
+        std::path::PathBuf::from(font_path),
+        font_index as u32,
+    ))
+}
+
+fn first_handle(fonts: &fc::FontSet) -> Option<Handle> {
+    fonts
+        .iter()
+        .filter_map(|pattern| handle_from_pattern(&pattern))
+        .next()
 }
 
 // A minimal fontconfig wrapper.
 mod fc {
     #![allow(dead_code)]
 
+    use error::{BackendError, SelectionError};
     use fontconfig::fontconfig as ffi;
 
     use std::ffi::{CStr, CString};
+    use std::marker::PhantomData;
     use std::os::raw::{c_char, c_uchar};
     use std::ptr;
 
-    #[derive(Clone, Copy)]
+    #[derive(Clone, Copy, Debug)]
     pub enum Error {
         NoMatch,
         TypeMismatch,
@@ -247,6 +851,18 @@ mod fc {
         OutOfMemory,
     }
 
+    impl From<Error> for SelectionError {
+        fn from(error: Error) -> SelectionError {
+            let code = match error {
+                Error::NoMatch => return SelectionError::NotFound,
+                Error::TypeMismatch => ffi::FcResultTypeMismatch,
+                Error::NoId => ffi::FcResultNoId,
+                Error::OutOfMemory => ffi::FcResultOutOfMemory,
+            };
+            SelectionError::CannotAccessSource(Some(BackendError::Fontconfig(code)))
+        }
+    }
+
     #[derive(Clone, Copy)]
     pub enum MatchKind {
         Pattern,
@@ -267,24 +883,58 @@ mod fc {
     // https://www.freedesktop.org/software/fontconfig/fontconfig-devel/x19.html
     #[derive(Clone, Copy)]
     pub enum Object {
+        Antialias,
+        CharSet,
+        EmbeddedBitmap,
         Family,
         File,
+        FontFormat,
+        FullName,
+        HintStyle,
+        Hinting,
         Index,
+        Lang,
+        LcdFilter,
+        PixelSize,
         PostScriptName,
+        Rgba,
+        Size,
+        Slant,
+        Spacing,
+        Variable,
+        Weight,
+        Width,
     }
 
     impl Object {
         fn as_bytes(&self) -> &[u8] {
             match self {
+                Object::Antialias => b"antialias\0",
+                Object::CharSet => b"charset\0",
+                Object::EmbeddedBitmap => b"embeddedbitmap\0",
                 Object::Family => b"family\0",
                 Object::File => b"file\0",
+                Object::FontFormat => b"fontformat\0",
+                Object::FullName => b"fullname\0",
+                Object::HintStyle => b"hintstyle\0",
+                Object::Hinting => b"hinting\0",
                 Object::Index => b"index\0",
+                Object::Lang => b"lang\0",
+                Object::LcdFilter => b"lcdfilter\0",
+                Object::PixelSize => b"pixelsize\0",
                 Object::PostScriptName => b"postscriptname\0",
+                Object::Rgba => b"rgba\0",
+                Object::Size => b"size\0",
+                Object::Slant => b"slant\0",
+                Object::Spacing => b"spacing\0",
+                Object::Variable => b"variable\0",
+                Object::Weight => b"weight\0",
+                Object::Width => b"width\0",
             }
         }
 
-        fn as_ptr(&self) -> *const libc::c_char {
-            self.as_bytes().as_ptr() as *const libc::c_char
+        fn as_ptr(&self) -> *const c_char {
+            self.as_bytes().as_ptr() as *const c_char
         }
     }
 
@@ -301,6 +951,17 @@ mod fc {
                 }
             }
         }
+
+        // FcConfigUptoDate
+        pub fn is_up_to_date(&self) -> bool {
+            unsafe { ffi::FcConfigUptoDate(self.d) != 0 }
+        }
+    }
+
+    impl Drop for Config {
+        fn drop(&mut self) {
+            unsafe { ffi::FcConfigDestroy(self.d) }
+        }
     }
 
     pub struct Pattern {
@@ -322,15 +983,23 @@ mod fc {
         }
 
         // FcNameParse
-        pub fn from_name(name: &str) -> Self {
-            let c_name = CString::new(name).unwrap();
-            unsafe { Pattern::from_ptr(ffi::FcNameParse(c_name.as_ptr() as *mut c_uchar)) }
+        //
+        // Names with embedded NULs can't match any font.
+        pub fn from_name(name: &str) -> Result<Self, Error> {
+            let c_name = CString::new(name).map_err(|_| Error::NoMatch)?;
+            unsafe {
+                let d = ffi::FcNameParse(c_name.as_ptr() as *mut c_uchar);
+                if d.is_null() {
+                    return Err(Error::NoMatch);
+                }
+                Ok(Pattern::from_ptr(d))
+            }
         }
 
         // FcPatternAddString
-        pub fn push_string(&mut self, object: Object, value: String) {
+        pub fn push_string(&mut self, object: Object, value: String) -> Result<(), Error> {
             unsafe {
-                let c_string = CString::new(value).unwrap();
+                let c_string = CString::new(value).map_err(|_| Error::NoMatch)?;
                 ffi::FcPatternAddString(
                     self.d,
                     object.as_ptr(),
@@ -338,14 +1007,53 @@ mod fc {
                 );
 
                 // We have to keep this string, because `FcPattern` has a pointer to it now.
-                self.c_strings.push(c_string)
+                self.c_strings.push(c_string);
+                Ok(())
+            }
+        }
+
+        // FcPatternAddInteger
+        pub fn push_integer(&mut self, object: Object, value: i32) {
+            unsafe {
+                ffi::FcPatternAddInteger(self.d, object.as_ptr(), value);
+            }
+        }
+
+        // FcPatternAddDouble
+        pub fn push_double(&mut self, object: Object, value: f64) {
+            unsafe {
+                ffi::FcPatternAddDouble(self.d, object.as_ptr(), value);
+            }
+        }
+
+        // FcPatternAddBool
+        pub fn push_bool(&mut self, object: Object, value: bool) {
+            unsafe {
+                ffi::FcPatternAddBool(self.d, object.as_ptr(), value as ffi::FcBool);
+            }
+        }
+
+        // FcPatternAddCharSet
+        //
+        // The pattern takes its own reference to the character set.
+        pub fn push_charset(&mut self, charset: &CharSet) {
+            unsafe {
+                ffi::FcPatternAddCharSet(self.d, Object::CharSet.as_ptr(), charset.d);
+            }
+        }
+
+        // The pattern's values can be read through the returned reference while `self` lives.
+        pub fn as_pattern_ref(&self) -> PatternRef<'_> {
+            PatternRef {
+                d: self.d,
+                phantom: PhantomData,
             }
         }
 
         // FcConfigSubstitute
-        pub fn config_substitute(&mut self, match_kind: MatchKind) {
+        pub fn config_substitute(&mut self, config: &Config, match_kind: MatchKind) {
             unsafe {
-                ffi::FcConfigSubstitute(ptr::null_mut(), self.d, match_kind.to_u32());
+                ffi::FcConfigSubstitute(config.d, self.d, match_kind.to_u32());
             }
         }
 
@@ -362,7 +1070,48 @@ mod fc {
             let d = unsafe { ffi::FcFontSort(config.d, self.d, 1, ptr::null_mut(), &mut res) };
 
             match res {
-                ffi::FcResultMatch => Ok(FontSet { d, idx: 0 }),
+                ffi::FcResultMatch => Ok(FontSet { d }),
+                ffi::FcResultTypeMismatch => Err(Error::TypeMismatch),
+                ffi::FcResultNoId => Err(Error::NoId),
+                ffi::FcResultOutOfMemory => Err(Error::OutOfMemory),
+                _ => Err(Error::NoMatch),
+            }
+        }
+
+        // FcFontSetSort, without trimming, since only the order matters.
+        pub fn sort_font_set(&self, config: &Config, fonts: &FontSet) -> Result<FontSet, Error> {
+            let mut res = ffi::FcResultMatch;
+            let mut sets = [fonts.d];
+            let d = unsafe {
+                ffi::FcFontSetSort(
+                    config.d,
+                    sets.as_mut_ptr(),
+                    1,
+                    self.d,
+                    0,
+                    ptr::null_mut(),
+                    &mut res,
+                )
+            };
+
+            match res {
+                ffi::FcResultMatch if !d.is_null() => Ok(FontSet { d }),
+                ffi::FcResultTypeMismatch => Err(Error::TypeMismatch),
+                ffi::FcResultNoId => Err(Error::NoId),
+                ffi::FcResultOutOfMemory => Err(Error::OutOfMemory),
+                _ => Err(Error::NoMatch),
+            }
+        }
+
+        // FcFontMatch
+        //
+        // The returned pattern is the best match with the configuration's font rules applied.
+        pub fn font_match(&self, config: &Config) -> Result<Pattern, Error> {
+            let mut res = ffi::FcResultMatch;
+            let d = unsafe { ffi::FcFontMatch(config.d, self.d, &mut res) };
+
+            match res {
+                ffi::FcResultMatch if !d.is_null() => Ok(Pattern::from_ptr(d)),
                 ffi::FcResultTypeMismatch => Err(Error::TypeMismatch),
                 ffi::FcResultNoId => Err(Error::NoId),
                 ffi::FcResultOutOfMemory => Err(Error::OutOfMemory),
@@ -370,11 +1119,21 @@ mod fc {
             }
         }
 
+        // FcFontList with all properties, as `FcFontSetSort()` needs them.
+        pub fn list_all(&self, config: &Config) -> Result<FontSet, Error> {
+            let d = unsafe { ffi::FcFontList(config.d, self.d, ptr::null_mut()) };
+            if !d.is_null() {
+                Ok(FontSet { d })
+            } else {
+                Err(Error::NoMatch)
+            }
+        }
+
         // FcFontList
         pub fn list(&self, config: &Config, set: ObjectSet) -> Result<FontSet, Error> {
             let d = unsafe { ffi::FcFontList(config.d, self.d, set.d) };
             if !d.is_null() {
-                Ok(FontSet { d, idx: 0 })
+                Ok(FontSet { d })
             } else {
                 Err(Error::NoMatch)
             }
@@ -388,12 +1147,14 @@ mod fc {
         }
     }
 
-    // A read-only `FcPattern` without a destructor.
-    pub struct PatternRef {
+    // A read-only `FcPattern` without a destructor, borrowed from the pattern or font set that
+    // owns it.
+    pub struct PatternRef<'a> {
         d: *mut ffi::FcPattern,
+        phantom: PhantomData<&'a ()>,
     }
 
-    impl PatternRef {
+    impl<'a> PatternRef<'a> {
         // FcPatternGetString
         pub fn get_string(&self, object: Object) -> Option<String> {
             unsafe {
@@ -414,6 +1175,54 @@ mod fc {
             }
         }
 
+        // FcPatternGetString, for every value of the object
+        pub fn get_strings(&self, object: Object) -> Vec<String> {
+            let mut strings = vec![];
+            for index in 0.. {
+                let mut string = ptr::null_mut();
+                unsafe {
+                    let res = ffi::FcPatternGetString(self.d, object.as_ptr(), index, &mut string);
+                    if res != ffi::FcResultMatch || string.is_null() {
+                        break;
+                    }
+                    if let Ok(string) = CStr::from_ptr(string as *const c_char).to_str() {
+                        strings.push(string.to_owned())
+                    }
+                }
+            }
+            strings
+        }
+
+        // FcPatternGetBool
+        pub fn get_bool(&self, object: Object) -> Option<bool> {
+            unsafe {
+                let mut boolean = 0;
+                let res = ffi::FcPatternGetBool(self.d, object.as_ptr(), 0, &mut boolean);
+                if res != ffi::FcResultMatch {
+                    return None;
+                }
+
+                Some(boolean != 0)
+            }
+        }
+
+        // FcPatternGetCharSet
+        pub fn get_charset(&self) -> Option<CharSetRef<'a>> {
+            unsafe {
+                let mut charset = ptr::null_mut();
+                let res =
+                    ffi::FcPatternGetCharSet(self.d, Object::CharSet.as_ptr(), 0, &mut charset);
+                if res != ffi::FcResultMatch || charset.is_null() {
+                    return None;
+                }
+
+                Some(CharSetRef {
+                    d: charset,
+                    phantom: PhantomData,
+                })
+            }
+        }
+
         // FcPatternGetInteger
         pub fn get_integer(&self, object: Object) -> Option<i32> {
             unsafe {
@@ -428,9 +1237,49 @@ mod fc {
         }
     }
 
+    // A read-only `FcCharSet` owned by a pattern.
+    pub struct CharSetRef<'a> {
+        d: *mut ffi::FcCharSet,
+        phantom: PhantomData<&'a ()>,
+    }
+
+    impl<'a> CharSetRef<'a> {
+        // FcCharSetHasChar
+        pub fn has_char(&self, character: char) -> bool {
+            unsafe { ffi::FcCharSetHasChar(self.d, character as u32) != 0 }
+        }
+    }
+
+    pub struct CharSet {
+        d: *mut ffi::FcCharSet,
+    }
+
+    impl CharSet {
+        // FcCharSetCreate
+        pub fn new() -> Self {
+            unsafe {
+                CharSet {
+                    d: ffi::FcCharSetCreate(),
+                }
+            }
+        }
+
+        // FcCharSetAddChar
+        pub fn push_char(&mut self, character: char) {
+            unsafe {
+                ffi::FcCharSetAddChar(self.d, character as u32);
+            }
+        }
+    }
+
+    impl Drop for CharSet {
+        fn drop(&mut self) {
+            unsafe { ffi::FcCharSetDestroy(self.d) }
+        }
+    }
+
     pub struct FontSet {
         d: *mut ffi::FcFontSet,
-        idx: usize,
     }
 
     impl FontSet {
@@ -441,25 +1290,38 @@ mod fc {
         pub fn len(&self) -> usize {
             unsafe { (*self.d).nfont as usize }
         }
+
+        pub fn iter(&self) -> FontSetIter<'_> {
+            FontSetIter { set: self, idx: 0 }
+        }
     }
 
-    impl Iterator for FontSet {
-        type Item = PatternRef;
+    // The patterns in a font set, which stay alive as long as the set does.
+    pub struct FontSetIter<'a> {
+        set: &'a FontSet,
+        idx: usize,
+    }
+
+    impl<'a> Iterator for FontSetIter<'a> {
+        type Item = PatternRef<'a>;
 
         fn next(&mut self) -> Option<Self::Item> {
-            if self.idx == self.len() {
+            if self.idx == self.set.len() {
                 return None;
             }
 
             let idx = self.idx;
             self.idx += 1;
 
-            let d = unsafe { *(*self.d).fonts.offset(idx as isize) };
-            Some(PatternRef { d })
+            let d = unsafe { *(*self.set.d).fonts.add(idx) };
+            Some(PatternRef {
+                d,
+                phantom: PhantomData,
+            })
         }
 
         fn size_hint(&self) -> (usize, Option<usize>) {
-            (0, Some(self.len()))
+            (0, Some(self.set.len() - self.idx))
         }
     }
 
@@ -484,11 +1346,14 @@ mod fc {
         }
 
         // FcObjectSetAdd
-        pub fn push_string(&mut self, object: Object) {
+        pub fn push_string(&mut self, object: Object) -> Result<(), Error> {
             unsafe {
                 // Returns `false` if the property name cannot be inserted
                 // into the set (due to allocation failure).
-                assert_eq!(ffi::FcObjectSetAdd(self.d, object.as_ptr()), 1);
+                if ffi::FcObjectSetAdd(self.d, object.as_ptr()) == 0 {
+                    return Err(Error::OutOfMemory);
+                }
+                Ok(())
             }
         }
     }
@@ -499,3 +1364,97 @@ mod fc {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{fc, LcdFilter, RenderingPreferences};
+    use canvas::{RasterizationOptions, SubpixelLayout};
+    use hinting::HintingOptions;
+    use properties::{Stretch, Style, Weight};
+
+    #[test]
+    fn test_css_to_fontconfig_font_weight() {
+        assert_eq!(super::css_to_fontconfig_weight(Weight(50.0)), 0);
+        assert_eq!(super::css_to_fontconfig_weight(Weight::THIN), 0);
+        assert_eq!(super::css_to_fontconfig_weight(Weight(250.0)), 45);
+        assert_eq!(super::css_to_fontconfig_weight(Weight::NORMAL), 80);
+        assert_eq!(super::css_to_fontconfig_weight(Weight::MEDIUM), 100);
+        assert_eq!(super::css_to_fontconfig_weight(Weight::SEMIBOLD), 180);
+        assert_eq!(super::css_to_fontconfig_weight(Weight::BOLD), 200);
+        assert_eq!(super::css_to_fontconfig_weight(Weight::BLACK), 210);
+        assert_eq!(super::css_to_fontconfig_weight(Weight(1000.0)), 215);
+    }
+
+    #[test]
+    fn test_css_to_fontconfig_slant_and_width() {
+        assert_eq!(super::css_to_fontconfig_slant(Style::Normal), 0);
+        assert_eq!(super::css_to_fontconfig_slant(Style::Italic), 100);
+        assert_eq!(super::css_to_fontconfig_slant(Style::Oblique), 110);
+        assert_eq!(super::css_to_fontconfig_width(Stretch::CONDENSED), 75);
+        assert_eq!(super::css_to_fontconfig_width(Stretch::SEMI_EXPANDED), 113);
+        assert_eq!(super::css_to_fontconfig_width(Stretch::ULTRA_EXPANDED), 200);
+    }
+
+    #[test]
+    fn test_fontconfig_to_css_font_weight() {
+        assert_eq!(super::fontconfig_to_css_weight(0), Weight::THIN);
+        assert_eq!(super::fontconfig_to_css_weight(45), Weight(250.0));
+        assert_eq!(super::fontconfig_to_css_weight(80), Weight::NORMAL);
+        assert_eq!(super::fontconfig_to_css_weight(200), Weight::BOLD);
+        assert_eq!(super::fontconfig_to_css_weight(215), Weight(1000.0));
+        assert_eq!(super::fontconfig_to_css_weight(250), Weight(1000.0));
+        for &weight in &[
+            Weight::LIGHT,
+            Weight::MEDIUM,
+            Weight::SEMIBOLD,
+            Weight::BLACK,
+        ] {
+            let fontconfig_weight = super::css_to_fontconfig_weight(weight);
+            assert_eq!(super::fontconfig_to_css_weight(fontconfig_weight), weight);
+        }
+    }
+
+    #[test]
+    fn test_rendering_preferences_from_pattern() {
+        let pattern = fc::Pattern::new();
+        assert_eq!(
+            super::rendering_preferences_from_pattern(&pattern.as_pattern_ref(), 16.0),
+            RenderingPreferences {
+                hinting_options: HintingOptions::Full(16.0),
+                rasterization_options: RasterizationOptions::GrayscaleAa,
+                subpixel_layout: None,
+                lcd_filter: LcdFilter::None,
+                embedded_bitmaps: true,
+            }
+        );
+
+        let mut pattern = fc::Pattern::new();
+        pattern.push_integer(fc::Object::HintStyle, 1);
+        pattern.push_integer(fc::Object::Rgba, 2);
+        pattern.push_integer(fc::Object::LcdFilter, 1);
+        pattern.push_bool(fc::Object::EmbeddedBitmap, false);
+        assert_eq!(
+            super::rendering_preferences_from_pattern(&pattern.as_pattern_ref(), 12.0),
+            RenderingPreferences {
+                hinting_options: HintingOptions::VerticalSubpixel(12.0),
+                rasterization_options: RasterizationOptions::SubpixelAa,
+                subpixel_layout: Some(SubpixelLayout::Bgr),
+                lcd_filter: LcdFilter::Default,
+                embedded_bitmaps: false,
+            }
+        );
+
+        let mut pattern = fc::Pattern::new();
+        pattern.push_bool(fc::Object::Antialias, false);
+        pattern.push_bool(fc::Object::Hinting, false);
+        pattern.push_integer(fc::Object::HintStyle, 3);
+        pattern.push_integer(fc::Object::Rgba, 5);
+        let preferences = super::rendering_preferences_from_pattern(&pattern.as_pattern_ref(), 9.0);
+        assert_eq!(preferences.hinting_options, HintingOptions::None);
+        assert_eq!(
+            preferences.rasterization_options,
+            RasterizationOptions::Bilevel
+        );
+        assert_eq!(preferences.subpixel_layout, None);
+    }
+}
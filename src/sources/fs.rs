@@ -14,8 +14,14 @@
 //!
 //! This is the native source on Android.
 
+use std::collections::HashMap;
 use std::fs::File;
-use std::path::PathBuf;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::thread::{self, JoinHandle};
 use walkdir::WalkDir;
 
 #[cfg(not(any(target_os = "android", target_family = "windows")))]
@@ -29,15 +35,19 @@ use winapi::shared::minwindef::{MAX_PATH, UINT};
 #[cfg(target_family = "windows")]
 use winapi::um::sysinfoapi;
 
-use error::SelectionError;
+use descriptor::FontDescriptor;
+use error::{FontLoadingError, SelectionError};
 use family_handle::FamilyHandle;
 use family_name::FamilyName;
 use file_type::FileType;
 use font::Font;
+use generic_family_map::GenericFamilyMap;
 use handle::Handle;
 use properties::Properties;
-use source::Source;
-use sources::mem::MemSource;
+use query::FontQuery;
+use source::{IndexingProgress, Source, SourceChanges};
+use sources::mem::{Diagnostic, LoadedFont, MemSource};
+use utils::{self, FileStamp};
 
 /// A source that loads fonts from a directory or directories on disk.
 ///
@@ -47,6 +57,8 @@ use sources::mem::MemSource;
 #[allow(missing_debug_implementations)]
 pub struct FsSource {
     mem_source: MemSource,
+    directories: Vec<PathBuf>,
+    file_stamps: HashMap<PathBuf, FileStamp>,
 }
 
 impl FsSource {
@@ -56,33 +68,49 @@ impl FsSource {
     /// locate fonts in the typical platform directories, but it is too simple to pick up fonts
     /// that are stored in unusual locations but nevertheless properly installed.
     pub fn new() -> FsSource {
-        let mut fonts = vec![];
-        for font_directory in default_font_directories() {
-            for directory_entry in WalkDir::new(font_directory).into_iter() {
-                let directory_entry = match directory_entry {
-                    Ok(directory_entry) => directory_entry,
-                    Err(_) => continue,
-                };
-                let path = directory_entry.path();
-                let mut file = match File::open(path) {
-                    Err(_) => continue,
-                    Ok(file) => file,
-                };
-                match Font::analyze_file(&mut file) {
-                    Err(_) => continue,
-                    Ok(FileType::Single) => fonts.push(Handle::from_path(path.to_owned(), 0)),
-                    Ok(FileType::Collection(font_count)) => {
-                        for font_index in 0..font_count {
-                            fonts.push(Handle::from_path(path.to_owned(), font_index))
-                        }
-                    }
-                }
-            }
-        }
+        FsSource::from_directories(default_font_directories().into_iter())
+    }
 
-        FsSource {
-            mem_source: MemSource::from_fonts(fonts.into_iter()).unwrap(),
+    /// Indexes the fonts found in the given directories and their subdirectories.
+    pub fn from_directories<I>(directories: I) -> FsSource
+    where
+        I: Iterator<Item = PathBuf>,
+    {
+        FsSource::from_directories_in_parallel(directories, 1, |_| {})
+    }
+
+    /// Indexes the fonts found in the given directories and their subdirectories, loading the
+    /// files on `thread_count` threads.
+    ///
+    /// A thread count of zero uses one thread per CPU. `progress` is called on this thread each
+    /// time a file has been indexed. The fonts end up in the same order as with
+    /// `from_directories()`.
+    pub fn from_directories_in_parallel<I, F>(
+        directories: I,
+        thread_count: usize,
+        mut progress: F,
+    ) -> FsSource
+    where
+        I: Iterator<Item = PathBuf>,
+        F: FnMut(IndexingProgress),
+    {
+        let mut fs_source = FsSource::empty(directories.collect());
+        let files = scan_directories(&fs_source.directories);
+        let indexed_files = utils::map_in_parallel(
+            &files,
+            thread_count,
+            |(path, _)| IndexedFile::new(path),
+            |indexed| {
+                progress(IndexingProgress {
+                    indexed,
+                    total: files.len(),
+                })
+            },
+        );
+        for (file, indexed_file) in files.iter().zip(indexed_files) {
+            fs_source.add_indexed_file(file, indexed_file);
         }
+        fs_source
     }
 
     /// Returns paths of all fonts installed on the system.
@@ -111,6 +139,50 @@ impl FsSource {
         self.mem_source.select_by_postscript_name(postscript_name)
     }
 
+    /// Returns the names, properties and format of the font referenced by the given handle.
+    ///
+    /// Fonts in this source are described from the index, without loading them again.
+    #[inline]
+    pub fn describe_font(&self, handle: &Handle) -> Result<FontDescriptor, SelectionError> {
+        self.mem_source.describe_font(handle)
+    }
+
+    #[inline]
+    pub(crate) fn describe_indexed_font(&self, handle: &Handle) -> Option<FontDescriptor> {
+        self.mem_source.describe_indexed_font(handle)
+    }
+
+    /// Returns descriptions of all fonts that meet the given criteria, in the order of
+    /// `all_fonts()`.
+    ///
+    /// Fonts are only loaded to check character coverage; see `MemSource::query_fonts()`.
+    #[inline]
+    pub fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        self.mem_source.query_fonts(query)
+    }
+
+    /// Returns the fonts that were indexed under a synthesized PostScript name or skipped because
+    /// they failed to load, along with the reasons.
+    ///
+    /// Files that aren't fonts at all are not reported.
+    #[inline]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        self.mem_source.diagnostics()
+    }
+
+    /// Returns the mapping from generic family names to concrete families that this source uses.
+    #[inline]
+    pub fn generic_family_map(&self) -> &GenericFamilyMap {
+        self.mem_source.generic_family_map()
+    }
+
+    /// Replaces the mapping from generic family names to concrete families that this source
+    /// uses.
+    #[inline]
+    pub fn set_generic_family_map(&mut self, generic_family_map: GenericFamilyMap) {
+        self.mem_source.set_generic_family_map(generic_family_map)
+    }
+
     /// Performs font matching according to the CSS Fonts Level 3 specification and returns the
     /// handle.
     #[inline]
@@ -121,6 +193,63 @@ impl FsSource {
     ) -> Result<Handle, SelectionError> {
         <Self as Source>::select_best_match(self, family_names, properties)
     }
+
+    /// Performs font matching like `select_best_match()`, but prefers fonts that declare support
+    /// for the language identified by the given BCP 47 tag (e.g. `ja` or `zh-Hant`).
+    #[inline]
+    pub fn select_best_match_for_locale(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: &str,
+    ) -> Result<Handle, SelectionError> {
+        <Self as Source>::select_best_match_for_locale(self, family_names, properties, locale)
+    }
+
+    /// Rescans the directories for fonts that have been added, removed or modified since they
+    /// were last scanned, and reports the changes.
+    ///
+    /// Only the files whose modification time or length differ from the last scan are loaded.
+    pub fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        let files = scan_directories(&self.directories);
+        let file_stamps: HashMap<_, _> = files.iter().cloned().collect();
+
+        let mut old_fonts = vec![];
+        let mut new_paths = vec![];
+        for (path, file_stamp) in &files {
+            if self.file_stamps.get(path) != Some(file_stamp) {
+                old_fonts.extend(self.mem_source.remove_fonts_in_file(path));
+                new_paths.push(path);
+            }
+        }
+        for path in self.file_stamps.keys() {
+            if !file_stamps.contains_key(path) {
+                old_fonts.extend(self.mem_source.remove_fonts_in_file(path));
+            }
+        }
+
+        let mut new_fonts = vec![];
+        for path in new_paths {
+            new_fonts.extend(IndexedFile::new(path).add_to(&mut self.mem_source));
+        }
+        self.file_stamps = file_stamps;
+
+        // Every font that survived came from a file that was modified.
+        Ok(SourceChanges::between(old_fonts, new_fonts, |_| true))
+    }
+
+    fn empty(directories: Vec<PathBuf>) -> FsSource {
+        FsSource {
+            mem_source: MemSource::empty(),
+            directories,
+            file_stamps: HashMap::new(),
+        }
+    }
+
+    fn add_indexed_file(&mut self, file: &(PathBuf, FileStamp), indexed_file: IndexedFile) {
+        indexed_file.add_to(&mut self.mem_source);
+        self.file_stamps.insert(file.0.clone(), file.1);
+    }
 }
 
 impl Source for FsSource {
@@ -141,8 +270,410 @@ impl Source for FsSource {
     fn select_by_postscript_name(&self, postscript_name: &str) -> Result<Handle, SelectionError> {
         self.select_by_postscript_name(postscript_name)
     }
+
+    #[inline]
+    fn generic_family_map(&self) -> &GenericFamilyMap {
+        self.generic_family_map()
+    }
+
+    #[inline]
+    fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        self.refresh()
+    }
+
+    #[inline]
+    fn describe_font(&self, handle: &Handle) -> Result<FontDescriptor, SelectionError> {
+        self.describe_font(handle)
+    }
+
+    #[inline]
+    fn describe_indexed_font(&self, handle: &Handle) -> Option<FontDescriptor> {
+        self.describe_indexed_font(handle)
+    }
+
+    #[inline]
+    fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        self.query_fonts(query)
+    }
+}
+
+/// A source that indexes the fonts in a directory or directories on disk on a background thread,
+/// and that can be queried while it does so.
+///
+/// Queries only see the fonts that have been indexed so far, which lets applications show their
+/// UI without waiting for every font. Once indexing has finished, this source behaves like the
+/// `FsSource` that `into_fs_source()` returns.
+#[allow(missing_debug_implementations)]
+pub struct IncrementalFsSource {
+    state: Arc<IncrementalState>,
+    generic_family_map: GenericFamilyMap,
+    indexing_thread: Option<JoinHandle<()>>,
+}
+
+struct IncrementalState {
+    fs_source: RwLock<FsSource>,
+    progress: Mutex<IndexingProgress>,
+    complete: AtomicBool,
+    cancelled: AtomicBool,
+}
+
+// A panic while indexing, such as in the progress callback, poisons the locks, but the fonts
+// indexed before it remain usable.
+impl IncrementalState {
+    fn fs_source(&self) -> RwLockReadGuard<'_, FsSource> {
+        self.fs_source
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn fs_source_mut(&self) -> RwLockWriteGuard<'_, FsSource> {
+        self.fs_source
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn progress(&self) -> MutexGuard<'_, IndexingProgress> {
+        self.progress.lock().unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
+impl IncrementalFsSource {
+    /// Starts indexing the default set of directories on this platform, as `FsSource::new()`
+    /// does, and returns immediately.
+    ///
+    /// See `from_directories()` for the meaning of the arguments.
+    pub fn new<F>(thread_count: usize, progress: F) -> IncrementalFsSource
+    where
+        F: FnMut(IndexingProgress) + Send + 'static,
+    {
+        IncrementalFsSource::from_directories(
+            default_font_directories().into_iter(),
+            thread_count,
+            progress,
+        )
+    }
+
+    /// Starts indexing the fonts found in the given directories and their subdirectories, and
+    /// returns immediately.
+    ///
+    /// The files are loaded on `thread_count` threads, or one per CPU if it is zero, and fonts
+    /// are added in the order they finish loading. `progress` is called on the background thread
+    /// once the directories have been scanned and each time a file has been indexed.
+    pub fn from_directories<I, F>(
+        directories: I,
+        thread_count: usize,
+        mut progress: F,
+    ) -> IncrementalFsSource
+    where
+        I: Iterator<Item = PathBuf>,
+        F: FnMut(IndexingProgress) + Send + 'static,
+    {
+        let directories: Vec<_> = directories.collect();
+        let state = Arc::new(IncrementalState {
+            fs_source: RwLock::new(FsSource::empty(directories.clone())),
+            progress: Mutex::new(IndexingProgress::default()),
+            complete: AtomicBool::new(false),
+            cancelled: AtomicBool::new(false),
+        });
+
+        let thread_state = state.clone();
+        let indexing_thread = thread::spawn(move || {
+            let state = thread_state;
+            let files = scan_directories(&directories);
+            let mut report = |indexed| {
+                let indexing_progress = IndexingProgress {
+                    indexed,
+                    total: files.len(),
+                };
+                *state.progress() = indexing_progress;
+                progress(indexing_progress)
+            };
+            report(0);
+
+            let mut indexed = 0;
+            utils::for_each_in_parallel(
+                &files,
+                thread_count,
+                |(path, _)| {
+                    if state.cancelled.load(Ordering::Relaxed) {
+                        None
+                    } else {
+                        Some(IndexedFile::new(path))
+                    }
+                },
+                |index, indexed_file| {
+                    if let Some(indexed_file) = indexed_file {
+                        let mut fs_source = state.fs_source_mut();
+                        fs_source.add_indexed_file(&files[index], indexed_file);
+                    }
+                    indexed += 1;
+                    report(indexed)
+                },
+            );
+            state.complete.store(true, Ordering::Release);
+        });
+
+        IncrementalFsSource {
+            state,
+            generic_family_map: GenericFamilyMap::new(),
+            indexing_thread: Some(indexing_thread),
+        }
+    }
+
+    /// Returns how far indexing has got.
+    #[inline]
+    pub fn progress(&self) -> IndexingProgress {
+        *self.state.progress()
+    }
+
+    /// Returns true if every file has been indexed.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.state.complete.load(Ordering::Acquire)
+    }
+
+    /// Blocks until every file has been indexed.
+    ///
+    /// If indexing panics, this returns once the indexing thread has stopped, and the fonts that
+    /// were indexed before the panic remain available; `is_complete()` stays false.
+    pub fn wait(&mut self) {
+        if let Some(indexing_thread) = self.indexing_thread.take() {
+            if indexing_thread.join().is_err() {
+                warn!("Indexing fonts panicked!");
+            }
+        }
+    }
+
+    /// Waits for every file to be indexed and returns the resulting filesystem source.
+    pub fn into_fs_source(mut self) -> FsSource {
+        self.wait();
+        let mut fs_source = mem::replace(&mut *self.state.fs_source_mut(), FsSource::empty(vec![]));
+        fs_source.set_generic_family_map(mem::take(&mut self.generic_family_map));
+        fs_source
+    }
+
+    /// Returns paths of all fonts indexed so far.
+    pub fn all_fonts(&self) -> Result<Vec<Handle>, SelectionError> {
+        self.state.fs_source().all_fonts()
+    }
+
+    /// Returns the names of all families indexed so far.
+    pub fn all_families(&self) -> Result<Vec<String>, SelectionError> {
+        self.state.fs_source().all_families()
+    }
+
+    /// Looks up a font family by name and returns the handles of all the fonts in that family
+    /// that have been indexed so far.
+    pub fn select_family_by_name(&self, family_name: &str) -> Result<FamilyHandle, SelectionError> {
+        self.state.fs_source().select_family_by_name(family_name)
+    }
+
+    /// Selects a font by PostScript name, which should be a unique identifier, among the fonts
+    /// indexed so far.
+    pub fn select_by_postscript_name(
+        &self,
+        postscript_name: &str,
+    ) -> Result<Handle, SelectionError> {
+        self.state
+            .fs_source()
+            .select_by_postscript_name(postscript_name)
+    }
+
+    /// Returns the names, properties and format of the font referenced by the given handle.
+    ///
+    /// Fonts that have been indexed are described from the index.
+    pub fn describe_font(&self, handle: &Handle) -> Result<FontDescriptor, SelectionError> {
+        self.state.fs_source().describe_font(handle)
+    }
+
+    fn describe_indexed_font(&self, handle: &Handle) -> Option<FontDescriptor> {
+        self.state.fs_source().describe_indexed_font(handle)
+    }
+
+    /// Returns descriptions of all fonts indexed so far that meet the given criteria.
+    pub fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        self.state.fs_source().query_fonts(query)
+    }
+
+    /// Returns the mapping from generic family names to concrete families that this source uses.
+    #[inline]
+    pub fn generic_family_map(&self) -> &GenericFamilyMap {
+        &self.generic_family_map
+    }
+
+    /// Replaces the mapping from generic family names to concrete families that this source
+    /// uses.
+    #[inline]
+    pub fn set_generic_family_map(&mut self, generic_family_map: GenericFamilyMap) {
+        self.generic_family_map = generic_family_map
+    }
+
+    /// Performs font matching according to the CSS Fonts Level 3 specification and returns the
+    /// handle.
+    #[inline]
+    pub fn select_best_match(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+    ) -> Result<Handle, SelectionError> {
+        <Self as Source>::select_best_match(self, family_names, properties)
+    }
+
+    /// Rescans the directories, as `FsSource::refresh()` does, once indexing has finished.
+    ///
+    /// Until then, this reports no changes; fonts that are indexed in the meantime are not
+    /// reported as added.
+    pub fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        if !self.is_complete() {
+            return Ok(SourceChanges::default());
+        }
+        self.state.fs_source_mut().refresh()
+    }
+}
+
+impl Drop for IncrementalFsSource {
+    fn drop(&mut self) {
+        // Skip the files that haven't been loaded yet.
+        self.state.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+impl Source for IncrementalFsSource {
+    #[inline]
+    fn all_fonts(&self) -> Result<Vec<Handle>, SelectionError> {
+        self.all_fonts()
+    }
+
+    #[inline]
+    fn all_families(&self) -> Result<Vec<String>, SelectionError> {
+        self.all_families()
+    }
+
+    fn select_family_by_name(&self, family_name: &str) -> Result<FamilyHandle, SelectionError> {
+        self.select_family_by_name(family_name)
+    }
+
+    fn select_by_postscript_name(&self, postscript_name: &str) -> Result<Handle, SelectionError> {
+        self.select_by_postscript_name(postscript_name)
+    }
+
+    #[inline]
+    fn generic_family_map(&self) -> &GenericFamilyMap {
+        self.generic_family_map()
+    }
+
+    #[inline]
+    fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        self.refresh()
+    }
+
+    #[inline]
+    fn describe_font(&self, handle: &Handle) -> Result<FontDescriptor, SelectionError> {
+        self.describe_font(handle)
+    }
+
+    #[inline]
+    fn describe_indexed_font(&self, handle: &Handle) -> Option<FontDescriptor> {
+        self.describe_indexed_font(handle)
+    }
+
+    #[inline]
+    fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        self.query_fonts(query)
+    }
+}
+
+// Returns the files in the given directories and their subdirectories, along with their stamps.
+fn scan_directories(directories: &[PathBuf]) -> Vec<(PathBuf, FileStamp)> {
+    let mut files = vec![];
+    for directory in directories {
+        for directory_entry in WalkDir::new(directory).into_iter() {
+            let directory_entry = match directory_entry {
+                Ok(directory_entry) => directory_entry,
+                Err(_) => continue,
+            };
+            if directory_entry.file_type().is_dir() {
+                continue;
+            }
+            let path = directory_entry.path();
+            if let Some(file_stamp) = utils::file_stamp(path) {
+                files.push((path.to_owned(), file_stamp));
+            }
+        }
+    }
+    files
+}
+
+// The fonts in a file, loaded ahead of time so that files can be indexed on several threads.
+struct IndexedFile {
+    path: PathBuf,
+    fonts: Result<Vec<(Handle, FontLoadingResult)>, FontLoadingError>,
+}
+
+type FontLoadingResult = Result<LoadedFont, FontLoadingError>;
+
+impl IndexedFile {
+    fn new(path: &Path) -> IndexedFile {
+        let fonts = font_handles_in_file(path).map(|handles| {
+            handles
+                .into_iter()
+                .map(|handle| (handle.clone(), LoadedFont::new(handle)))
+                .collect()
+        });
+        IndexedFile {
+            path: path.to_owned(),
+            fonts,
+        }
+    }
+
+    // Adds the fonts that loaded successfully and returns their handles. Fonts that fail to load
+    // are diagnosed, as are files that have a font extension but can't be loaded; other files are
+    // assumed not to be fonts.
+    fn add_to(self, mem_source: &mut MemSource) -> Vec<Handle> {
+        let fonts = match self.fonts {
+            Ok(fonts) => fonts,
+            Err(error) => {
+                if has_font_extension(&self.path) {
+                    mem_source.diagnose_loading_failure(Handle::from_path(self.path, 0), error)
+                }
+                return vec![];
+            }
+        };
+        let mut added = vec![];
+        for (handle, loaded_font) in fonts {
+            match loaded_font {
+                Ok(loaded_font) => added.push(mem_source.add_loaded_font(loaded_font)),
+                Err(error) => mem_source.diagnose_loading_failure(handle, error),
+            }
+        }
+        added
+    }
+}
+
+// Returns a handle for each font in the file.
+fn font_handles_in_file(path: &Path) -> Result<Vec<Handle>, FontLoadingError> {
+    let mut file = File::open(path)?;
+    Ok(match Font::analyze_file(&mut file)? {
+        FileType::Single => vec![Handle::from_path(path.to_owned(), 0)],
+        FileType::Collection(font_count) => (0..font_count)
+            .map(|font_index| Handle::from_path(path.to_owned(), font_index))
+            .collect(),
+    })
+}
+
+fn has_font_extension(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => FONT_EXTENSIONS
+            .iter()
+            .any(|font_extension| extension.eq_ignore_ascii_case(font_extension)),
+        None => false,
+    }
+}
+
+static FONT_EXTENSIONS: [&str; 9] = [
+    "dfont", "otc", "otf", "pcf", "pfa", "pfb", "ttc", "ttf", "woff",
+];
+
 #[cfg(target_os = "android")]
 fn default_font_directories() -> Vec<PathBuf> {
     vec![PathBuf::from("/system/fonts")]
@@ -153,7 +684,9 @@ fn default_font_directories() -> Vec<PathBuf> {
     unsafe {
         let mut buffer = vec![0; MAX_PATH];
         let len = sysinfoapi::GetWindowsDirectoryW(buffer.as_mut_ptr(), buffer.len() as UINT);
-        assert!(len != 0);
+        if len == 0 {
+            return vec![];
+        }
         buffer.truncate(len as usize);
 
         let mut path = PathBuf::from(OsString::from_wide(&buffer));
@@ -10,41 +10,118 @@
 
 //! A source that keeps fonts in memory.
 
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+
+use descriptor::FontDescriptor;
 use error::{FontLoadingError, SelectionError};
 use family_handle::FamilyHandle;
 use family_name::FamilyName;
+use features::Tag;
+use file_type::FileType;
 use font::Font;
+use generic_family_map::GenericFamilyMap;
 use handle::Handle;
-use properties::Properties;
-use source::Source;
+use properties::{Properties, Style, Weight};
+use query::FontQuery;
+use source::{IndexingProgress, Source, SourceChanges};
+use utils::{self, FileStamp};
 
 /// A source that keeps fonts in memory.
 #[allow(missing_debug_implementations)]
 pub struct MemSource {
     families: Vec<FamilyEntry>,
+    generic_family_map: GenericFamilyMap,
+    next_font_id: u64,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Identifies a font that was added to a `MemSource`, so that it can be removed later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct FontId(u64);
+
+/// A font that a `MemSource` couldn't index as usual.
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// The font in question.
+    pub font: Handle,
+    /// What went wrong.
+    pub kind: DiagnosticKind,
+    font_id: Option<FontId>,
+}
+
+/// The reason that a font appears in a `MemSource`'s diagnostics.
+#[derive(Debug)]
+pub enum DiagnosticKind {
+    /// The font has no PostScript name, so it was indexed under the given synthesized one.
+    SynthesizedPostScriptName(String),
+    /// The font failed to load, so it was skipped.
+    LoadingFailed(FontLoadingError),
 }
 
 impl MemSource {
+    /// Creates a new memory source that contains no fonts.
+    ///
+    /// Fonts can be added later with `add_font()`, `add_fonts()` and `add_font_data()`.
+    pub fn empty() -> MemSource {
+        MemSource {
+            families: vec![],
+            generic_family_map: GenericFamilyMap::new(),
+            next_font_id: 0,
+            diagnostics: vec![],
+        }
+    }
+
     /// Creates a new memory source that contains the given set of font handles.
     ///
-    /// The fonts referenced by the handles are eagerly loaded into memory.
+    /// The fonts referenced by the handles are eagerly loaded into memory. Fonts without a
+    /// PostScript name are indexed under a synthesized one; see `diagnostics()`.
     pub fn from_fonts<I>(fonts: I) -> Result<MemSource, FontLoadingError>
     where
         I: Iterator<Item = Handle>,
     {
-        let mut families = vec![];
+        let mut mem_source = MemSource::empty();
         for handle in fonts {
-            let font = Font::from_handle(&handle)?;
-            if let Some(postscript_name) = font.postscript_name() {
-                families.push(FamilyEntry {
-                    family_name: font.family_name(),
-                    postscript_name: postscript_name,
-                    font: handle,
+            let font_id = mem_source.next_font_id();
+            let family_entry = FamilyEntry::new(handle, font_id)?;
+            mem_source.insert_family_entry(family_entry);
+        }
+        Ok(mem_source)
+    }
+
+    /// Creates a new memory source that contains the given set of font handles, loading the
+    /// fonts on `thread_count` threads.
+    ///
+    /// A thread count of zero uses one thread per CPU. `progress` is called on this thread each
+    /// time a font has been loaded. The fonts end up in the same order as with `from_fonts()`,
+    /// and, as with it, the first font that fails to load is reported as an error.
+    pub fn from_fonts_in_parallel<I, F>(
+        fonts: I,
+        thread_count: usize,
+        mut progress: F,
+    ) -> Result<MemSource, FontLoadingError>
+    where
+        I: Iterator<Item = Handle>,
+        F: FnMut(IndexingProgress),
+    {
+        let fonts: Vec<_> = fonts.collect();
+        let loaded_fonts = utils::map_in_parallel(
+            &fonts,
+            thread_count,
+            |handle| LoadedFont::new(handle.clone()),
+            |indexed| {
+                progress(IndexingProgress {
+                    indexed,
+                    total: fonts.len(),
                 })
-            }
+            },
+        );
+        let mut mem_source = MemSource::empty();
+        for loaded_font in loaded_fonts {
+            mem_source.add_loaded_font(loaded_font?);
         }
-        families.sort_by(|a, b| a.family_name.cmp(&b.family_name));
-        Ok(MemSource { families })
+        Ok(mem_source)
     }
 
     /// Returns paths of all fonts installed on the system.
@@ -101,18 +178,65 @@ impl MemSource {
     ///
     /// The default implementation, which is used by the DirectWrite and the filesystem backends,
     /// does a brute-force search of installed fonts to find the one that matches.
+    ///
+    /// Fonts that have a PostScript name are preferred over fonts whose name was synthesized.
     pub fn select_by_postscript_name(
         &self,
         postscript_name: &str,
     ) -> Result<Handle, SelectionError> {
-        self.families
-            .iter()
-            .filter(|family_entry| family_entry.postscript_name == postscript_name)
+        let matching = |synthesized| {
+            self.families.iter().find(|family_entry| {
+                family_entry.postscript_name == postscript_name
+                    && family_entry.postscript_name_is_synthesized == synthesized
+            })
+        };
+        matching(false)
+            .or_else(|| matching(true))
             .map(|family_entry| family_entry.font.clone())
-            .next()
             .ok_or(SelectionError::NotFound)
     }
 
+    /// Returns the names, properties and format of the font referenced by the given handle.
+    ///
+    /// Fonts in this source are described from the index. Other fonts are read from their
+    /// headers, as `FontDescriptor::from_handle()` does.
+    pub fn describe_font(&self, handle: &Handle) -> Result<FontDescriptor, SelectionError> {
+        match self.describe_indexed_font(handle) {
+            Some(descriptor) => Ok(descriptor),
+            None => Ok(FontDescriptor::from_handle(handle)?),
+        }
+    }
+
+    pub(crate) fn describe_indexed_font(&self, handle: &Handle) -> Option<FontDescriptor> {
+        self.families
+            .iter()
+            .find(|family_entry| same_font(&family_entry.font, handle))
+            .map(|family_entry| family_entry.descriptor.clone())
+    }
+
+    /// Returns the fonts that this source indexed under a synthesized PostScript name or, when
+    /// building a `FsSource` or refreshing, skipped because they failed to load, along with the
+    /// reasons.
+    ///
+    /// Entries are dropped when their fonts are removed.
+    #[inline]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Returns the mapping from generic family names to concrete families that this source uses.
+    #[inline]
+    pub fn generic_family_map(&self) -> &GenericFamilyMap {
+        &self.generic_family_map
+    }
+
+    /// Replaces the mapping from generic family names to concrete families that this source
+    /// uses.
+    #[inline]
+    pub fn set_generic_family_map(&mut self, generic_family_map: GenericFamilyMap) {
+        self.generic_family_map = generic_family_map
+    }
+
     /// Performs font matching according to the CSS Fonts Level 3 specification and returns the
     /// handle.
     #[inline]
@@ -123,6 +247,221 @@ impl MemSource {
     ) -> Result<Handle, SelectionError> {
         <Self as Source>::select_best_match(self, family_names, properties)
     }
+
+    /// Performs font matching like `select_best_match()`, but prefers fonts that declare support
+    /// for the language identified by the given BCP 47 tag (e.g. `ja` or `zh-Hant`).
+    #[inline]
+    pub fn select_best_match_for_locale(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: &str,
+    ) -> Result<Handle, SelectionError> {
+        <Self as Source>::select_best_match_for_locale(self, family_names, properties, locale)
+    }
+
+    /// Returns the handles of all fonts that declare the given OpenType feature (e.g. `smcp`) in
+    /// their `GSUB` or `GPOS` tables.
+    ///
+    /// Fonts that fail to load are skipped.
+    pub fn select_fonts_with_feature(&self, feature: Tag) -> Result<Vec<Handle>, SelectionError> {
+        Ok(self
+            .families
+            .iter()
+            .filter(|family_entry| match Font::from_handle(&family_entry.font) {
+                Ok(font) => font.layout_features().supports_feature(feature),
+                Err(_) => false,
+            })
+            .map(|family_entry| family_entry.font.clone())
+            .collect())
+    }
+
+    /// Returns descriptions of all fonts that meet the given criteria, in the order of
+    /// `all_fonts()`.
+    ///
+    /// Everything but character coverage is answered from the index. Fonts are only loaded if
+    /// the query requires characters or scripts, and only those that meet the other criteria.
+    pub fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        let characters = query.required_characters()?;
+        Ok(self
+            .families
+            .iter()
+            .map(|family_entry| &family_entry.descriptor)
+            .filter(|descriptor| query.matches(descriptor))
+            .filter(|descriptor| {
+                characters.is_empty()
+                    || match Font::from_handle(&descriptor.handle) {
+                        Ok(font) => query.is_covered_by(&font),
+                        Err(_) => false,
+                    }
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Reloads the fonts on disk whose files have been modified or deleted since they were added
+    /// to this source, and reports the changes.
+    ///
+    /// Fonts whose files can no longer be loaded are removed. Fonts in memory never change.
+    pub fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        let mut changes = SourceChanges::default();
+        for family_entry in mem::take(&mut self.families) {
+            if !family_entry.is_stale() {
+                self.families.push(family_entry);
+                continue;
+            }
+            let font_id = family_entry.font_id;
+            self.remove_diagnostics(|diagnostic| diagnostic.font_id == Some(font_id));
+            match FamilyEntry::new(family_entry.font.clone(), font_id) {
+                Ok(new_family_entry) => {
+                    changes.changed.push(new_family_entry.font.clone());
+                    self.diagnose_family_entry(&new_family_entry);
+                    self.families.push(new_family_entry);
+                }
+                Err(error) => {
+                    self.diagnose_loading_failure(family_entry.font.clone(), error);
+                    changes.removed.push(family_entry.font)
+                }
+            }
+        }
+        self.sort_families();
+        Ok(changes)
+    }
+
+    /// Loads the font referenced by the given handle and adds it to this source.
+    ///
+    /// Returns an identifier that can be passed to `remove_font()`.
+    pub fn add_font(&mut self, handle: Handle) -> Result<FontId, FontLoadingError> {
+        let font_id = self.next_font_id();
+        let family_entry = FamilyEntry::new(handle, font_id)?;
+        Ok(self.insert_family_entry(family_entry))
+    }
+
+    /// Loads the fonts referenced by the given handles and adds them to this source.
+    ///
+    /// Returns an identifier for each font, in order. If any font fails to load, none of them are
+    /// added.
+    pub fn add_fonts<I>(&mut self, fonts: I) -> Result<Vec<FontId>, FontLoadingError>
+    where
+        I: Iterator<Item = Handle>,
+    {
+        let mut family_entries = vec![];
+        for handle in fonts {
+            let font_id = self.next_font_id();
+            family_entries.push(FamilyEntry::new(handle, font_id)?);
+        }
+        Ok(family_entries
+            .into_iter()
+            .map(|family_entry| self.insert_family_entry(family_entry))
+            .collect())
+    }
+
+    /// Adds the fonts in the given raw TrueType/OpenType/etc. data to this source.
+    ///
+    /// If the data is a collection, every font in it is added, and an identifier is returned for
+    /// each of them in order. As with `add_fonts()`, either all fonts are added or none are.
+    pub fn add_font_data(
+        &mut self,
+        font_data: Arc<Vec<u8>>,
+    ) -> Result<Vec<FontId>, FontLoadingError> {
+        let font_count = match Font::analyze_bytes(font_data.clone())? {
+            FileType::Single => 1,
+            FileType::Collection(font_count) => font_count,
+        };
+        self.add_fonts(
+            (0..font_count).map(|font_index| Handle::from_memory(font_data.clone(), font_index)),
+        )
+    }
+
+    /// Removes the font with the given identifier from this source and returns its handle, or
+    /// `None` if it has already been removed.
+    pub fn remove_font(&mut self, font_id: FontId) -> Option<Handle> {
+        let index = self
+            .families
+            .iter()
+            .position(|family_entry| family_entry.font_id == font_id)?;
+        self.remove_diagnostics(|diagnostic| diagnostic.font_id == Some(font_id));
+        Some(self.families.remove(index).font)
+    }
+
+    // Adds a font that was loaded ahead of time and returns its handle.
+    pub(crate) fn add_loaded_font(&mut self, loaded_font: LoadedFont) -> Handle {
+        let mut family_entry = loaded_font.0;
+        family_entry.font_id = self.next_font_id();
+        let font = family_entry.font.clone();
+        self.insert_family_entry(family_entry);
+        font
+    }
+
+    // Records that a font was skipped because it failed to load.
+    pub(crate) fn diagnose_loading_failure(&mut self, font: Handle, error: FontLoadingError) {
+        self.diagnostics.push(Diagnostic {
+            font,
+            kind: DiagnosticKind::LoadingFailed(error),
+            font_id: None,
+        })
+    }
+
+    // Removes the fonts stored in the file at `path` and returns their handles.
+    pub(crate) fn remove_fonts_in_file(&mut self, path: &Path) -> Vec<Handle> {
+        let in_file = |font: &Handle| match *font {
+            Handle::Path {
+                path: ref font_path,
+                ..
+            } => font_path == path,
+            Handle::Memory { .. } => false,
+        };
+        self.remove_diagnostics(|diagnostic| in_file(&diagnostic.font));
+        let (removed, kept) = mem::take(&mut self.families)
+            .into_iter()
+            .partition(|family_entry| in_file(&family_entry.font));
+        self.families = kept;
+        removed
+            .into_iter()
+            .map(|family_entry: FamilyEntry| family_entry.font)
+            .collect()
+    }
+
+    fn next_font_id(&mut self) -> FontId {
+        self.next_font_id += 1;
+        FontId(self.next_font_id - 1)
+    }
+
+    // Keeps the families sorted by name, after any fonts of the same family that are already
+    // present.
+    fn insert_family_entry(&mut self, family_entry: FamilyEntry) -> FontId {
+        self.diagnose_family_entry(&family_entry);
+        let font_id = family_entry.font_id;
+        let index = self
+            .families
+            .partition_point(|other| other.family_name <= family_entry.family_name);
+        self.families.insert(index, family_entry);
+        font_id
+    }
+
+    fn diagnose_family_entry(&mut self, family_entry: &FamilyEntry) {
+        if family_entry.postscript_name_is_synthesized {
+            self.diagnostics.push(Diagnostic {
+                font: family_entry.font.clone(),
+                kind: DiagnosticKind::SynthesizedPostScriptName(
+                    family_entry.postscript_name.clone(),
+                ),
+                font_id: Some(family_entry.font_id),
+            })
+        }
+    }
+
+    fn remove_diagnostics<F>(&mut self, mut predicate: F)
+    where
+        F: FnMut(&Diagnostic) -> bool,
+    {
+        self.diagnostics.retain(|diagnostic| !predicate(diagnostic))
+    }
+
+    fn sort_families(&mut self) {
+        self.families
+            .sort_by(|a, b| a.family_name.cmp(&b.family_name));
+    }
 }
 
 impl Source for MemSource {
@@ -143,10 +482,132 @@ impl Source for MemSource {
     fn select_by_postscript_name(&self, postscript_name: &str) -> Result<Handle, SelectionError> {
         self.select_by_postscript_name(postscript_name)
     }
+
+    #[inline]
+    fn generic_family_map(&self) -> &GenericFamilyMap {
+        self.generic_family_map()
+    }
+
+    #[inline]
+    fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        self.refresh()
+    }
+
+    #[inline]
+    fn describe_font(&self, handle: &Handle) -> Result<FontDescriptor, SelectionError> {
+        self.describe_font(handle)
+    }
+
+    #[inline]
+    fn describe_indexed_font(&self, handle: &Handle) -> Option<FontDescriptor> {
+        self.describe_indexed_font(handle)
+    }
+
+    #[inline]
+    fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        self.query_fonts(query)
+    }
+}
+
+// A font that has been loaded, possibly on another thread, but not yet added to a source.
+pub(crate) struct LoadedFont(FamilyEntry);
+
+impl LoadedFont {
+    pub(crate) fn new(handle: Handle) -> Result<LoadedFont, FontLoadingError> {
+        // The identifier is assigned when the font is added.
+        Ok(LoadedFont(FamilyEntry::new(handle, FontId(0))?))
+    }
 }
 
 struct FamilyEntry {
     family_name: String,
     postscript_name: String,
+    postscript_name_is_synthesized: bool,
     font: Handle,
+    font_id: FontId,
+    file_stamp: Option<FileStamp>,
+    descriptor: FontDescriptor,
+}
+
+impl FamilyEntry {
+    fn new(handle: Handle, font_id: FontId) -> Result<FamilyEntry, FontLoadingError> {
+        // Stamp the file before loading it, so that a concurrent write shows up as staleness.
+        let file_stamp = match handle {
+            Handle::Path { ref path, .. } => utils::file_stamp(path),
+            Handle::Memory { .. } => None,
+        };
+        let font = Font::from_handle(&handle)?;
+        let (postscript_name, postscript_name_is_synthesized) = match font.postscript_name() {
+            Some(postscript_name) => (postscript_name, false),
+            None => (synthesize_postscript_name(&font), true),
+        };
+        Ok(FamilyEntry {
+            family_name: font.family_name(),
+            postscript_name,
+            postscript_name_is_synthesized,
+            descriptor: FontDescriptor::from_font(handle.clone(), &font),
+            font: handle,
+            font_id,
+            file_stamp,
+        })
+    }
+
+    // Whether the file that this font was loaded from has changed since.
+    fn is_stale(&self) -> bool {
+        match self.font {
+            Handle::Path { ref path, .. } => utils::file_stamp(path) != self.file_stamp,
+            Handle::Memory { .. } => false,
+        }
+    }
+}
+
+// Fonts in memory are only the same if they share their data.
+fn same_font(a: &Handle, b: &Handle) -> bool {
+    match (a, b) {
+        (
+            &Handle::Path {
+                path: ref a_path,
+                font_index: a_font_index,
+            },
+            &Handle::Path {
+                path: ref b_path,
+                font_index: b_font_index,
+            },
+        ) => a_path == b_path && a_font_index == b_font_index,
+        (
+            &Handle::Memory {
+                bytes: ref a_bytes,
+                font_index: a_font_index,
+            },
+            &Handle::Memory {
+                bytes: ref b_bytes,
+                font_index: b_font_index,
+            },
+        ) => Arc::ptr_eq(a_bytes, b_bytes) && a_font_index == b_font_index,
+        _ => false,
+    }
+}
+
+// Builds a name of the usual `Family-Style` form from the family name and properties, like
+// `TimesNewRoman-BoldItalic`. Synthesized names aren't necessarily unique.
+fn synthesize_postscript_name(font: &Font) -> String {
+    let family_name: String = font
+        .family_name()
+        .chars()
+        .filter(|character| character.is_ascii_alphanumeric())
+        .collect();
+    let properties = font.properties();
+    let mut style = String::new();
+    if properties.weight >= Weight::BOLD {
+        style.push_str("Bold");
+    }
+    match properties.style {
+        Style::Normal => {}
+        Style::Italic => style.push_str("Italic"),
+        Style::Oblique => style.push_str("Oblique"),
+    }
+    if style.is_empty() {
+        style.push_str("Regular");
+    }
+    format!("{}-{}", family_name, style)
 }
@@ -13,56 +13,228 @@
 //! This is useful when an application wants a library of fonts consisting of the installed system
 //! fonts plus some other application-supplied fonts.
 
+use std::cmp;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::mem;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use descriptor::FontDescriptor;
 use error::SelectionError;
 use family_handle::FamilyHandle;
 use family_name::FamilyName;
+use generic_family_map::GenericFamilyMap;
 use handle::Handle;
 use properties::Properties;
-use source::Source;
+use query::FontQuery;
+use source::{Source, SourceChanges};
 
 /// A source that encapsulates multiple sources and allows them to be queried as a group.
 ///
 /// This is useful when an application wants a library of fonts consisting of the installed system
 /// fonts plus some other application-supplied fonts.
+///
+/// Subsources are consulted in order of priority, highest first; subsources with equal priorities
+/// are consulted in the order they were added. Identical fonts that appear in several subsources,
+/// either because they have the same path and index or because they are in memory and have the
+/// same contents, are only reported once, from the subsource with the highest priority. Families
+/// are merged across subsources, and, within a family, a font from a higher-priority subsource
+/// shadows fonts with the same PostScript name from lower-priority ones; this lets application
+/// fonts replace system fonts. Fonts are described for matching and queries from the index of the
+/// highest-priority subsource that has indexed them, if any.
 #[allow(missing_debug_implementations)]
 pub struct MultiSource {
-    subsources: Vec<Box<Source>>,
+    subsources: Vec<Subsource>,
+    next_source_id: usize,
+    generic_family_map: GenericFamilyMap,
+}
+
+/// Identifies a subsource of a `MultiSource`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SourceId(usize);
+
+struct Subsource {
+    source: Box<dyn Source>,
+    priority: i32,
+    id: SourceId,
 }
 
 impl MultiSource {
     /// Creates a new source that contains all the fonts in the supplied sources.
-    pub fn from_sources(subsources: Vec<Box<Source>>) -> MultiSource {
-        MultiSource { subsources }
+    ///
+    /// The sources all have priority 0, so earlier sources take precedence over later ones. Their
+    /// identifiers are assigned in order.
+    pub fn from_sources(subsources: Vec<Box<dyn Source>>) -> MultiSource {
+        let mut multi_source = MultiSource {
+            subsources: vec![],
+            next_source_id: 0,
+            generic_family_map: GenericFamilyMap::new(),
+        };
+        for subsource in subsources {
+            multi_source.add_source(subsource, 0);
+        }
+        multi_source
+    }
+
+    /// Adds a subsource with the given priority and returns its identifier.
+    ///
+    /// The subsource takes precedence over those with lower priorities and, among those with the
+    /// same priority, over those added before it.
+    pub fn add_source(&mut self, subsource: Box<dyn Source>, priority: i32) -> SourceId {
+        let id = SourceId(self.next_source_id);
+        self.next_source_id += 1;
+        let index = self
+            .subsources
+            .iter()
+            .position(|other| other.priority < priority)
+            .unwrap_or(self.subsources.len());
+        self.subsources.insert(
+            index,
+            Subsource {
+                source: subsource,
+                priority,
+                id,
+            },
+        );
+        id
+    }
+
+    /// Returns the subsource with the given identifier.
+    pub fn subsource(&self, id: SourceId) -> Option<&dyn Source> {
+        self.subsources
+            .iter()
+            .find(|subsource| subsource.id == id)
+            .map(|subsource| &*subsource.source)
+    }
+
+    /// Returns the subsource with the given identifier mutably.
+    pub fn subsource_mut(&mut self, id: SourceId) -> Option<&mut dyn Source> {
+        match self
+            .subsources
+            .iter_mut()
+            .find(|subsource| subsource.id == id)
+        {
+            Some(subsource) => Some(&mut *subsource.source),
+            None => None,
+        }
     }
 
     /// Returns paths of all fonts installed on the system.
+    ///
+    /// Identical fonts are only returned once; see `all_fonts_with_sources()`.
     pub fn all_fonts(&self) -> Result<Vec<Handle>, SelectionError> {
+        Ok(self
+            .all_fonts_with_sources()?
+            .into_iter()
+            .map(|(handle, _)| handle)
+            .collect())
+    }
+
+    /// Returns the handles of all fonts in all subsources, along with the subsource that each one
+    /// came from.
+    ///
+    /// Fonts are ordered by the priority of their subsources. Identical fonts are only returned
+    /// once, from the subsource with the highest priority.
+    pub fn all_fonts_with_sources(&self) -> Result<Vec<(Handle, SourceId)>, SelectionError> {
+        let mut font_keys = HashSet::new();
         let mut handles = vec![];
         for subsource in &self.subsources {
-            handles.extend(subsource.all_fonts()?.into_iter())
+            for handle in not_found_as_empty(subsource.source.all_fonts())? {
+                if font_keys.insert(FontKey::new(&handle)) {
+                    handles.push((handle, subsource.id))
+                }
+            }
         }
         Ok(handles)
     }
 
+    /// Returns the subsource that the given font came from, or `None` if it isn't in any of them.
+    ///
+    /// This searches all fonts, so prefer `all_fonts_with_sources()` or
+    /// `select_family_by_name_with_sources()` when looking up many fonts.
+    pub fn source_of(&self, handle: &Handle) -> Result<Option<SourceId>, SelectionError> {
+        let font_key = FontKey::new(handle);
+        Ok(self
+            .all_fonts_with_sources()?
+            .into_iter()
+            .find(|(other_handle, _)| FontKey::new(other_handle) == font_key)
+            .map(|(_, id)| id))
+    }
+
     /// Returns the names of all families installed on the system.
+    ///
+    /// Families in several subsources are only returned once.
     pub fn all_families(&self) -> Result<Vec<String>, SelectionError> {
+        let mut family_names = HashSet::new();
         let mut families = vec![];
         for subsource in &self.subsources {
-            families.extend(subsource.all_families()?.into_iter())
+            for family in not_found_as_empty(subsource.source.all_families())? {
+                if family_names.insert(family.clone()) {
+                    families.push(family)
+                }
+            }
         }
         Ok(families)
     }
 
     /// Looks up a font family by name and returns the handles of all the fonts in that family.
+    ///
+    /// The family is merged from all subsources that contain it; see
+    /// `select_family_by_name_with_sources()`.
     pub fn select_family_by_name(&self, family_name: &str) -> Result<FamilyHandle, SelectionError> {
+        let fonts = self.select_family_by_name_with_sources(family_name)?;
+        Ok(FamilyHandle::from_font_handles(
+            fonts.into_iter().map(|(handle, _)| handle),
+        ))
+    }
+
+    /// Looks up a font family by name in all subsources and returns the handles of all the fonts
+    /// in that family, along with the subsource that each one came from.
+    ///
+    /// Fonts are ordered by the priority of their subsources. Identical fonts are only returned
+    /// once, and fonts with the same PostScript name as a font from a higher-priority subsource
+    /// are left out.
+    pub fn select_family_by_name_with_sources(
+        &self,
+        family_name: &str,
+    ) -> Result<Vec<(Handle, SourceId)>, SelectionError> {
+        let (mut font_keys, mut postscript_names) = (HashSet::new(), HashSet::new());
+        let mut fonts = vec![];
         for subsource in &self.subsources {
-            match subsource.select_family_by_name(family_name) {
-                Ok(family) => return Ok(family),
-                Err(SelectionError::NotFound) => {}
+            let family = match subsource.source.select_family_by_name(family_name) {
+                Ok(family) => family,
+                Err(SelectionError::NotFound) => continue,
                 Err(err) => return Err(err),
+            };
+
+            // Shadow by PostScript name only once this subsource's fonts have all been seen, so
+            // that fonts within a subsource never shadow each other.
+            let mut new_postscript_names = vec![];
+            for handle in family.fonts {
+                if !font_keys.insert(FontKey::new(&handle)) {
+                    continue;
+                }
+                let postscript_name = match subsource.source.describe_font(&handle) {
+                    Ok(descriptor) => descriptor.postscript_name,
+                    Err(_) => None,
+                };
+                if let Some(postscript_name) = postscript_name {
+                    if postscript_names.contains(&postscript_name) {
+                        continue;
+                    }
+                    new_postscript_names.push(postscript_name);
+                }
+                fonts.push((handle, subsource.id))
             }
+            postscript_names.extend(new_postscript_names);
+        }
+
+        if fonts.is_empty() {
+            Err(SelectionError::NotFound)
+        } else {
+            Ok(fonts)
         }
-        Err(SelectionError::NotFound)
     }
 
     /// Selects a font by PostScript name, which should be a unique identifier.
@@ -71,7 +243,7 @@ impl MultiSource {
         postscript_name: &str,
     ) -> Result<Handle, SelectionError> {
         for subsource in &self.subsources {
-            match subsource.select_by_postscript_name(postscript_name) {
+            match subsource.source.select_by_postscript_name(postscript_name) {
                 Ok(font) => return Ok(font),
                 Err(SelectionError::NotFound) => {}
                 Err(err) => return Err(err),
@@ -80,6 +252,41 @@ impl MultiSource {
         Err(SelectionError::NotFound)
     }
 
+    /// Returns the names, properties and format of the font referenced by the given handle.
+    ///
+    /// Fonts are described from the index of the highest-priority subsource that has indexed
+    /// them, if any. Other fonts are read from their headers, as `FontDescriptor::from_handle()`
+    /// does.
+    pub fn describe_font(&self, handle: &Handle) -> Result<FontDescriptor, SelectionError> {
+        match self.describe_indexed_font(handle) {
+            Some(descriptor) => Ok(descriptor),
+            None => Ok(FontDescriptor::from_handle(handle)?),
+        }
+    }
+
+    fn describe_indexed_font(&self, handle: &Handle) -> Option<FontDescriptor> {
+        self.subsources
+            .iter()
+            .filter_map(|subsource| subsource.source.describe_indexed_font(handle))
+            .next()
+    }
+
+    /// Returns the mapping from generic family names to concrete families that this source uses.
+    ///
+    /// Generic families are resolved with this mapping, not with those of the subsources, and the
+    /// resulting concrete families are then looked up in each subsource.
+    #[inline]
+    pub fn generic_family_map(&self) -> &GenericFamilyMap {
+        &self.generic_family_map
+    }
+
+    /// Replaces the mapping from generic family names to concrete families that this source
+    /// uses.
+    #[inline]
+    pub fn set_generic_family_map(&mut self, generic_family_map: GenericFamilyMap) {
+        self.generic_family_map = generic_family_map
+    }
+
     /// Performs font matching according to the CSS Fonts Level 3 specification and returns the
     /// handle.
     #[inline]
@@ -90,6 +297,111 @@ impl MultiSource {
     ) -> Result<Handle, SelectionError> {
         <Self as Source>::select_best_match(self, family_names, properties)
     }
+
+    /// Performs font matching like `select_best_match()`, but prefers fonts that declare support
+    /// for the language identified by the given BCP 47 tag (e.g. `ja` or `zh-Hant`).
+    #[inline]
+    pub fn select_best_match_for_locale(
+        &self,
+        family_names: &[FamilyName],
+        properties: &Properties,
+        locale: &str,
+    ) -> Result<Handle, SelectionError> {
+        <Self as Source>::select_best_match_for_locale(self, family_names, properties, locale)
+    }
+
+    /// Returns descriptions of all fonts in each subsource that meet the given criteria, in order
+    /// of the priorities of the subsources.
+    ///
+    /// Identical fonts are only returned once.
+    pub fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        let mut font_keys = HashSet::new();
+        let mut descriptors = vec![];
+        for subsource in &self.subsources {
+            for descriptor in subsource.source.query_fonts(query)? {
+                if font_keys.insert(FontKey::new(&descriptor.handle)) {
+                    descriptors.push(descriptor)
+                }
+            }
+        }
+        Ok(descriptors)
+    }
+
+    /// Refreshes each subsource in turn and reports all of their changes.
+    pub fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        let mut changes = SourceChanges::default();
+        for subsource in &mut self.subsources {
+            changes.append(subsource.source.refresh()?);
+        }
+        Ok(changes)
+    }
+}
+
+// Identifies a font regardless of which subsource it came from. Fonts in memory are compared by
+// their contents, so that separately loaded copies of the same data are recognized. They are only
+// hashed by their length and the start of their data, which holds the table directory and thus
+// the checksum of every table, so that hashing stays cheap for large fonts.
+enum FontKey {
+    Path(PathBuf, u32),
+    Memory(Arc<Vec<u8>>, u32),
+}
+
+// The number of bytes at the start of a font in memory that its key is hashed by.
+const MEMORY_FONT_HASH_LENGTH: usize = 1024;
+
+impl FontKey {
+    fn new(handle: &Handle) -> FontKey {
+        match *handle {
+            Handle::Path {
+                ref path,
+                font_index,
+            } => FontKey::Path(path.clone(), font_index),
+            Handle::Memory {
+                ref bytes,
+                font_index,
+            } => FontKey::Memory(bytes.clone(), font_index),
+        }
+    }
+}
+
+impl PartialEq for FontKey {
+    fn eq(&self, other: &FontKey) -> bool {
+        match (self, other) {
+            (&FontKey::Path(ref a_path, a_index), &FontKey::Path(ref b_path, b_index)) => {
+                a_path == b_path && a_index == b_index
+            }
+            (&FontKey::Memory(ref a_bytes, a_index), &FontKey::Memory(ref b_bytes, b_index)) => {
+                a_index == b_index && (Arc::ptr_eq(a_bytes, b_bytes) || a_bytes == b_bytes)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Eq for FontKey {}
+
+impl Hash for FontKey {
+    fn hash<H>(&self, state: &mut H)
+    where
+        H: Hasher,
+    {
+        mem::discriminant(self).hash(state);
+        match *self {
+            FontKey::Path(ref path, font_index) => (path, font_index).hash(state),
+            FontKey::Memory(ref bytes, font_index) => {
+                let hashed_length = cmp::min(bytes.len(), MEMORY_FONT_HASH_LENGTH);
+                (bytes.len(), font_index, &bytes[..hashed_length]).hash(state)
+            }
+        }
+    }
+}
+
+// Some sources report that they have no fonts at all as an error.
+fn not_found_as_empty<T>(result: Result<Vec<T>, SelectionError>) -> Result<Vec<T>, SelectionError> {
+    match result {
+        Err(SelectionError::NotFound) => Ok(vec![]),
+        result => result,
+    }
 }
 
 impl Source for MultiSource {
@@ -112,4 +424,29 @@ impl Source for MultiSource {
     fn select_by_postscript_name(&self, postscript_name: &str) -> Result<Handle, SelectionError> {
         self.select_by_postscript_name(postscript_name)
     }
+
+    #[inline]
+    fn describe_font(&self, handle: &Handle) -> Result<FontDescriptor, SelectionError> {
+        self.describe_font(handle)
+    }
+
+    #[inline]
+    fn describe_indexed_font(&self, handle: &Handle) -> Option<FontDescriptor> {
+        self.describe_indexed_font(handle)
+    }
+
+    #[inline]
+    fn generic_family_map(&self) -> &GenericFamilyMap {
+        self.generic_family_map()
+    }
+
+    #[inline]
+    fn refresh(&mut self) -> Result<SourceChanges, SelectionError> {
+        self.refresh()
+    }
+
+    #[inline]
+    fn query_fonts(&self, query: &FontQuery) -> Result<Vec<FontDescriptor>, SelectionError> {
+        self.query_fonts(query)
+    }
 }
@@ -0,0 +1,1271 @@
+// font-kit/src/subset.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Font subsetting: producing a standalone font file that contains only some glyphs of a font.
+//!
+//! This is mainly useful for embedding fonts in documents such as PDFs. Both TrueType (`glyf`)
+//! and CFF outlines are supported. The glyphs are renumbered compactly, and the `cmap`, `hmtx`,
+//! `loca`, `post` and `name` tables are rebuilt to match. Hinting instructions are kept, but
+//! OpenType layout tables (`GSUB`, `GPOS`, `GDEF`, `kern`) are dropped, so the subset is meant to
+//! be fed already-shaped glyph IDs.
+//!
+//! Composite TrueType glyphs pull in their components automatically. CFF `seac` accented
+//! characters are not followed, and `CFF2` and bitmap-only fonts are not supported.
+
+use byteorder::{BigEndian, ByteOrder};
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::iter;
+
+use error::SubsetError;
+use features::Tag;
+use loader::Loader;
+use sfnt::{self, read_i16, read_u16, read_u32};
+
+const COMPOSITE_ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+const COMPOSITE_WE_HAVE_A_SCALE: u16 = 0x0008;
+const COMPOSITE_MORE_COMPONENTS: u16 = 0x0020;
+const COMPOSITE_WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+const COMPOSITE_WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+
+const CFF_OPERATOR_CHARSET: u16 = 15;
+const CFF_OPERATOR_ENCODING: u16 = 16;
+const CFF_OPERATOR_CHAR_STRINGS: u16 = 17;
+const CFF_OPERATOR_PRIVATE: u16 = 18;
+const CFF_OPERATOR_SUBRS: u16 = 19;
+const CFF_OPERATOR_CHARSTRING_TYPE: u16 = 0x0c06;
+const CFF_OPERATOR_ROS: u16 = 0x0c1e;
+const CFF_OPERATOR_FD_ARRAY: u16 = 0x0c24;
+const CFF_OPERATOR_FD_SELECT: u16 = 0x0c25;
+
+const MALFORMED_CFF: SubsetError = SubsetError::MalformedTable(Tag(sfnt::CFF));
+const MALFORMED_CMAP: SubsetError = SubsetError::MalformedTable(Tag(sfnt::CMAP));
+const MALFORMED_GLYF: SubsetError = SubsetError::MalformedTable(Tag(sfnt::GLYF));
+
+/// The largest name ID kept in the subset's `name` table (the PostScript name).
+const MAX_NAME_ID: u16 = 6;
+
+/// Which glyphs to keep when subsetting a font.
+///
+/// The `.notdef` glyph (glyph 0) is always kept. This object supports a method chaining style
+/// for idiomatic initialization; e.g.
+///
+///     # use font_kit::subset::SubsetOptions;
+///     println!("{:?}", SubsetOptions::new().text("Hello").glyph_ids(&[42]));
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SubsetOptions {
+    /// The IDs of glyphs to keep.
+    pub glyph_ids: Vec<u32>,
+    /// Characters whose glyphs to keep.
+    ///
+    /// Each is looked up with `Loader::glyph_for_char()`; characters that the font doesn't
+    /// cover are ignored.
+    pub chars: Vec<char>,
+}
+
+impl SubsetOptions {
+    /// Creates options that keep only the `.notdef` glyph.
+    #[inline]
+    pub fn new() -> SubsetOptions {
+        SubsetOptions::default()
+    }
+
+    /// Adds glyphs to keep and returns these options for method chaining.
+    #[inline]
+    pub fn glyph_ids(&mut self, glyph_ids: &[u32]) -> &mut SubsetOptions {
+        self.glyph_ids.extend_from_slice(glyph_ids);
+        self
+    }
+
+    /// Adds the characters of a string to keep and returns these options for method chaining.
+    #[inline]
+    pub fn text(&mut self, text: &str) -> &mut SubsetOptions {
+        self.chars.extend(text.chars());
+        self
+    }
+}
+
+/// A standalone font file produced by `subset()`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SubsetFont {
+    /// The data of the font file, suitable for `Loader::from_bytes()`.
+    pub data: Vec<u8>,
+    /// The original ID of each glyph in the subset, indexed by new glyph ID.
+    ///
+    /// Glyphs keep their relative order, so this is sorted and starts with 0 (`.notdef`).
+    pub glyph_ids: Vec<u32>,
+}
+
+impl SubsetFont {
+    /// Returns the ID in the subset of the glyph with the given ID in the original font, if the
+    /// subset contains it.
+    pub fn new_glyph_id(&self, original_glyph_id: u32) -> Option<u32> {
+        self.glyph_ids
+            .binary_search(&original_glyph_id)
+            .ok()
+            .map(|glyph_id| glyph_id as u32)
+    }
+}
+
+/// Builds a standalone font file that contains only the requested glyphs of `font`.
+///
+/// The `cmap` of the subset maps every character of the original font whose glyph was kept, not
+/// just the characters in `options.chars`.
+pub fn subset<L>(font: &L, options: &SubsetOptions) -> Result<SubsetFont, SubsetError>
+where
+    L: Loader,
+{
+    let load_table = |tag| {
+        font.load_font_table(tag)
+            .ok_or(SubsetError::MissingTable(Tag(tag)))
+    };
+    let mut head = load_table(sfnt::HEAD)?.into_vec();
+    let mut maxp = load_table(sfnt::MAXP)?.into_vec();
+    let glyph_count =
+        read_u16(&maxp, 4).ok_or(SubsetError::MalformedTable(Tag(sfnt::MAXP)))? as u32;
+
+    let mut requested_glyph_ids = BTreeSet::new();
+    requested_glyph_ids.insert(0);
+    for &glyph_id in &options.glyph_ids {
+        if glyph_id >= glyph_count {
+            return Err(SubsetError::NoSuchGlyph(glyph_id));
+        }
+        requested_glyph_ids.insert(glyph_id);
+    }
+    for &character in &options.chars {
+        if let Some(glyph_id) = font.glyph_for_char(character) {
+            requested_glyph_ids.insert(glyph_id);
+        }
+    }
+
+    let mut tables = vec![];
+    let version;
+    let glyph_ids: Vec<u32>;
+    if let (Some(glyf), Some(loca)) = (
+        font.load_font_table(sfnt::GLYF),
+        font.load_font_table(sfnt::LOCA),
+    ) {
+        let long_loca =
+            read_i16(&head, 50).ok_or(SubsetError::MalformedTable(Tag(sfnt::HEAD)))? != 0;
+        let glyph_table = GlyphTable::new(&glyf, &loca, long_loca, glyph_count);
+        glyph_table.add_components(&mut requested_glyph_ids)?;
+        glyph_ids = requested_glyph_ids.into_iter().collect();
+
+        let (new_glyf, new_loca) = glyph_table.subset(&glyph_ids)?;
+        tables.push((sfnt::GLYF, new_glyf));
+        tables.push((sfnt::LOCA, new_loca));
+        write_i16_at(&mut head, sfnt::HEAD, 50, 1)?;
+        version = sfnt::TRUETYPE_VERSION;
+    } else if let Some(cff) = font.load_font_table(sfnt::CFF) {
+        glyph_ids = requested_glyph_ids.into_iter().collect();
+        tables.push((sfnt::CFF, subset_cff(&cff, &glyph_ids)?));
+        version = sfnt::OPENTYPE_CFF_VERSION;
+    } else {
+        return Err(SubsetError::UnsupportedFormat);
+    }
+
+    write_u16_at(&mut maxp, sfnt::MAXP, 4, glyph_ids.len() as u16)?;
+    tables.push((sfnt::HEAD, head));
+    tables.push((sfnt::MAXP, maxp));
+
+    let hhea = load_table(sfnt::HHEA)?;
+    let hmtx = load_table(sfnt::HMTX)?;
+    let (new_hhea, new_hmtx) =
+        subset_metrics((sfnt::HHEA, &hhea), (sfnt::HMTX, &hmtx), &glyph_ids)?;
+    tables.push((sfnt::HHEA, new_hhea));
+    tables.push((sfnt::HMTX, new_hmtx));
+
+    if let (Some(vhea), Some(vmtx)) = (
+        font.load_font_table(sfnt::VHEA),
+        font.load_font_table(sfnt::VMTX),
+    ) {
+        let (new_vhea, new_vmtx) =
+            subset_metrics((sfnt::VHEA, &vhea), (sfnt::VMTX, &vmtx), &glyph_ids)?;
+        tables.push((sfnt::VHEA, new_vhea));
+        tables.push((sfnt::VMTX, new_vmtx));
+    }
+
+    let mut mappings = vec![];
+    if let Some(cmap) = font.load_font_table(sfnt::CMAP) {
+        for (character, glyph_id) in parse_cmap(&cmap, glyph_count)? {
+            if let Ok(new_glyph_id) = glyph_ids.binary_search(&glyph_id) {
+                mappings.push((character, new_glyph_id as u32));
+            }
+        }
+    }
+    tables.push((sfnt::CMAP, write_cmap(&mappings)));
+
+    if let Some(os2) = font.load_font_table(sfnt::OS_2) {
+        let mut os2 = os2.into_vec();
+        if let (Some(&(first_char, _)), Some(&(last_char, _))) = (mappings.first(), mappings.last())
+        {
+            write_u16_at(&mut os2, sfnt::OS_2, 64, first_char.min(0xffff) as u16)?;
+            write_u16_at(&mut os2, sfnt::OS_2, 66, last_char.min(0xffff) as u16)?;
+        }
+        tables.push((sfnt::OS_2, os2));
+    }
+
+    if let Some(post) = font.load_font_table(sfnt::POST) {
+        // Version 3 has no glyph names, which spares us from renumbering them.
+        let mut post = post
+            .get(0..32)
+            .ok_or(SubsetError::MalformedTable(Tag(sfnt::POST)))?
+            .to_vec();
+        BigEndian::write_u32(&mut post, 0x0003_0000);
+        tables.push((sfnt::POST, post));
+    }
+
+    if let Some(name) = font.load_font_table(sfnt::NAME) {
+        tables.push((sfnt::NAME, subset_name(&name)?));
+    }
+
+    for &tag in &[sfnt::CVT, sfnt::FPGM, sfnt::PREP, sfnt::GASP] {
+        if let Some(table) = font.load_font_table(tag) {
+            tables.push((tag, table.into_vec()));
+        }
+    }
+
+    Ok(SubsetFont {
+        data: sfnt::write_font(version, tables)?,
+        glyph_ids,
+    })
+}
+
+/// Copies one font out of a TrueType or OpenType collection (`.ttc` or `.otc`) into a standalone
+/// font file.
+///
+/// All tables of the font, including those it shares with other members of the collection, are
+/// copied unchanged, except for any digital signature, which would no longer be valid. If `data`
+/// is a single font, only `font_index` 0 is accepted, and the font is rewritten the same way.
+pub fn extract_font(data: &[u8], font_index: u32) -> Result<Vec<u8>, SubsetError> {
+    let (version, tables) = sfnt::read_font_tables(data, font_index)?;
+    let tables = tables
+        .into_iter()
+        .filter(|&(tag, _)| tag != sfnt::DSIG)
+        .map(|(tag, table)| (tag, table.to_vec()))
+        .collect();
+    sfnt::write_font(version, tables)
+}
+
+fn write_u16_at(
+    table: &mut [u8],
+    table_tag: u32,
+    offset: usize,
+    value: u16,
+) -> Result<(), SubsetError> {
+    let bytes = table
+        .get_mut(offset..(offset + 2))
+        .ok_or(SubsetError::MalformedTable(Tag(table_tag)))?;
+    BigEndian::write_u16(bytes, value);
+    Ok(())
+}
+
+fn write_i16_at(
+    table: &mut [u8],
+    table_tag: u32,
+    offset: usize,
+    value: i16,
+) -> Result<(), SubsetError> {
+    write_u16_at(table, table_tag, offset, value as u16)
+}
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.extend_from_slice(&value.to_be_bytes());
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend_from_slice(&value.to_be_bytes());
+}
+
+/// Rebuilds an `hhea`/`hmtx` or `vhea`/`vmtx` table pair for the given glyphs.
+fn subset_metrics(
+    (header_tag, header): (u32, &[u8]),
+    (metrics_tag, metrics): (u32, &[u8]),
+    glyph_ids: &[u32],
+) -> Result<(Vec<u8>, Vec<u8>), SubsetError> {
+    let long_metric_count =
+        read_u16(header, 34).ok_or(SubsetError::MalformedTable(Tag(header_tag)))?;
+    let mut glyph_metrics = Vec::with_capacity(glyph_ids.len());
+    for &glyph_id in glyph_ids {
+        glyph_metrics.push(
+            sfnt::long_glyph_metrics(metrics, long_metric_count, glyph_id)
+                .ok_or(SubsetError::MalformedTable(Tag(metrics_tag)))?,
+        );
+    }
+
+    // Trailing glyphs with the same advance only need their bearings.
+    let mut new_long_metric_count = glyph_metrics.len();
+    while new_long_metric_count > 1
+        && glyph_metrics[new_long_metric_count - 1].0 == glyph_metrics[new_long_metric_count - 2].0
+    {
+        new_long_metric_count -= 1;
+    }
+
+    let mut new_metrics = vec![];
+    for (index, &(advance, bearing)) in glyph_metrics.iter().enumerate() {
+        if index < new_long_metric_count {
+            push_u16(&mut new_metrics, advance);
+        }
+        push_u16(&mut new_metrics, bearing as u16);
+    }
+
+    let mut new_header = header.to_vec();
+    write_u16_at(
+        &mut new_header,
+        header_tag,
+        34,
+        new_long_metric_count as u16,
+    )?;
+    Ok((new_header, new_metrics))
+}
+
+/// Keeps the names up to and including the PostScript name, dropping descriptions, license text
+/// and the like.
+fn subset_name(name: &[u8]) -> Result<Vec<u8>, SubsetError> {
+    let record_count =
+        read_u16(name, 2).ok_or(SubsetError::MalformedTable(Tag(sfnt::NAME)))? as usize;
+    let storage_offset =
+        read_u16(name, 4).ok_or(SubsetError::MalformedTable(Tag(sfnt::NAME)))? as usize;
+
+    let (mut records, mut storage) = (vec![], vec![]);
+    let mut new_record_count = 0;
+    for record_index in 0..record_count {
+        let record = name
+            .get((6 + record_index * 12)..(18 + record_index * 12))
+            .ok_or(SubsetError::MalformedTable(Tag(sfnt::NAME)))?;
+        if BigEndian::read_u16(&record[6..]) > MAX_NAME_ID {
+            continue;
+        }
+        let length = BigEndian::read_u16(&record[8..]) as usize;
+        let offset = storage_offset + BigEndian::read_u16(&record[10..]) as usize;
+        let string = name
+            .get(offset..(offset + length))
+            .ok_or(SubsetError::MalformedTable(Tag(sfnt::NAME)))?;
+
+        records.extend_from_slice(&record[0..10]);
+        push_u16(&mut records, storage.len() as u16);
+        storage.extend_from_slice(string);
+        new_record_count += 1;
+    }
+
+    let mut new_name = vec![];
+    push_u16(&mut new_name, 0);
+    push_u16(&mut new_name, new_record_count);
+    push_u16(&mut new_name, 6 + new_record_count * 12);
+    new_name.extend_from_slice(&records);
+    new_name.extend_from_slice(&storage);
+    Ok(new_name)
+}
+
+/// Reads the Unicode mappings of a `cmap` table, sorted by character.
+///
+/// Formats 4, 6 and 12 of the Unicode and Windows Unicode subtables are understood; others are
+/// ignored. Segments and groups must be sorted and must not overlap, as the specification
+/// requires, so that malformed subtables can't make parsing take more than one step per
+/// character.
+fn parse_cmap(cmap: &[u8], glyph_count: u32) -> Result<BTreeMap<u32, u32>, SubsetError> {
+    let mut mappings = BTreeMap::new();
+    let subtable_count = read_u16(cmap, 2).unwrap_or(0) as usize;
+    for subtable_index in 0..subtable_count {
+        let record_offset = 4 + subtable_index * 8;
+        let (platform_id, encoding_id, offset) = match (
+            read_u16(cmap, record_offset),
+            read_u16(cmap, record_offset + 2),
+            read_u32(cmap, record_offset + 4),
+        ) {
+            (Some(platform_id), Some(encoding_id), Some(offset)) => {
+                (platform_id, encoding_id, offset as usize)
+            }
+            _ => break,
+        };
+        let is_unicode =
+            platform_id == 0 || (platform_id == 3 && (encoding_id == 1 || encoding_id == 10));
+        if !is_unicode {
+            continue;
+        }
+
+        if let Some(subtable) = cmap.get(offset..) {
+            let mut add_mapping = |character: u32, glyph_id: u32| {
+                if glyph_id != 0 && glyph_id < glyph_count {
+                    mappings.entry(character).or_insert(glyph_id);
+                }
+            };
+            match read_u16(subtable, 0) {
+                Some(4) => parse_cmap_format_4(subtable, &mut add_mapping)?,
+                Some(6) => parse_cmap_format_6(subtable, &mut add_mapping)?,
+                Some(12) => parse_cmap_format_12(subtable, glyph_count, &mut add_mapping)?,
+                _ => {}
+            }
+        }
+    }
+    Ok(mappings)
+}
+
+fn parse_cmap_format_4<F>(subtable: &[u8], add_mapping: &mut F) -> Result<(), SubsetError>
+where
+    F: FnMut(u32, u32),
+{
+    let segment_count_x2 = read_u16(subtable, 6).ok_or(MALFORMED_CMAP)? as usize;
+    let end_codes_offset = 14;
+    let start_codes_offset = end_codes_offset + segment_count_x2 + 2;
+    let id_deltas_offset = start_codes_offset + segment_count_x2;
+    let id_range_offsets_offset = id_deltas_offset + segment_count_x2;
+
+    let mut next_character = 0;
+    for segment_offset in (0..segment_count_x2).step_by(2) {
+        let read = |offset| read_u16(subtable, offset).ok_or(MALFORMED_CMAP);
+        let end_code = read(end_codes_offset + segment_offset)?;
+        let start_code = read(start_codes_offset + segment_offset)?;
+        let id_delta = read(id_deltas_offset + segment_offset)?;
+        let id_range_offset_offset = id_range_offsets_offset + segment_offset;
+        let id_range_offset = read(id_range_offset_offset)? as usize;
+        if (start_code as u32) < next_character || start_code > end_code {
+            return Err(MALFORMED_CMAP);
+        }
+        next_character = end_code as u32 + 1;
+
+        for character in start_code..=end_code {
+            if character == 0xffff {
+                break;
+            }
+            let glyph_id = if id_range_offset == 0 {
+                character.wrapping_add(id_delta)
+            } else {
+                let glyph_offset = id_range_offset_offset
+                    + id_range_offset
+                    + (character - start_code) as usize * 2;
+                match read(glyph_offset)? {
+                    0 => 0,
+                    glyph_id => glyph_id.wrapping_add(id_delta),
+                }
+            };
+            add_mapping(character as u32, glyph_id as u32);
+        }
+    }
+    Ok(())
+}
+
+fn parse_cmap_format_6<F>(subtable: &[u8], add_mapping: &mut F) -> Result<(), SubsetError>
+where
+    F: FnMut(u32, u32),
+{
+    let first_code = read_u16(subtable, 6).ok_or(MALFORMED_CMAP)? as u32;
+    let entry_count = read_u16(subtable, 8).ok_or(MALFORMED_CMAP)? as usize;
+    for entry_index in 0..entry_count {
+        let glyph_id = read_u16(subtable, 10 + entry_index * 2).ok_or(MALFORMED_CMAP)?;
+        add_mapping(first_code + entry_index as u32, glyph_id as u32);
+    }
+    Ok(())
+}
+
+// Groups are clamped to the Unicode range and to the glyphs that exist.
+fn parse_cmap_format_12<F>(
+    subtable: &[u8],
+    glyph_count: u32,
+    add_mapping: &mut F,
+) -> Result<(), SubsetError>
+where
+    F: FnMut(u32, u32),
+{
+    let group_count = read_u32(subtable, 12).ok_or(MALFORMED_CMAP)? as usize;
+    let mut next_character = 0;
+    for group_index in 0..group_count {
+        let group_offset = 16 + group_index * 12;
+        let read = |offset| read_u32(subtable, offset).ok_or(MALFORMED_CMAP);
+        let start_code = read(group_offset)?;
+        let end_code = read(group_offset + 4)?;
+        let start_glyph_id = read(group_offset + 8)?;
+        if start_code < next_character || start_code > end_code {
+            return Err(MALFORMED_CMAP);
+        }
+        next_character = end_code.saturating_add(1);
+
+        if start_glyph_id >= glyph_count {
+            continue;
+        }
+        let last_glyph_code = start_code.saturating_add(glyph_count - 1 - start_glyph_id);
+        let end_code = end_code.min(last_glyph_code).min(0x10ffff);
+        for character in start_code..=end_code {
+            add_mapping(character, start_glyph_id + (character - start_code));
+        }
+    }
+    Ok(())
+}
+
+/// Writes a `cmap` table with a format 4 subtable for the Basic Multilingual Plane and, if there
+/// are characters beyond it, a format 12 subtable for everything.
+fn write_cmap(mappings: &[(u32, u32)]) -> Vec<u8> {
+    // Runs of consecutive characters mapped to consecutive glyphs.
+    let mut ranges: Vec<(u32, u32, u32)> = vec![];
+    for &(character, glyph_id) in mappings {
+        match ranges.last_mut() {
+            Some(&mut (start, ref mut end, start_glyph_id))
+                if *end + 1 == character && start_glyph_id + (character - start) == glyph_id =>
+            {
+                *end = character
+            }
+            _ => ranges.push((character, character, glyph_id)),
+        }
+    }
+
+    let format_4 = write_cmap_format_4(&ranges);
+    let format_12 = if format_4.is_none()
+        || mappings
+            .last()
+            .map_or(false, |&(character, _)| character > 0xffff)
+    {
+        Some(write_cmap_format_12(&ranges))
+    } else {
+        None
+    };
+
+    // Encoding records must be sorted by platform and then encoding.
+    let mut encodings = vec![];
+    if format_4.is_some() {
+        encodings.push((0, 3, false));
+    }
+    if format_12.is_some() {
+        encodings.push((0, 4, true));
+    }
+    if format_4.is_some() {
+        encodings.push((3, 1, false));
+    }
+    if format_12.is_some() {
+        encodings.push((3, 10, true));
+    }
+
+    let format_4_offset = 4 + encodings.len() as u32 * 8;
+    let format_12_offset = format_4_offset + format_4.as_ref().map_or(0, |data| data.len() as u32);
+    let mut cmap = vec![];
+    push_u16(&mut cmap, 0);
+    push_u16(&mut cmap, encodings.len() as u16);
+    for &(platform_id, encoding_id, is_format_12) in &encodings {
+        push_u16(&mut cmap, platform_id);
+        push_u16(&mut cmap, encoding_id);
+        push_u32(
+            &mut cmap,
+            if is_format_12 {
+                format_12_offset
+            } else {
+                format_4_offset
+            },
+        );
+    }
+    for subtable in format_4.iter().chain(format_12.iter()) {
+        cmap.extend_from_slice(subtable);
+    }
+    cmap
+}
+
+/// Returns `None` if the characters don't fit in a format 4 subtable.
+fn write_cmap_format_4(ranges: &[(u32, u32, u32)]) -> Option<Vec<u8>> {
+    let mut segments: Vec<(u16, u16, u16)> = ranges
+        .iter()
+        .filter(|&&(start, _, _)| start < 0xffff)
+        .map(|&(start, end, start_glyph_id)| {
+            let end = end.min(0xfffe);
+            (
+                start as u16,
+                end as u16,
+                (start_glyph_id as u16).wrapping_sub(start as u16),
+            )
+        })
+        .collect();
+    // The final segment maps 0xffff to glyph 0.
+    segments.push((0xffff, 0xffff, 1));
+
+    let segment_count = segments.len();
+    let length = 16 + segment_count * 8;
+    if length > 0xffff {
+        return None;
+    }
+    let mut entry_selector = 0;
+    while (2 << entry_selector) <= segment_count {
+        entry_selector += 1;
+    }
+    let search_range = 2 << entry_selector;
+
+    let mut subtable = vec![];
+    push_u16(&mut subtable, 4);
+    push_u16(&mut subtable, length as u16);
+    push_u16(&mut subtable, 0);
+    push_u16(&mut subtable, segment_count as u16 * 2);
+    push_u16(&mut subtable, search_range as u16);
+    push_u16(&mut subtable, entry_selector);
+    push_u16(&mut subtable, (segment_count * 2 - search_range) as u16);
+    for &(_, end, _) in &segments {
+        push_u16(&mut subtable, end);
+    }
+    push_u16(&mut subtable, 0);
+    for &(start, _, _) in &segments {
+        push_u16(&mut subtable, start);
+    }
+    for &(_, _, id_delta) in &segments {
+        push_u16(&mut subtable, id_delta);
+    }
+    for _ in &segments {
+        push_u16(&mut subtable, 0);
+    }
+    Some(subtable)
+}
+
+fn write_cmap_format_12(ranges: &[(u32, u32, u32)]) -> Vec<u8> {
+    let mut subtable = vec![];
+    push_u16(&mut subtable, 12);
+    push_u16(&mut subtable, 0);
+    push_u32(&mut subtable, 16 + ranges.len() as u32 * 12);
+    push_u32(&mut subtable, 0);
+    push_u32(&mut subtable, ranges.len() as u32);
+    for &(start, end, start_glyph_id) in ranges {
+        push_u32(&mut subtable, start);
+        push_u32(&mut subtable, end);
+        push_u32(&mut subtable, start_glyph_id);
+    }
+    subtable
+}
+
+/// The `glyf` and `loca` tables of a TrueType font.
+struct GlyphTable<'a> {
+    glyf: &'a [u8],
+    loca: &'a [u8],
+    long_loca: bool,
+    glyph_count: u32,
+}
+
+impl<'a> GlyphTable<'a> {
+    fn new(glyf: &'a [u8], loca: &'a [u8], long_loca: bool, glyph_count: u32) -> GlyphTable<'a> {
+        GlyphTable {
+            glyf,
+            loca,
+            long_loca,
+            glyph_count,
+        }
+    }
+
+    fn glyph_data(&self, glyph_id: u32) -> Result<&'a [u8], SubsetError> {
+        let glyph_id = glyph_id as usize;
+        let (start, end) = if self.long_loca {
+            (
+                read_u32(self.loca, glyph_id * 4).map(|offset| offset as usize),
+                read_u32(self.loca, glyph_id * 4 + 4).map(|offset| offset as usize),
+            )
+        } else {
+            (
+                read_u16(self.loca, glyph_id * 2).map(|offset| offset as usize * 2),
+                read_u16(self.loca, glyph_id * 2 + 2).map(|offset| offset as usize * 2),
+            )
+        };
+        match (start, end) {
+            (Some(start), Some(end)) => self.glyf.get(start..end).ok_or(MALFORMED_GLYF),
+            _ => Err(SubsetError::MalformedTable(Tag(sfnt::LOCA))),
+        }
+    }
+
+    /// Adds the components of composite glyphs, recursively, to the set of glyphs.
+    fn add_components(&self, glyph_ids: &mut BTreeSet<u32>) -> Result<(), SubsetError> {
+        let mut pending: Vec<u32> = glyph_ids.iter().cloned().collect();
+        while let Some(glyph_id) = pending.pop() {
+            let glyph_data = self.glyph_data(glyph_id)?;
+            for component_offset in component_offsets(glyph_data)? {
+                let component_id = BigEndian::read_u16(&glyph_data[component_offset..]) as u32;
+                if component_id >= self.glyph_count {
+                    return Err(MALFORMED_GLYF);
+                }
+                if glyph_ids.insert(component_id) {
+                    pending.push(component_id);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns new `glyf` and long-format `loca` tables containing the given glyphs, with
+    /// composite glyphs referring to the new IDs of their components.
+    fn subset(&self, glyph_ids: &[u32]) -> Result<(Vec<u8>, Vec<u8>), SubsetError> {
+        let (mut glyf, mut loca) = (vec![], vec![]);
+        for &glyph_id in glyph_ids {
+            push_u32(&mut loca, glyf.len() as u32);
+
+            let glyph_start = glyf.len();
+            let glyph_data = self.glyph_data(glyph_id)?;
+            glyf.extend_from_slice(glyph_data);
+            for component_offset in component_offsets(glyph_data)? {
+                let offset = glyph_start + component_offset;
+                let component_id = BigEndian::read_u16(&glyf[offset..]) as u32;
+                let new_component_id = glyph_ids
+                    .binary_search(&component_id)
+                    .map_err(|_| MALFORMED_GLYF)?;
+                BigEndian::write_u16(&mut glyf[offset..], new_component_id as u16);
+            }
+
+            let padded_len = (glyf.len() + 3) & !3;
+            glyf.resize(padded_len, 0);
+        }
+        push_u32(&mut loca, glyf.len() as u32);
+        Ok((glyf, loca))
+    }
+}
+
+/// Returns the offsets, within the glyph data, of the glyph IDs of the components of a composite
+/// glyph. Simple and empty glyphs have none.
+fn component_offsets(glyph_data: &[u8]) -> Result<Vec<usize>, SubsetError> {
+    let mut offsets = vec![];
+    if glyph_data.is_empty() || read_i16(glyph_data, 0).ok_or(MALFORMED_GLYF)? >= 0 {
+        return Ok(offsets);
+    }
+
+    let mut offset = 10;
+    loop {
+        let flags = read_u16(glyph_data, offset).ok_or(MALFORMED_GLYF)?;
+        read_u16(glyph_data, offset + 2).ok_or(MALFORMED_GLYF)?;
+        offsets.push(offset + 2);
+
+        offset += 4;
+        offset += if flags & COMPOSITE_ARG_1_AND_2_ARE_WORDS != 0 {
+            4
+        } else {
+            2
+        };
+        if flags & COMPOSITE_WE_HAVE_A_SCALE != 0 {
+            offset += 2
+        } else if flags & COMPOSITE_WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+            offset += 4
+        } else if flags & COMPOSITE_WE_HAVE_A_TWO_BY_TWO != 0 {
+            offset += 8
+        }
+
+        if flags & COMPOSITE_MORE_COMPONENTS == 0 {
+            return Ok(offsets);
+        }
+    }
+}
+
+/// Rebuilds a `CFF ` table with only the given glyphs.
+///
+/// The charset keeps the original glyph names or CIDs. Global and local subroutines are copied
+/// unchanged, as are all Font DICTs of CID-keyed fonts. The encoding is dropped, since the `cmap`
+/// is authoritative in OpenType fonts.
+fn subset_cff(cff: &[u8], glyph_ids: &[u32]) -> Result<Vec<u8>, SubsetError> {
+    if cff.first() != Some(&1) {
+        return Err(SubsetError::UnsupportedFormat);
+    }
+    let header_size = *cff.get(2).ok_or(MALFORMED_CFF)? as usize;
+    let (names, offset) = parse_cff_index(cff, header_size)?;
+    let (top_dicts, offset) = parse_cff_index(cff, offset)?;
+    let (strings, offset) = parse_cff_index(cff, offset)?;
+    let (global_subrs, _) = parse_cff_index(cff, offset)?;
+    if top_dicts.len() != 1 {
+        return Err(SubsetError::UnsupportedFormat);
+    }
+
+    let top_dict = parse_cff_dict(top_dicts[0])?;
+    if let Some(charstring_type) = cff_dict_integers(&top_dict, CFF_OPERATOR_CHARSTRING_TYPE)? {
+        if charstring_type.first() != Some(&2) {
+            return Err(SubsetError::UnsupportedFormat);
+        }
+    }
+
+    let char_strings_offset =
+        cff_dict_offset(&top_dict, CFF_OPERATOR_CHAR_STRINGS)?.ok_or(MALFORMED_CFF)?;
+    let (char_strings, _) = parse_cff_index(cff, char_strings_offset)?;
+    let charset_offset = cff_dict_offset(&top_dict, CFF_OPERATOR_CHARSET)?.unwrap_or(0);
+    let charset = parse_cff_charset(cff, charset_offset, char_strings.len())?;
+
+    let mut new_char_strings = Vec::with_capacity(glyph_ids.len());
+    let mut new_charset = vec![0];
+    for &glyph_id in glyph_ids {
+        let glyph_id = glyph_id as usize;
+        new_char_strings.push(*char_strings.get(glyph_id).ok_or(MALFORMED_CFF)?);
+        if glyph_id != 0 {
+            push_u16(&mut new_charset, charset[glyph_id]);
+        }
+    }
+    let new_char_strings = write_cff_index(&new_char_strings);
+
+    // Gather the Private DICTs, either one from the Top DICT or one per Font DICT.
+    let is_cid_keyed = top_dict
+        .iter()
+        .any(|entry| entry.operator == CFF_OPERATOR_ROS);
+    let mut font_dicts = vec![];
+    let mut new_fd_select = vec![];
+    let mut private_dicts = vec![];
+    if is_cid_keyed {
+        let fd_array_offset =
+            cff_dict_offset(&top_dict, CFF_OPERATOR_FD_ARRAY)?.ok_or(MALFORMED_CFF)?;
+        let fd_select_offset =
+            cff_dict_offset(&top_dict, CFF_OPERATOR_FD_SELECT)?.ok_or(MALFORMED_CFF)?;
+        for font_dict in parse_cff_index(cff, fd_array_offset)?.0 {
+            let font_dict = parse_cff_dict(font_dict)?;
+            private_dicts.push(parse_cff_private_dict(cff, &font_dict)?);
+            font_dicts.push(font_dict);
+        }
+
+        let fd_select = parse_cff_fd_select(cff, fd_select_offset, char_strings.len())?;
+        new_fd_select.push(0);
+        for &glyph_id in glyph_ids {
+            let font_dict_index = fd_select[glyph_id as usize];
+            if font_dict_index as usize >= font_dicts.len() {
+                return Err(MALFORMED_CFF);
+            }
+            new_fd_select.push(font_dict_index);
+        }
+    } else {
+        private_dicts.push(parse_cff_private_dict(cff, &top_dict)?);
+    }
+
+    // Every offset we write is a five-byte integer, so the sizes of the DICTs don't depend on
+    // the offsets and we can lay everything out in one pass.
+    let private_dict_sizes: Vec<usize> = private_dicts
+        .iter()
+        .map(|private_dict| private_dict.write(0).len())
+        .collect();
+    let write_top_dict = |charset_offset: i32,
+                          char_strings_offset: i32,
+                          fd_offsets: (i32, i32),
+                          private: Vec<i32>| {
+        let mut replacements = vec![
+            (CFF_OPERATOR_CHARSET, vec![charset_offset]),
+            (CFF_OPERATOR_CHAR_STRINGS, vec![char_strings_offset]),
+        ];
+        if is_cid_keyed {
+            replacements.push((CFF_OPERATOR_FD_ARRAY, vec![fd_offsets.0]));
+            replacements.push((CFF_OPERATOR_FD_SELECT, vec![fd_offsets.1]));
+        } else {
+            replacements.push((CFF_OPERATOR_PRIVATE, private));
+        }
+        write_cff_dict(&top_dict, &replacements, &[CFF_OPERATOR_ENCODING])
+    };
+    let write_font_dicts = |private_offsets: &[(i32, i32)]| {
+        let font_dicts: Vec<Vec<u8>> = font_dicts
+            .iter()
+            .zip(private_offsets.iter())
+            .map(|(font_dict, &(size, offset))| {
+                write_cff_dict(
+                    font_dict,
+                    &[(CFF_OPERATOR_PRIVATE, vec![size, offset])],
+                    &[],
+                )
+            })
+            .collect();
+        let font_dicts: Vec<&[u8]> = font_dicts.iter().map(|dict| &dict[..]).collect();
+        write_cff_index(&font_dicts)
+    };
+
+    let header = [1, 0, 4, 4];
+    let names = write_cff_index(&names);
+    let strings = write_cff_index(&strings);
+    let global_subrs = write_cff_index(&global_subrs);
+    let top_dict_size = write_top_dict(0, 0, (0, 0), vec![0, 0]).len();
+    let top_dict_index_size = write_cff_index(&[&vec![0; top_dict_size][..]]).len();
+
+    let charset_offset =
+        header.len() + names.len() + top_dict_index_size + strings.len() + global_subrs.len();
+    let fd_select_offset = charset_offset + new_charset.len();
+    let char_strings_offset = fd_select_offset + new_fd_select.len();
+    let fd_array_offset = char_strings_offset + new_char_strings.len();
+    let placeholder_private_offsets = vec![(0, 0); font_dicts.len()];
+    let fd_array_size = if is_cid_keyed {
+        write_font_dicts(&placeholder_private_offsets).len()
+    } else {
+        0
+    };
+
+    let mut private_offsets = vec![];
+    let mut private_offset = fd_array_offset + fd_array_size;
+    for (private_dict, &size) in private_dicts.iter().zip(private_dict_sizes.iter()) {
+        private_offsets.push((size as i32, private_offset as i32));
+        private_offset += size + private_dict.local_subrs.len();
+    }
+
+    let top_dict = write_top_dict(
+        charset_offset as i32,
+        char_strings_offset as i32,
+        (fd_array_offset as i32, fd_select_offset as i32),
+        private_offsets
+            .first()
+            .map_or(vec![0, 0], |&(size, offset)| vec![size, offset]),
+    );
+
+    let mut new_cff = header.to_vec();
+    new_cff.extend_from_slice(&names);
+    new_cff.extend_from_slice(&write_cff_index(&[&top_dict[..]]));
+    new_cff.extend_from_slice(&strings);
+    new_cff.extend_from_slice(&global_subrs);
+    new_cff.extend_from_slice(&new_charset);
+    new_cff.extend_from_slice(&new_fd_select);
+    new_cff.extend_from_slice(&new_char_strings);
+    if is_cid_keyed {
+        new_cff.extend_from_slice(&write_font_dicts(&private_offsets));
+    }
+    for (private_dict, &size) in private_dicts.iter().zip(private_dict_sizes.iter()) {
+        new_cff.extend_from_slice(&private_dict.write(size as i32));
+        new_cff.extend_from_slice(private_dict.local_subrs);
+    }
+    Ok(new_cff)
+}
+
+/// Parses a CFF INDEX, returning its items and the offset just past its end.
+fn parse_cff_index(cff: &[u8], offset: usize) -> Result<(Vec<&[u8]>, usize), SubsetError> {
+    let count = read_u16(cff, offset).ok_or(MALFORMED_CFF)? as usize;
+    if count == 0 {
+        return Ok((vec![], offset + 2));
+    }
+
+    let offset_size = *cff.get(offset + 2).ok_or(MALFORMED_CFF)? as usize;
+    if offset_size == 0 || offset_size > 4 {
+        return Err(MALFORMED_CFF);
+    }
+    let offsets_start = offset + 3;
+    // Item offsets are relative to the byte before the data.
+    let data_start = offsets_start + (count + 1) * offset_size - 1;
+    let read_offset = |index: usize| {
+        let start = offsets_start + index * offset_size;
+        cff.get(start..(start + offset_size))
+            .map(|bytes| {
+                bytes
+                    .iter()
+                    .fold(0, |offset, &byte| offset << 8 | byte as usize)
+            })
+            .ok_or(MALFORMED_CFF)
+    };
+
+    let mut items = Vec::with_capacity(count);
+    let mut item_start = read_offset(0)?;
+    for index in 1..=count {
+        let item_end = read_offset(index)?;
+        items.push(
+            cff.get((data_start + item_start)..(data_start + item_end))
+                .ok_or(MALFORMED_CFF)?,
+        );
+        item_start = item_end;
+    }
+    Ok((items, data_start + item_start))
+}
+
+fn write_cff_index(items: &[&[u8]]) -> Vec<u8> {
+    let mut index = vec![];
+    push_u16(&mut index, items.len() as u16);
+    if items.is_empty() {
+        return index;
+    }
+
+    let last_offset = items.iter().map(|item| item.len()).sum::<usize>() + 1;
+    let offset_size = match last_offset {
+        0..=0xff => 1,
+        0x100..=0xffff => 2,
+        0x1_0000..=0xff_ffff => 3,
+        _ => 4,
+    };
+    index.push(offset_size as u8);
+
+    let mut offset = 1;
+    for item_len in iter::once(0).chain(items.iter().map(|item| item.len())) {
+        offset += item_len;
+        index.extend_from_slice(&(offset as u32).to_be_bytes()[(4 - offset_size)..]);
+    }
+    for item in items {
+        index.extend_from_slice(item);
+    }
+    index
+}
+
+/// An operator in a CFF DICT, with its operands still encoded.
+struct CffDictEntry<'a> {
+    operator: u16,
+    operands: &'a [u8],
+}
+
+fn parse_cff_dict<'a>(dict: &'a [u8]) -> Result<Vec<CffDictEntry<'a>>, SubsetError> {
+    let mut entries = vec![];
+    let (mut operands_start, mut offset) = (0, 0);
+    while offset < dict.len() {
+        let byte = dict[offset];
+        match byte {
+            0..=21 => {
+                let operands = &dict[operands_start..offset];
+                let operator = if byte == 12 {
+                    offset += 1;
+                    0x0c00 | *dict.get(offset).ok_or(MALFORMED_CFF)? as u16
+                } else {
+                    byte as u16
+                };
+                entries.push(CffDictEntry { operator, operands });
+                offset += 1;
+                operands_start = offset;
+            }
+            28 => offset += 3,
+            29 => offset += 5,
+            30 => {
+                // Real numbers are packed into nibbles and end with a nibble of 0xf.
+                offset += 1;
+                loop {
+                    let nibbles = *dict.get(offset).ok_or(MALFORMED_CFF)?;
+                    offset += 1;
+                    if nibbles & 0xf == 0xf || nibbles >> 4 == 0xf {
+                        break;
+                    }
+                }
+            }
+            32..=246 => offset += 1,
+            247..=254 => offset += 2,
+            _ => return Err(MALFORMED_CFF),
+        }
+    }
+    Ok(entries)
+}
+
+/// Returns the integer operands of the given operator, if the DICT contains it.
+fn cff_dict_integers(
+    dict: &[CffDictEntry],
+    operator: u16,
+) -> Result<Option<Vec<i32>>, SubsetError> {
+    let entry = match dict.iter().find(|entry| entry.operator == operator) {
+        None => return Ok(None),
+        Some(entry) => entry,
+    };
+
+    let (operands, mut integers, mut offset) = (entry.operands, vec![], 0);
+    let byte = |offset: usize| {
+        operands
+            .get(offset)
+            .map(|&byte| byte as i32)
+            .ok_or(MALFORMED_CFF)
+    };
+    while offset < operands.len() {
+        let (integer, size) = match byte(offset)? {
+            28 => (
+                (byte(offset + 1)? << 8 | byte(offset + 2)?) as i16 as i32,
+                3,
+            ),
+            29 => (
+                byte(offset + 1)? << 24
+                    | byte(offset + 2)? << 16
+                    | byte(offset + 3)? << 8
+                    | byte(offset + 4)?,
+                5,
+            ),
+            b0 @ 32..=246 => (b0 - 139, 1),
+            b0 @ 247..=250 => ((b0 - 247) * 256 + byte(offset + 1)? + 108, 2),
+            b0 @ 251..=254 => (-(b0 - 251) * 256 - byte(offset + 1)? - 108, 2),
+            _ => return Err(MALFORMED_CFF),
+        };
+        integers.push(integer);
+        offset += size;
+    }
+    Ok(Some(integers))
+}
+
+/// Returns the last integer operand of the given operator, which is where offsets live.
+fn cff_dict_offset(dict: &[CffDictEntry], operator: u16) -> Result<Option<usize>, SubsetError> {
+    match cff_dict_integers(dict, operator)? {
+        None => Ok(None),
+        Some(integers) => match integers.last() {
+            Some(&offset) if offset >= 0 => Ok(Some(offset as usize)),
+            _ => Err(MALFORMED_CFF),
+        },
+    }
+}
+
+/// Writes a DICT with some operators replaced (or added) and some removed.
+///
+/// Replacement operands are written as five-byte integers so that the size of the DICT doesn't
+/// depend on their values.
+fn write_cff_dict(
+    dict: &[CffDictEntry],
+    replacements: &[(u16, Vec<i32>)],
+    removals: &[u16],
+) -> Vec<u8> {
+    let mut data = vec![];
+    let write_operator = |data: &mut Vec<u8>, operator: u16| {
+        if operator >= 0x0c00 {
+            data.push(12);
+        }
+        data.push(operator as u8);
+    };
+
+    for entry in dict {
+        if removals.contains(&entry.operator)
+            || replacements
+                .iter()
+                .any(|&(operator, _)| operator == entry.operator)
+        {
+            continue;
+        }
+        data.extend_from_slice(entry.operands);
+        write_operator(&mut data, entry.operator);
+    }
+    for &(operator, ref operands) in replacements {
+        for &operand in operands {
+            data.push(29);
+            data.extend_from_slice(&operand.to_be_bytes());
+        }
+        write_operator(&mut data, operator);
+    }
+    data
+}
+
+/// A Private DICT and the local subroutines that follow it.
+struct CffPrivateDict<'a> {
+    dict: Vec<CffDictEntry<'a>>,
+    local_subrs: &'a [u8],
+}
+
+impl<'a> CffPrivateDict<'a> {
+    /// Writes the DICT, with its local subroutines placed right after it.
+    fn write(&self, size: i32) -> Vec<u8> {
+        let mut replacements = vec![];
+        if !self.local_subrs.is_empty() {
+            replacements.push((CFF_OPERATOR_SUBRS, vec![size]));
+        }
+        write_cff_dict(&self.dict, &replacements, &[CFF_OPERATOR_SUBRS])
+    }
+}
+
+/// Parses the Private DICT referred to by a Top DICT or Font DICT.
+fn parse_cff_private_dict<'a>(
+    cff: &'a [u8],
+    dict: &[CffDictEntry],
+) -> Result<CffPrivateDict<'a>, SubsetError> {
+    let (size, offset) = match cff_dict_integers(dict, CFF_OPERATOR_PRIVATE)? {
+        Some(ref integers) if integers.len() == 2 && integers[0] >= 0 && integers[1] >= 0 => {
+            (integers[0] as usize, integers[1] as usize)
+        }
+        _ => return Err(MALFORMED_CFF),
+    };
+    let private_dict = cff.get(offset..(offset + size)).ok_or(MALFORMED_CFF)?;
+    let private_dict = parse_cff_dict(private_dict)?;
+
+    let local_subrs = match cff_dict_offset(&private_dict, CFF_OPERATOR_SUBRS)? {
+        None => &[][..],
+        Some(subrs_offset) => {
+            let subrs_start = offset + subrs_offset;
+            let (_, subrs_end) = parse_cff_index(cff, subrs_start)?;
+            &cff[subrs_start..subrs_end]
+        }
+    };
+    Ok(CffPrivateDict {
+        dict: private_dict,
+        local_subrs,
+    })
+}
+
+/// Returns the SID (or CID) of each glyph, written as a format 0 charset.
+fn parse_cff_charset(
+    cff: &[u8],
+    offset: usize,
+    glyph_count: usize,
+) -> Result<Vec<u16>, SubsetError> {
+    // The ISOAdobe charset maps glyphs to SIDs one to one.
+    const ISO_ADOBE_LAST_SID: usize = 228;
+    match offset {
+        0 if glyph_count <= ISO_ADOBE_LAST_SID + 1 => return Ok((0..glyph_count as u16).collect()),
+        0..=2 => return Err(SubsetError::UnsupportedFormat),
+        _ => {}
+    }
+
+    let format = *cff.get(offset).ok_or(MALFORMED_CFF)?;
+    let mut charset = Vec::with_capacity(glyph_count);
+    charset.push(0);
+    let mut offset = offset + 1;
+    while charset.len() < glyph_count {
+        let first = read_u16(cff, offset).ok_or(MALFORMED_CFF)?;
+        match format {
+            0 => {
+                charset.push(first);
+                offset += 2;
+            }
+            1 | 2 => {
+                let left = if format == 1 {
+                    *cff.get(offset + 2).ok_or(MALFORMED_CFF)? as usize
+                } else {
+                    read_u16(cff, offset + 2).ok_or(MALFORMED_CFF)? as usize
+                };
+                for sid in (first as usize)..=(first as usize + left) {
+                    if charset.len() == glyph_count {
+                        break;
+                    }
+                    charset.push(sid as u16);
+                }
+                offset += if format == 1 { 3 } else { 4 };
+            }
+            _ => return Err(MALFORMED_CFF),
+        }
+    }
+    Ok(charset)
+}
+
+/// Returns the Font DICT index of each glyph of a CID-keyed font.
+fn parse_cff_fd_select(
+    cff: &[u8],
+    offset: usize,
+    glyph_count: usize,
+) -> Result<Vec<u8>, SubsetError> {
+    match cff.get(offset) {
+        Some(0) => cff
+            .get((offset + 1)..(offset + 1 + glyph_count))
+            .map(|fd_select| fd_select.to_vec())
+            .ok_or(MALFORMED_CFF),
+        Some(3) => {
+            let range_count = read_u16(cff, offset + 1).ok_or(MALFORMED_CFF)? as usize;
+            let mut fd_select = Vec::with_capacity(glyph_count);
+            for range_index in 0..range_count {
+                let range_offset = offset + 3 + range_index * 3;
+                let first = read_u16(cff, range_offset).ok_or(MALFORMED_CFF)? as usize;
+                let font_dict_index = *cff.get(range_offset + 2).ok_or(MALFORMED_CFF)?;
+                // The first glyph of the next range, or the sentinel.
+                let end = read_u16(cff, range_offset + 3).ok_or(MALFORMED_CFF)? as usize;
+                if first != fd_select.len() || end < first {
+                    return Err(MALFORMED_CFF);
+                }
+                fd_select.resize(end.min(glyph_count), font_dict_index);
+            }
+            if fd_select.len() != glyph_count {
+                return Err(MALFORMED_CFF);
+            }
+            Ok(fd_select)
+        }
+        _ => Err(MALFORMED_CFF),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_cmap, MALFORMED_CMAP};
+
+    // A `cmap` table with a single Windows UCS-4 subtable in format 12.
+    fn cmap_format_12(groups: &[(u32, u32, u32)]) -> Vec<u8> {
+        let mut cmap = vec![0, 0, 0, 1, 0, 3, 0, 10, 0, 0, 0, 12];
+        cmap.extend_from_slice(&[0, 12, 0, 0]);
+        cmap.extend_from_slice(&(16 + groups.len() as u32 * 12).to_be_bytes());
+        cmap.extend_from_slice(&[0; 4]);
+        cmap.extend_from_slice(&(groups.len() as u32).to_be_bytes());
+        for &(start_code, end_code, start_glyph_id) in groups {
+            for &value in &[start_code, end_code, start_glyph_id] {
+                cmap.extend_from_slice(&value.to_be_bytes());
+            }
+        }
+        cmap
+    }
+
+    #[test]
+    fn clamp_oversized_cmap_groups() {
+        let mappings = parse_cmap(&cmap_format_12(&[(0x41, 0xffff_ffff, 1)]), 4).unwrap();
+        assert_eq!(
+            mappings.into_iter().collect::<Vec<_>>(),
+            vec![(0x41, 1), (0x42, 2), (0x43, 3)]
+        );
+    }
+
+    #[test]
+    fn reject_overlapping_cmap_groups() {
+        let cmap = cmap_format_12(&[(0x41, 0x10ffff, 1), (0, 0x10ffff, 1)]);
+        assert_eq!(parse_cmap(&cmap, 4), Err(MALFORMED_CMAP));
+    }
+}
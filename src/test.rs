@@ -8,22 +8,44 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use euclid::{Point2D, Rect, Size2D, Vector2D};
+use euclid::{Point2D, Rect, Size2D, Transform2D, Vector2D};
 use lyon_path::builder::FlatPathBuilder;
 use lyon_path::default::Path;
 use lyon_path::PathEvent;
-use std::fs::File;
+use std::cmp;
+use std::env;
+use std::fs::{self, File};
 use std::io::Read;
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc;
 use std::sync::Arc;
+use std::time::Duration;
 
 use canvas::{Canvas, Format, RasterizationOptions};
+use descriptor::{self, FontDescriptor};
+use error::{FontLoadingError, GlyphLoadingError, SelectionError, SubsetError};
 use family_name::FamilyName;
-use file_type::FileType;
+use features::Tag;
+use file_type::{FileType, FontFormat};
 use font::Font;
+use fuzzing;
+use generic_family_map::GenericFamilyMap;
+use handle::Handle;
 use hinting::HintingOptions;
-use properties::{Properties, Stretch, Weight};
-use source::SystemSource;
+use layout::{self, LayoutOptions};
+use outline::{Outline, Segment};
+use properties::{Properties, Stretch, Style, Weight};
+use query::FontQuery;
+use sfnt;
+use source::{IndexingProgress, Source, SystemSource};
+use sources::fs::{FsSource, IncrementalFsSource};
+use sources::mem::{DiagnosticKind, MemSource};
+use sources::multi::MultiSource;
+use std::error::Error;
+use subset::{self, SubsetFont, SubsetOptions};
 use utils;
+use watcher::PollingWatcher;
 
 static TEST_FONT_FILE_PATH: &'static str = "resources/tests/eb-garamond/EBGaramond12-Regular.otf";
 static TEST_FONT_POSTSCRIPT_NAME: &'static str = "EBGaramond12-Regular";
@@ -36,6 +58,9 @@ static FILE_PATH_EB_GARAMOND_TTF: &'static str =
     "resources/tests/eb-garamond/EBGaramond12-Regular.ttf";
 static FILE_PATH_INCONSOLATA_TTF: &'static str =
     "resources/tests/inconsolata/Inconsolata-Regular.ttf";
+static FILE_PATH_EB_GARAMOND_ITALIC_OTF: &'static str =
+    "resources/tests/eb-garamond/EBGaramond12-Italic.otf";
+static FILE_PATH_TIMES_ROMAN_PCF: &'static str = "resources/tests/times-roman-pcf/timR12.pcf";
 
 #[test]
 pub fn get_font_full_name() {
@@ -83,6 +108,47 @@ pub fn analyze_bytes() {
     );
 }
 
+#[test]
+pub fn load_font_from_missing_file() {
+    let error = Font::from_path("resources/tests/missing.otf", 0).unwrap_err();
+    match error {
+        FontLoadingError::Path { ref path, .. } => {
+            assert_eq!(path.to_str(), Some("resources/tests/missing.otf"))
+        }
+        _ => panic!("Expected the error to carry the path: {:?}", error),
+    }
+    match *error.without_path() {
+        FontLoadingError::Io(_) => {}
+        ref error => panic!("Expected an I/O error: {:?}", error),
+    }
+    assert!(error.source().is_some());
+}
+
+#[test]
+pub fn compare_selection_errors_from_missing_files() {
+    let load =
+        || SelectionError::from(Font::from_path("resources/tests/missing.otf", 0).unwrap_err());
+    let error = load();
+    assert_eq!(error.clone(), error);
+    assert_eq!(load(), error);
+    assert_ne!(
+        SelectionError::from(Font::from_path("resources/tests/missing.ttf", 0).unwrap_err()),
+        error
+    );
+    assert_ne!(SelectionError::NotFound, error);
+}
+
+#[test]
+pub fn load_nonexistent_font_from_collection() {
+    let mut file = File::open(TEST_FONT_COLLECTION_FILE_PATH).unwrap();
+    let mut font_data = vec![];
+    file.read_to_end(&mut font_data).unwrap();
+    match Font::from_bytes(Arc::new(font_data), 2) {
+        Err(FontLoadingError::NoSuchFontInCollection { font_index: 2 }) => {}
+        result => panic!("Expected a missing collection member: {:?}", result.err()),
+    }
+}
+
 #[test]
 pub fn get_glyph_for_char() {
     let font = SystemSource::new()
@@ -497,6 +563,17 @@ pub fn get_glyph_advance_and_origin() {
     assert_eq!(font.origin(glyph), Ok(Point2D::zero()));
 }
 
+#[cfg(not(any(target_family = "windows", target_os = "macos")))]
+#[test]
+pub fn get_advance_of_nonexistent_glyph() {
+    let font = Font::from_path(TEST_FONT_FILE_PATH, 0).unwrap();
+    let glyph_id = font.glyph_count();
+    assert_eq!(
+        font.advance(glyph_id),
+        Err(GlyphLoadingError::NoSuchGlyph(glyph_id))
+    );
+}
+
 #[cfg(any(target_family = "windows", target_os = "macos"))]
 #[test]
 pub fn get_font_metrics() {
@@ -535,6 +612,72 @@ pub fn get_font_metrics() {
     assert_eq!(metrics.x_height, 0.0); // FIXME(pcwalton): Huh?!
 }
 
+#[test]
+pub fn has_font_table() {
+    let font = Font::from_path(FILE_PATH_INCONSOLATA_TTF, 0).unwrap();
+    assert!(font.has_font_table(sfnt::GLYF));
+    assert!(!font.has_font_table(sfnt::FVAR));
+}
+
+#[test]
+pub fn get_vertical_metrics_without_vertical_tables() {
+    let font = Font::from_path(FILE_PATH_INCONSOLATA_TTF, 0).unwrap();
+    assert!(font.load_font_table(0x6865_6164).is_some()); // 'head'
+    assert!(font.load_font_table(0x7668_6561).is_none()); // 'vhea'
+
+    let metrics = font.metrics();
+    assert_eq!(metrics.vertical_ascent, metrics.units_per_em as f32 * 0.5);
+    assert_eq!(
+        metrics.vertical_descent,
+        -(metrics.units_per_em as f32 * 0.5)
+    );
+    assert_eq!(metrics.vertical_line_gap, 0.0);
+
+    let glyph = font.glyph_for_char('l').expect("No glyph for char!");
+    assert_eq!(
+        font.vertical_advance(glyph).unwrap(),
+        Vector2D::new(0.0, -(metrics.ascent - metrics.descent))
+    );
+    assert_eq!(
+        font.vertical_origin(glyph).unwrap(),
+        Point2D::new(font.advance(glyph).unwrap().x * 0.5, metrics.ascent)
+    );
+    assert!(font.vertical_advance(font.glyph_count()).is_err());
+}
+
+#[test]
+pub fn get_extended_font_metrics() {
+    let font = Font::from_path(FILE_PATH_INCONSOLATA_TTF, 0).unwrap();
+    let metrics = font.metrics();
+    assert_eq!(metrics.hhea_ascent, 859.0);
+    assert_eq!(metrics.hhea_descent, -190.0);
+    assert_eq!(metrics.hhea_line_gap, 0.0);
+    assert_eq!(metrics.typo_ascent, 859.0);
+    assert_eq!(metrics.typo_descent, -190.0);
+    assert_eq!(metrics.typo_line_gap, 0.0);
+    assert_eq!(metrics.win_ascent, 1004.0);
+    assert_eq!(metrics.win_descent, -306.0);
+    assert!(metrics.use_typo_metrics);
+    assert_eq!(metrics.strikeout_position, 274.0);
+    assert_eq!(metrics.strikeout_thickness, 50.0);
+    assert_eq!(metrics.subscript_size, Size2D::new(650.0, 600.0));
+    assert_eq!(metrics.subscript_offset, Vector2D::new(0.0, -75.0));
+    assert_eq!(metrics.superscript_size, Size2D::new(650.0, 600.0));
+    assert_eq!(metrics.superscript_offset, Vector2D::new(0.0, 350.0));
+    assert_eq!(metrics.caret_slope, Vector2D::new(0.0, 1.0));
+    assert_eq!(metrics.caret_offset, 0.0);
+    assert_eq!(metrics.average_char_width, 500.0);
+
+    let font = Font::from_path(FILE_PATH_EB_GARAMOND_ITALIC_OTF, 0).unwrap();
+    let metrics = font.metrics();
+    assert_eq!(metrics.win_descent, -290.0);
+    assert!(!metrics.use_typo_metrics);
+    assert_eq!(metrics.subscript_offset, Vector2D::new(131.0, -140.0));
+    assert_eq!(metrics.superscript_offset, Vector2D::new(-451.0, 480.0));
+    assert_eq!(metrics.caret_slope, Vector2D::new(31.0, 100.0));
+    assert_eq!(metrics.average_char_width, 562.0);
+}
+
 #[test]
 pub fn get_font_properties() {
     let font = SystemSource::new()
@@ -693,12 +836,1123 @@ fn load_fonts_from_opentype_collection() {
     );
 }
 
+#[test]
+fn get_handle_of_font_in_collection() {
+    let font = Font::from_path(TEST_FONT_COLLECTION_FILE_PATH, 1).unwrap();
+    assert_eq!(font.font_index(), 1);
+    match font.handle().unwrap() {
+        Handle::Memory { font_index, .. } => assert_eq!(font_index, 1),
+        Handle::Path { .. } => panic!("expected a memory handle"),
+    }
+
+    let reloaded_font = Font::from_handle(&font.handle().unwrap()).unwrap();
+    assert_eq!(
+        reloaded_font.postscript_name().unwrap(),
+        TEST_FONT_COLLECTION_POSTSCRIPT_NAME[1]
+    );
+}
+
+#[test]
+fn copy_standalone_font_data_from_collection() {
+    let font = Font::from_path(TEST_FONT_COLLECTION_FILE_PATH, 1).unwrap();
+    let collection_data = font.copy_font_data().unwrap();
+    let font_data = font.copy_standalone_font_data().unwrap();
+    assert!(font_data.len() < collection_data.len());
+    assert_eq!(
+        Font::analyze_bytes(font_data.clone()).unwrap(),
+        FileType::Single
+    );
+
+    let standalone_font = Font::from_bytes(font_data, 0).unwrap();
+    assert_eq!(standalone_font.font_index(), 0);
+    assert_eq!(
+        standalone_font.postscript_name().unwrap(),
+        TEST_FONT_COLLECTION_POSTSCRIPT_NAME[1]
+    );
+
+    // Single fonts are returned as is.
+    let font = Font::from_path(TEST_FONT_FILE_PATH, 0).unwrap();
+    assert_eq!(font.copy_standalone_font_data(), font.copy_font_data());
+}
+
 #[test]
 fn get_glyph_count() {
     let font = Font::from_path(TEST_FONT_FILE_PATH, 0).unwrap();
     assert_eq!(font.glyph_count(), 3084);
 }
 
+#[test]
+fn get_layout_features() {
+    let font = Font::from_path(TEST_FONT_FILE_PATH, 0).unwrap();
+    let features = font.layout_features();
+    assert_eq!(
+        features.scripts(),
+        vec![
+            Tag::DEFAULT_SCRIPT,
+            Tag::from_bytes(b"cyrl"),
+            Tag::from_bytes(b"grek"),
+            Tag::from_bytes(b"latn"),
+        ]
+    );
+    assert!(features.supports_feature(Tag::from_bytes(b"smcp")));
+    assert!(features.supports_feature(Tag::from_bytes(b"onum")));
+    assert!(features.supports_feature(Tag::from_bytes(b"kern")));
+    assert!(!features.supports_feature(Tag::from_bytes(b"zero")));
+    assert!(features.gsub.features.contains(&Tag::from_bytes(b"liga")));
+    assert!(!features.gpos.features.contains(&Tag::from_bytes(b"liga")));
+
+    // Localized forms are only enabled for specific languages.
+    let latin = features.gsub.script(Tag::from_bytes(b"latn")).unwrap();
+    assert_eq!(latin.language_systems.len(), 7);
+    let default_language_system = latin.language_system(None).unwrap();
+    assert!(!default_language_system
+        .features
+        .contains(&Tag::from_bytes(b"locl")));
+    let turkish = latin
+        .language_system(Some(Tag::from_bytes(b"TRK ")))
+        .unwrap();
+    assert!(turkish.features.contains(&Tag::from_bytes(b"locl")));
+    assert_eq!(turkish.required_feature, None);
+    assert_eq!(format!("{:?}", turkish.tag.unwrap()), "Tag(\"TRK \")");
+}
+
+#[test]
+fn select_fonts_with_feature_from_mem_source() {
+    let source = MemSource::from_fonts(
+        vec![
+            Handle::from_path(TEST_FONT_FILE_PATH.into(), 0),
+            Handle::from_path(FILE_PATH_INCONSOLATA_TTF.into(), 0),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    let small_caps = source
+        .select_fonts_with_feature(Tag::from_bytes(b"smcp"))
+        .unwrap();
+    assert_eq!(small_caps.len(), 1);
+    let font = Font::from_handle(&small_caps[0]).unwrap();
+    assert_eq!(font.postscript_name().unwrap(), TEST_FONT_POSTSCRIPT_NAME);
+    let slashed_zero = source
+        .select_fonts_with_feature(Tag::from_bytes(b"zero"))
+        .unwrap();
+    assert_eq!(slashed_zero.len(), 1);
+    let mark_positioning = source
+        .select_fonts_with_feature(Tag::from_bytes(b"mark"))
+        .unwrap();
+    assert_eq!(mark_positioning.len(), 2);
+}
+
+#[test]
+fn select_generic_family_from_mem_source() {
+    let mut source = MemSource::from_fonts(
+        vec![
+            Handle::from_path(TEST_FONT_FILE_PATH.into(), 0),
+            Handle::from_path(FILE_PATH_INCONSOLATA_TTF.into(), 0),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    let mut generic_family_map = GenericFamilyMap::empty();
+    generic_family_map
+        .set(FamilyName::SansSerif, &["Nonexistent Sans", "EB Garamond"])
+        .set_for_locale("ja", FamilyName::SansSerif, &["Inconsolata"]);
+    source.set_generic_family_map(generic_family_map);
+
+    let postscript_name = |family_names: &[FamilyName]| {
+        let handle = source
+            .select_best_match(family_names, &Properties::new())
+            .unwrap();
+        Font::from_handle(&handle)
+            .unwrap()
+            .postscript_name()
+            .unwrap()
+    };
+    assert_eq!(
+        postscript_name(&[FamilyName::SansSerif]),
+        TEST_FONT_POSTSCRIPT_NAME
+    );
+    assert_eq!(
+        postscript_name(&[FamilyName::Serif, FamilyName::UiSansSerif]),
+        TEST_FONT_POSTSCRIPT_NAME
+    );
+
+    let family = source
+        .select_family_by_generic_name_for_locale(&FamilyName::SansSerif, Some("ja-JP"))
+        .unwrap();
+    let font = Font::from_handle(&family.fonts()[0]).unwrap();
+    assert_eq!(font.family_name(), "Inconsolata");
+}
+
+#[test]
+fn select_best_match_for_locale_from_mem_source() {
+    let inconsolata_zh_hant =
+        font_with_declared_languages(FILE_PATH_INCONSOLATA_TTF, 0, Some("zh-Hant"));
+    let eb_garamond_zh_hans =
+        font_with_declared_languages(FILE_PATH_EB_GARAMOND_TTF, (1 << 0) | (1 << 18), None);
+    let inconsolata_ja = font_with_declared_languages(FILE_PATH_INCONSOLATA_TTF, 0, Some("ja"));
+    let source = MemSource::from_fonts(
+        vec![inconsolata_zh_hant, eb_garamond_zh_hans, inconsolata_ja].into_iter(),
+    )
+    .unwrap();
+
+    let family_names = [
+        FamilyName::Title("Inconsolata".to_owned()),
+        FamilyName::Title("EB Garamond".to_owned()),
+    ];
+    let select = |locale| {
+        let handle = source
+            .select_best_match_for_locale(&family_names, &Properties::new(), locale)
+            .unwrap();
+        let font = Font::from_handle(&handle).unwrap();
+        (font.family_name(), font.language_support().meta_languages)
+    };
+    assert_eq!(
+        select("ja-JP"),
+        ("Inconsolata".to_owned(), vec!["ja".to_owned()])
+    );
+    assert_eq!(
+        select("zh-TW"),
+        ("Inconsolata".to_owned(), vec!["zh-Hant".to_owned()])
+    );
+    assert_eq!(select("zh-Hans"), ("EB Garamond".to_owned(), vec![]));
+    // Nothing declares Korean, so the first family wins.
+    assert_eq!(
+        select("ko"),
+        ("Inconsolata".to_owned(), vec!["zh-Hant".to_owned()])
+    );
+}
+
+#[test]
+fn read_language_support_from_header() {
+    let handle = font_with_declared_languages(FILE_PATH_INCONSOLATA_TTF, 1 << 17, Some("ja"));
+    let language_support = descriptor::language_support_from_handle(&handle).unwrap();
+    assert_eq!(
+        language_support,
+        Font::from_handle(&handle).unwrap().language_support()
+    );
+    assert_eq!(language_support.meta_languages, vec!["ja".to_owned()]);
+    assert_eq!(language_support.code_page_ranges[0], 1 << 17);
+}
+
+#[test]
+fn add_and_remove_fonts_in_mem_source() {
+    let mut source = MemSource::empty();
+    let regular_id = source
+        .add_font(Handle::from_path(TEST_FONT_FILE_PATH.into(), 0))
+        .unwrap();
+    let mut collection_data = vec![];
+    File::open(TEST_FONT_COLLECTION_FILE_PATH)
+        .unwrap()
+        .read_to_end(&mut collection_data)
+        .unwrap();
+    let collection_ids = source.add_font_data(Arc::new(collection_data)).unwrap();
+    assert_eq!(collection_ids.len(), 2);
+    assert_eq!(
+        source
+            .select_family_by_name("EB Garamond")
+            .unwrap()
+            .fonts()
+            .len(),
+        3
+    );
+
+    // Nothing is added if any of the fonts fails to load.
+    assert!(source
+        .add_fonts(
+            vec![
+                Handle::from_path(FILE_PATH_INCONSOLATA_TTF.into(), 0),
+                Handle::from_memory(Arc::new(vec![0; 64]), 0),
+            ]
+            .into_iter()
+        )
+        .is_err());
+    assert!(source.add_font_data(Arc::new(vec![0; 64])).is_err());
+    assert_eq!(source.all_families().unwrap(), vec!["EB Garamond"]);
+
+    let inconsolata_ids = source
+        .add_fonts(vec![Handle::from_path(FILE_PATH_INCONSOLATA_TTF.into(), 0)].into_iter())
+        .unwrap();
+    assert_eq!(
+        source.all_families().unwrap(),
+        vec!["EB Garamond", "Inconsolata"]
+    );
+
+    assert!(source.remove_font(regular_id).is_some());
+    assert!(source.remove_font(regular_id).is_none());
+    assert!(source.remove_font(inconsolata_ids[0]).is_some());
+    let family = source.select_family_by_name("EB Garamond").unwrap();
+    let postscript_names: Vec<_> = family
+        .fonts()
+        .iter()
+        .map(|handle| {
+            Font::from_handle(handle)
+                .unwrap()
+                .postscript_name()
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(postscript_names, TEST_FONT_COLLECTION_POSTSCRIPT_NAME);
+    assert_eq!(source.all_families().unwrap(), vec!["EB Garamond"]);
+}
+
+#[test]
+fn index_fonts_without_postscript_names() {
+    let font_data = Arc::new(font_without_postscript_name(FILE_PATH_EB_GARAMOND_TTF));
+    assert!(Font::from_bytes(font_data.clone(), 0)
+        .unwrap()
+        .postscript_name()
+        .is_none());
+
+    let source = MemSource::from_fonts(
+        vec![
+            Handle::from_memory(font_data.clone(), 0),
+            Handle::from_path(FILE_PATH_INCONSOLATA_TTF.into(), 0),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    assert_eq!(source.all_fonts().unwrap().len(), 2);
+    let family_names = [FamilyName::Title("EB Garamond".to_owned())];
+    let handle = source
+        .select_best_match(&family_names, &Properties::new())
+        .unwrap();
+    assert_eq!(
+        Font::from_handle(&handle).unwrap().family_name(),
+        "EB Garamond"
+    );
+    assert!(source
+        .select_by_postscript_name("EBGaramond-Regular")
+        .is_ok());
+    assert_eq!(source.diagnostics().len(), 1);
+    match source.diagnostics()[0].kind {
+        DiagnosticKind::SynthesizedPostScriptName(ref postscript_name) => {
+            assert_eq!(postscript_name, "EBGaramond-Regular")
+        }
+        ref kind => panic!("unexpected diagnostic: {:?}", kind),
+    }
+
+    let directory = env::temp_dir().join(format!("font-kit-diagnostics-{}", process::id()));
+    drop(fs::remove_dir_all(&directory));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("nameless.ttf"), &**font_data).unwrap();
+    fs::write(directory.join("broken.ttf"), &[0; 64][..]).unwrap();
+    fs::write(directory.join("README.txt"), "Not a font.").unwrap();
+
+    let mut source = FsSource::from_directories(vec![directory.clone()].into_iter());
+    assert_eq!(source.all_fonts().unwrap().len(), 1);
+    let mut diagnostics: Vec<_> = source
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| match diagnostic.kind {
+            DiagnosticKind::SynthesizedPostScriptName(_) => "synthesized",
+            DiagnosticKind::LoadingFailed(_) => "failed",
+        })
+        .collect();
+    diagnostics.sort();
+    assert_eq!(diagnostics, vec!["failed", "synthesized"]);
+
+    fs::remove_file(directory.join("broken.ttf")).unwrap();
+    source.refresh().unwrap();
+    assert_eq!(source.diagnostics().len(), 1);
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn query_fonts_in_mem_source() {
+    let source = MemSource::from_fonts(
+        vec![
+            Handle::from_path(TEST_FONT_FILE_PATH.into(), 0),
+            Handle::from_path(FILE_PATH_EB_GARAMOND_ITALIC_OTF.into(), 0),
+            Handle::from_path(FILE_PATH_INCONSOLATA_TTF.into(), 0),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    let query = |query: &FontQuery| -> Vec<_> {
+        source
+            .query_fonts(query)
+            .unwrap()
+            .into_iter()
+            .map(|descriptor| descriptor.postscript_name.unwrap())
+            .collect()
+    };
+
+    assert_eq!(query(&FontQuery::new()).len(), 3);
+    assert_eq!(
+        query(FontQuery::new().family_name("garamond")),
+        TEST_FONT_COLLECTION_POSTSCRIPT_NAME
+    );
+    assert_eq!(
+        query(FontQuery::new().monospace(true)),
+        vec!["Inconsolata-Regular"]
+    );
+    assert_eq!(
+        query(FontQuery::new().style(Style::Italic)),
+        vec!["EBGaramond12-Italic"]
+    );
+    assert_eq!(
+        query(FontQuery::new().format(FontFormat::TrueType)),
+        vec!["Inconsolata-Regular"]
+    );
+    assert_eq!(
+        query(FontQuery::new().format(FontFormat::Cff).variable(false)).len(),
+        2
+    );
+    assert!(query(FontQuery::new().weight(Weight::BOLD..=Weight::BLACK)).is_empty());
+    assert_eq!(
+        query(FontQuery::new().family_name("Garamond").covering("ﬀ")),
+        TEST_FONT_COLLECTION_POSTSCRIPT_NAME
+    );
+    assert!(query(FontQuery::new().script("Hani")).is_empty());
+    assert_eq!(query(FontQuery::new().script("Latn")).len(), 3);
+    match source.query_fonts(FontQuery::new().script("Xyzw")) {
+        Err(SelectionError::UnsupportedScript(ref script)) if script == "Xyzw" => {}
+        result => panic!("Expected an unsupported script: {:?}", result),
+    }
+}
+
+#[test]
+fn describe_fonts_from_headers() {
+    let mut collection_data = vec![];
+    File::open(TEST_FONT_COLLECTION_FILE_PATH)
+        .unwrap()
+        .read_to_end(&mut collection_data)
+        .unwrap();
+    let collection_data = Arc::new(collection_data);
+    let handles = vec![
+        Handle::from_path(TEST_FONT_FILE_PATH.into(), 0),
+        Handle::from_path(FILE_PATH_EB_GARAMOND_ITALIC_OTF.into(), 0),
+        Handle::from_path(FILE_PATH_EB_GARAMOND_TTF.into(), 0),
+        Handle::from_path(FILE_PATH_INCONSOLATA_TTF.into(), 0),
+        Handle::from_path(TEST_FONT_COLLECTION_FILE_PATH.into(), 1),
+        Handle::from_memory(collection_data.clone(), 0),
+        Handle::from_memory(collection_data.clone(), 1),
+        Handle::from_path(FILE_PATH_TIMES_ROMAN_PCF.into(), 0),
+    ];
+    for handle in &handles {
+        let descriptor = FontDescriptor::from_handle(handle).unwrap();
+        let font = Font::from_handle(handle).unwrap();
+        assert_eq!(descriptor.family_name, font.family_name());
+        assert_eq!(descriptor.postscript_name, font.postscript_name());
+        assert_eq!(descriptor.properties, font.properties());
+        assert_eq!(descriptor.is_monospace, font.is_monospace());
+        assert_eq!(
+            descriptor.format,
+            FontDescriptor::from_font(handle.clone(), &font).format
+        );
+    }
+    // Full names come from the `name` table, which the FreeType loader only consults for Unicode
+    // platform names.
+    let descriptor = FontDescriptor::from_handle(&handles[0]).unwrap();
+    assert_eq!(descriptor.full_name, "EB Garamond 12 Regular");
+    let descriptor = FontDescriptor::from_handle(&handles[6]).unwrap();
+    assert_eq!(descriptor.font_index(), 1);
+    assert_eq!(descriptor.properties.style, Style::Italic);
+    assert_eq!(descriptor.format, FontFormat::Cff);
+    let descriptor = FontDescriptor::from_handle(&handles[7]).unwrap();
+    assert_eq!(descriptor.format, FontFormat::Bitmap);
+    assert!(FontDescriptor::from_handle(&Handle::from_memory(collection_data, 2)).is_err());
+
+    // Fonts in a memory source are described from its index.
+    let source = MemSource::from_fonts(handles[..4].iter().cloned()).unwrap();
+    let descriptor = source.describe_font(&handles[3]).unwrap();
+    assert_eq!(descriptor.postscript_name.unwrap(), "Inconsolata-Regular");
+    assert!(descriptor.is_monospace);
+    assert_eq!(
+        source
+            .select_by_postscript_name("EBGaramond12-Italic")
+            .unwrap()
+            .load()
+            .unwrap()
+            .properties()
+            .style,
+        Style::Italic
+    );
+}
+
+#[test]
+fn merge_and_prioritize_fonts_in_multi_source() {
+    let read_font = |path: &str| {
+        let mut font_data = vec![];
+        File::open(path)
+            .unwrap()
+            .read_to_end(&mut font_data)
+            .unwrap();
+        Arc::new(font_data)
+    };
+    let system_source = MemSource::from_fonts(
+        vec![
+            Handle::from_path(TEST_FONT_COLLECTION_FILE_PATH.into(), 0),
+            Handle::from_path(TEST_FONT_COLLECTION_FILE_PATH.into(), 1),
+            Handle::from_memory(read_font(FILE_PATH_INCONSOLATA_TTF), 0),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+    let app_source = MemSource::from_fonts(
+        vec![
+            Handle::from_path(TEST_FONT_FILE_PATH.into(), 0),
+            Handle::from_memory(read_font(FILE_PATH_INCONSOLATA_TTF), 0),
+        ]
+        .into_iter(),
+    )
+    .unwrap();
+
+    // The application fonts take precedence even though they were added last.
+    let mut source = MultiSource::from_sources(vec![]);
+    let system_id = source.add_source(Box::new(system_source), 0);
+    let app_id = source.add_source(Box::new(app_source), 10);
+    assert_ne!(system_id, app_id);
+
+    // The two copies of Inconsolata are the same font.
+    let fonts = source.all_fonts_with_sources().unwrap();
+    assert_eq!(
+        fonts.iter().map(|&(_, id)| id).collect::<Vec<_>>(),
+        vec![app_id, app_id, system_id, system_id]
+    );
+    assert_eq!(
+        source.all_families().unwrap(),
+        vec!["EB Garamond", "Inconsolata"]
+    );
+
+    // The regular face of the collection is shadowed by the application's one.
+    let family = source
+        .select_family_by_name_with_sources("EB Garamond")
+        .unwrap();
+    assert_eq!(family.len(), 2);
+    assert_eq!(family[0].1, app_id);
+    assert_eq!(family[1].1, system_id);
+    assert_eq!(
+        family[1].0.load().unwrap().postscript_name().unwrap(),
+        "EBGaramond12-Italic"
+    );
+    assert_eq!(
+        source
+            .select_family_by_name("EB Garamond")
+            .unwrap()
+            .fonts()
+            .len(),
+        2
+    );
+
+    let handle = Handle::from_path(TEST_FONT_COLLECTION_FILE_PATH.into(), 1);
+    assert_eq!(source.source_of(&handle).unwrap(), Some(system_id));
+    let handle = Handle::from_memory(read_font(FILE_PATH_INCONSOLATA_TTF), 0);
+    assert_eq!(source.source_of(&handle).unwrap(), Some(app_id));
+    let handle = Handle::from_path(FILE_PATH_EB_GARAMOND_TTF.into(), 0);
+    assert_eq!(source.source_of(&handle).unwrap(), None);
+    let handle = source
+        .select_by_postscript_name("EBGaramond12-Regular")
+        .unwrap();
+    assert_eq!(source.source_of(&handle).unwrap(), Some(app_id));
+    assert_eq!(source.query_fonts(&FontQuery::new()).unwrap().len(), 4);
+    assert!(source.subsource(app_id).is_some());
+}
+
+// Fonts are described from the index of the subsource that has them, so they can still be
+// matched after their files have gone.
+#[test]
+fn describe_fonts_from_multi_source_subsource_index() {
+    let directory = env::temp_dir().join(format!("font-kit-multi-{}", process::id()));
+    drop(fs::remove_dir_all(&directory));
+    fs::create_dir_all(&directory).unwrap();
+    fs::copy(TEST_FONT_FILE_PATH, directory.join("font.otf")).unwrap();
+
+    let mut source = MultiSource::from_sources(vec![]);
+    source.add_source(Box::new(MemSource::empty()), 10);
+    source.add_source(
+        Box::new(FsSource::from_directories(
+            vec![directory.clone()].into_iter(),
+        )),
+        0,
+    );
+    fs::remove_dir_all(&directory).unwrap();
+
+    let handle = source.all_fonts().unwrap().remove(0);
+    assert!(FontDescriptor::from_handle(&handle).is_err());
+    assert_eq!(
+        source.describe_font(&handle).unwrap().postscript_name,
+        Some(TEST_FONT_POSTSCRIPT_NAME.to_owned())
+    );
+    let best_match = source
+        .select_best_match(
+            &[FamilyName::Title("EB Garamond".to_owned())],
+            &Properties::new(),
+        )
+        .unwrap();
+    assert!(source.describe_font(&best_match).is_ok());
+}
+
+#[test]
+fn refresh_fs_source_with_watcher() {
+    let directory = env::temp_dir().join(format!("font-kit-refresh-{}", process::id()));
+    drop(fs::remove_dir_all(&directory));
+    fs::create_dir_all(&directory).unwrap();
+    let font_path = directory.join("font.otf");
+
+    let source = FsSource::from_directories(vec![directory.clone()].into_iter());
+    let mut watcher = PollingWatcher::new(source, Duration::from_secs(3600));
+    assert!(watcher.source().all_fonts().unwrap().is_empty());
+    assert!(watcher.poll_now().unwrap().is_none());
+
+    fs::copy(TEST_FONT_FILE_PATH, &font_path).unwrap();
+    // The interval hasn't elapsed yet.
+    assert!(watcher.poll().unwrap().is_none());
+    let changes = watcher.poll_now().unwrap().unwrap();
+    assert_eq!(
+        (
+            changes.added.len(),
+            changes.removed.len(),
+            changes.changed.len()
+        ),
+        (1, 0, 0)
+    );
+    assert!(watcher
+        .source()
+        .select_by_postscript_name(TEST_FONT_POSTSCRIPT_NAME)
+        .is_ok());
+
+    // Replacing the font with a collection changes the first font and adds the second.
+    fs::copy(TEST_FONT_COLLECTION_FILE_PATH, &font_path).unwrap();
+    let changes = watcher.poll_now().unwrap().unwrap();
+    assert_eq!(
+        (
+            changes.added.len(),
+            changes.removed.len(),
+            changes.changed.len()
+        ),
+        (1, 0, 1)
+    );
+    assert!(watcher
+        .source()
+        .select_by_postscript_name(TEST_FONT_COLLECTION_POSTSCRIPT_NAME[1])
+        .is_ok());
+
+    fs::remove_file(&font_path).unwrap();
+    let changes = watcher.poll_now().unwrap().unwrap();
+    assert_eq!(
+        (
+            changes.added.len(),
+            changes.removed.len(),
+            changes.changed.len()
+        ),
+        (0, 2, 0)
+    );
+    assert!(watcher.source().all_fonts().unwrap().is_empty());
+
+    fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn index_fonts_in_parallel() {
+    let describe_fonts = |source: &dyn Source| -> Vec<String> {
+        source
+            .all_fonts()
+            .unwrap()
+            .iter()
+            .map(|handle| format!("{:?}", handle))
+            .collect()
+    };
+
+    let handles = vec![
+        Handle::from_path(TEST_FONT_FILE_PATH.into(), 0),
+        Handle::from_path(FILE_PATH_INCONSOLATA_TTF.into(), 0),
+        Handle::from_path(FILE_PATH_EB_GARAMOND_ITALIC_OTF.into(), 0),
+        Handle::from_path(TEST_FONT_COLLECTION_FILE_PATH.into(), 1),
+    ];
+    let mut progress = vec![];
+    let source = MemSource::from_fonts_in_parallel(handles.iter().cloned(), 3, |indexing| {
+        progress.push(indexing)
+    })
+    .unwrap();
+    assert_eq!(
+        describe_fonts(&source),
+        describe_fonts(&MemSource::from_fonts(handles.iter().cloned()).unwrap())
+    );
+    assert_eq!(
+        progress
+            .iter()
+            .map(|indexing| indexing.indexed)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3, 4]
+    );
+    assert!(progress.iter().all(|indexing| indexing.total == 4));
+    let handles = vec![
+        Handle::from_path(TEST_FONT_FILE_PATH.into(), 0),
+        Handle::from_path(TEST_FONT_COLLECTION_FILE_PATH.into(), 2),
+    ];
+    assert!(MemSource::from_fonts_in_parallel(handles.into_iter(), 0, |_| {}).is_err());
+
+    let directories = vec![PathBuf::from("resources/tests")];
+    let mut last_progress = IndexingProgress::default();
+    let source =
+        FsSource::from_directories_in_parallel(directories.iter().cloned(), 0, |indexing| {
+            last_progress = indexing
+        });
+    assert_eq!(
+        describe_fonts(&source),
+        describe_fonts(&FsSource::from_directories(directories.into_iter()))
+    );
+    assert_eq!(last_progress.indexed, last_progress.total);
+    assert!(source
+        .select_by_postscript_name(TEST_FONT_POSTSCRIPT_NAME)
+        .is_ok());
+}
+
+#[test]
+fn query_incremental_fs_source_while_indexing() {
+    let (sender, receiver) = mpsc::channel();
+    let mut source = IncrementalFsSource::from_directories(
+        vec![PathBuf::from("resources/tests")].into_iter(),
+        2,
+        move |indexing| {
+            let _ = sender.send(indexing);
+        },
+    );
+    // The source can be queried straight away, though it may not have any fonts yet.
+    assert!(source.all_fonts().is_ok());
+
+    source.wait();
+    assert!(source.is_complete());
+    let progress = source.progress();
+    assert!(progress.total > 0);
+    assert_eq!(progress.indexed, progress.total);
+    assert_eq!(receiver.iter().last(), Some(progress));
+    assert!(source
+        .select_by_postscript_name(TEST_FONT_POSTSCRIPT_NAME)
+        .is_ok());
+    assert!(source.refresh().unwrap().is_empty());
+
+    let font_count = source.all_fonts().unwrap().len();
+    let source = source.into_fs_source();
+    assert_eq!(source.all_fonts().unwrap().len(), font_count);
+}
+
+#[test]
+fn wait_for_incremental_fs_source_after_panic() {
+    // The progress callback panics once the first file has been indexed.
+    let mut source = IncrementalFsSource::from_directories(
+        vec![PathBuf::from("resources/tests")].into_iter(),
+        2,
+        |indexing| assert_eq!(indexing.indexed, 0),
+    );
+    source.wait();
+    assert!(!source.is_complete());
+    assert!(source.all_fonts().is_ok());
+    assert_eq!(source.progress().indexed, 1);
+}
+
+#[test]
+fn layout_text_with_kerning_and_ligatures() {
+    let font = Font::from_path(TEST_FONT_FILE_PATH, 0).unwrap();
+    let units_per_em = font.metrics().units_per_em as f32;
+
+    // At one pixel per font unit, the kerning between "A" and "V" shows up unscaled.
+    let glyph_a = font.glyph_for_char('A').unwrap();
+    let kerned = layout::layout(&font, "AV", &LayoutOptions::new(units_per_em));
+    let unkerned = layout::layout(&font, "AV", LayoutOptions::new(units_per_em).kerning(false));
+    assert_eq!(kerned.glyphs.len(), 2);
+    assert_eq!(
+        kerned.glyphs[0].advance,
+        -160.0 + font.advance(glyph_a).unwrap().x
+    );
+    assert_eq!(unkerned.glyphs[0].advance, font.advance(glyph_a).unwrap().x);
+    assert_eq!(kerned.glyphs[1].position.x, kerned.glyphs[0].advance);
+    assert_eq!(kerned.glyphs[1].cluster, 1);
+    assert_eq!(kerned.bounds.size.width, 1204.0);
+
+    let ligated = layout::layout(&font, "Qyx", &LayoutOptions::new(units_per_em));
+    let glyphs: Vec<(u32, usize)> = ligated
+        .glyphs
+        .iter()
+        .map(|glyph| (glyph.glyph_id, glyph.cluster))
+        .collect();
+    assert_eq!(
+        glyphs,
+        vec![(2815, 0), (font.glyph_for_char('x').unwrap(), 2)]
+    );
+    let unligated = layout::layout(
+        &font,
+        "Qyx",
+        LayoutOptions::new(units_per_em).ligatures(false),
+    );
+    assert_eq!(unligated.glyphs.len(), 3);
+}
+
+#[test]
+fn layout_text_with_legacy_kerning() {
+    // Inconsolata has a `GPOS` table but no kerning in it, so a `kern` table is used instead.
+    let font = Font::from_path(FILE_PATH_INCONSOLATA_TTF, 0).unwrap();
+    let (glyph_a, glyph_v) = (
+        font.glyph_for_char('A').unwrap(),
+        font.glyph_for_char('V').unwrap(),
+    );
+    let mut kern = vec![0, 0, 0, 1, 0, 0, 0, 20, 0, 1, 0, 1, 0, 6, 0, 0, 0, 0];
+    kern.extend_from_slice(&(glyph_a as u16).to_be_bytes());
+    kern.extend_from_slice(&(glyph_v as u16).to_be_bytes());
+    kern.extend_from_slice(&(-100i16).to_be_bytes());
+    let data = rewrite_font_tables(
+        FILE_PATH_INCONSOLATA_TTF,
+        vec![(sfnt::KERN, kern)],
+        |_, _| {},
+    );
+    let kerned_font = Font::from_bytes(Arc::new(data), 0).unwrap();
+    let units_per_em = kerned_font.metrics().units_per_em as f32;
+
+    let layout = layout::layout(&kerned_font, "AV", &LayoutOptions::new(units_per_em));
+    assert_eq!(
+        layout.glyphs[0].advance,
+        -100.0 + font.advance(glyph_a).unwrap().x
+    );
+    let layout = layout::layout(&kerned_font, "VA", &LayoutOptions::new(units_per_em));
+    assert_eq!(layout.glyphs[0].advance, font.advance(glyph_v).unwrap().x);
+    let layout = layout::layout(&font, "AV", &LayoutOptions::new(units_per_em));
+    assert_eq!(layout.glyphs[0].advance, font.advance(glyph_a).unwrap().x);
+}
+
+// Damaged kerning tables must not overflow: a coverage range whose start index pushes past the
+// last coverage index, and `kern` subtables whose values add up to more than fits in 16 bits.
+#[test]
+fn layout_text_with_overflowing_kerning() {
+    let font = Font::from_path(FILE_PATH_INCONSOLATA_TTF, 0).unwrap();
+    let (glyph_a, glyph_v) = (
+        font.glyph_for_char('A').unwrap() as u16,
+        font.glyph_for_char('V').unwrap() as u16,
+    );
+    let units_per_em = font.metrics().units_per_em as f32;
+    let advance_a = font.advance(glyph_a as u32).unwrap().x;
+
+    // A `GPOS` table with a `kern` feature whose one pair adjustment subtable covers 'A' through
+    // a range that starts at coverage index 0xffff one glyph earlier.
+    let mut gpos = vec![];
+    for values in &[
+        // Header
+        &[1, 0, 10, 30, 44][..],
+        // Script list, with one `DFLT` script and its default language system
+        &[1, 0x4446, 0x4c54, 8, 4, 0, 0, 0xffff, 1, 0],
+        // Feature list, with one `kern` feature
+        &[1, 0x6b65, 0x726e, 8, 0, 1, 0],
+        // Lookup list, with one pair adjustment lookup
+        &[1, 4, 2, 0, 1, 8],
+        // Pair adjustment subtable
+        &[1, 12, 4, 0, 1, 22],
+        // Coverage
+        &[2, 1, glyph_a - 1, glyph_a, 0xffff],
+        // Pair set
+        &[1, glyph_v, -100i16 as u16],
+    ] {
+        for &value in values.iter() {
+            gpos.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+    let data = rewrite_font_tables(FILE_PATH_INCONSOLATA_TTF, vec![], |tag, table| {
+        if tag == sfnt::GPOS {
+            *table = gpos.clone()
+        }
+    });
+    let gpos_font = Font::from_bytes(Arc::new(data), 0).unwrap();
+    let layout = layout::layout(&gpos_font, "AV", &LayoutOptions::new(units_per_em));
+    assert_eq!(layout.glyphs[0].advance, advance_a);
+
+    // Two `kern` subtables that each move 'A' and 'V' 20000 units closer together.
+    let mut kern = vec![0, 0, 0, 2];
+    for _ in 0..2 {
+        kern.extend_from_slice(&[0, 0, 0, 20, 0, 1, 0, 1, 0, 6, 0, 0, 0, 0]);
+        kern.extend_from_slice(&glyph_a.to_be_bytes());
+        kern.extend_from_slice(&glyph_v.to_be_bytes());
+        kern.extend_from_slice(&(-20000i16).to_be_bytes());
+    }
+    let data = rewrite_font_tables(
+        FILE_PATH_INCONSOLATA_TTF,
+        vec![(sfnt::KERN, kern)],
+        |_, _| {},
+    );
+    let kern_font = Font::from_bytes(Arc::new(data), 0).unwrap();
+    let layout = layout::layout(&kern_font, "AV", &LayoutOptions::new(units_per_em));
+    assert_eq!(
+        layout.glyphs[0].advance,
+        i16::min_value() as f32 + advance_a
+    );
+}
+
+#[test]
+fn layout_text_into_lines() {
+    let font = Font::from_path(TEST_FONT_FILE_PATH, 0).unwrap();
+    let text = "Hello world, this is a test\nsecond\r\n\nend";
+    let layout = layout::layout(&font, text, LayoutOptions::new(16.0).max_width(100.0));
+
+    let line_texts: Vec<&str> = layout
+        .lines
+        .iter()
+        .map(|line| &text[line.text_range.clone()])
+        .collect();
+    assert_eq!(
+        line_texts,
+        vec!["Hello world, ", "this is a test", "second", "", "end"]
+    );
+    for line in &layout.lines {
+        assert!(line.width <= 100.0);
+        for glyph in &layout.glyphs[line.glyph_range.clone()] {
+            assert_eq!(glyph.position.y, line.baseline);
+        }
+    }
+    assert_eq!(layout.lines[3].width, 0.0);
+
+    let metrics = font.metrics();
+    let scale = 16.0 / metrics.units_per_em as f32;
+    let line_height = (metrics.ascent - metrics.descent + metrics.line_gap) * scale;
+    assert_eq!(layout.lines[0].baseline, metrics.ascent * scale);
+    assert!(
+        (layout.lines[4].baseline - layout.lines[0].baseline - line_height * 4.0).abs() < 0.001
+    );
+    assert!((layout.bounds.size.height - line_height * 5.0).abs() < 0.001);
+    assert_eq!(layout.bounds.size.width, layout.lines[0].width);
+
+    // Characters missing from the font come from a fallback font or are shown as `.notdef`.
+    let layout = layout::layout(&font, "a\u{4e2d}b", &LayoutOptions::new(16.0));
+    assert_eq!(layout.glyphs.len(), 3);
+    assert_eq!(layout.glyphs[1].cluster, 1);
+    assert!(layout.glyphs[1].font_index != 0 || layout.glyphs[1].glyph_id == 0);
+    assert_eq!(layout.glyphs[2].cluster, 4);
+}
+
+#[cfg(feature = "harfbuzz")]
+#[test]
+fn shape_text_with_harfbuzz() {
+    use harfbuzz::{Direction, HarfBuzzFont, ShapingOptions};
+
+    let font = Font::from_path(TEST_FONT_FILE_PATH, 0).unwrap();
+    let hb_font = HarfBuzzFont::new(&font, 1000.0);
+
+    // Unlike the simple shaper, HarfBuzz applies the contextual lookups that join "f" to "i".
+    let glyphs = hb_font.shape("fix", &ShapingOptions::new());
+    assert_eq!(glyphs.len(), 3);
+    assert!(glyphs[0].glyph_id != font.glyph_for_char('f').unwrap());
+    assert!(glyphs[1].glyph_id != font.glyph_for_char('i').unwrap());
+    assert_eq!(glyphs[2].glyph_id, font.glyph_for_char('x').unwrap());
+    assert_eq!(glyphs[2].cluster, 2);
+
+    let glyphs = hb_font.shape("Qy", &ShapingOptions::new());
+    assert_eq!(glyphs.len(), 1);
+    assert_eq!(glyphs[0].cluster, 0);
+
+    let glyphs = hb_font.shape("AV", &ShapingOptions::new());
+    let glyph_a = font.glyph_for_char('A').unwrap();
+    assert_eq!(glyphs[0].glyph_id, glyph_a);
+    assert_eq!(
+        glyphs[0].advance.x,
+        font.advance(glyph_a).unwrap().x - 160.0
+    );
+    assert_eq!(glyphs[0].offset, Vector2D::zero());
+
+    let unkerned = hb_font.shape(
+        "AV",
+        ShapingOptions::new().feature(Tag::from_bytes(b"kern"), 0),
+    );
+    assert_eq!(unkerned[0].advance.x, font.advance(glyph_a).unwrap().x);
+
+    // Right-to-left runs come back in visual order.
+    let glyphs = hb_font.shape(
+        "AV",
+        ShapingOptions::new().direction(Direction::RightToLeft),
+    );
+    assert_eq!(glyphs[0].cluster, 1);
+    assert_eq!(glyphs[1].cluster, 0);
+}
+
+#[test]
+fn subset_truetype_font() {
+    let font = Font::from_path(FILE_PATH_EB_GARAMOND_TTF, 0).unwrap();
+    let subset_font = subset::subset(&font, SubsetOptions::new().text("H\u{e9}!")).unwrap();
+
+    // The composite "é" pulls in its components.
+    assert_eq!(subset_font.glyph_ids, vec![0, 4, 43, 72, 171, 668]);
+    check_subset_font(&font, &subset_font, "H\u{e9}!e");
+}
+
+#[test]
+fn subset_cff_font() {
+    let font = Font::from_path(TEST_FONT_FILE_PATH, 0).unwrap();
+    let glyph_id_q = font.glyph_for_char('Q').unwrap();
+    let subset_font = subset::subset(
+        &font,
+        SubsetOptions::new().text("Ay").glyph_ids(&[glyph_id_q]),
+    )
+    .unwrap();
+    assert_eq!(subset_font.glyph_ids.len(), 4);
+    assert_eq!(subset_font.new_glyph_id(glyph_id_q), Some(2));
+    check_subset_font(&font, &subset_font, "AQy");
+}
+
+#[test]
+fn subset_font_with_invalid_glyph_id() {
+    let font = Font::from_path(FILE_PATH_INCONSOLATA_TTF, 0).unwrap();
+    let options = SubsetOptions::new()
+        .glyph_ids(&[font.glyph_count()])
+        .clone();
+    assert_eq!(
+        subset::subset(&font, &options),
+        Err(SubsetError::NoSuchGlyph(font.glyph_count()))
+    );
+}
+
+#[test]
+fn extract_font_from_collection() {
+    let mut collection_data = vec![];
+    File::open(TEST_FONT_COLLECTION_FILE_PATH)
+        .unwrap()
+        .read_to_end(&mut collection_data)
+        .unwrap();
+    let font_data = subset::extract_font(&collection_data, 1).unwrap();
+    assert!(font_data.len() < collection_data.len());
+
+    let font = Font::from_bytes(Arc::new(font_data), 0).unwrap();
+    assert_eq!(
+        font.postscript_name().unwrap(),
+        TEST_FONT_COLLECTION_POSTSCRIPT_NAME[1]
+    );
+    let collection_font = Font::from_bytes(Arc::new(collection_data.clone()), 1).unwrap();
+    let glyph_id = font.glyph_for_char('a').unwrap();
+    assert_eq!(collection_font.glyph_for_char('a'), Some(glyph_id));
+    assert_eq!(
+        font.glyph_outline(glyph_id, HintingOptions::None).unwrap(),
+        collection_font
+            .glyph_outline(glyph_id, HintingOptions::None)
+            .unwrap()
+    );
+
+    assert_eq!(
+        subset::extract_font(&collection_data, 2),
+        Err(SubsetError::NoSuchFontInCollection { font_index: 2 })
+    );
+}
+
+#[test]
+fn extract_font_with_unwritable_table_directory() {
+    // A table directory whose tables are all empty and start right after it.
+    fn table_directory(tags: &[u32]) -> Vec<u8> {
+        let mut data = vec![0x00, 0x01, 0x00, 0x00];
+        data.extend_from_slice(&(tags.len() as u16).to_be_bytes());
+        data.extend_from_slice(&[0; 6]);
+        let table_offset = (12 + tags.len() * 16) as u32;
+        for &tag in tags {
+            data.extend_from_slice(&tag.to_be_bytes());
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(&table_offset.to_be_bytes());
+            data.extend_from_slice(&[0; 4]);
+        }
+        data
+    }
+
+    // The signature is dropped, which leaves no tables.
+    for tags in &[vec![], vec![sfnt::DSIG]] {
+        assert_eq!(
+            subset::extract_font(&table_directory(tags), 0),
+            Err(SubsetError::Parse)
+        );
+    }
+    let tags: Vec<u32> = (0..0x1000).collect();
+    assert_eq!(
+        subset::extract_font(&table_directory(&tags), 0),
+        Err(SubsetError::Parse)
+    );
+    let tags: Vec<u32> = (0..0xfff).collect();
+    assert!(subset::extract_font(&table_directory(&tags), 0).is_ok());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_properties() {
+    use properties::Style;
+    use serde::de::value::{Error, MapDeserializer};
+    use serde::de::{Deserialize, IntoDeserializer};
+
+    let style: Result<_, Error> = Style::deserialize("italic".into_deserializer());
+    assert_eq!(style, Ok(Style::Italic));
+    let weight: Result<_, Error> = Weight::deserialize(700.0.into_deserializer());
+    assert_eq!(weight, Ok(Weight::BOLD));
+    let stretch: Result<_, Error> = Stretch::deserialize(0.75.into_deserializer());
+    assert_eq!(stretch, Ok(Stretch::CONDENSED));
+    let family_name: Result<_, Error> = FamilyName::deserialize("sans-serif".into_deserializer());
+    assert_eq!(family_name, Ok(FamilyName::SansSerif));
+
+    // Properties that are left out take their default values.
+    let fields = MapDeserializer::new(vec![("weight", 700.0)].into_iter());
+    let properties: Result<_, Error> = Properties::deserialize(fields);
+    assert_eq!(properties, Ok(*Properties::new().weight(Weight::BOLD)));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn deserialize_memory_handle_bytes() {
+    use handle::memory_bytes;
+    use serde::de::value::{BytesDeserializer, Error, SeqDeserializer};
+
+    let font_data = vec![0, 1, 0, 0];
+    let bytes: Result<_, Error> = memory_bytes::deserialize(BytesDeserializer::new(&font_data));
+    assert_eq!(bytes.unwrap(), Arc::new(font_data.clone()));
+    // JSON and similar formats write bytes as a sequence of numbers.
+    let bytes: Result<_, Error> =
+        memory_bytes::deserialize(SeqDeserializer::new(font_data.clone().into_iter()));
+    assert_eq!(bytes.unwrap(), Arc::new(font_data));
+}
+
+// Loads damaged copies of the test fonts and runs every per-glyph API on whatever loads. None of
+// this may panic; errors are fine.
+#[test]
+fn load_corrupted_fonts() {
+    let font_paths = [
+        TEST_FONT_FILE_PATH,
+        FILE_PATH_EB_GARAMOND_TTF,
+        FILE_PATH_INCONSOLATA_TTF,
+        TEST_FONT_COLLECTION_FILE_PATH,
+    ];
+    // A fixed xorshift generator keeps failures reproducible.
+    let mut seed = 0x2545_f491_u32;
+    let mut next_random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as usize
+    };
+
+    for font_path in &font_paths {
+        let mut font_data = vec![];
+        File::open(font_path)
+            .unwrap()
+            .read_to_end(&mut font_data)
+            .unwrap();
+
+        for iteration in 0..32 {
+            let mut corrupted_data = font_data.clone();
+            if iteration % 4 == 0 {
+                let new_len = next_random() % corrupted_data.len();
+                corrupted_data.truncate(new_len);
+            } else {
+                // Favor the table directory and the start of the tables, where offsets live.
+                let region_len = if iteration % 2 == 0 {
+                    cmp::min(corrupted_data.len(), 1024)
+                } else {
+                    corrupted_data.len()
+                };
+                for _ in 0..(1 + next_random() % 16) {
+                    let offset = next_random() % region_len;
+                    corrupted_data[offset] = next_random() as u8;
+                }
+            }
+
+            let corrupted_data = Arc::new(corrupted_data);
+            let _ = Font::analyze_bytes(corrupted_data.clone());
+            for font_index in 0..2 {
+                if let Ok(font) = Font::from_bytes(corrupted_data.clone(), font_index) {
+                    fuzzing::exercise_font(&font);
+                }
+            }
+        }
+    }
+}
+
 // The initial off-curve point used to cause an assertion in the FreeType backend.
 #[test]
 fn get_glyph_outline_eb_garamond_exclam() {
@@ -969,6 +2223,98 @@ fn get_glyph_outline_inconsolata_J() {
     assert_eq!(events.next(), Some(PathEvent::Close));
 }
 
+#[test]
+fn get_glyph_outline_as_svg_path() {
+    let font = Font::from_path(FILE_PATH_INCONSOLATA_TTF, 0).unwrap();
+    let glyph = font.glyph_for_char('l').expect("No glyph for char!");
+    let outline = font.glyph_outline(glyph, HintingOptions::None).unwrap();
+    assert_eq!(
+        outline.to_svg_path(),
+        "M79 58 L214 58 L214 607 L85 607 L85 665 L287 665 L287 58 L421 58 L421 0 L79 0 Z"
+    );
+    assert_eq!(outline.bounds(), font.typographic_bounds(glyph).unwrap());
+
+    // Replaying the recorded outline must produce the same events as the loader.
+    let mut path_builder = Path::builder();
+    font.outline(glyph, HintingOptions::None, &mut path_builder)
+        .unwrap();
+    let mut replay_builder = Path::builder();
+    outline.send_to(&mut replay_builder);
+    assert!(path_builder
+        .build()
+        .into_iter()
+        .eq(replay_builder.build().into_iter()));
+
+    let space_glyph = font.glyph_for_char(' ').expect("No glyph for char!");
+    assert!(font
+        .glyph_outline(space_glyph, HintingOptions::None)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn convert_glyph_outline_curves() {
+    let font = Font::from_path(TEST_FONT_FILE_PATH, 0).unwrap();
+    let glyph = font.glyph_for_char('l').expect("No glyph for char!");
+    let outline = font.glyph_outline(glyph, HintingOptions::None).unwrap();
+    assert_eq!(outline.bounds(), font.typographic_bounds(glyph).unwrap());
+
+    let cubic_count = |outline: &Outline| {
+        outline
+            .contours
+            .iter()
+            .flat_map(|contour| contour.segments.iter())
+            .filter(|segment| match **segment {
+                Segment::Cubic(..) => true,
+                _ => false,
+            })
+            .count()
+    };
+    assert!(cubic_count(&outline) > 0);
+
+    let quadratic_outline = outline.to_quadratics(0.1);
+    assert_eq!(cubic_count(&quadratic_outline), 0);
+    let quadratic_bounds = quadratic_outline.bounds();
+    let bounds = outline.bounds();
+    assert!((quadratic_bounds.min_x() - bounds.min_x()).abs() < 0.5);
+    assert!((quadratic_bounds.max_y() - bounds.max_y()).abs() < 0.5);
+
+    // Converting quadratics back to cubics is exact.
+    let cubic_outline = quadratic_outline.to_cubics();
+    assert!(cubic_outline
+        .contours
+        .iter()
+        .all(
+            |contour| contour.segments.iter().all(|segment| match *segment {
+                Segment::Quadratic(..) => false,
+                _ => true,
+            })
+        ));
+}
+
+#[test]
+fn transform_and_reverse_glyph_outline() {
+    let font = Font::from_path(FILE_PATH_INCONSOLATA_TTF, 0).unwrap();
+    let glyph = font.glyph_for_char('l').expect("No glyph for char!");
+    let outline = font.glyph_outline(glyph, HintingOptions::None).unwrap();
+
+    let mut reversed = outline.clone();
+    reversed.reverse();
+    assert_eq!(
+        reversed.to_svg_path(),
+        "M79 0 L421 0 L421 58 L287 58 L287 665 L85 665 L85 607 L214 607 L214 58 L79 58 Z"
+    );
+    reversed.reverse();
+    assert_eq!(reversed, outline);
+
+    let mut flipped = outline.clone();
+    flipped.transform(&Transform2D::create_scale(1.0, -1.0));
+    assert_eq!(
+        flipped.bounds(),
+        Rect::new(Point2D::new(79.0, -665.0), Size2D::new(342.0, 665.0))
+    );
+}
+
 // Makes sure that a canvas has an "L" shape in it. This is used to test rasterization.
 #[allow(non_snake_case)]
 fn check_L_shape(canvas: &Canvas) {
@@ -1047,3 +2393,146 @@ fn stripe_width(pixels: &[u8]) -> Option<u32> {
     assert_eq!(x, pixels.len());
     Some(stripe_width)
 }
+
+// Makes sure that a subset font maps the characters of `text` to the glyphs that the original
+// font maps them to, with the same advances and outlines.
+fn check_subset_font(font: &Font, subset_font: &SubsetFont, text: &str) {
+    let loaded_font = Font::from_bytes(Arc::new(subset_font.data.clone()), 0).unwrap();
+    assert_eq!(
+        loaded_font.glyph_count(),
+        subset_font.glyph_ids.len() as u32
+    );
+    assert_eq!(loaded_font.postscript_name(), font.postscript_name());
+    assert_eq!(loaded_font.glyph_for_char('z'), None);
+
+    for character in text.chars() {
+        let original_glyph_id = font.glyph_for_char(character).unwrap();
+        let glyph_id = loaded_font.glyph_for_char(character).unwrap();
+        assert_eq!(subset_font.glyph_ids[glyph_id as usize], original_glyph_id);
+        assert_eq!(
+            loaded_font.advance(glyph_id).unwrap(),
+            font.advance(original_glyph_id).unwrap()
+        );
+        assert_eq!(
+            loaded_font
+                .glyph_outline(glyph_id, HintingOptions::None)
+                .unwrap(),
+            font.glyph_outline(original_glyph_id, HintingOptions::None)
+                .unwrap()
+        );
+    }
+}
+
+// Rewrites a font so that its `OS/2` table declares the given code pages and, optionally, its
+// `meta` table declares the given design languages.
+fn font_with_declared_languages(
+    path: &str,
+    code_page_range: u32,
+    design_languages: Option<&str>,
+) -> Handle {
+    let new_tables = design_languages
+        .map(|design_languages| (sfnt::META, meta_table(design_languages)))
+        .into_iter()
+        .collect();
+    let data = rewrite_font_tables(path, new_tables, |tag, table| {
+        if tag == sfnt::OS_2 {
+            table[78..82].copy_from_slice(&code_page_range.to_be_bytes());
+            table[82..86].copy_from_slice(&[0; 4]);
+        }
+    });
+    Handle::from_memory(Arc::new(data), 0)
+}
+
+// Drops the PostScript name records from the `name` table of the TrueType font at `path`.
+fn font_without_postscript_name(path: &str) -> Vec<u8> {
+    rewrite_font_tables(path, vec![], |tag, table| {
+        if tag == sfnt::NAME {
+            *table = rewrite_name_table(
+                table,
+                |name_id| name_id != sfnt::NAME_ID_POSTSCRIPT_NAME,
+                &[],
+            );
+        }
+    })
+}
+
+// Rewrites the tables of the TrueType or OpenType font at `path` with `rewrite`, adds
+// `new_tables`, and returns the new font. Tables that `rewrite` empties are dropped.
+pub(crate) fn rewrite_font_tables<F>(
+    path: &str,
+    new_tables: Vec<(u32, Vec<u8>)>,
+    mut rewrite: F,
+) -> Vec<u8>
+where
+    F: FnMut(u32, &mut Vec<u8>),
+{
+    let mut data = vec![];
+    File::open(path).unwrap().read_to_end(&mut data).unwrap();
+    let (version, tables) = sfnt::read_font_tables(&data, 0).unwrap();
+    let mut tables: Vec<(u32, Vec<u8>)> = tables
+        .into_iter()
+        .map(|(tag, table)| (tag, table.to_vec()))
+        .collect();
+    for &mut (tag, ref mut table) in &mut tables {
+        rewrite(tag, table)
+    }
+    tables.retain(|&(_, ref table)| !table.is_empty());
+    tables.extend(new_tables);
+    sfnt::write_font(version, tables).unwrap()
+}
+
+// Rebuilds a version 0 `name` table from the records of `name` whose name IDs `keep` accepts,
+// followed by Windows US English records for the given (name ID, string) pairs.
+pub(crate) fn rewrite_name_table<F>(name: &[u8], keep: F, new_names: &[(u16, &str)]) -> Vec<u8>
+where
+    F: Fn(u16) -> bool,
+{
+    assert_eq!(name[0..2], [0, 0]);
+    let record_count = u16::from_be_bytes([name[2], name[3]]) as usize;
+    let storage_offset = u16::from_be_bytes([name[4], name[5]]) as usize;
+    let mut records: Vec<u8> = name[6..(6 + record_count * 12)]
+        .chunks(12)
+        .filter(|record| keep(u16::from_be_bytes([record[6], record[7]])))
+        .flat_map(|record| record.to_vec())
+        .collect();
+    let mut storage = name[storage_offset..].to_vec();
+    for &(name_id, string) in new_names {
+        let string: Vec<u8> = string
+            .encode_utf16()
+            .flat_map(|unit| unit.to_be_bytes().to_vec())
+            .collect();
+        let record = [
+            3,
+            1,
+            0x409,
+            name_id,
+            string.len() as u16,
+            storage.len() as u16,
+        ];
+        for &value in &record {
+            records.extend_from_slice(&value.to_be_bytes());
+        }
+        storage.extend(string);
+    }
+
+    let record_count = records.len() / 12;
+    let mut new_name = vec![0, 0];
+    new_name.extend_from_slice(&(record_count as u16).to_be_bytes());
+    new_name.extend_from_slice(&((6 + record_count * 12) as u16).to_be_bytes());
+    new_name.extend(records);
+    new_name.extend(storage);
+    new_name
+}
+
+// Builds a `meta` table whose `dlng` entry declares the given design languages.
+pub(crate) fn meta_table(design_languages: &str) -> Vec<u8> {
+    let mut meta = vec![];
+    for &value in &[1, 0, 0, 1] {
+        meta.extend_from_slice(&u32::to_be_bytes(value));
+    }
+    meta.extend_from_slice(b"dlng");
+    meta.extend_from_slice(&u32::to_be_bytes(28));
+    meta.extend_from_slice(&u32::to_be_bytes(design_languages.len() as u32));
+    meta.extend_from_slice(design_languages.as_bytes());
+    meta
+}
@@ -12,6 +12,16 @@
 
 #![allow(dead_code)]
 
+use crossbeam_utils::thread as scoped_thread;
+use std::cmp;
+use std::fs;
+use std::panic;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::SystemTime;
+
 pub(crate) static SFNT_VERSIONS: [[u8; 4]; 4] = [
     [0x00, 0x01, 0x00, 0x00],
     [b'O', b'T', b'T', b'O'],
@@ -38,3 +48,85 @@ pub(crate) fn lerp(a: f32, b: f32, t: f32) -> f32 {
 pub(crate) fn div_round_up(a: usize, b: usize) -> usize {
     (a + b - 1) / b
 }
+
+// The modification time and length of a file, which change when the file is rewritten.
+pub(crate) type FileStamp = (SystemTime, u64);
+
+pub(crate) fn file_stamp(path: &Path) -> Option<FileStamp> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+// Applies `f` to each item on `thread_count` threads and hands each result to `on_result`, along
+// with the index of its item, on the calling thread as soon as it is ready. Results arrive in no
+// particular order. A thread count of zero uses one thread per CPU, and a thread count of one
+// doesn't spawn any threads.
+pub(crate) fn for_each_in_parallel<T, U, F, R>(
+    items: &[T],
+    thread_count: usize,
+    f: F,
+    mut on_result: R,
+) where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync,
+    R: FnMut(usize, U),
+{
+    let thread_count = match thread_count {
+        0 => thread::available_parallelism().map_or(1, |count| count.get()),
+        thread_count => thread_count,
+    };
+    let thread_count = cmp::min(thread_count, items.len());
+    if thread_count <= 1 {
+        for (index, item) in items.iter().enumerate() {
+            on_result(index, f(item))
+        }
+        return;
+    }
+
+    let next_index = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    // Scoped threads can borrow the items; `std::thread::scope` would need Rust 1.63.
+    let result = scoped_thread::scope(|scope| {
+        for _ in 0..thread_count {
+            let (sender, next_index, f) = (sender.clone(), &next_index, &f);
+            scope.spawn(move |_| loop {
+                let index = next_index.fetch_add(1, Ordering::Relaxed);
+                if index >= items.len() || sender.send((index, f(&items[index]))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+        for (index, result) in receiver {
+            on_result(index, result)
+        }
+    });
+    if let Err(payload) = result {
+        panic::resume_unwind(payload)
+    }
+}
+
+// Like `for_each_in_parallel()`, but collects the results in the order of the items. `progress`
+// is called on the calling thread with the number of results so far as each one arrives.
+pub(crate) fn map_in_parallel<T, U, F, P>(
+    items: &[T],
+    thread_count: usize,
+    f: F,
+    mut progress: P,
+) -> Vec<U>
+where
+    T: Sync,
+    U: Send,
+    F: Fn(&T) -> U + Sync,
+    P: FnMut(usize),
+{
+    let mut results: Vec<Option<U>> = items.iter().map(|_| None).collect();
+    let mut result_count = 0;
+    for_each_in_parallel(items, thread_count, f, |index, result| {
+        results[index] = Some(result);
+        result_count += 1;
+        progress(result_count)
+    });
+    results.into_iter().map(|result| result.unwrap()).collect()
+}
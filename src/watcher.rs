@@ -0,0 +1,83 @@
+// font-kit/src/watcher.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Notices when fonts are installed, removed or modified by periodically refreshing a source.
+//!
+//! This allows long-running applications to pick up new fonts without restarting. The watcher
+//! doesn't spawn any threads; call `PollingWatcher::poll()` from the application's event loop or
+//! a timer, and it refreshes the source at most once per interval.
+
+use std::time::{Duration, Instant};
+
+use error::SelectionError;
+use source::{Source, SourceChanges};
+
+/// Periodically refreshes a source and reports the fonts that changed.
+#[allow(missing_debug_implementations)]
+pub struct PollingWatcher<S> {
+    source: S,
+    interval: Duration,
+    last_refresh: Instant,
+}
+
+impl<S> PollingWatcher<S>
+where
+    S: Source,
+{
+    /// Creates a watcher that refreshes the given source at most once per `interval`.
+    ///
+    /// The source is assumed to be up to date when the watcher is created.
+    pub fn new(source: S, interval: Duration) -> PollingWatcher<S> {
+        PollingWatcher {
+            source,
+            interval,
+            last_refresh: Instant::now(),
+        }
+    }
+
+    /// Returns the watched source.
+    #[inline]
+    pub fn source(&self) -> &S {
+        &self.source
+    }
+
+    /// Returns the watched source mutably.
+    #[inline]
+    pub fn source_mut(&mut self) -> &mut S {
+        &mut self.source
+    }
+
+    /// Stops watching and returns the source.
+    #[inline]
+    pub fn into_source(self) -> S {
+        self.source
+    }
+
+    /// Refreshes the source if the interval has elapsed since the last refresh, and returns the
+    /// changes if there were any.
+    pub fn poll(&mut self) -> Result<Option<SourceChanges>, SelectionError> {
+        if self.last_refresh.elapsed() < self.interval {
+            return Ok(None);
+        }
+        self.poll_now()
+    }
+
+    /// Refreshes the source immediately, regardless of the interval, and returns the changes if
+    /// there were any.
+    pub fn poll_now(&mut self) -> Result<Option<SourceChanges>, SelectionError> {
+        self.last_refresh = Instant::now();
+        let changes = self.source.refresh()?;
+        if changes.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(changes))
+        }
+    }
+}
@@ -0,0 +1,74 @@
+// font-kit/tests/fuzz_corpus.rs
+//
+// Copyright © 2018 The Pathfinder Project Developers.
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Replays the fuzzing seeds, and any local fuzzing corpus, through the fuzz target bodies.
+
+extern crate font_kit;
+
+use font_kit::fuzzing;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+// The fonts that seed the fuzzers.
+static SEED_DIRECTORY: &'static str = "resources/tests";
+// Where `cargo fuzz run` stores the corpus that it builds up, one subdirectory per target.
+static CORPUS_DIRECTORY: &'static str = "fuzz/corpus";
+
+#[test]
+fn replay_layout_text_corpus() {
+    for path in corpus_files("layout_text") {
+        fuzzing::layout_text(&read_file(&path));
+    }
+}
+
+#[test]
+fn replay_load_font_corpus() {
+    for path in corpus_files("load_font") {
+        fuzzing::load_font(&read_file(&path));
+    }
+}
+
+#[test]
+fn replay_rasterize_glyphs_corpus() {
+    for path in corpus_files("rasterize_glyphs") {
+        fuzzing::rasterize_glyphs(&read_file(&path));
+    }
+}
+
+fn corpus_files(target_name: &str) -> Vec<PathBuf> {
+    let mut paths = vec![];
+    add_files_in_directory(&mut paths, Path::new(SEED_DIRECTORY));
+    add_files_in_directory(&mut paths, &Path::new(CORPUS_DIRECTORY).join(target_name));
+    paths.sort();
+    paths
+}
+
+// Missing directories are fine: the corpus only exists after the fuzzer has been run locally.
+fn add_files_in_directory(paths: &mut Vec<PathBuf>, directory: &Path) {
+    let entries = match fs::read_dir(directory) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            add_files_in_directory(paths, &path)
+        } else {
+            paths.push(path)
+        }
+    }
+}
+
+fn read_file(path: &Path) -> Vec<u8> {
+    let mut data = vec![];
+    File::open(path).unwrap().read_to_end(&mut data).unwrap();
+    data
+}
@@ -149,6 +149,9 @@ mod test {
 #[cfg(target_os = "linux")]
 mod test {
     use super::*;
+    use font_kit::properties::Weight;
+    use font_kit::query::FontQuery;
+    use font_kit::source::Source;
 
     #[test]
     fn select_best_match_serif() {
@@ -221,6 +224,108 @@ mod test {
         );
     }
 
+    #[test]
+    fn select_best_match_semi_condensed_bold_sans_serif() {
+        let handle = SystemSource::new()
+            .select_best_match(
+                &[FamilyName::SansSerif],
+                &Properties {
+                    style: font_kit::properties::Style::Normal,
+                    weight: font_kit::properties::Weight::BOLD,
+                    stretch: font_kit::properties::Stretch::SEMI_CONDENSED,
+                },
+            )
+            .unwrap();
+        match_handle!(
+            handle,
+            "/usr/share/fonts/truetype/dejavu/DejaVuSansCondensed-Bold.ttf",
+            0
+        );
+    }
+
+    #[test]
+    fn select_fallbacks_sans_serif() {
+        let fallbacks = SystemSource::new()
+            .select_fallbacks(
+                &[FamilyName::SansSerif],
+                &Properties::default(),
+                "en-US",
+                "Aé\u{2603}",
+            )
+            .unwrap();
+        match_handle!(
+            fallbacks[0].handle,
+            "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+            0
+        );
+        assert_eq!(fallbacks[0].coverage, vec![0..6]);
+        assert_eq!(fallbacks.len(), 1);
+    }
+
+    #[test]
+    fn query_bold_monospace_fonts() {
+        let descriptors = SystemSource::new()
+            .query_fonts(
+                FontQuery::new()
+                    .monospace(true)
+                    .weight(Weight::BOLD..=Weight::BLACK)
+                    .covering("A\u{416}"),
+            )
+            .unwrap();
+        assert!(!descriptors.is_empty());
+        for descriptor in &descriptors {
+            assert!(descriptor.is_monospace);
+            assert!(descriptor.properties.weight >= Weight::BOLD);
+        }
+        assert!(descriptors
+            .iter()
+            .any(|descriptor| descriptor.family_name == "DejaVu Sans Mono"));
+    }
+
+    #[test]
+    fn select_best_match_for_locale_with_overridden_sans_serif() {
+        let mut source = SystemSource::new();
+        let mut generic_family_map = source.generic_family_map().clone();
+        generic_family_map.set_for_locale("ja", FamilyName::SansSerif, &["DejaVu Serif"]);
+        source.set_generic_family_map(generic_family_map);
+
+        let handle = source
+            .select_best_match_for_locale(&[FamilyName::SansSerif], &Properties::default(), "ja-JP")
+            .unwrap();
+        let descriptor = source.describe_font(&handle).unwrap();
+        assert_eq!(descriptor.family_name, "DejaVu Serif");
+    }
+
+    #[test]
+    fn select_best_match_bold_by_name() {
+        let source = SystemSource::new();
+        let handle = source
+            .select_best_match(
+                &[FamilyName::Title("DejaVu Serif".to_owned())],
+                &Properties {
+                    style: font_kit::properties::Style::Normal,
+                    weight: Weight::BOLD,
+                    stretch: font_kit::properties::Stretch::NORMAL,
+                },
+            )
+            .unwrap();
+        let descriptor = source.describe_font(&handle).unwrap();
+        assert_eq!(descriptor.family_name, "DejaVu Serif");
+        assert_eq!(descriptor.properties.weight, Weight::BOLD);
+    }
+
+    #[test]
+    fn select_best_match_not_found() {
+        let result = SystemSource::new().select_best_match(
+            &[FamilyName::Title("zxhjfgkadsfhg".to_string())],
+            &Properties::default(),
+        );
+        match result {
+            Err(SelectionError::NotFound) => {}
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
     #[test]
     fn select_best_match_by_name_after_invalid() {
         let handle = SystemSource::new()